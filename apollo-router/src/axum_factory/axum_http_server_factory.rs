@@ -20,6 +20,7 @@ use futures::channel::oneshot;
 use futures::future::join_all;
 use futures::prelude::*;
 use http::header::ACCEPT_ENCODING;
+use http::header::ALT_SVC;
 use http::header::CONTENT_ENCODING;
 use http::HeaderValue;
 use http::Request;
@@ -27,6 +28,7 @@ use http_body::combinators::UnsyncBoxBody;
 use hyper::Body;
 use itertools::Itertools;
 use multimap::MultiMap;
+use parking_lot::Mutex;
 use serde::Serialize;
 use serde_json::json;
 #[cfg(unix)]
@@ -38,15 +40,19 @@ use tower::BoxError;
 use tower::ServiceBuilder;
 use tower::ServiceExt;
 use tower_http::decompression::DecompressionBody;
+use tower_http::set_header::SetResponseHeaderLayer;
 use tower_http::trace::TraceLayer;
 use tracing::instrument::WithSubscriber;
 use tracing::Instrument;
 
+use super::http3;
 use super::listeners::ensure_endpoints_consistency;
 use super::listeners::ensure_listenaddrs_consistency;
 use super::listeners::extra_endpoints;
 use super::listeners::ListenersAndRouters;
 use super::utils::PropagatingMakeSpan;
+use super::websocket::is_websocket_request;
+use super::websocket::upgrade;
 use super::ListenAddrAndRouter;
 use super::ENDPOINT_CALLBACK;
 use crate::axum_factory::compression::Compressor;
@@ -54,6 +60,7 @@ use crate::axum_factory::listeners::get_extra_listeners;
 use crate::axum_factory::listeners::serve_router_on_listen_addr;
 use crate::configuration::Configuration;
 use crate::configuration::ListenAddr;
+use crate::configuration::SupergraphWebSocket;
 use crate::graphql;
 use crate::http_server_factory::HttpServerFactory;
 use crate::http_server_factory::HttpServerHandle;
@@ -96,6 +103,8 @@ impl Drop for SessionCountGuard {
 pub(crate) struct AxumHttpServerFactory {
     live: Arc<AtomicBool>,
     ready: Arc<AtomicBool>,
+    /// QUIC endpoint of the HTTP/3 listener, kept across reloads like the TCP listener
+    http3_endpoint: Arc<Mutex<Option<quinn::Endpoint>>>,
 }
 
 impl AxumHttpServerFactory {
@@ -133,6 +142,7 @@ where
     ensure_listenaddrs_consistency(configuration, &endpoints)?;
 
     if configuration.health_check.enabled {
+        let router_factory = service_factory.clone();
         tracing::info!(
             "Health check exposed at {}{}",
             configuration.health_check.listen,
@@ -148,15 +158,16 @@ where
                         let query_upper = query.to_ascii_uppercase();
                         // Could be more precise, but sloppy match is fine for this use case
                         if query_upper.starts_with("READY") {
-                            let status = if ready.load(Ordering::SeqCst) {
-                                HealthStatus::Up
-                            } else {
-                                // It's hard to get k8s to parse payloads. Especially since we
-                                // can't install curl or jq into our docker images because of CVEs.
-                                // So, compromise, k8s will interpret this as probe fail.
-                                status_code = StatusCode::SERVICE_UNAVAILABLE;
-                                HealthStatus::Down
-                            };
+                            let status =
+                                if ready.load(Ordering::SeqCst) && router_factory.is_ready() {
+                                    HealthStatus::Up
+                                } else {
+                                    // It's hard to get k8s to parse payloads. Especially since we
+                                    // can't install curl or jq into our docker images because of CVEs.
+                                    // So, compromise, k8s will interpret this as probe fail.
+                                    status_code = StatusCode::SERVICE_UNAVAILABLE;
+                                    HealthStatus::Down
+                                };
                             Health { status }
                         } else if query_upper.starts_with("LIVE") {
                             let status = if live.load(Ordering::SeqCst) {
@@ -237,6 +248,7 @@ impl HttpServerFactory for AxumHttpServerFactory {
     {
         let live = self.live.clone();
         let ready = self.ready.clone();
+        let http3_endpoint = self.http3_endpoint.clone();
         Box::pin(async move {
             let all_routers = make_axum_router(
                 live.clone(),
@@ -301,10 +313,41 @@ impl HttpServerFactory for AxumHttpServerFactory {
                 .local_addr()
                 .map_err(ApolloRouterError::ServerCreationError)?;
 
+            // serve main router over HTTP/3, and advertise it on the main listener
+            let mut main_router = all_routers.main.1;
+            let endpoint = http3::bind(http3_endpoint.lock().take(), &configuration)?;
+            let (http3_server, http3_shutdown_sender) = match &endpoint {
+                Some(endpoint) => {
+                    let address = endpoint
+                        .local_addr()
+                        .map_err(ApolloRouterError::ServerCreationError)?;
+                    let (server, shutdown_sender) = http3::serve_router_on_endpoint(
+                        endpoint.clone(),
+                        main_router.clone(),
+                        all_connections_stopped_sender.clone(),
+                    );
+                    main_router = main_router.layer(SetResponseHeaderLayer::if_not_present(
+                        ALT_SVC,
+                        http3::alt_svc(
+                            address.port(),
+                            configuration.supergraph.http3.alt_svc_max_age,
+                        ),
+                    ));
+                    tracing::info!(
+                        "GraphQL endpoint exposed over HTTP/3 at {}{} 🚀",
+                        address,
+                        configuration.supergraph.path
+                    );
+                    (Some(server), Some(shutdown_sender))
+                }
+                None => (None, None),
+            };
+            *http3_endpoint.lock() = endpoint;
+
             let (main_server, main_shutdown_sender) = serve_router_on_listen_addr(
                 main_listener,
                 actual_main_listen_address.clone(),
-                all_routers.main.1,
+                main_router,
                 all_connections_stopped_sender.clone(),
             );
 
@@ -369,6 +412,11 @@ impl HttpServerFactory for AxumHttpServerFactory {
                 if let Err(_err) = main_shutdown_sender.send(()) {
                     tracing::error!("Failed to notify http thread of shutdown");
                 }
+                if let Some(http3_shutdown_sender) = http3_shutdown_sender {
+                    if let Err(_err) = http3_shutdown_sender.send(()) {
+                        tracing::error!("Failed to notify http3 thread of shutdown");
+                    }
+                }
             });
 
             let (outer_extra_shutdown_sender, outer_extra_shutdown_receiver) =
@@ -383,9 +431,14 @@ impl HttpServerFactory for AxumHttpServerFactory {
             });
 
             // Spawn the main (GraphQL) server into a task
-            let main_future = tokio::task::spawn(main_server)
-                .map_err(|_| ApolloRouterError::HttpServerLifecycleError)
-                .boxed();
+            let main_future = tokio::task::spawn(async move {
+                match http3_server {
+                    Some(http3_server) => future::join(main_server, http3_server).await.0,
+                    None => main_server.await,
+                }
+            })
+            .map_err(|_| ApolloRouterError::HttpServerLifecycleError)
+            .boxed();
 
             // Spawn all other servers (health, metrics, etc...) into a task
             let extra_futures = tokio::task::spawn(join_all(servers))
@@ -400,7 +453,8 @@ impl HttpServerFactory for AxumHttpServerFactory {
                 Some(actual_main_listen_address),
                 actual_extra_listen_adresses,
                 all_connections_stopped_sender,
-            ))
+            )
+            .with_http3_endpoint(http3_endpoint))
         })
     }
 
@@ -557,14 +611,17 @@ where
 {
     let early_cancel = configuration.supergraph.early_cancel;
     let experimental_log_on_broken_pipe = configuration.supergraph.experimental_log_on_broken_pipe;
+    let websocket = configuration.supergraph.websocket.clone();
     let mut router = Router::new().route(
         &configuration.supergraph.sanitized_path(),
         get({
+            let websocket = websocket.clone();
             move |Extension(service): Extension<RF>, request: Request<DecompressionBody<Body>>| {
-                handle_graphql(
-                    service.create().boxed(),
+                handle_get(
+                    service,
                     early_cancel,
                     experimental_log_on_broken_pipe,
+                    websocket,
                     request,
                 )
             }
@@ -587,10 +644,11 @@ where
             get({
                 move |Extension(service): Extension<RF>,
                       request: Request<DecompressionBody<Body>>| {
-                    handle_graphql(
-                        service.create().boxed(),
+                    handle_get(
+                        service,
                         early_cancel,
                         experimental_log_on_broken_pipe,
+                        websocket,
                         request,
                     )
                 }
@@ -612,6 +670,30 @@ where
     router
 }
 
+async fn handle_get<RF>(
+    service_factory: RF,
+    early_cancel: bool,
+    experimental_log_on_broken_pipe: bool,
+    websocket: SupergraphWebSocket,
+    http_request: Request<DecompressionBody<Body>>,
+) -> Response
+where
+    RF: RouterFactory,
+{
+    if websocket.enabled && is_websocket_request(&http_request) {
+        upgrade(service_factory, websocket, http_request).await
+    } else {
+        handle_graphql(
+            service_factory.create().boxed(),
+            early_cancel,
+            experimental_log_on_broken_pipe,
+            http_request,
+        )
+        .await
+        .into_response()
+    }
+}
+
 async fn handle_graphql(
     service: router::BoxService,
     early_cancel: bool,
@@ -0,0 +1,229 @@
+//! HTTP/3 listener for the supergraph endpoint
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::Router;
+use bytes::Buf;
+use bytes::Bytes;
+use futures::channel::oneshot;
+use futures::prelude::*;
+use h3::error::ErrorLevel;
+use h3::server::RequestStream;
+use http::HeaderValue;
+use hyper::body::HttpBody;
+use hyper::Body;
+use tokio::sync::mpsc;
+use tokio::sync::Notify;
+use tower::ServiceExt;
+
+use crate::axum_factory::utils::ClientCertificate;
+use crate::axum_factory::utils::ConnectionInfo;
+use crate::configuration::Configuration;
+use crate::router::ApolloRouterError;
+
+/// Creates the QUIC endpoint of the HTTP/3 listener, if it is enabled
+///
+/// The endpoint of the previous server is reused if it listens on the same address, so that its
+/// connections are not interrupted by a configuration or schema reload.
+pub(super) fn bind(
+    previous: Option<quinn::Endpoint>,
+    configuration: &Configuration,
+) -> Result<Option<quinn::Endpoint>, ApolloRouterError> {
+    let address = configuration
+        .supergraph
+        .http3
+        .listen_address(&configuration.supergraph.listen);
+    let (Some(address), Some(tls)) = (address, configuration.tls.supergraph.as_ref()) else {
+        if let Some(previous) = previous {
+            // stop accepting new connections, the current ones are still served
+            previous.set_server_config(None);
+        }
+        return Ok(None);
+    };
+
+    let mut tls_config = (*tls.tls_config()?).clone();
+    tls_config.alpn_protocols = vec![b"h3".to_vec()];
+    let server_config = quinn::ServerConfig::with_crypto(Arc::new(tls_config));
+
+    match previous {
+        Some(previous) if previous.local_addr().ok() == Some(address) => {
+            previous.set_server_config(Some(server_config));
+            Ok(Some(previous))
+        }
+        previous => {
+            if let Some(previous) = previous {
+                previous.set_server_config(None);
+            }
+            quinn::Endpoint::server(server_config, address)
+                .map(Some)
+                .map_err(ApolloRouterError::ServerCreationError)
+        }
+    }
+}
+
+/// Value of the `Alt-Svc` header advertising the HTTP/3 listener
+pub(super) fn alt_svc(port: u16, max_age: Duration) -> HeaderValue {
+    HeaderValue::from_str(&format!("h3=\":{port}\"; ma={}", max_age.as_secs()))
+        .expect("the Alt-Svc header value is valid")
+}
+
+pub(super) fn serve_router_on_endpoint(
+    endpoint: quinn::Endpoint,
+    router: Router,
+    all_connections_stopped_sender: mpsc::Sender<()>,
+) -> (impl Future<Output = ()>, oneshot::Sender<()>) {
+    let (shutdown_sender, shutdown_receiver) = oneshot::channel::<()>();
+    let server = async move {
+        tokio::pin!(shutdown_receiver);
+
+        let connection_shutdown = Arc::new(Notify::new());
+        let server_address = endpoint.local_addr().ok();
+
+        loop {
+            tokio::select! {
+                _ = &mut shutdown_receiver => {
+                    break;
+                }
+                connecting = endpoint.accept() => {
+                    // the endpoint was closed
+                    let Some(connecting) = connecting else {
+                        break;
+                    };
+                    let app = router.clone();
+                    let connection_shutdown = connection_shutdown.clone();
+                    let connection_stop_signal = all_connections_stopped_sender.clone();
+
+                    tokio::task::spawn(async move {
+                        // this sender must be moved into the session to track that it is still running
+                        let _connection_stop_signal = connection_stop_signal;
+
+                        match connecting.await {
+                            Ok(connection) => {
+                                serve_connection(connection, server_address, app, connection_shutdown)
+                                    .await
+                            }
+                            Err(error) => tracing::debug!("QUIC handshake failed: {error}"),
+                        }
+                    });
+                }
+            }
+        }
+
+        // the shutdown receiver was triggered so we tell the currently active connections to stop
+        connection_shutdown.notify_waiters();
+    };
+    (server, shutdown_sender)
+}
+
+async fn serve_connection(
+    connection: quinn::Connection,
+    server_address: Option<SocketAddr>,
+    app: Router,
+    connection_shutdown: Arc<Notify>,
+) {
+    let connection_info = ConnectionInfo {
+        peer_address: Some(connection.remote_address()),
+        server_address,
+        client_certificate: connection
+            .peer_identity()
+            .and_then(|identity| identity.downcast::<Vec<rustls::Certificate>>().ok())
+            .and_then(|certificates| {
+                certificates
+                    .first()
+                    .and_then(|certificate| ClientCertificate::from_der(&certificate.0))
+            })
+            .map(Arc::new),
+    };
+
+    let mut connection =
+        match h3::server::Connection::new(h3_quinn::Connection::new(connection)).await {
+            Ok(connection) => connection,
+            Err(error) => {
+                tracing::debug!("cannot establish the HTTP/3 connection: {error}");
+                return;
+            }
+        };
+
+    let mut shutting_down = false;
+    loop {
+        tokio::select! {
+            request = connection.accept() => match request {
+                Ok(Some((request, stream))) => {
+                    let app = app.clone();
+                    let connection_info = connection_info.clone();
+                    tokio::task::spawn(async move {
+                        if let Err(error) = handle_request(app, connection_info, request, stream).await {
+                            tracing::debug!("cannot send the HTTP/3 response: {error}");
+                        }
+                    });
+                }
+                // the connection was closed, or all requests were handled after a shutdown
+                Ok(None) => break,
+                Err(error) => match error.get_error_level() {
+                    ErrorLevel::ConnectionError => break,
+                    ErrorLevel::StreamError => continue,
+                },
+            },
+            // the server is shutting down: refuse new requests and let the ongoing ones finish
+            _ = connection_shutdown.notified(), if !shutting_down => {
+                shutting_down = true;
+                if connection.shutdown(0).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+async fn handle_request(
+    app: Router,
+    connection_info: ConnectionInfo,
+    request: http::Request<()>,
+    stream: RequestStream<h3_quinn::BidiStream<Bytes>, Bytes>,
+) -> Result<(), h3::Error> {
+    let (mut send, mut recv) = stream.split();
+
+    // the request body is forwarded while the router handles the request
+    let (mut body_sender, body) = Body::channel();
+    tokio::task::spawn(async move {
+        loop {
+            match recv.recv_data().await {
+                Ok(Some(mut chunk)) => {
+                    let chunk = chunk.copy_to_bytes(chunk.remaining());
+                    if body_sender.send_data(chunk).await.is_err() {
+                        break;
+                    }
+                }
+                Ok(None) => break,
+                Err(_) => {
+                    body_sender.abort();
+                    break;
+                }
+            }
+        }
+    });
+
+    let (parts, ()) = request.into_parts();
+    let mut request = http::Request::from_parts(parts, body);
+    request.extensions_mut().insert(connection_info);
+    let response = app
+        .oneshot(request)
+        .await
+        .unwrap_or_else(|never| match never {});
+
+    let (parts, mut body) = response.into_parts();
+    send.send_response(http::Response::from_parts(parts, ()))
+        .await?;
+    while let Some(chunk) = body.data().await {
+        match chunk {
+            Ok(chunk) => send.send_data(chunk).await?,
+            Err(error) => {
+                tracing::debug!("cannot read the response body: {error}");
+                break;
+            }
+        }
+    }
+    send.finish().await
+}
@@ -2387,3 +2387,635 @@ async fn test_supergraph_timeout() {
     let body = response.bytes().await.unwrap();
     assert_eq!(std::str::from_utf8(&body).unwrap(), "request timed out");
 }
+
+type ClientWebSocket =
+    tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
+
+async fn init_websocket(
+    router_service: impl Service<
+            router::Request,
+            Response = router::Response,
+            Error = BoxError,
+            Future = BoxFuture<'static, router::ServiceResult>,
+        > + Send
+        + 'static,
+) -> (HttpServerHandle, ClientWebSocket) {
+    let server = init_websocket_server(
+        router_service,
+        crate::configuration::SupergraphWebSocket {
+            enabled: true,
+            ..Default::default()
+        },
+    )
+    .await;
+    let socket = connect_websocket(&server, None).await.unwrap();
+
+    (server, socket)
+}
+
+async fn init_websocket_server(
+    router_service: impl Service<
+            router::Request,
+            Response = router::Response,
+            Error = BoxError,
+            Future = BoxFuture<'static, router::ServiceResult>,
+        > + Send
+        + 'static,
+    websocket: crate::configuration::SupergraphWebSocket,
+) -> HttpServerHandle {
+    let conf = Arc::new(
+        Configuration::fake_builder()
+            .supergraph(Supergraph::fake_builder().websocket(websocket).build())
+            .build()
+            .unwrap(),
+    );
+    let (server, _) = init_with_config(router_service, conf, MultiMap::new())
+        .await
+        .unwrap();
+    server
+}
+
+async fn connect_websocket(
+    server: &HttpServerHandle,
+    origin: Option<&'static str>,
+) -> Result<ClientWebSocket, tokio_tungstenite::tungstenite::Error> {
+    use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+
+    let url =
+        format!("{}/", server.graphql_listen_address().as_ref().unwrap()).replacen("http", "ws", 1);
+    let mut request = url.into_client_request().unwrap();
+    request.headers_mut().insert(
+        header::SEC_WEBSOCKET_PROTOCOL,
+        HeaderValue::from_static("graphql-transport-ws"),
+    );
+    if let Some(origin) = origin {
+        request
+            .headers_mut()
+            .insert(header::ORIGIN, HeaderValue::from_static(origin));
+    }
+    let (socket, response) = tokio_tungstenite::connect_async(request).await?;
+    assert_eq!(
+        response.headers().get(header::SEC_WEBSOCKET_PROTOCOL),
+        Some(&HeaderValue::from_static("graphql-transport-ws"))
+    );
+
+    Ok(socket)
+}
+
+#[tokio::test]
+async fn it_serves_graphql_over_websocket() -> Result<(), ApolloRouterError> {
+    use futures::SinkExt;
+    use tokio_tungstenite::tungstenite::Message;
+
+    let router_service = router::service::from_supergraph_mock_callback(|req| {
+        assert_eq!(
+            req.supergraph_request.headers().get("authorization"),
+            Some(&HeaderValue::from_static("Bearer token"))
+        );
+        // only the configured entries of the payload are copied to the headers
+        assert!(req.supergraph_request.headers().get("x-custom").is_none());
+        assert_ne!(
+            req.supergraph_request.headers().get(header::HOST),
+            Some(&HeaderValue::from_static("attacker.example.com"))
+        );
+        assert_eq!(
+            req.context
+                .get::<_, serde_json::Value>(websocket::CONNECTION_INIT_PAYLOAD)
+                .unwrap(),
+            Some(json!({
+                "authorization": "Bearer token",
+                "x-custom": "value",
+                "host": "attacker.example.com"
+            }))
+        );
+        let body = stream::iter(vec![
+            graphql::Response::builder()
+                .data(json!({ "me": "id" }))
+                .has_next(true)
+                .build(),
+            graphql::Response::builder().has_next(false).build(),
+        ])
+        .boxed();
+        Ok(SupergraphResponse::new_from_response(
+            http::Response::builder().status(200).body(body).unwrap(),
+            req.context,
+        ))
+    })
+    .await;
+    let (server, mut socket) = init_websocket(router_service).await;
+
+    for message in [
+        json!({
+            "type": "connection_init",
+            "payload": {
+                "authorization": "Bearer token",
+                "x-custom": "value",
+                "host": "attacker.example.com"
+            }
+        }),
+        json!({
+            "type": "subscribe",
+            "id": "1",
+            "payload": { "query": "query { me { id ... @defer { name } } }" }
+        }),
+    ] {
+        socket
+            .send(Message::Text(message.to_string()))
+            .await
+            .unwrap();
+    }
+
+    let mut messages = Vec::new();
+    while let Some(Ok(Message::Text(text))) = socket.next().await {
+        let message: serde_json::Value = serde_json::from_str(&text).unwrap();
+        let is_complete = message["type"] == "complete";
+        messages.push(message);
+        if is_complete {
+            break;
+        }
+    }
+    assert_eq!(
+        messages,
+        vec![
+            json!({ "type": "connection_ack" }),
+            json!({ "type": "next", "id": "1", "payload": { "data": { "me": "id" }, "hasNext": true } }),
+            json!({ "type": "next", "id": "1", "payload": { "hasNext": false } }),
+            json!({ "type": "complete", "id": "1" }),
+        ]
+    );
+
+    server.shutdown().await
+}
+
+#[tokio::test]
+async fn it_closes_websocket_connections_subscribing_before_init() -> Result<(), ApolloRouterError>
+{
+    use futures::SinkExt;
+    use tokio_tungstenite::tungstenite::Message;
+
+    let router_service = router::service::from_supergraph_mock_callback(|_| {
+        panic!("the operation should not be executed")
+    })
+    .await;
+    let (server, mut socket) = init_websocket(router_service).await;
+
+    socket
+        .send(Message::Text(
+            json!({
+                "type": "subscribe",
+                "id": "1",
+                "payload": { "query": "{ me }" }
+            })
+            .to_string(),
+        ))
+        .await
+        .unwrap();
+
+    match socket.next().await {
+        Some(Ok(Message::Close(Some(frame)))) => assert_eq!(u16::from(frame.code), 4401),
+        message => panic!("expected a close frame, got {message:?}"),
+    }
+
+    server.shutdown().await
+}
+
+#[tokio::test]
+async fn it_refuses_websocket_connections_from_other_origins() -> Result<(), ApolloRouterError> {
+    let router_service = router::service::from_supergraph_mock_callback(|req| {
+        Ok(SupergraphResponse::new_from_graphql_response(
+            graphql::Response::builder()
+                .data(json!({ "__typename": "Query" }))
+                .build(),
+            req.context,
+        ))
+    })
+    .await;
+    let server = init_websocket_server(
+        router_service,
+        crate::configuration::SupergraphWebSocket {
+            enabled: true,
+            allowed_origins: vec!["https://app.example.com".to_string()],
+            ..Default::default()
+        },
+    )
+    .await;
+
+    match connect_websocket(&server, Some("https://attacker.example.com")).await {
+        Err(tokio_tungstenite::tungstenite::Error::Http(response)) => {
+            assert_eq!(response.status(), StatusCode::FORBIDDEN)
+        }
+        result => panic!("expected the upgrade to be refused, got {result:?}"),
+    }
+    assert!(connect_websocket(&server, Some("https://app.example.com"))
+        .await
+        .is_ok());
+    // clients that are not browsers do not send an origin
+    assert!(connect_websocket(&server, None).await.is_ok());
+
+    server.shutdown().await
+}
+
+#[tokio::test]
+async fn it_closes_websocket_connections_failing_authentication_at_init(
+) -> Result<(), ApolloRouterError> {
+    use futures::SinkExt;
+    use tokio_tungstenite::tungstenite::Message;
+
+    let router_service = router::service::from_supergraph_mock_callback(|req| {
+        assert_eq!(
+            req.supergraph_request.headers().get("authorization"),
+            Some(&HeaderValue::from_static("Bearer invalid"))
+        );
+        let mut response = SupergraphResponse::new_from_graphql_response(
+            graphql::Response::builder()
+                .error(
+                    graphql::Error::builder()
+                        .message("invalid token")
+                        .extension_code("AUTH_ERROR")
+                        .build(),
+                )
+                .build(),
+            req.context,
+        );
+        *response.response.status_mut() = StatusCode::UNAUTHORIZED;
+        Ok(response)
+    })
+    .await;
+    let (server, mut socket) = init_websocket(router_service).await;
+
+    socket
+        .send(Message::Text(
+            json!({
+                "type": "connection_init",
+                "payload": { "authorization": "Bearer invalid" }
+            })
+            .to_string(),
+        ))
+        .await
+        .unwrap();
+
+    // the connection is closed without being acknowledged
+    match socket.next().await {
+        Some(Ok(Message::Close(Some(frame)))) => assert_eq!(u16::from(frame.code), 4403),
+        message => panic!("expected a close frame, got {message:?}"),
+    }
+
+    server.shutdown().await
+}
+
+#[tokio::test]
+async fn it_passes_the_client_certificate_of_websocket_connections() -> Result<(), ApolloRouterError>
+{
+    use futures::SinkExt;
+    use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+    use tokio_tungstenite::tungstenite::Message;
+    use tokio_tungstenite::Connector;
+
+    use crate::axum_factory::utils::ConnectionInfo;
+
+    let router_service = router::service::from_supergraph_mock_callback(|req| {
+        // both the authentication query and the operation see the certificate of the connection
+        let connection_info = req
+            .supergraph_request
+            .extensions()
+            .get::<ConnectionInfo>()
+            .cloned()
+            .unwrap();
+        assert!(connection_info.peer_address.is_some());
+        let common_name = connection_info
+            .client_certificate
+            .unwrap()
+            .common_name
+            .clone();
+        Ok(SupergraphResponse::new_from_graphql_response(
+            graphql::Response::builder()
+                .data(json!({ "commonName": common_name }))
+                .build(),
+            req.context,
+        ))
+    })
+    .await;
+    let configuration = json!({
+        "supergraph": {
+            "listen": "127.0.0.1:0",
+            "websocket": { "enabled": true }
+        },
+        "tls": {
+            "supergraph": {
+                "certificate": include_str!("../services/http/testdata/server.crt"),
+                "key": include_str!("../services/http/testdata/server.key"),
+                "certificate_chain": include_str!("../services/http/testdata/CA/ca.crt"),
+                "client_authentication": {
+                    "certificate_authorities": include_str!("../services/http/testdata/CA/ca.crt")
+                }
+            }
+        }
+    });
+    let (server, _) = init_with_config(
+        router_service,
+        Arc::new(Configuration::from_str(&configuration.to_string()).unwrap()),
+        MultiMap::new(),
+    )
+    .await?;
+    let Some(ListenAddr::SocketAddr(address)) = server.graphql_listen_address().clone() else {
+        panic!("the router listens on a socket address");
+    };
+
+    let mut roots = rustls::RootCertStore::empty();
+    for certificate in
+        crate::configuration::load_certs(include_str!("../services/http/testdata/CA/ca.crt"))
+            .unwrap()
+    {
+        roots.add(&certificate).unwrap();
+    }
+    let tls_config = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_client_auth_cert(
+            crate::configuration::load_certs(include_str!("../services/http/testdata/client.crt"))
+                .unwrap(),
+            crate::configuration::load_key(include_str!("../services/http/testdata/client.key"))
+                .unwrap(),
+        )
+        .unwrap();
+    let mut request = format!("wss://localhost:{}/", address.port())
+        .into_client_request()
+        .unwrap();
+    request.headers_mut().insert(
+        header::SEC_WEBSOCKET_PROTOCOL,
+        HeaderValue::from_static("graphql-transport-ws"),
+    );
+    let (mut socket, _) = tokio_tungstenite::connect_async_tls_with_config(
+        request,
+        None,
+        false,
+        Some(Connector::Rustls(Arc::new(tls_config))),
+    )
+    .await
+    .unwrap();
+
+    for message in [
+        json!({ "type": "connection_init" }),
+        json!({
+            "type": "subscribe",
+            "id": "1",
+            "payload": { "query": "{ commonName }" }
+        }),
+    ] {
+        socket
+            .send(Message::Text(message.to_string()))
+            .await
+            .unwrap();
+    }
+
+    let mut messages = Vec::new();
+    while let Some(Ok(Message::Text(text))) = socket.next().await {
+        let message: serde_json::Value = serde_json::from_str(&text).unwrap();
+        let is_complete = message["type"] == "complete";
+        messages.push(message);
+        if is_complete {
+            break;
+        }
+    }
+    assert_eq!(
+        messages,
+        vec![
+            json!({ "type": "connection_ack" }),
+            json!({ "type": "next", "id": "1", "payload": { "data": { "commonName": "router" } } }),
+            json!({ "type": "complete", "id": "1" }),
+        ]
+    );
+
+    server.shutdown().await
+}
+
+fn http3_configuration(http3_listen: SocketAddr) -> Arc<Configuration> {
+    let configuration = json!({
+        "supergraph": {
+            "listen": "127.0.0.1:0",
+            "http3": { "enabled": true, "listen": http3_listen.to_string() }
+        },
+        "tls": {
+            "supergraph": {
+                "certificate": include_str!("../services/http/testdata/server.crt"),
+                "key": include_str!("../services/http/testdata/server.key"),
+                "certificate_chain": include_str!("../services/http/testdata/CA/ca.crt")
+            }
+        }
+    });
+    Arc::new(Configuration::from_str(&configuration.to_string()).unwrap())
+}
+
+fn unused_udp_address() -> SocketAddr {
+    std::net::UdpSocket::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+}
+
+/// Sends a request over HTTP/3 and returns the response with its body
+async fn http3_request(address: SocketAddr, body: &str) -> (http::Response<()>, Vec<u8>) {
+    use bytes::Buf;
+
+    let mut roots = rustls::RootCertStore::empty();
+    for certificate in
+        rustls_pemfile::certs(&mut include_str!("../services/http/testdata/CA/ca.crt").as_bytes())
+            .unwrap()
+    {
+        roots.add(&rustls::Certificate(certificate)).unwrap();
+    }
+    let mut tls_config = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    tls_config.alpn_protocols = vec![b"h3".to_vec()];
+
+    let mut endpoint = quinn::Endpoint::client(SocketAddr::from(([127, 0, 0, 1], 0))).unwrap();
+    endpoint.set_default_client_config(quinn::ClientConfig::new(Arc::new(tls_config)));
+    let connection = endpoint
+        .connect(address, "localhost")
+        .unwrap()
+        .await
+        .unwrap();
+    let (mut driver, mut send_request) = h3::client::new(h3_quinn::Connection::new(connection))
+        .await
+        .unwrap();
+    let driver =
+        tokio::spawn(async move { std::future::poll_fn(|cx| driver.poll_close(cx)).await });
+
+    let request = http::Request::post(format!("https://localhost:{}/", address.port()))
+        .header(CONTENT_TYPE, APPLICATION_JSON.essence_str())
+        .header(ACCEPT, APPLICATION_JSON.essence_str())
+        .body(())
+        .unwrap();
+    let mut stream = send_request.send_request(request).await.unwrap();
+    stream
+        .send_data(bytes::Bytes::copy_from_slice(body.as_bytes()))
+        .await
+        .unwrap();
+    stream.finish().await.unwrap();
+    let response = stream.recv_response().await.unwrap();
+    let mut response_body = Vec::new();
+    while let Some(mut chunk) = stream.recv_data().await.unwrap() {
+        response_body.extend_from_slice(&chunk.copy_to_bytes(chunk.remaining()));
+    }
+
+    drop(stream);
+    drop(send_request);
+    let _ = driver.await;
+    endpoint.close(0u32.into(), b"");
+    endpoint.wait_idle().await;
+    (response, response_body)
+}
+
+/// Waits for the UDP socket of a closed HTTP/3 listener to be released
+async fn assert_udp_address_released(address: SocketAddr) {
+    for _ in 0..100 {
+        if std::net::UdpSocket::bind(address).is_ok() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("the HTTP/3 listener still listens on {address}");
+}
+
+#[tokio::test]
+async fn it_answers_over_http3_and_advertises_it() -> Result<(), ApolloRouterError> {
+    let expected_response = graphql::Response::builder()
+        .data(json!({"response": "yay"}))
+        .build();
+    let example_response = expected_response.clone();
+    let router_service = router::service::from_supergraph_mock_callback(move |req| {
+        Ok(SupergraphResponse::new_from_graphql_response(
+            example_response.clone(),
+            req.context,
+        ))
+    })
+    .await;
+    let http3_address = unused_udp_address();
+    let (server, _) = init_with_config(
+        router_service,
+        http3_configuration(http3_address),
+        MultiMap::new(),
+    )
+    .await?;
+    let Some(ListenAddr::SocketAddr(address)) = server.graphql_listen_address().clone() else {
+        panic!("the router listens on a socket address");
+    };
+
+    // the HTTP/2 response advertises the HTTP/3 listener
+    let client = reqwest::Client::builder()
+        .add_root_certificate(
+            reqwest::Certificate::from_pem(include_bytes!("../services/http/testdata/CA/ca.crt"))
+                .unwrap(),
+        )
+        .build()
+        .unwrap();
+    let response = client
+        .post(format!("https://localhost:{}/", address.port()))
+        .header(CONTENT_TYPE, APPLICATION_JSON.essence_str())
+        .body(json!({ "query": "query { me { name } }" }).to_string())
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    assert_eq!(response.version(), http::Version::HTTP_2);
+    assert_eq!(
+        response.headers().get(header::ALT_SVC).unwrap(),
+        &format!("h3=\":{}\"; ma=86400", http3_address.port())
+    );
+
+    let (response, body) = http3_request(
+        http3_address,
+        &json!({ "query": "query { me { name } }" }).to_string(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().get(header::ALT_SVC).is_none());
+    assert_eq!(
+        serde_json::from_slice::<graphql::Response>(&body).unwrap(),
+        expected_response
+    );
+
+    server.shutdown().await?;
+    assert_udp_address_released(http3_address).await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn it_rebinds_http3_listener_on_restart() {
+    let mut router_service = router::service::from_supergraph_mock_callback(move |req| {
+        Ok(SupergraphResponse::new_from_graphql_response(
+            graphql::Response::builder()
+                .data(json!({"response": "yay"}))
+                .build(),
+            req.context,
+        ))
+    })
+    .await;
+    let (service, mut handle) = tower_test::mock::spawn();
+    tokio::spawn(async move {
+        while let Some((request, responder)) = handle.next_request().await {
+            match router_service.ready().await.unwrap().call(request).await {
+                Ok(response) => responder.send_response(response),
+                Err(err) => responder.send_error(err),
+            }
+        }
+    });
+    let server_factory = AxumHttpServerFactory::new();
+    let supergraph_service_factory = TestRouterFactory {
+        inner: service.into_inner(),
+    };
+    let (all_connections_stopped_sender, _) = mpsc::channel::<()>(1);
+    let body = json!({ "query": "query { me { name } }" }).to_string();
+
+    let first_address = unused_udp_address();
+    let server = server_factory
+        .create(
+            supergraph_service_factory.clone(),
+            http3_configuration(first_address),
+            None,
+            vec![],
+            MultiMap::new(),
+            LicenseState::default(),
+            all_connections_stopped_sender,
+        )
+        .await
+        .unwrap();
+    let (response, _) = http3_request(first_address, &body).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // the endpoint is kept when its address does not change
+    let server = server
+        .restart(
+            &server_factory,
+            supergraph_service_factory.clone(),
+            http3_configuration(first_address),
+            MultiMap::new(),
+            LicenseState::default(),
+        )
+        .await
+        .unwrap();
+    let (response, _) = http3_request(first_address, &body).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // and replaced when it changes
+    let second_address = unused_udp_address();
+    let server = server
+        .restart(
+            &server_factory,
+            supergraph_service_factory,
+            http3_configuration(second_address),
+            MultiMap::new(),
+            LicenseState::default(),
+        )
+        .await
+        .unwrap();
+    let (response, _) = http3_request(second_address, &body).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_udp_address_released(first_address).await;
+
+    server.shutdown().await.unwrap();
+    assert_udp_address_released(second_address).await;
+}
@@ -1,12 +1,21 @@
 //! Utilities used for [`super::AxumHttpServerFactory`]
 
+use std::net::IpAddr;
 use std::net::SocketAddr;
+use std::sync::Arc;
 
 use opentelemetry::global;
 use opentelemetry::trace::TraceContextExt;
+use serde::Deserialize;
+use serde::Serialize;
+use sha2::Digest;
+use sha2::Sha256;
 use tower_http::trace::MakeSpan;
 use tower_service::Service;
 use tracing::Span;
+use x509_parser::certificate::X509Certificate;
+use x509_parser::extensions::GeneralName;
+use x509_parser::prelude::FromDer;
 
 use crate::plugins::telemetry::SpanMode;
 use crate::plugins::telemetry::OTEL_STATUS_CODE;
@@ -73,6 +82,67 @@ pub(crate) struct InjectConnectionInfo<S> {
 pub(crate) struct ConnectionInfo {
     pub(crate) peer_address: Option<SocketAddr>,
     pub(crate) server_address: Option<SocketAddr>,
+    /// Certificate presented by the client and verified during the TLS handshake
+    pub(crate) client_certificate: Option<Arc<ClientCertificate>>,
+}
+
+/// Information extracted from a verified client certificate
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub(crate) struct ClientCertificate {
+    /// Distinguished name of the subject, in RFC 4514 format
+    pub(crate) subject: String,
+    /// Common name of the subject
+    pub(crate) common_name: Option<String>,
+    /// DNS names, email addresses, URIs and IP addresses from the subject alternative name extension
+    pub(crate) subject_alternative_names: Vec<String>,
+    /// Hex encoded SHA-256 fingerprint of the DER encoded certificate
+    pub(crate) fingerprint: String,
+}
+
+impl ClientCertificate {
+    pub(crate) fn from_der(der: &[u8]) -> Option<Self> {
+        let (_, certificate) = X509Certificate::from_der(der).ok()?;
+
+        let common_name = certificate
+            .subject()
+            .iter_common_name()
+            .next()
+            .and_then(|cn| cn.as_str().ok())
+            .map(|cn| cn.to_string());
+
+        let subject_alternative_names = certificate
+            .subject_alternative_name()
+            .ok()
+            .flatten()
+            .map(|extension| {
+                extension
+                    .value
+                    .general_names
+                    .iter()
+                    .filter_map(|name| match name {
+                        GeneralName::DNSName(name)
+                        | GeneralName::RFC822Name(name)
+                        | GeneralName::URI(name) => Some(name.to_string()),
+                        GeneralName::IPAddress(bytes) => match bytes.len() {
+                            4 => Some(IpAddr::from(<[u8; 4]>::try_from(*bytes).ok()?).to_string()),
+                            16 => {
+                                Some(IpAddr::from(<[u8; 16]>::try_from(*bytes).ok()?).to_string())
+                            }
+                            _ => None,
+                        },
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Some(ClientCertificate {
+            subject: certificate.subject().to_string(),
+            common_name,
+            subject_alternative_names,
+            fingerprint: hex::encode(Sha256::digest(der)),
+        })
+    }
 }
 
 impl<S> InjectConnectionInfo<S> {
@@ -0,0 +1,422 @@
+//! GraphQL over WebSocket endpoint for clients, using the `graphql-transport-ws` protocol
+//!
+//! See <https://github.com/enisdenjo/graphql-ws/blob/master/PROTOCOL.md>. Every operation
+//! received on a connection is sent to the router service as a regular HTTP request, so it goes
+//! through the whole pipeline, authentication plugins included. The `connection_init` payload
+//! entries listed in the configuration are added to the headers of those requests, and the
+//! connection is only acknowledged once a request carrying them passes authentication.
+use std::borrow::Cow;
+use std::time::Duration;
+
+use axum::extract::ws::CloseFrame;
+use axum::extract::ws::Message;
+use axum::extract::ws::WebSocket;
+use axum::extract::FromRequestParts;
+use axum::extract::WebSocketUpgrade;
+use axum::response::IntoResponse;
+use axum::response::Response;
+use futures::future::ready;
+use futures::stream::once;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use http::header;
+use http::HeaderMap;
+use http::HeaderName;
+use http::HeaderValue;
+use http::Method;
+use http::Request;
+use http::StatusCode;
+use http::Uri;
+use hyper::Body;
+use serde_json_bytes::Value;
+use tokio_stream::StreamMap;
+use tower::BoxError;
+use tower::ServiceExt;
+
+use crate::axum_factory::utils::ConnectionInfo;
+use crate::configuration::SupergraphWebSocket;
+use crate::graphql;
+use crate::protocols::websocket::ClientMessage;
+use crate::protocols::websocket::ServerError;
+use crate::protocols::websocket::ServerMessage;
+use crate::router_factory::RouterFactory;
+use crate::services::router;
+use crate::Context;
+
+const PROTOCOL: &str = "graphql-transport-ws";
+
+/// Context key holding the payload of the `connection_init` message
+pub(crate) const CONNECTION_INIT_PAYLOAD: &str = "apollo::websocket::connection_init_payload";
+
+// close codes defined by the protocol
+const INVALID_MESSAGE: u16 = 4400;
+const UNAUTHORIZED: u16 = 4401;
+const FORBIDDEN: u16 = 4403;
+const INIT_TIMEOUT: u16 = 4408;
+const SUBSCRIBER_EXISTS: u16 = 4409;
+const TOO_MANY_INIT_REQUESTS: u16 = 4429;
+const INTERNAL_ERROR: u16 = 4500;
+
+/// Query sent with the `connection_init` payload to authenticate a connection
+const AUTHENTICATION_QUERY: &str = "{ __typename }";
+
+/// Returns true if the request asks to open a WebSocket connection
+pub(crate) fn is_websocket_request<B>(request: &Request<B>) -> bool {
+    request
+        .headers()
+        .get(header::UPGRADE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.eq_ignore_ascii_case("websocket"))
+}
+
+/// Accepts a `graphql-transport-ws` connection and serves it in the background
+pub(crate) async fn upgrade<RF, B>(
+    service_factory: RF,
+    configuration: SupergraphWebSocket,
+    request: Request<B>,
+) -> Response
+where
+    RF: RouterFactory,
+{
+    let (mut parts, _) = request.into_parts();
+
+    let offers_protocol = parts
+        .headers
+        .get_all(header::SEC_WEBSOCKET_PROTOCOL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|protocol| protocol.trim() == PROTOCOL);
+    if !offers_protocol {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("the '{PROTOCOL}' WebSocket subprotocol is required"),
+        )
+            .into_response();
+    }
+
+    // browsers send the cookies of the router's origin with the upgrade request whatever the page
+    // opening the connection, and CORS does not apply to WebSockets
+    if let Some(origin) = parts.headers.get(header::ORIGIN) {
+        let is_allowed = origin.to_str().is_ok_and(|origin| {
+            configuration
+                .allowed_origins
+                .iter()
+                .any(|allowed| allowed == origin)
+        });
+        if !is_allowed {
+            return (
+                StatusCode::FORBIDDEN,
+                "the origin is not allowed to open a WebSocket connection",
+            )
+                .into_response();
+        }
+    }
+
+    let upgrade = match WebSocketUpgrade::from_request_parts(&mut parts, &()).await {
+        Ok(upgrade) => upgrade,
+        Err(rejection) => return rejection.into_response(),
+    };
+
+    let mut headers = parts.headers;
+    for name in [
+        header::CONNECTION,
+        header::UPGRADE,
+        header::SEC_WEBSOCKET_KEY,
+        header::SEC_WEBSOCKET_VERSION,
+        header::SEC_WEBSOCKET_PROTOCOL,
+        header::SEC_WEBSOCKET_EXTENSIONS,
+        header::CONTENT_LENGTH,
+    ] {
+        headers.remove(name);
+    }
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    headers.insert(
+        header::ACCEPT,
+        HeaderValue::from_static("application/json, text/event-stream"),
+    );
+
+    let connection = Connection {
+        service_factory,
+        uri: parts.uri,
+        headers,
+        connection_info: parts.extensions.get::<ConnectionInfo>().cloned(),
+        init_headers: configuration
+            .connection_init_headers
+            .iter()
+            .filter_map(|name| HeaderName::from_bytes(name.as_bytes()).ok())
+            .collect(),
+    };
+    let init_timeout = configuration.connection_init_timeout;
+    upgrade
+        .protocols([PROTOCOL])
+        .on_upgrade(move |socket| connection.serve(socket, init_timeout))
+}
+
+enum Outgoing {
+    Message(ServerMessage),
+    Close(u16, Cow<'static, str>),
+}
+
+struct Connection<RF> {
+    service_factory: RF,
+    uri: Uri,
+    /// headers of the upgrade request, used for every operation
+    headers: HeaderMap,
+    /// peer address and client certificate of the upgrade request, used for every operation
+    connection_info: Option<ConnectionInfo>,
+    /// `connection_init` payload entries added to the headers of every operation
+    init_headers: Vec<HeaderName>,
+}
+
+impl<RF> Connection<RF>
+where
+    RF: RouterFactory,
+{
+    async fn serve(self, mut socket: WebSocket, init_timeout: Duration) {
+        let init_deadline = tokio::time::sleep(init_timeout);
+        tokio::pin!(init_deadline);
+        let mut init_payload: Option<Value> = None;
+        // operations are cancelled by removing them from the map
+        let mut operations: StreamMap<String, BoxStream<'static, Outgoing>> = StreamMap::new();
+
+        let close = loop {
+            tokio::select! {
+                _ = &mut init_deadline, if init_payload.is_none() => {
+                    break Some((INIT_TIMEOUT, "Connection initialisation timeout".into()));
+                }
+                Some((_, outgoing)) = operations.next(), if !operations.is_empty() => match outgoing {
+                    Outgoing::Message(message) => {
+                        if send(&mut socket, &message).await.is_err() {
+                            break None;
+                        }
+                    }
+                    Outgoing::Close(code, reason) => break Some((code, reason)),
+                },
+                message = socket.recv() => {
+                    let text = match message {
+                        Some(Ok(Message::Text(text))) => text,
+                        Some(Ok(Message::Binary(_))) => {
+                            break Some((INVALID_MESSAGE, "Binary messages are not supported".into()));
+                        }
+                        Some(Ok(Message::Ping(_) | Message::Pong(_))) => continue,
+                        Some(Ok(Message::Close(_)) | Err(_)) | None => break None,
+                    };
+                    let message = match serde_json::from_str::<ClientMessage>(&text) {
+                        Ok(message) => message,
+                        Err(err) => break Some((INVALID_MESSAGE, err.to_string().into())),
+                    };
+
+                    match message {
+                        ClientMessage::ConnectionInit { payload } => {
+                            if init_payload.is_some() {
+                                break Some((
+                                    TOO_MANY_INIT_REQUESTS,
+                                    "Too many initialisation requests".into(),
+                                ));
+                            }
+                            let payload = payload.unwrap_or(Value::Null);
+                            match self.authenticate(&payload).await {
+                                Ok(true) => {}
+                                Ok(false) => break Some((FORBIDDEN, "Forbidden".into())),
+                                Err(err) => {
+                                    tracing::error!(code = "INTERNAL_SERVER_ERROR", %err);
+                                    break Some((INTERNAL_ERROR, "Internal server error".into()));
+                                }
+                            }
+                            init_payload = Some(payload);
+                            if send(&mut socket, &ServerMessage::ConnectionAck).await.is_err() {
+                                break None;
+                            }
+                        }
+                        ClientMessage::Subscribe { id, payload } => {
+                            let Some(init_payload) = &init_payload else {
+                                break Some((UNAUTHORIZED, "Unauthorized".into()));
+                            };
+                            if operations.contains_key(&id) {
+                                break Some((
+                                    SUBSCRIBER_EXISTS,
+                                    format!("Subscriber for {id} already exists").into(),
+                                ));
+                            }
+                            let operation = self.execute(id.clone(), payload, init_payload);
+                            operations.insert(id, operation);
+                        }
+                        ClientMessage::Complete { id } => {
+                            operations.remove(&id);
+                        }
+                        ClientMessage::Ping { .. } => {
+                            if send(&mut socket, &ServerMessage::Pong { payload: None })
+                                .await
+                                .is_err()
+                            {
+                                break None;
+                            }
+                        }
+                        ClientMessage::Pong { .. } => {}
+                        // messages of the legacy subscriptions-transport-ws protocol
+                        ClientMessage::OldStart { .. }
+                        | ClientMessage::OldStop { .. }
+                        | ClientMessage::ConnectionTerminate => {
+                            break Some((INVALID_MESSAGE, "Invalid message received".into()));
+                        }
+                    }
+                }
+            }
+        };
+
+        if let Some((code, reason)) = close {
+            let _ = socket
+                .send(Message::Close(Some(CloseFrame { code, reason })))
+                .await;
+        }
+    }
+
+    /// Sends a query carrying the `connection_init` payload through the router service, so the
+    /// configured authentication runs before the connection is acknowledged
+    async fn authenticate(&self, init_payload: &Value) -> Result<bool, BoxError> {
+        let request = self.router_request(
+            &graphql::Request::builder()
+                .query(AUTHENTICATION_QUERY)
+                .build(),
+            init_payload,
+        )?;
+        let response = self.service_factory.create().oneshot(request).await?;
+        let status = response.response.status();
+        Ok(status != StatusCode::UNAUTHORIZED && status != StatusCode::FORBIDDEN)
+    }
+
+    /// Sends an operation through the router service, and turns its responses into messages
+    fn execute(
+        &self,
+        id: String,
+        request: graphql::Request,
+        init_payload: &Value,
+    ) -> BoxStream<'static, Outgoing> {
+        let service = self.service_factory.create().boxed();
+        let request = self.router_request(&request, init_payload);
+
+        once(async move {
+            let response = match request {
+                Ok(request) => service.oneshot(request).await,
+                Err(err) => Err(err),
+            };
+            match response {
+                Err(err) => {
+                    tracing::error!(code = "INTERNAL_SERVER_ERROR", %err);
+                    once(ready(Outgoing::Message(ServerMessage::Error {
+                        id,
+                        payload: ServerError::Error(
+                            graphql::Error::builder()
+                                .message("internal server error")
+                                .extension_code("INTERNAL_SERVER_ERROR")
+                                .build(),
+                        ),
+                    })))
+                    .boxed()
+                }
+                Ok(response)
+                    if response.response.status() == StatusCode::UNAUTHORIZED
+                        || response.response.status() == StatusCode::FORBIDDEN =>
+                {
+                    once(ready(Outgoing::Close(FORBIDDEN, "Forbidden".into()))).boxed()
+                }
+                Ok(response) => {
+                    let complete_id = id.clone();
+                    response
+                        .into_graphql_response_stream()
+                        .await
+                        .enumerate()
+                        .map(move |(index, response)| {
+                            let id = id.clone();
+                            Outgoing::Message(match response {
+                                // the request failed before execution
+                                Ok(response)
+                                    if index == 0
+                                        && response.data.is_none()
+                                        && !response.errors.is_empty() =>
+                                {
+                                    ServerMessage::Error {
+                                        id,
+                                        payload: ServerError::Errors(response.errors),
+                                    }
+                                }
+                                Ok(payload) => ServerMessage::Next { id, payload },
+                                Err(err) => ServerMessage::Error {
+                                    id,
+                                    payload: ServerError::Error(
+                                        graphql::Error::builder()
+                                            .message(format!("cannot read the response: {err}"))
+                                            .extension_code("INTERNAL_SERVER_ERROR")
+                                            .build(),
+                                    ),
+                                },
+                            })
+                        })
+                        .chain(once(ready(Outgoing::Message(ServerMessage::Complete {
+                            id: complete_id,
+                        }))))
+                        // no complete message is sent after an error
+                        .scan(false, |errored, outgoing| {
+                            if *errored {
+                                return ready(None);
+                            }
+                            *errored =
+                                matches!(outgoing, Outgoing::Message(ServerMessage::Error { .. }));
+                            ready(Some(outgoing))
+                        })
+                        .boxed()
+                }
+            }
+        })
+        .flatten()
+        .boxed()
+    }
+
+    fn router_request(
+        &self,
+        request: &graphql::Request,
+        init_payload: &Value,
+    ) -> Result<router::Request, BoxError> {
+        let mut router_request = http::Request::builder()
+            .method(Method::POST)
+            .uri(self.uri.clone())
+            .body(Body::from(serde_json::to_vec(request)?))?;
+        *router_request.headers_mut() = self.headers.clone();
+        // the other extensions of the upgrade request are only relevant to the upgrade itself
+        if let Some(connection_info) = &self.connection_info {
+            router_request
+                .extensions_mut()
+                .insert(connection_info.clone());
+        }
+        if let Value::Object(entries) = init_payload {
+            for (name, value) in entries {
+                let Ok(name) = HeaderName::from_bytes(name.as_str().as_bytes()) else {
+                    continue;
+                };
+                if !self.init_headers.contains(&name) {
+                    continue;
+                }
+                if let Some(Ok(value)) = value.as_str().map(HeaderValue::from_str) {
+                    router_request.headers_mut().insert(name, value);
+                }
+            }
+        }
+
+        let context = Context::new();
+        context.insert(CONNECTION_INIT_PAYLOAD, init_payload.clone())?;
+        Ok(router::Request {
+            router_request,
+            context,
+        })
+    }
+}
+
+async fn send(socket: &mut WebSocket, message: &ServerMessage) -> Result<(), BoxError> {
+    let text = serde_json::to_string(message)?;
+    socket.send(Message::Text(text)).await?;
+    Ok(())
+}
@@ -31,6 +31,8 @@ use crate::services::SubgraphRequest;
 use crate::services::SubgraphResponse;
 use crate::Context;
 
+pub(crate) mod window;
+
 /// A query that is part of a batch.
 /// Note: It's ok to make transient clones of this struct, but *do not* store clones anywhere apart
 /// from the single copy in the extensions. The batching co-ordinator relies on the fact that all
@@ -0,0 +1,459 @@
+//! Batching of the fetches that concurrent, unrelated client requests make to a subgraph.
+//!
+//! Unlike [`super::Batch`], which groups the fetches of the operations of one client batch, a
+//! [`BatchWindow`] collects the fetches made to a subgraph during a short time window, whatever
+//! the client request they come from, and sends them in a single batched HTTP request.
+
+use std::sync::Arc;
+
+use futures::future::join_all;
+use opentelemetry::trace::TraceContextExt;
+use opentelemetry::Context as otelContext;
+use tokio::sync::mpsc;
+use tokio::sync::oneshot;
+use tower::BoxError;
+use tracing::Instrument;
+use tracing::Span;
+
+use super::assemble_batch;
+use super::BatchQueryInfo;
+use crate::configuration::BatchingWindow;
+use crate::error::FetchError;
+use crate::graphql;
+use crate::plugins::authentication::subgraph::SigningParamsConfig;
+use crate::plugins::telemetry::otel::span_ext::OpenTelemetrySpanExt;
+use crate::services::call_single_http;
+use crate::services::http::HttpClientServiceFactory;
+use crate::services::notify_batch_query;
+use crate::services::process_batch;
+use crate::services::SubgraphRequest;
+use crate::services::SubgraphResponse;
+
+/// A fetch waiting for the end of the batch window
+struct WindowedFetch {
+    request: SubgraphRequest,
+    gql_request: graphql::Request,
+    sender: oneshot::Sender<Result<SubgraphResponse, BoxError>>,
+    span_context: otelContext,
+}
+
+/// Collects the fetches made to a subgraph within a time window into batches
+///
+/// The spawned task stops once the `BatchWindow` is dropped.
+#[derive(Debug)]
+pub(crate) struct BatchWindow {
+    service: String,
+    sender: mpsc::Sender<WindowedFetch>,
+}
+
+impl BatchWindow {
+    pub(crate) fn spawn(
+        service: impl Into<String>,
+        config: &BatchingWindow,
+        client_factory: HttpClientServiceFactory,
+    ) -> Self {
+        let service = service.into();
+        let duration = config.duration;
+        let max_size = config.max_size.get();
+        let (sender, mut receiver) = mpsc::channel::<WindowedFetch>(max_size);
+
+        let name = service.clone();
+        tokio::spawn(async move {
+            // The window opens with the first fetch, and closes after `duration` or once it
+            // holds `max_size` fetches
+            while let Some(first) = receiver.recv().await {
+                let deadline = tokio::time::Instant::now() + duration;
+                let mut fetches = vec![first];
+                while fetches.len() < max_size {
+                    match tokio::time::timeout_at(deadline, receiver.recv()).await {
+                        Ok(Some(fetch)) => fetches.push(fetch),
+                        Ok(None) | Err(_) => break,
+                    }
+                }
+                tokio::spawn(send_fetches(client_factory.clone(), name.clone(), fetches));
+            }
+        });
+
+        Self { service, sender }
+    }
+
+    /// Adds a fetch to the current window, and waits for its response
+    pub(crate) async fn fetch(
+        &self,
+        request: SubgraphRequest,
+        gql_request: graphql::Request,
+    ) -> Result<SubgraphResponse, BoxError> {
+        let (sender, receiver) = oneshot::channel();
+        self.sender
+            .send(WindowedFetch {
+                request,
+                gql_request,
+                sender,
+                span_context: Span::current().context(),
+            })
+            .await
+            .map_err(|_| FetchError::SubrequestBatchingError {
+                service: self.service.clone(),
+                reason: "the batch window is closed".to_string(),
+            })?;
+
+        receiver
+            .await
+            .map_err(|err| FetchError::SubrequestBatchingError {
+                service: self.service.clone(),
+                reason: format!("tx receive failed: {err}"),
+            })?
+    }
+}
+
+/// Sends the fetches of a window, in one batch per distinct HTTP request
+async fn send_fetches(
+    client_factory: HttpClientServiceFactory,
+    service: String,
+    fetches: Vec<WindowedFetch>,
+) {
+    // Fetches are only batched with fetches sent to the same URL with the same headers and the
+    // same signing identity, so that the headers propagated from a client request, or the token
+    // exchanged for it, are never used for another client's fetch
+    let mut groups: Vec<Vec<WindowedFetch>> = Vec::new();
+    for fetch in fetches {
+        match groups
+            .iter_mut()
+            .find(|group| same_http_request(&group[0].request, &fetch.request))
+        {
+            Some(group) => group.push(fetch),
+            None => groups.push(vec![fetch]),
+        }
+    }
+
+    join_all(
+        groups
+            .into_iter()
+            .map(|group| send_group(&client_factory, &service, group)),
+    )
+    .await;
+}
+
+fn same_http_request(left: &SubgraphRequest, right: &SubgraphRequest) -> bool {
+    let (left, right) = (&left.subgraph_request, &right.subgraph_request);
+    left.method() == right.method()
+        && left.uri() == right.uri()
+        && left.headers() == right.headers()
+        && same_signing_identity(left, right)
+}
+
+/// The batch is signed with the parameters of its first fetch, see [`assemble_batch`]
+fn same_signing_identity(
+    left: &http::Request<graphql::Request>,
+    right: &http::Request<graphql::Request>,
+) -> bool {
+    let (left, right) = (
+        left.extensions().get::<Arc<SigningParamsConfig>>(),
+        right.extensions().get::<Arc<SigningParamsConfig>>(),
+    );
+    match (left, right) {
+        (Some(left), Some(right)) => left.same_identity(right),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+async fn send_group(
+    client_factory: &HttpClientServiceFactory,
+    service: &str,
+    mut group: Vec<WindowedFetch>,
+) {
+    let span = tracing::info_span!("batch_request", size = group.len());
+
+    // A fetch that is alone in its window is sent as a regular request
+    if group.len() == 1 {
+        let WindowedFetch {
+            request,
+            gql_request,
+            sender,
+            span_context,
+        } = group.pop().expect("the group has one fetch");
+        span.set_parent(span_context);
+        let context = request.context.clone();
+        let client = client_factory.create(service);
+        let response = call_single_http(request, gql_request, context, client, service)
+            .instrument(span)
+            .await;
+        if sender.send(response).is_err() {
+            tracing::debug!(
+                service,
+                "the fetch was cancelled before its response was received"
+            );
+        }
+        return;
+    }
+
+    let requests = group
+        .into_iter()
+        .map(|fetch| {
+            span.add_link(fetch.span_context.span().span_context().clone());
+            BatchQueryInfo {
+                request: fetch.request,
+                gql_request: fetch.gql_request,
+                sender: fetch.sender,
+            }
+        })
+        .collect();
+    let result = async {
+        let (_op_name, contexts, request, senders) = assemble_batch(requests).await?;
+        let listener_count = senders.len();
+        // Errors in the response of one fetch only go to that fetch: only the failure of the
+        // whole HTTP request is sent to every fetch of the batch
+        let batch_result = process_batch(
+            client_factory.clone(),
+            service.to_string(),
+            contexts,
+            request,
+            listener_count,
+        )
+        .await;
+        notify_batch_query(service.to_string(), senders, batch_result).await
+    }
+    .instrument(span)
+    .await;
+    if let Err(error) = result {
+        tracing::error!(service, error = %error, "failed to send a batch of fetches");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::num::NonZeroUsize;
+    use std::time::Duration;
+
+    use serde_json_bytes::json;
+    use wiremock::matchers::header;
+    use wiremock::matchers::method;
+    use wiremock::Mock;
+    use wiremock::MockServer;
+    use wiremock::ResponseTemplate;
+
+    use super::*;
+    use crate::plugins::authentication::subgraph::make_signing_params;
+    use crate::plugins::authentication::subgraph::AuthConfig;
+    use crate::plugins::traffic_shaping::Http2Config;
+    use crate::Configuration;
+
+    /// Answers each operation with its name, and fails the operation named `failing`
+    fn respond(request: &wiremock::Request) -> ResponseTemplate {
+        let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        let respond_one = |operation: &serde_json::Value| {
+            let name = operation["operationName"].as_str().unwrap();
+            if name == "failing" {
+                serde_json::json!({ "errors": [{ "message": "failed" }] })
+            } else {
+                serde_json::json!({ "data": { "name": name } })
+            }
+        };
+        match body.as_array() {
+            Some(operations) => ResponseTemplate::new(200)
+                .set_body_json(operations.iter().map(respond_one).collect::<Vec<_>>()),
+            None => ResponseTemplate::new(200).set_body_json(respond_one(&body)),
+        }
+    }
+
+    fn window(max_size: usize) -> Arc<BatchWindow> {
+        let config = BatchingWindow {
+            duration: Duration::from_millis(50),
+            max_size: NonZeroUsize::new(max_size).unwrap(),
+        };
+        let client_factory = HttpClientServiceFactory::from_config(
+            "products",
+            &Configuration::default(),
+            Http2Config::Disable,
+        );
+        Arc::new(BatchWindow::spawn("products", &config, client_factory))
+    }
+
+    fn fetch(
+        window: &Arc<BatchWindow>,
+        uri: &str,
+        token: &str,
+        operation_name: &str,
+    ) -> tokio::task::JoinHandle<Result<SubgraphResponse, BoxError>> {
+        let gql_request = graphql::Request::fake_builder()
+            .operation_name(operation_name)
+            .query(format!("query {operation_name} {{ name }}"))
+            .build();
+        let request = SubgraphRequest::fake_builder()
+            .subgraph_request(
+                http::Request::builder()
+                    .method(http::Method::POST)
+                    .uri(uri)
+                    .header("authorization", token)
+                    .body(gql_request.clone())
+                    .unwrap(),
+            )
+            .subgraph_name("products")
+            .build();
+        let window = window.clone();
+        tokio::spawn(async move { window.fetch(request, gql_request).await })
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn it_batches_concurrent_fetches() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(respond)
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let window = window(10);
+        let first = fetch(&window, &server.uri(), "token", "first");
+        let failing = fetch(&window, &server.uri(), "token", "failing");
+        let last = fetch(&window, &server.uri(), "token", "last");
+
+        let first = first.await.unwrap().unwrap().response.into_body();
+        assert_eq!(first.data, Some(json!({ "name": "first" })));
+        assert!(first.errors.is_empty());
+        let failing = failing.await.unwrap().unwrap().response.into_body();
+        assert_eq!(failing.errors.len(), 1);
+        let last = last.await.unwrap().unwrap().response.into_body();
+        assert_eq!(last.data, Some(json!({ "name": "last" })));
+        assert!(last.errors.is_empty());
+
+        let received = server.received_requests().await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&received[0].body).unwrap();
+        assert_eq!(body.as_array().unwrap().len(), 3);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn it_separates_fetches_with_different_headers() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(respond)
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let window = window(10);
+        let first = fetch(&window, &server.uri(), "first token", "first");
+        let second = fetch(&window, &server.uri(), "second token", "second");
+
+        let first = first.await.unwrap().unwrap().response.into_body();
+        assert_eq!(first.data, Some(json!({ "name": "first" })));
+        let second = second.await.unwrap().unwrap().response.into_body();
+        assert_eq!(second.data, Some(json!({ "name": "second" })));
+
+        // Each fetch is alone with its headers, so it is sent as a regular request
+        for request in server.received_requests().await.unwrap() {
+            let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+            assert!(body.is_object());
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn it_separates_fetches_exchanging_different_client_tokens() {
+        let token_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(|request: &wiremock::Request| {
+                let form: HashMap<String, String> = url::form_urlencoded::parse(&request.body)
+                    .into_owned()
+                    .collect();
+                ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "access_token": format!("exchanged-{}", form["subject_token"]),
+                    "token_type": "Bearer",
+                    "expires_in": 3600,
+                }))
+            })
+            .mount(&token_server)
+            .await;
+        let server = MockServer::start().await;
+        for client_token in ["alice", "bob"] {
+            Mock::given(method("POST"))
+                .and(header(
+                    "authorization",
+                    format!("Bearer exchanged-{client_token}").as_str(),
+                ))
+                .respond_with(respond)
+                .expect(1)
+                .mount(&server)
+                .await;
+        }
+
+        let signing_params = Arc::new(
+            make_signing_params(
+                &AuthConfig::OAuth2TokenExchange(
+                    serde_json::from_value(serde_json::json!({
+                        "token_url": format!("{}/token", token_server.uri()),
+                        "client_id": "router",
+                        "client_secret": "secret",
+                    }))
+                    .unwrap(),
+                ),
+                "products",
+            )
+            .await
+            .unwrap(),
+        );
+        let window = window(10);
+        // Both fetches have the same subgraph headers, but come from different clients
+        let fetches: Vec<_> = ["alice", "bob"]
+            .into_iter()
+            .map(|client_token| {
+                let gql_request = graphql::Request::fake_builder()
+                    .operation_name(client_token)
+                    .query(format!("query {client_token} {{ name }}"))
+                    .build();
+                let mut request = SubgraphRequest::fake_builder()
+                    .supergraph_request(Arc::new(
+                        http::Request::builder()
+                            .header("authorization", format!("Bearer {client_token}"))
+                            .body(graphql::Request::default())
+                            .unwrap(),
+                    ))
+                    .subgraph_request(
+                        http::Request::builder()
+                            .method(http::Method::POST)
+                            .uri(server.uri())
+                            .body(gql_request.clone())
+                            .unwrap(),
+                    )
+                    .subgraph_name("products")
+                    .build();
+                let signing_params = signing_params.for_request(&request);
+                request
+                    .subgraph_request
+                    .extensions_mut()
+                    .insert(signing_params);
+                let window = window.clone();
+                tokio::spawn(async move { window.fetch(request, gql_request).await })
+            })
+            .collect();
+        for fetch in fetches {
+            fetch.await.unwrap().unwrap();
+        }
+
+        // Each fetch is sent alone, with the token exchanged for its own client token
+        for request in server.received_requests().await.unwrap() {
+            let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+            assert!(body.is_object());
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn it_limits_the_size_of_batches() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(respond)
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let window = window(2);
+        let fetches: Vec<_> = (0..4)
+            .map(|index| fetch(&window, &server.uri(), "token", &format!("op{index}")))
+            .collect();
+        for (index, fetch) in fetches.into_iter().enumerate() {
+            let response = fetch.await.unwrap().unwrap().response.into_body();
+            assert_eq!(response.data, Some(json!({ "name": format!("op{index}") })));
+        }
+    }
+}
@@ -262,6 +262,11 @@ impl RedisCacheStorage {
         self.ttl
     }
 
+    /// Underlying client, for commands that are not about cached entries
+    pub(crate) fn client(&self) -> Arc<RedisClient> {
+        self.inner.clone()
+    }
+
     fn preprocess_urls(urls: Vec<Url>) -> Result<Url, RedisError> {
         let url_len = urls.len();
         let mut urls_iter = urls.into_iter();
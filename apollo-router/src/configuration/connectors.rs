@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde::Serialize;
+
+/// REST connectors, executing the requests of a subgraph against a REST API
+#[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields, default)]
+pub(crate) struct Connectors {
+    /// Connectors, keyed by the name of the subgraph they replace
+    pub(crate) subgraphs: HashMap<String, ConnectorSource>,
+}
+
+/// REST API replacing a subgraph
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ConnectorSource {
+    /// Base URL of the REST API, prepended to the path of every endpoint
+    pub(crate) base_url: String,
+
+    /// Headers sent with every request to the REST API
+    #[serde(default)]
+    pub(crate) headers: Vec<ConnectorHeader>,
+
+    /// Endpoints resolving root fields, keyed by `Type.field` (example: `Query.products`)
+    #[serde(default)]
+    pub(crate) fields: HashMap<String, ConnectorEndpoint>,
+
+    /// Endpoints resolving entities, keyed by type name. The key fields of the entity are available as `$this`
+    #[serde(default)]
+    pub(crate) entities: HashMap<String, ConnectorEndpoint>,
+
+    /// Maximum number of concurrent calls made to resolve the entities of a subgraph request
+    /// Default: 10
+    #[serde(default = "default_max_concurrent_requests")]
+    pub(crate) max_concurrent_requests: NonZeroUsize,
+}
+
+fn default_max_concurrent_requests() -> NonZeroUsize {
+    NonZeroUsize::new(10).expect("cannot fail")
+}
+
+/// HTTP request to the REST API, and mapping of its response
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ConnectorEndpoint {
+    /// HTTP method
+    /// Default: GET
+    #[serde(default)]
+    pub(crate) method: ConnectorMethod,
+
+    /// Path of the endpoint, appended to the base URL. Values of the field arguments (`{$args.id}`)
+    /// or of the entity key fields (`{$this.id}`) can be inserted in it
+    pub(crate) path: String,
+
+    /// Headers sent with requests to this endpoint
+    #[serde(default)]
+    pub(crate) headers: Vec<ConnectorHeader>,
+
+    /// JSON selection building the request body from `$args` or `$this`
+    pub(crate) body: Option<String>,
+
+    /// JSON selection mapping the response body to the fields of the GraphQL type
+    pub(crate) selection: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "UPPERCASE")]
+pub(crate) enum ConnectorMethod {
+    #[default]
+    Get,
+    Post,
+    Put,
+    Patch,
+    Delete,
+}
+
+impl From<ConnectorMethod> for http::Method {
+    fn from(method: ConnectorMethod) -> Self {
+        match method {
+            ConnectorMethod::Get => http::Method::GET,
+            ConnectorMethod::Post => http::Method::POST,
+            ConnectorMethod::Put => http::Method::PUT,
+            ConnectorMethod::Patch => http::Method::PATCH,
+            ConnectorMethod::Delete => http::Method::DELETE,
+        }
+    }
+}
+
+/// Header sent to the REST API
+///
+/// Without `value` or `from`, the header is copied from the client request.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ConnectorHeader {
+    /// Name of the header
+    pub(crate) name: String,
+
+    /// Copy the value of this header from the client request
+    pub(crate) from: Option<String>,
+
+    /// Fixed value of the header
+    pub(crate) value: Option<String>,
+}
@@ -10,16 +10,26 @@ use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
 
+pub(crate) use connectors::ConnectorEndpoint;
+pub(crate) use connectors::ConnectorHeader;
+pub(crate) use connectors::ConnectorMethod;
+pub(crate) use connectors::ConnectorSource;
+pub(crate) use connectors::Connectors;
 use derivative::Derivative;
 use displaydoc::Display;
 use itertools::Itertools;
 use once_cell::sync::Lazy;
 pub(crate) use persisted_queries::PersistedQueries;
 #[cfg(test)]
+pub(crate) use persisted_queries::PersistedQueriesPrewarmQueryPlanCache;
+#[cfg(test)]
 pub(crate) use persisted_queries::PersistedQueriesSafelist;
 use regex::Regex;
+use rustls::server::AllowAnyAnonymousOrAuthenticatedClient;
+use rustls::server::AllowAnyAuthenticatedClient;
 use rustls::Certificate;
 use rustls::PrivateKey;
+use rustls::RootCertStore;
 use rustls::ServerConfig;
 use rustls_pemfile::certs;
 use rustls_pemfile::read_one;
@@ -53,6 +63,7 @@ use crate::plugins::subscription::APOLLO_SUBSCRIPTION_PLUGIN_NAME;
 use crate::uplink::UplinkConfig;
 use crate::ApolloRouterError;
 
+mod connectors;
 pub(crate) mod cors;
 pub(crate) mod expansion;
 mod experimental;
@@ -190,6 +201,10 @@ pub struct Configuration {
     #[serde(default)]
     pub(crate) batching: Batching,
 
+    /// REST connectors replacing subgraphs
+    #[serde(default)]
+    pub(crate) connectors: Connectors,
+
     /// Type conditioned fetching configuration.
     #[serde(default)]
     pub(crate) experimental_type_conditioned_fetching: bool,
@@ -270,6 +285,7 @@ impl<'de> serde::Deserialize<'de> for Configuration {
             limits: Limits,
             experimental_chaos: Chaos,
             batching: Batching,
+            connectors: Connectors,
             experimental_type_conditioned_fetching: bool,
             experimental_apollo_metrics_generation_mode: ApolloMetricsGenerationMode,
             experimental_api_schema_generation_mode: ApiSchemaMode,
@@ -300,6 +316,7 @@ impl<'de> serde::Deserialize<'de> for Configuration {
             plugins: ad_hoc.plugins,
             apollo_plugins: ad_hoc.apollo_plugins,
             batching: ad_hoc.batching,
+            connectors: ad_hoc.connectors,
 
             // serde(skip)
             notify,
@@ -343,6 +360,7 @@ impl Configuration {
         experimental_api_schema_generation_mode: Option<ApiSchemaMode>,
         experimental_type_conditioned_fetching: Option<bool>,
         batching: Option<Batching>,
+        connectors: Option<Connectors>,
         experimental_apollo_metrics_generation_mode: Option<ApolloMetricsGenerationMode>,
         experimental_query_planner_mode: Option<QueryPlannerMode>,
     ) -> Result<Self, ConfigurationError> {
@@ -373,6 +391,7 @@ impl Configuration {
             tls: tls.unwrap_or_default(),
             uplink,
             batching: batching.unwrap_or_default(),
+            connectors: connectors.unwrap_or_default(),
             experimental_type_conditioned_fetching: experimental_type_conditioned_fetching
                 .unwrap_or_default(),
             notify,
@@ -464,6 +483,7 @@ impl Configuration {
         chaos: Option<Chaos>,
         uplink: Option<UplinkConfig>,
         batching: Option<Batching>,
+        connectors: Option<Connectors>,
         experimental_api_schema_generation_mode: Option<ApiSchemaMode>,
         experimental_type_conditioned_fetching: Option<bool>,
         experimental_apollo_metrics_generation_mode: Option<ApolloMetricsGenerationMode>,
@@ -497,6 +517,7 @@ impl Configuration {
             experimental_type_conditioned_fetching: experimental_type_conditioned_fetching
                 .unwrap_or_default(),
             batching: batching.unwrap_or_default(),
+            connectors: connectors.unwrap_or_default(),
         };
 
         configuration.validate()
@@ -553,6 +574,29 @@ impl Configuration {
             );
         }
 
+        if self.supergraph.http3.enabled {
+            if self.tls.supergraph.is_none() {
+                return Err(ConfigurationError::InvalidConfiguration {
+                    message: "HTTP/3 requires TLS",
+                    error: "set a certificate and key in tls.supergraph to enable supergraph.http3"
+                        .into(),
+                });
+            }
+            if self
+                .supergraph
+                .http3
+                .listen_address(&self.supergraph.listen)
+                .is_none()
+            {
+                return Err(ConfigurationError::InvalidConfiguration {
+                    message: "HTTP/3 requires a UDP socket address",
+                    error:
+                        "set supergraph.http3.listen when the supergraph listens on a Unix socket"
+                            .into(),
+                });
+            }
+        }
+
         // PQs.
         if self.persisted_queries.enabled {
             if self.persisted_queries.safelist.enabled && self.apq.enabled {
@@ -709,6 +753,10 @@ pub(crate) struct Supergraph {
     /// Set to false to disable defer support
     pub(crate) defer_support: bool,
 
+    /// Set to true to enable stream support
+    /// Default: false
+    pub(crate) stream_support: bool,
+
     /// Query planning options
     pub(crate) query_planning: QueryPlanning,
 
@@ -721,6 +769,12 @@ pub(crate) struct Supergraph {
     /// Log a message if the client closes the connection before the response is sent.
     /// Default: false.
     pub(crate) experimental_log_on_broken_pipe: bool,
+
+    /// GraphQL over WebSocket endpoint for clients
+    pub(crate) websocket: SupergraphWebSocket,
+
+    /// HTTP/3 listener for the supergraph endpoint
+    pub(crate) http3: SupergraphHttp3,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
@@ -754,17 +808,21 @@ impl Supergraph {
         path: Option<String>,
         introspection: Option<bool>,
         defer_support: Option<bool>,
+        stream_support: Option<bool>,
         query_planning: Option<QueryPlanning>,
         reuse_query_fragments: Option<bool>,
         generate_query_fragments: Option<bool>,
         early_cancel: Option<bool>,
         experimental_log_on_broken_pipe: Option<bool>,
+        websocket: Option<SupergraphWebSocket>,
+        http3: Option<SupergraphHttp3>,
     ) -> Self {
         Self {
             listen: listen.unwrap_or_else(default_graphql_listen),
             path: path.unwrap_or_else(default_graphql_path),
             introspection: introspection.unwrap_or_else(default_graphql_introspection),
             defer_support: defer_support.unwrap_or_else(default_defer_support),
+            stream_support: stream_support.unwrap_or_default(),
             query_planning: query_planning.unwrap_or_default(),
             reuse_query_fragments: generate_query_fragments.and_then(|v|
                 if v {
@@ -778,6 +836,8 @@ impl Supergraph {
             generate_query_fragments: generate_query_fragments.unwrap_or_default(),
             early_cancel: early_cancel.unwrap_or_default(),
             experimental_log_on_broken_pipe: experimental_log_on_broken_pipe.unwrap_or_default(),
+            websocket: websocket.unwrap_or_default(),
+            http3: http3.unwrap_or_default(),
         }
     }
 }
@@ -791,17 +851,21 @@ impl Supergraph {
         path: Option<String>,
         introspection: Option<bool>,
         defer_support: Option<bool>,
+        stream_support: Option<bool>,
         query_planning: Option<QueryPlanning>,
         reuse_query_fragments: Option<bool>,
         generate_query_fragments: Option<bool>,
         early_cancel: Option<bool>,
         experimental_log_on_broken_pipe: Option<bool>,
+        websocket: Option<SupergraphWebSocket>,
+        http3: Option<SupergraphHttp3>,
     ) -> Self {
         Self {
             listen: listen.unwrap_or_else(test_listen),
             path: path.unwrap_or_else(default_graphql_path),
             introspection: introspection.unwrap_or_else(default_graphql_introspection),
             defer_support: defer_support.unwrap_or_else(default_defer_support),
+            stream_support: stream_support.unwrap_or_default(),
             query_planning: query_planning.unwrap_or_default(),
             reuse_query_fragments: generate_query_fragments.and_then(|v|
                 if v {
@@ -815,6 +879,8 @@ impl Supergraph {
             generate_query_fragments: generate_query_fragments.unwrap_or_default(),
             early_cancel: early_cancel.unwrap_or_default(),
             experimental_log_on_broken_pipe: experimental_log_on_broken_pipe.unwrap_or_default(),
+            websocket: websocket.unwrap_or_default(),
+            http3: http3.unwrap_or_default(),
         }
     }
 }
@@ -842,6 +908,91 @@ impl Supergraph {
     }
 }
 
+/// GraphQL over WebSocket endpoint, using the `graphql-transport-ws` protocol
+///
+/// The endpoint is served on the supergraph path.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields, default)]
+pub(crate) struct SupergraphWebSocket {
+    /// Accept WebSocket connections from clients
+    /// Default: false
+    pub(crate) enabled: bool,
+
+    /// Time a client has to send the `connection_init` message after connecting
+    /// Default: 3s
+    #[serde(with = "humantime_serde")]
+    #[schemars(with = "String")]
+    pub(crate) connection_init_timeout: Duration,
+
+    /// Origins allowed to open a connection. Browsers always send the `Origin` header, so a
+    /// connection from a page served on another origin is refused unless it is listed here.
+    /// Requests without an `Origin` header are accepted.
+    /// Default: []
+    pub(crate) allowed_origins: Vec<String>,
+
+    /// Names of the `connection_init` payload entries copied to the headers of every operation
+    /// Default: ["authorization"]
+    pub(crate) connection_init_headers: Vec<String>,
+}
+
+impl Default for SupergraphWebSocket {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            connection_init_timeout: Duration::from_secs(3),
+            allowed_origins: Vec::new(),
+            connection_init_headers: vec!["authorization".to_string()],
+        }
+    }
+}
+
+/// HTTP/3 listener for the supergraph endpoint
+///
+/// It uses the `tls.supergraph` certificate and serves the same endpoints as the main listener.
+/// Responses from the main listener advertise it with the `Alt-Svc` header.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields, default)]
+pub(crate) struct SupergraphHttp3 {
+    /// Accept HTTP/3 connections over QUIC
+    /// Default: false
+    pub(crate) enabled: bool,
+
+    /// The UDP socket address and port to listen on
+    /// Defaults to the address of the supergraph listener
+    #[schemars(with = "Option<String>")]
+    pub(crate) listen: Option<SocketAddr>,
+
+    /// How long clients can keep using the HTTP/3 listener once it was advertised
+    /// Default: 24h
+    #[serde(with = "humantime_serde")]
+    #[schemars(with = "String")]
+    pub(crate) alt_svc_max_age: Duration,
+}
+
+impl Default for SupergraphHttp3 {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            listen: None,
+            alt_svc_max_age: Duration::from_secs(24 * 60 * 60),
+        }
+    }
+}
+
+impl SupergraphHttp3 {
+    /// The UDP socket address of the HTTP/3 listener, if it is enabled
+    pub(crate) fn listen_address(&self, supergraph_listen: &ListenAddr) -> Option<SocketAddr> {
+        if !self.enabled {
+            return None;
+        }
+        self.listen.or_else(|| match supergraph_listen {
+            ListenAddr::SocketAddr(addr) => Some(*addr),
+            #[cfg(unix)]
+            ListenAddr::UnixSocket(_) => None,
+        })
+    }
+}
+
 /// Configuration for operation limits, parser limits, HTTP limits, etc.
 #[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 #[serde(deny_unknown_fields, default)]
@@ -1141,7 +1292,7 @@ impl Default for InMemoryCache {
     }
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 /// Redis cache configuration
 pub(crate) struct RedisCache {
@@ -1231,6 +1382,21 @@ pub(crate) struct TlsSupergraph {
     #[serde(deserialize_with = "deserialize_certificate_chain", skip_serializing)]
     #[schemars(with = "String")]
     pub(crate) certificate_chain: Vec<Certificate>,
+    /// client certificate verification
+    pub(crate) client_authentication: Option<TlsSupergraphClientAuthentication>,
+}
+
+/// Verification of the certificates presented by clients
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct TlsSupergraphClientAuthentication {
+    /// list of certificate authorities in PEM format, used to verify client certificates
+    #[serde(deserialize_with = "deserialize_certificate_chain", skip_serializing)]
+    #[schemars(with = "String")]
+    pub(crate) certificate_authorities: Vec<Certificate>,
+    /// accept connections from clients that do not present a certificate. Certificates that are presented must still be valid
+    #[serde(default)]
+    pub(crate) optional: bool,
 }
 
 impl TlsSupergraph {
@@ -1238,9 +1404,26 @@ impl TlsSupergraph {
         let mut certificates = vec![self.certificate.clone()];
         certificates.extend(self.certificate_chain.iter().cloned());
 
-        let mut config = ServerConfig::builder()
-            .with_safe_defaults()
-            .with_no_client_auth()
+        let builder = ServerConfig::builder().with_safe_defaults();
+        let builder = match &self.client_authentication {
+            None => builder.with_no_client_auth(),
+            Some(client_authentication) => {
+                let mut roots = RootCertStore::empty();
+                for certificate in &client_authentication.certificate_authorities {
+                    roots.add(certificate).map_err(ApolloRouterError::Rustls)?;
+                }
+                if client_authentication.optional {
+                    builder.with_client_cert_verifier(
+                        AllowAnyAnonymousOrAuthenticatedClient::new(roots).boxed(),
+                    )
+                } else {
+                    builder
+                        .with_client_cert_verifier(AllowAnyAuthenticatedClient::new(roots).boxed())
+                }
+            }
+        };
+
+        let mut config = builder
             .with_single_cert(certificates, self.key.clone())
             .map_err(ApolloRouterError::Rustls)?;
         config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
@@ -1330,7 +1513,7 @@ pub(crate) fn load_key(data: &str) -> io::Result<PrivateKey> {
 }
 
 /// Configuration options pertaining to the subgraph server component.
-#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 #[serde(default)]
 pub(crate) struct TlsClient {
@@ -1361,7 +1544,7 @@ impl Default for TlsClient {
 }
 
 /// TLS client authentication
-#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct TlsClientAuth {
     /// list of certificates in PEM format
@@ -1653,6 +1836,48 @@ pub(crate) struct Batching {
 
     /// Subgraph options for batching
     pub(crate) subgraph: Option<SubgraphConfiguration<CommonBatchingConfig>>,
+
+    /// Maximum number of operations in a batch
+    #[serde(default)]
+    pub(crate) max_size: Option<usize>,
+
+    /// Maximum sum of the depths of the operations in a batch
+    #[serde(default)]
+    pub(crate) max_total_depth: Option<u32>,
+
+    /// Maximum sum of the estimated costs of the operations in a batch
+    #[serde(default)]
+    pub(crate) max_total_cost: Option<BatchingCost>,
+
+    /// Maximum duration of the execution of a batch
+    #[serde(deserialize_with = "humantime_serde::deserialize", default)]
+    #[schemars(with = "Option<String>", default)]
+    pub(crate) timeout: Option<Duration>,
+
+    /// What happens when an operation of a batch cannot be parsed, exceeds a limit or times out
+    #[serde(default)]
+    pub(crate) item_errors: BatchItemErrors,
+}
+
+/// Cost limit of a batch, estimated like the `static_estimated` strategy of demand control
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct BatchingCost {
+    /// The assumed length of lists returned by the operations
+    pub(crate) list_size: u32,
+    /// The maximum total cost of the operations of a batch
+    pub(crate) max: f64,
+}
+
+/// Handling of the operations of a batch that fail
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum BatchItemErrors {
+    /// Reject the whole batch
+    #[default]
+    FailBatch,
+    /// Return an error for the failing operations, and execute the other ones
+    PerItem,
 }
 
 /// Common options for configuring subgraph batching
@@ -1660,6 +1885,23 @@ pub(crate) struct Batching {
 pub(crate) struct CommonBatchingConfig {
     /// Whether this batching config should be enabled
     pub(crate) enabled: bool,
+
+    /// Batches the fetches made by concurrent client requests within a time window
+    #[serde(default)]
+    pub(crate) window: Option<BatchingWindow>,
+}
+
+/// Batching of the fetches that unrelated client requests make to a subgraph
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct BatchingWindow {
+    /// How long the first fetch of a batch waits for other fetches
+    #[serde(with = "humantime_serde")]
+    #[schemars(with = "String")]
+    pub(crate) duration: Duration,
+
+    /// Maximum number of fetches in a batch
+    pub(crate) max_size: NonZeroUsize,
 }
 
 impl Batching {
@@ -1688,4 +1930,13 @@ impl Batching {
             None => false,
         }
     }
+
+    /// The batch window of a subgraph, if the fetches of unrelated client requests should be
+    /// batched
+    pub(crate) fn batch_window(&self, service_name: &str) -> Option<&BatchingWindow> {
+        if !self.batch_include(service_name) {
+            return None;
+        }
+        self.subgraph.as_ref()?.get(service_name).window.as_ref()
+    }
 }
@@ -14,6 +14,9 @@ pub struct PersistedQueries {
 
     /// Restricts execution of operations that are not found in the Persisted Query List
     pub safelist: PersistedQueriesSafelist,
+
+    /// Experimental feature to prewarm the query plan cache with persisted queries
+    pub experimental_prewarm_query_plan_cache: PersistedQueriesPrewarmQueryPlanCache,
 }
 
 #[cfg(test)]
@@ -24,11 +27,14 @@ impl PersistedQueries {
         enabled: Option<bool>,
         log_unknown: Option<bool>,
         safelist: Option<PersistedQueriesSafelist>,
+        experimental_prewarm_query_plan_cache: Option<PersistedQueriesPrewarmQueryPlanCache>,
     ) -> Self {
         Self {
             enabled: enabled.unwrap_or_else(default_pq),
             safelist: safelist.unwrap_or_default(),
             log_unknown: log_unknown.unwrap_or_else(default_log_unknown),
+            experimental_prewarm_query_plan_cache: experimental_prewarm_query_plan_cache
+                .unwrap_or_default(),
         }
     }
 }
@@ -56,12 +62,44 @@ impl PersistedQueriesSafelist {
     }
 }
 
+/// Persisted Queries (PQ) query plan cache prewarming configuration
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields, default)]
+pub struct PersistedQueriesPrewarmQueryPlanCache {
+    /// Plans every operation of the persisted query list in the background when the router starts (disabled by default)
+    pub on_startup: bool,
+
+    /// Plans every operation of the persisted query list in the background after a schema or configuration reload, instead of during the reload's query plan cache warm up (disabled by default)
+    pub on_reload: bool,
+
+    /// Reports the router as not ready until the prewarming started at startup is complete (disabled by default)
+    pub wait_for_readiness: bool,
+}
+
+#[cfg(test)]
+#[buildstructor::buildstructor]
+impl PersistedQueriesPrewarmQueryPlanCache {
+    #[builder]
+    pub(crate) fn new(
+        on_startup: Option<bool>,
+        on_reload: Option<bool>,
+        wait_for_readiness: Option<bool>,
+    ) -> Self {
+        Self {
+            on_startup: on_startup.unwrap_or_else(default_prewarm_on_startup),
+            on_reload: on_reload.unwrap_or_else(default_prewarm_on_reload),
+            wait_for_readiness: wait_for_readiness.unwrap_or_else(default_wait_for_readiness),
+        }
+    }
+}
+
 impl Default for PersistedQueries {
     fn default() -> Self {
         Self {
             enabled: default_pq(),
             safelist: PersistedQueriesSafelist::default(),
             log_unknown: default_log_unknown(),
+            experimental_prewarm_query_plan_cache: PersistedQueriesPrewarmQueryPlanCache::default(),
         }
     }
 }
@@ -75,6 +113,16 @@ impl Default for PersistedQueriesSafelist {
     }
 }
 
+impl Default for PersistedQueriesPrewarmQueryPlanCache {
+    fn default() -> Self {
+        Self {
+            on_startup: default_prewarm_on_startup(),
+            on_reload: default_prewarm_on_reload(),
+            wait_for_readiness: default_wait_for_readiness(),
+        }
+    }
+}
+
 const fn default_pq() -> bool {
     false
 }
@@ -90,3 +138,15 @@ const fn default_require_id() -> bool {
 const fn default_log_unknown() -> bool {
     false
 }
+
+const fn default_prewarm_on_startup() -> bool {
+    false
+}
+
+const fn default_prewarm_on_reload() -> bool {
+    false
+}
+
+const fn default_wait_for_readiness() -> bool {
+    false
+}
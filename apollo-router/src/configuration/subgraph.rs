@@ -91,8 +91,7 @@ impl<T> SubgraphConfiguration<T>
 where
     T: Default + Serialize + JsonSchema,
 {
-    #[allow(dead_code)]
-    fn get(&self, subgraph_name: &str) -> &T {
+    pub(crate) fn get(&self, subgraph_name: &str) -> &T {
         self.subgraphs.get(subgraph_name).unwrap_or(&self.all)
     }
 }
@@ -753,6 +753,21 @@ fn test_configuration_validate_and_sanitize() {
         .is_err());
 }
 
+#[test]
+fn http3_requires_tls() {
+    let http3: SupergraphHttp3 = serde_json::from_value(json!({ "enabled": true })).unwrap();
+    assert_eq!(
+        http3.listen_address(&SocketAddr::from(([127, 0, 0, 1], 4000)).into()),
+        Some(SocketAddr::from(([127, 0, 0, 1], 4000)))
+    );
+
+    assert!(Configuration::builder()
+        .supergraph(Supergraph::builder().http3(http3).build())
+        .build()
+        .and_then(|configuration| configuration.validate())
+        .is_err());
+}
+
 #[test]
 fn load_tls() {
     let mut cert_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -786,6 +801,44 @@ tls:
     cfg.tls.supergraph.unwrap().tls_config().unwrap();
 }
 
+#[test]
+fn load_tls_with_client_authentication() {
+    let mut cert_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    cert_path.push("src");
+    cert_path.push("configuration");
+    cert_path.push("testdata");
+    cert_path.push("server.crt");
+    let cert_path = cert_path.to_string_lossy();
+
+    let mut key_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    key_path.push("src");
+    key_path.push("configuration");
+    key_path.push("testdata");
+    key_path.push("server.key");
+    let key_path = key_path.to_string_lossy();
+
+    let cfg = validate_yaml_configuration(
+        &format!(
+            r#"
+tls:
+  supergraph:
+    certificate: ${{file.{cert_path}}}
+    certificate_chain: ${{file.{cert_path}}}
+    key: ${{file.{key_path}}}
+    client_authentication:
+      certificate_authorities: ${{file.{cert_path}}}
+      optional: true
+"#,
+        ),
+        Expansion::builder().supported_mode("file").build(),
+        Mode::NoUpgrade,
+    )
+    .expect("should not have resulted in an error");
+    let tls = cfg.tls.supergraph.unwrap();
+    assert!(tls.client_authentication.as_ref().unwrap().optional);
+    tls.tls_config().unwrap();
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 struct TestSubgraphOverride {
     value: Option<u8>,
@@ -1054,6 +1107,44 @@ fn it_processes_batching_subgraph_accounts_disabled_correctly() {
     assert!(!config.batch_include("accounts"));
 }
 
+#[test]
+fn it_processes_batching_subgraph_window_correctly() {
+    let json_config = json!({
+        "enabled": false,
+        "mode": "batch_http_link",
+        "subgraph": {
+            "all": {
+                "enabled": true,
+                "window": {
+                    "duration": "2ms",
+                    "max_size": 10
+                }
+            },
+            "subgraphs": {
+                "accounts": {
+                    "enabled": false
+                },
+                "products": {
+                    "window": {
+                        "duration": "5ms",
+                        "max_size": 20
+                    }
+                }
+            }
+        }
+    });
+
+    let config: Batching = serde_json::from_value(json_config).unwrap();
+
+    let window = config.batch_window("anything").unwrap();
+    assert_eq!(window.duration, Duration::from_millis(2));
+    assert_eq!(window.max_size.get(), 10);
+    assert!(config.batch_window("accounts").is_none());
+    let window = config.batch_window("products").unwrap();
+    assert_eq!(window.duration, Duration::from_millis(5));
+    assert_eq!(window.max_size.get(), 20);
+}
+
 #[test]
 fn it_processes_batching_subgraph_accounts_override_disabled_correctly() {
     let json_config = json!({
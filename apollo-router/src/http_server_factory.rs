@@ -7,6 +7,7 @@ use futures::channel::oneshot;
 use futures::prelude::*;
 use itertools::Itertools;
 use multimap::MultiMap;
+use parking_lot::Mutex;
 use tokio::sync::mpsc;
 
 use super::router::ApolloRouterError;
@@ -74,6 +75,9 @@ pub(crate) struct HttpServerHandle {
 
     /// copied into every client session, to track if there are still running sessions when shutting down
     all_connections_stopped_sender: mpsc::Sender<()>,
+
+    /// QUIC endpoint of the HTTP/3 listener, kept for the next server on restart and closed on shutdown
+    http3_endpoint: Arc<Mutex<Option<quinn::Endpoint>>>,
 }
 
 impl HttpServerHandle {
@@ -98,15 +102,32 @@ impl HttpServerHandle {
             graphql_listen_address,
             listen_addresses,
             all_connections_stopped_sender,
+            http3_endpoint: Default::default(),
         }
     }
 
+    pub(crate) fn with_http3_endpoint(
+        mut self,
+        http3_endpoint: Arc<Mutex<Option<quinn::Endpoint>>>,
+    ) -> Self {
+        self.http3_endpoint = http3_endpoint;
+        self
+    }
+
     pub(crate) async fn shutdown(mut self) -> Result<(), ApolloRouterError> {
         #[cfg(unix)]
         let listen_addresses = std::mem::take(&mut self.listen_addresses);
+        let http3_endpoint = self.http3_endpoint.lock().take();
 
         let (_main_listener, _extra_listener) = self.wait_for_servers().await?;
 
+        // the UDP socket is released once the connections, already notified of the shutdown,
+        // are closed and the last handle to the endpoint is dropped
+        if let Some(endpoint) = http3_endpoint {
+            endpoint.set_server_config(None);
+            endpoint.wait_idle().await;
+        }
+
         #[cfg(unix)]
         // listen_addresses includes the main graphql_address
         for listen_address in listen_addresses {
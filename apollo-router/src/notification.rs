@@ -13,6 +13,7 @@ use std::time::Instant;
 use futures::Sink;
 use futures::Stream;
 use futures::StreamExt;
+use parking_lot::RwLock;
 use pin_project_lite::pin_project;
 use thiserror::Error;
 use tokio::sync::broadcast;
@@ -30,8 +31,12 @@ use crate::graphql;
 use crate::spec::Schema;
 use crate::Configuration;
 
+mod redis;
+
+pub(crate) use self::redis::RedisNotify;
+
 static NOTIFY_CHANNEL_SIZE: usize = 1024;
-static DEFAULT_MSG_CHANNEL_SIZE: usize = 128;
+pub(crate) static DEFAULT_MSG_CHANNEL_SIZE: usize = 128;
 
 #[derive(Error, Debug)]
 pub(crate) enum NotifyError<V> {
@@ -139,6 +144,8 @@ pub struct Notify<K, V> {
     /// Size (number of events) of the channel to receive message
     pub(crate) queue_size: Option<usize>,
     router_broadcasts: Arc<RouterBroadcasts>,
+    /// Shares the topics with other router instances
+    cluster: Arc<RwLock<Option<Arc<RedisNotify>>>>,
 }
 
 #[buildstructor::buildstructor]
@@ -160,6 +167,7 @@ where
             sender,
             queue_size,
             router_broadcasts: Arc::new(RouterBroadcasts::new()),
+            cluster: Default::default(),
         }
     }
 
@@ -171,6 +179,7 @@ where
             sender,
             queue_size: None,
             router_broadcasts: Arc::new(RouterBroadcasts::new()),
+            cluster: Default::default(),
         }
     }
 }
@@ -192,6 +201,16 @@ impl<K, V> Notify<K, V> {
     pub(crate) fn subscribe_schema(&self) -> impl Stream<Item = Arc<Schema>> {
         self.router_broadcasts.subscribe_schema()
     }
+    /// Share the topics with other router instances, or stop sharing them
+    ///
+    /// Subgraph connections opened before keep publishing with the backend they were opened with.
+    pub(crate) fn set_cluster(&self, cluster: Option<Arc<RedisNotify>>) {
+        *self.cluster.write() = cluster;
+    }
+    /// Backend sharing the topics with other router instances, if any
+    pub(crate) fn cluster(&self) -> Option<Arc<RedisNotify>> {
+        self.cluster.read().clone()
+    }
 }
 
 impl<K, V> Notify<K, V>
@@ -547,6 +566,11 @@ where
 
         Ok(())
     }
+
+    /// Returns true if nobody listens to the topic anymore
+    pub(crate) fn is_closed(&self) -> bool {
+        self.msg_sender.receiver_count() == 0
+    }
 }
 
 impl<K, V> Sink<V> for HandleSink<K, V>
@@ -0,0 +1,520 @@
+//! Redis backend sharing subscriptions between router instances
+//!
+//! The first instance subscribing to a topic claims it with an ownership key and is the only one
+//! opening the subgraph subscription. Events are published on a Redis channel per topic, and every
+//! instance with clients for that topic relays them to its local [`Notify`].
+//!
+//! In passthrough mode, the owner keeps the ownership key alive while it publishes the events of
+//! its subgraph connection. In callback mode the subgraph can reach any instance, so the instance
+//! receiving a callback publishes it, and the key is kept alive by the instances having clients.
+//!
+//! Like the in memory [`Notify`], a slow topic does not hold back the others: each topic has its
+//! own bounded queue, dropping its oldest events when the local clients cannot keep up.
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use fred::interfaces::EventInterface;
+use fred::interfaces::PubsubInterface;
+use fred::prelude::ClientLike;
+use fred::prelude::KeysInterface;
+use fred::prelude::RedisClient;
+use fred::types::Expiration;
+use fred::types::SetOptions;
+use futures::Stream;
+use futures::StreamExt;
+use serde::Deserialize;
+use serde::Serialize;
+use tokio::sync::broadcast;
+use tokio::sync::oneshot;
+use tokio::sync::Mutex;
+use tower::BoxError;
+use uuid::Uuid;
+
+use super::Handle;
+use super::HandleSink;
+use super::Notify;
+use crate::cache::redis::RedisCacheStorage;
+use crate::configuration::RedisCache;
+use crate::graphql;
+
+/// Lifetime of the key marking a topic as opened in the cluster
+const TOPIC_TTL: Duration = Duration::from_secs(15);
+const CALLBACK_HMAC_KEY: &str = "subscription_callback_hmac_key";
+/// Lifetime of the callback HMAC key once no instance refreshes it, long enough to survive a
+/// restart of the whole cluster
+const CALLBACK_HMAC_KEY_TTL: Duration = Duration::from_secs(60 * 60);
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+const RELAY_CHANNEL_SIZE: usize = 128;
+
+type Relays = Arc<Mutex<HashMap<String, broadcast::Sender<Event>>>>;
+
+/// Message published on the channel of a topic
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum Event {
+    Next {
+        response: graphql::Response,
+        // not part of the serialized response
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        subscribed: Option<bool>,
+    },
+    Heartbeat,
+    Complete,
+}
+
+impl Event {
+    fn next(response: graphql::Response) -> Self {
+        Event::Next {
+            subscribed: response.subscribed,
+            response,
+        }
+    }
+}
+
+pub(crate) struct RedisNotify {
+    publisher: Arc<RedisClient>,
+    /// client in subscribed state, it cannot send other commands
+    subscriber: Arc<RedisClient>,
+    /// configuration the clients were created with
+    config: RedisCache,
+    namespace: Option<String>,
+    instance_id: String,
+    /// local relays, by channel
+    relays: Relays,
+    // stops the dispatch task when dropped
+    _shutdown: oneshot::Sender<()>,
+}
+
+impl RedisNotify {
+    pub(crate) async fn new(config: RedisCache) -> Result<Arc<Self>, BoxError> {
+        let namespace = config.namespace.clone();
+        let publisher = RedisCacheStorage::new(config.clone()).await?.client();
+        let subscriber = RedisCacheStorage::new(config.clone()).await?.client();
+        let relays = Relays::default();
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
+
+        tokio::spawn(dispatch(
+            publisher.clone(),
+            subscriber.clone(),
+            relays.clone(),
+            namespaced(&namespace, CALLBACK_HMAC_KEY),
+            shutdown_rx,
+        ));
+
+        Ok(Arc::new(Self {
+            publisher,
+            subscriber,
+            config,
+            namespace,
+            instance_id: Uuid::new_v4().to_string(),
+            relays,
+            _shutdown: shutdown_tx,
+        }))
+    }
+
+    pub(crate) fn config(&self) -> &RedisCache {
+        &self.config
+    }
+
+    fn key(&self, name: &str) -> String {
+        namespaced(&self.namespace, name)
+    }
+
+    fn topic_key(&self, topic: &str) -> String {
+        self.key(&format!("subscription:{topic}"))
+    }
+
+    fn channel(&self, topic: &str) -> String {
+        self.key(&format!("subscription:{topic}:events"))
+    }
+
+    /// Subscribes to a topic on this instance, and claims it in the cluster if it is new here
+    ///
+    /// The boolean in the tuple is true if this instance must open the subgraph subscription.
+    pub(crate) async fn create_or_subscribe(
+        self: &Arc<Self>,
+        notify: &mut Notify<String, graphql::Response>,
+        topic: String,
+        heartbeat_enabled: bool,
+    ) -> Result<(Handle<String, graphql::Response>, bool), BoxError> {
+        let (handle, created) = notify
+            .create_or_subscribe(topic.clone(), heartbeat_enabled)
+            .await?;
+        if !created {
+            return Ok((handle, false));
+        }
+
+        // listen before claiming the topic, so we cannot miss the first events of the owner
+        let (relay_tx, relay_rx) = self.listen(&topic).await?;
+        let owner = self
+            .publisher
+            .set::<Option<String>, _, _>(
+                self.topic_key(&topic),
+                self.instance_id.clone(),
+                Some(Expiration::PX(TOPIC_TTL.as_millis() as i64)),
+                Some(SetOptions::NX),
+                false,
+            )
+            .await?
+            .is_some();
+
+        let sink = notify.subscribe(topic.clone()).await?.into_sink();
+        tokio::spawn(self.clone().relay(
+            notify.clone(),
+            topic,
+            (relay_tx, relay_rx),
+            sink,
+            heartbeat_enabled,
+        ));
+
+        Ok((handle, owner))
+    }
+
+    /// Publishes the events of a subgraph subscription opened by this instance
+    ///
+    /// Publishing stops when no instance listens to the topic anymore.
+    pub(crate) async fn publish_stream(
+        self: Arc<Self>,
+        topic: String,
+        stream: impl Stream<Item = graphql::Response>,
+    ) {
+        let topic_key = self.topic_key(&topic);
+        let channel = self.channel(&topic);
+        let mut refresh = tokio::time::interval(REFRESH_INTERVAL);
+        futures::pin_mut!(stream);
+
+        loop {
+            let event = tokio::select! {
+                response = stream.next() => match response {
+                    Some(response) => Event::next(response),
+                    None => break,
+                },
+                _ = refresh.tick() => {
+                    if let Err(err) = self
+                        .publisher
+                        .pexpire::<bool, _>(&topic_key, TOPIC_TTL.as_millis() as i64)
+                        .await
+                    {
+                        tracing::error!("cannot refresh the subscription in Redis: {err}");
+                    }
+                    Event::Heartbeat
+                }
+            };
+            match self.publish(&channel, &event).await {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(err) => {
+                    tracing::error!("cannot publish the subscription event to Redis: {err}");
+                    break;
+                }
+            }
+        }
+
+        let _ = self.publish(&channel, &Event::Complete).await;
+        let _ = self.publisher.del::<(), _>(&topic_key).await;
+    }
+
+    /// Publishes an event received on the callback endpoint
+    ///
+    /// Returns false if no instance listens to this topic.
+    pub(crate) async fn next(
+        &self,
+        topic: &str,
+        response: graphql::Response,
+    ) -> Result<bool, BoxError> {
+        Ok(self
+            .publish(&self.channel(topic), &Event::next(response))
+            .await?
+            > 0)
+    }
+
+    /// Check if the topic is opened in the cluster
+    pub(crate) async fn exist(&self, topic: &str) -> Result<bool, BoxError> {
+        Ok(self
+            .publisher
+            .exists::<bool, _>(self.topic_key(topic))
+            .await?)
+    }
+
+    /// Given a list of topics, returns the list of valid and invalid topics
+    /// Heartbeat the given valid topics on every instance
+    pub(crate) async fn invalid_ids(
+        &self,
+        topics: Vec<String>,
+    ) -> Result<(Vec<String>, Vec<String>), BoxError> {
+        let mut valid_ids = Vec::new();
+        let mut invalid_ids = Vec::new();
+        for topic in topics {
+            if self.exist(&topic).await? {
+                self.publish(&self.channel(&topic), &Event::Heartbeat)
+                    .await?;
+                valid_ids.push(topic);
+            } else {
+                invalid_ids.push(topic);
+            }
+        }
+
+        Ok((valid_ids, invalid_ids))
+    }
+
+    /// Closes the topic on every instance, after sending the errors to the clients
+    ///
+    /// Returns false if the topic is not opened in the cluster.
+    pub(crate) async fn complete(
+        &self,
+        topic: &str,
+        errors: Option<Vec<graphql::Error>>,
+    ) -> Result<bool, BoxError> {
+        if !self.exist(topic).await? {
+            return Ok(false);
+        }
+        let channel = self.channel(topic);
+        if let Some(errors) = errors {
+            let response = graphql::Response::builder().errors(errors).build();
+            self.publish(&channel, &Event::next(response)).await?;
+        }
+        self.publish(&channel, &Event::Complete).await?;
+        self.publisher.del::<(), _>(self.topic_key(topic)).await?;
+
+        Ok(true)
+    }
+
+    /// Returns the key signing callback verifiers, shared by all instances
+    ///
+    /// `candidate` is used if no instance has set it yet. The key expires once no instance is
+    /// running anymore to refresh it.
+    pub(crate) async fn callback_hmac_key(&self, candidate: String) -> Result<String, BoxError> {
+        let key = self.key(CALLBACK_HMAC_KEY);
+        self.publisher
+            .set::<Option<String>, _, _>(
+                &key,
+                candidate,
+                Some(Expiration::PX(CALLBACK_HMAC_KEY_TTL.as_millis() as i64)),
+                Some(SetOptions::NX),
+                false,
+            )
+            .await?;
+        self.publisher
+            .get::<Option<String>, _>(&key)
+            .await?
+            .ok_or_else(|| "cannot get the subscription callback hmac key from Redis".into())
+    }
+
+    async fn publish(&self, channel: &str, event: &Event) -> Result<usize, BoxError> {
+        let message = serde_json::to_string(event)?;
+        Ok(self
+            .publisher
+            .publish::<usize, _, _>(channel, message)
+            .await?)
+    }
+
+    async fn listen(
+        &self,
+        topic: &str,
+    ) -> Result<(broadcast::Sender<Event>, broadcast::Receiver<Event>), BoxError> {
+        let channel = self.channel(topic);
+        let (relay_tx, relay_rx) = broadcast::channel(RELAY_CHANNEL_SIZE);
+        // the lock is kept while subscribing so SUBSCRIBE and UNSUBSCRIBE commands are ordered
+        let mut relays = self.relays.lock().await;
+        relays.insert(channel.clone(), relay_tx.clone());
+        if let Err(err) = self.subscriber.subscribe::<(), _>(channel.clone()).await {
+            relays.remove(&channel);
+            return Err(err.into());
+        }
+
+        Ok((relay_tx, relay_rx))
+    }
+
+    async fn unlisten(&self, topic: &str, relay_tx: &broadcast::Sender<Event>) {
+        let channel = self.channel(topic);
+        let mut relays = self.relays.lock().await;
+        // the topic could have been created again with another relay
+        if !relays
+            .get(&channel)
+            .is_some_and(|current| current.same_channel(relay_tx))
+        {
+            return;
+        }
+        relays.remove(&channel);
+        if let Err(err) = self.subscriber.unsubscribe::<(), _>(channel).await {
+            tracing::error!("cannot unsubscribe from the Redis channel: {err}");
+        }
+    }
+
+    /// Forwards the events of the topic to the local clients, until there are none left
+    async fn relay(
+        self: Arc<Self>,
+        mut notify: Notify<String, graphql::Response>,
+        topic: String,
+        (relay_tx, mut relay_rx): (broadcast::Sender<Event>, broadcast::Receiver<Event>),
+        mut sink: HandleSink<String, graphql::Response>,
+        keep_alive: bool,
+    ) {
+        let topic_key = self.topic_key(&topic);
+        let mut check = tokio::time::interval(REFRESH_INTERVAL);
+
+        loop {
+            tokio::select! {
+                event = relay_rx.recv() => match event {
+                    Ok(Event::Next { mut response, subscribed }) => {
+                        response.subscribed = subscribed;
+                        if sink.send_sync(response).is_err() {
+                            break;
+                        }
+                    }
+                    Ok(Event::Heartbeat) => {
+                        let _ = notify.exist(topic.clone()).await;
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => {
+                        tracing::info!(monotonic_counter.apollo_router_skipped_event_count = 1u64,);
+                    }
+                    Ok(Event::Complete) | Err(broadcast::error::RecvError::Closed) => {
+                        let _ = notify.force_delete(topic.clone()).await;
+                        break;
+                    }
+                },
+                _ = check.tick() => {
+                    if sink.is_closed() {
+                        break;
+                    }
+                    // in callback mode the topic lives as long as an instance has clients,
+                    // otherwise as long as the owner publishes
+                    let opened = if keep_alive {
+                        self.publisher
+                            .pexpire::<bool, _>(&topic_key, TOPIC_TTL.as_millis() as i64)
+                            .await
+                    } else {
+                        self.publisher.exists::<bool, _>(&topic_key).await
+                    };
+                    match opened {
+                        Ok(true) => {}
+                        Ok(false) => {
+                            let _ = sink.send_sync(
+                                graphql::Response::builder()
+                                    .error(
+                                        graphql::Error::builder()
+                                            .message("the subscription has been closed by the router instance running it")
+                                            .extension_code("SUBSCRIPTION_CLOSED")
+                                            .build(),
+                                    )
+                                    .build(),
+                            );
+                            let _ = notify.force_delete(topic.clone()).await;
+                            break;
+                        }
+                        Err(err) => {
+                            tracing::error!("cannot check the subscription in Redis: {err}");
+                        }
+                    }
+                }
+            }
+        }
+
+        self.unlisten(&topic, &relay_tx).await;
+    }
+}
+
+/// Routes the messages received from Redis to the local relays, and keeps the callback HMAC key
+/// alive while this instance runs
+async fn dispatch(
+    publisher: Arc<RedisClient>,
+    subscriber: Arc<RedisClient>,
+    relays: Relays,
+    callback_hmac_key: String,
+    mut shutdown: oneshot::Receiver<()>,
+) {
+    let mut messages = subscriber.message_rx();
+    let mut reconnections = subscriber.reconnect_rx();
+    let mut refresh = tokio::time::interval(REFRESH_INTERVAL);
+
+    loop {
+        tokio::select! {
+            _ = &mut shutdown => break,
+            _ = refresh.tick() => {
+                // does nothing if callback mode is not used
+                if let Err(err) = publisher
+                    .pexpire::<bool, _>(&callback_hmac_key, CALLBACK_HMAC_KEY_TTL.as_millis() as i64)
+                    .await
+                {
+                    tracing::error!("cannot refresh the subscription callback hmac key in Redis: {err}");
+                }
+            }
+            message = messages.recv() => {
+                let message = match message {
+                    Ok(message) => message,
+                    Err(broadcast::error::RecvError::Lagged(count)) => {
+                        tracing::warn!("skipped {count} subscription events received from Redis");
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                let Some(relay_tx) = relays.lock().await.get(&*message.channel).cloned() else {
+                    continue;
+                };
+                let event = message
+                    .value
+                    .as_str()
+                    .and_then(|value| serde_json::from_str::<Event>(&value).ok());
+                match event {
+                    // never waits: the oldest events are dropped if the relay lags behind
+                    Some(event) => {
+                        let _ = relay_tx.send(event);
+                    }
+                    None => tracing::error!("invalid subscription event received from Redis"),
+                }
+            }
+            Ok(_) = reconnections.recv() => {
+                // channel subscriptions are not restored by the client after a reconnection
+                let relays = relays.lock().await;
+                let channels: Vec<String> = relays.keys().cloned().collect();
+                if !channels.is_empty() {
+                    if let Err(err) = subscriber.subscribe::<(), _>(channels).await {
+                        tracing::error!("cannot subscribe again to the Redis channels: {err}");
+                    }
+                }
+            }
+        }
+    }
+
+    let _ = subscriber.quit().await;
+    let _ = publisher.quit().await;
+}
+
+fn namespaced(namespace: &Option<String>, name: &str) -> String {
+    match namespace {
+        Some(namespace) => format!("{namespace}:{name}"),
+        None => name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json_bytes::json;
+
+    use super::Event;
+    use crate::graphql;
+
+    #[test]
+    fn serialize_events() {
+        let event = Event::next(
+            graphql::Response::builder()
+                .data(json!({ "userWasCreated": { "id": "1" } }))
+                .subscribed(true)
+                .build(),
+        );
+        let serialized = serde_json::to_string(&event).unwrap();
+        assert_eq!(
+            serialized,
+            r#"{"kind":"next","response":{"data":{"userWasCreated":{"id":"1"}}},"subscribed":true}"#
+        );
+        assert_eq!(serde_json::from_str::<Event>(&serialized).unwrap(), event);
+
+        let serialized = serde_json::to_string(&Event::Complete).unwrap();
+        assert_eq!(serialized, r#"{"kind":"complete"}"#);
+        assert_eq!(
+            serde_json::from_str::<Event>(&serialized).unwrap(),
+            Event::Complete
+        );
+    }
+}
@@ -211,6 +211,37 @@ where
     deserializer.deserialize_str(RegexVisitor)
 }
 
+/// De-serialize an optional [`Regex`].
+pub(crate) fn deserialize_option_regex<'de, D>(deserializer: D) -> Result<Option<Regex>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct OptionRegexVisitor;
+
+    impl<'de> Visitor<'de> for OptionRegexVisitor {
+        type Value = Option<Regex>;
+
+        fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+            formatter.write_str("struct Regex")
+        }
+
+        fn visit_none<E>(self) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            Ok(None)
+        }
+
+        fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: de::Deserializer<'de>,
+        {
+            Ok(Some(deserialize_regex(deserializer)?))
+        }
+    }
+    deserializer.deserialize_option(OptionRegexVisitor)
+}
+
 pub(crate) fn deserialize_jsonpath<'de, D>(deserializer: D) -> Result<JsonPathInst, D::Error>
 where
     D: serde::Deserializer<'de>,
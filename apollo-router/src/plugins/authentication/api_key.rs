@@ -0,0 +1,275 @@
+//! Authentication with API keys looked up in a key store file
+
+use std::collections::HashMap;
+use std::ops::ControlFlow;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::RwLock;
+
+use futures::future::select;
+use futures::future::Either;
+use futures::pin_mut;
+use futures::StreamExt;
+use http::StatusCode;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::Digest;
+use sha2::Sha256;
+use tokio::sync::oneshot;
+use tower::BoxError;
+
+use super::authentication_failure;
+use super::AuthenticationError;
+use super::APOLLO_AUTHENTICATION_JWT_CLAIMS;
+use crate::services::router;
+
+const AUTHENTICATION_KIND: &str = "api_key";
+
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(super) struct ApiKeyConf {
+    /// Path to the key store file, in YAML or JSON format
+    #[schemars(with = "String")]
+    path: PathBuf,
+    /// Locations where the API key is looked for, in order. Defaults to the `x-api-key` header
+    #[serde(default = "default_sources")]
+    sources: Vec<ApiKeySource>,
+    /// Reload the key store when the file changes
+    #[serde(default = "default_watch")]
+    watch: bool,
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields, rename_all = "snake_case", tag = "type")]
+pub(super) enum ApiKeySource {
+    Header {
+        /// Name of the header containing the API key
+        name: String,
+    },
+    QueryParameter {
+        /// Name of the query parameter containing the API key
+        name: String,
+    },
+}
+
+fn default_sources() -> Vec<ApiKeySource> {
+    vec![ApiKeySource::Header {
+        name: "x-api-key".to_string(),
+    }]
+}
+
+fn default_watch() -> bool {
+    true
+}
+
+/// Content of the key store file
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeyStoreFile {
+    keys: Vec<KeyEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeyEntry {
+    /// Hex encoded SHA-256 hash of the API key
+    sha256: String,
+    client_name: Option<String>,
+    #[serde(default)]
+    scopes: Vec<String>,
+    /// Additional claims, like a rate limit tier
+    #[serde(default)]
+    attributes: serde_json::Map<String, Value>,
+}
+
+impl KeyEntry {
+    fn claims(self) -> Value {
+        let mut claims = self.attributes;
+        if let Some(client_name) = self.client_name {
+            claims.insert("client_name".to_string(), client_name.into());
+        }
+        if !self.scopes.is_empty() {
+            // scopes are a space separated list, like in OAuth2 tokens
+            claims.insert("scope".to_string(), self.scopes.join(" ").into());
+        }
+        Value::Object(claims)
+    }
+}
+
+/// Claims of each API key, indexed by the hex encoded SHA-256 hash of the key
+type Keys = HashMap<String, Value>;
+
+#[derive(Clone)]
+pub(super) struct ApiKeyAuthenticator {
+    sources: Arc<Vec<ApiKeySource>>,
+    keys: Arc<RwLock<Keys>>,
+    _drop_signal: Arc<oneshot::Sender<()>>,
+}
+
+impl ApiKeyAuthenticator {
+    pub(super) async fn new(conf: &ApiKeyConf) -> Result<Self, BoxError> {
+        let keys = load(&conf.path).await?;
+        tracing::info!(
+            path = %conf.path.display(),
+            keys = keys.len(),
+            "API key authentication using key store"
+        );
+        let keys = Arc::new(RwLock::new(keys));
+
+        let (_drop_signal, drop_receiver) = oneshot::channel::<()>();
+        if conf.watch {
+            tokio::task::spawn(watch(conf.path.clone(), keys.clone(), drop_receiver));
+        }
+
+        Ok(Self {
+            sources: Arc::new(conf.sources.clone()),
+            keys,
+            _drop_signal: Arc::new(_drop_signal),
+        })
+    }
+
+    /// Looks up the API key of the request in the key store, and uses its claims if the request was
+    /// not authenticated with a token
+    pub(super) fn authenticate(
+        &self,
+        request: router::Request,
+    ) -> ControlFlow<router::Response, router::Request> {
+        let key = match self.find_key(&request) {
+            // no API key, the request is left to the other authentication methods
+            None => return ControlFlow::Continue(request),
+            Some(Err(error)) => {
+                return authentication_failure(
+                    request.context,
+                    AUTHENTICATION_KIND,
+                    error,
+                    StatusCode::BAD_REQUEST,
+                )
+            }
+            Some(Ok(key)) => key,
+        };
+
+        let hash = hex::encode(Sha256::digest(key.as_bytes()));
+        let claims = match self.keys.read() {
+            Ok(keys) => keys.get(&hash).cloned(),
+            Err(_) => None,
+        };
+        let claims = match claims {
+            Some(claims) => claims,
+            None => {
+                return authentication_failure(
+                    request.context,
+                    AUTHENTICATION_KIND,
+                    AuthenticationError::InvalidApiKey,
+                    StatusCode::UNAUTHORIZED,
+                )
+            }
+        };
+
+        if !request
+            .context
+            .contains_key(APOLLO_AUTHENTICATION_JWT_CLAIMS)
+        {
+            if let Err(e) = request
+                .context
+                .insert(APOLLO_AUTHENTICATION_JWT_CLAIMS, claims)
+            {
+                return authentication_failure(
+                    request.context,
+                    AUTHENTICATION_KIND,
+                    AuthenticationError::CannotInsertClaimsIntoContext(e),
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                );
+            }
+        }
+
+        // This is a metric and will not appear in the logs
+        tracing::info!(
+            monotonic_counter.apollo_authentication_success_count = 1u64,
+            kind = %AUTHENTICATION_KIND
+        );
+        ControlFlow::Continue(request)
+    }
+
+    fn find_key<'a>(
+        &self,
+        request: &router::Request,
+    ) -> Option<Result<String, AuthenticationError<'a>>> {
+        self.sources.iter().find_map(|source| match source {
+            ApiKeySource::Header { name } => {
+                let value = request.router_request.headers().get(name)?;
+                Some(
+                    value
+                        .to_str()
+                        .map(|key| key.trim().to_string())
+                        .map_err(|_| AuthenticationError::CannotConvertToString),
+                )
+            }
+            ApiKeySource::QueryParameter { name } => {
+                let query = request.router_request.uri().query()?;
+                url::form_urlencoded::parse(query.as_bytes())
+                    .find(|(parameter, _)| parameter == name)
+                    .map(|(_, key)| Ok(key.into_owned()))
+            }
+        })
+    }
+}
+
+async fn load(path: &Path) -> Result<Keys, BoxError> {
+    let content = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|e| format!("could not read the API key store {}: {e}", path.display()))?;
+    let file: KeyStoreFile = serde_yaml::from_str(&content)
+        .map_err(|e| format!("invalid API key store {}: {e}", path.display()))?;
+
+    let mut keys = HashMap::with_capacity(file.keys.len());
+    for entry in file.keys {
+        let hash = entry.sha256.to_ascii_lowercase();
+        if hash.len() != 64 || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(format!(
+                "invalid API key store {}: '{}' is not a hex encoded SHA-256 hash",
+                path.display(),
+                entry.sha256
+            )
+            .into());
+        }
+        keys.insert(hash, entry.claims());
+    }
+    Ok(keys)
+}
+
+async fn watch(path: PathBuf, keys: Arc<RwLock<Keys>>, drop_receiver: oneshot::Receiver<()>) {
+    // the first event is sent when the watch starts, but the keys were already loaded
+    let mut changes = crate::files::watch(&path).skip(1);
+
+    pin_mut!(drop_receiver);
+
+    loop {
+        let next = changes.next();
+        pin_mut!(next);
+
+        match select(drop_receiver, next).await {
+            // the _drop_signal was dropped, we must shut down the task
+            Either::Left((_res, _)) => return,
+            Either::Right((Some(()), receiver)) => {
+                drop_receiver = receiver;
+                // invalid changes are ignored, the previous keys stay in use
+                match load(&path).await {
+                    Ok(new_keys) => {
+                        tracing::info!(
+                            path = %path.display(),
+                            keys = new_keys.len(),
+                            "reloaded the API key store"
+                        );
+                        if let Ok(mut keys) = keys.write() {
+                            *keys = new_keys;
+                        }
+                    }
+                    Err(e) => tracing::error!("{e}, keeping the previous API keys"),
+                }
+            }
+            Either::Right((None, _)) => return,
+        };
+    }
+}
@@ -0,0 +1,121 @@
+//! Declarative validation of JWT claims
+
+use std::collections::BTreeMap;
+
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::AuthenticationError;
+use crate::plugin::serde::deserialize_option_regex;
+
+/// Requirements on the claims of a token, indexed by claim name
+pub(super) type ClaimRequirements = BTreeMap<String, ClaimRequirement>;
+
+/// Requirement on a claim: the claim must be present and satisfy every configured condition
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(super) struct ClaimRequirement {
+    /// The claim must be equal to this value
+    equals: Option<Value>,
+    /// The claim must be one of these values. If the claim is an array, one of its elements must be one of these values
+    one_of: Option<Vec<Value>>,
+    /// The claim must be a string matching this regular expression
+    #[schemars(with = "Option<String>")]
+    #[serde(default, deserialize_with = "deserialize_option_regex")]
+    matches: Option<Regex>,
+    /// The claim must be a number greater than this value
+    gt: Option<f64>,
+    /// The claim must be a number greater than or equal to this value
+    gte: Option<f64>,
+    /// The claim must be a number lower than this value
+    lt: Option<f64>,
+    /// The claim must be a number lower than or equal to this value
+    lte: Option<f64>,
+}
+
+/// Checks the claims of a token against the requirements
+pub(super) fn validate<'a>(
+    requirements: &ClaimRequirements,
+    claims: &Value,
+) -> Result<(), AuthenticationError<'a>> {
+    for (name, requirement) in requirements {
+        if let Err(error) = requirement.check(name, claims.get(name)) {
+            // This is a metric and will not appear in the logs
+            tracing::info!(
+                monotonic_counter.apollo_authentication_claim_validation_failure_count = 1u64,
+                claim = %name,
+                reason = error.claim_failure_reason().unwrap_or_default()
+            );
+            return Err(error);
+        }
+    }
+    Ok(())
+}
+
+impl ClaimRequirement {
+    fn check<'a>(&self, name: &str, claim: Option<&Value>) -> Result<(), AuthenticationError<'a>> {
+        let claim = claim.ok_or_else(|| AuthenticationError::MissingClaim(name.to_string()))?;
+
+        if let Some(expected) = &self.equals {
+            if claim != expected {
+                return Err(AuthenticationError::UnexpectedClaimValue {
+                    claim: name.to_string(),
+                    expected: expected.clone(),
+                });
+            }
+        }
+
+        if let Some(accepted) = &self.one_of {
+            let is_accepted = match claim {
+                Value::Array(values) => values.iter().any(|value| accepted.contains(value)),
+                value => accepted.contains(value),
+            };
+            if !is_accepted {
+                return Err(AuthenticationError::ClaimNotAccepted(name.to_string()));
+            }
+        }
+
+        if let Some(regex) = &self.matches {
+            let value = claim
+                .as_str()
+                .ok_or_else(|| AuthenticationError::InvalidClaimType {
+                    claim: name.to_string(),
+                    expected: "string",
+                })?;
+            if !regex.is_match(value) {
+                return Err(AuthenticationError::ClaimDoesNotMatch {
+                    claim: name.to_string(),
+                    pattern: regex.as_str().to_string(),
+                });
+            }
+        }
+
+        let comparisons: [(Option<f64>, &str, fn(f64, f64) -> bool); 4] = [
+            (self.gt, "greater than", |v, bound| v > bound),
+            (self.gte, "greater than or equal to", |v, bound| v >= bound),
+            (self.lt, "lower than", |v, bound| v < bound),
+            (self.lte, "lower than or equal to", |v, bound| v <= bound),
+        ];
+        for (bound, comparison, compare) in comparisons {
+            if let Some(bound) = bound {
+                let value =
+                    claim
+                        .as_f64()
+                        .ok_or_else(|| AuthenticationError::InvalidClaimType {
+                            claim: name.to_string(),
+                            expected: "number",
+                        })?;
+                if !compare(value, bound) {
+                    return Err(AuthenticationError::ClaimOutOfRange {
+                        claim: name.to_string(),
+                        comparison: format!("{comparison} {bound}"),
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
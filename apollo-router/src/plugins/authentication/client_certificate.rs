@@ -0,0 +1,140 @@
+//! Authentication with the certificate presented by the client during the TLS handshake
+
+use std::collections::HashMap;
+use std::ops::ControlFlow;
+
+use http::StatusCode;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::authentication_failure;
+use super::AuthenticationError;
+use super::APOLLO_AUTHENTICATION_JWT_CLAIMS;
+use crate::axum_factory::utils::ClientCertificate;
+use crate::axum_factory::utils::ConnectionInfo;
+use crate::services::router;
+
+pub(crate) const APOLLO_AUTHENTICATION_CLIENT_CERTIFICATE: &str =
+    "apollo_authentication::client_certificate";
+const AUTHENTICATION_KIND: &str = "client_certificate";
+
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(super) struct ClientCertificateConf {
+    /// Reject requests that were not made over a connection authenticated with a client certificate
+    #[serde(default)]
+    required: bool,
+    /// Claims generated from the client certificate, indexed by claim name. They are used for requests that did not authenticate with a token
+    #[serde(default)]
+    claims: HashMap<String, ClientCertificateField>,
+}
+
+/// Information from the client certificate
+#[derive(Clone, Debug, Deserialize, JsonSchema, PartialEq)]
+#[serde(deny_unknown_fields, rename_all = "snake_case")]
+pub(crate) enum ClientCertificateField {
+    /// Distinguished name of the subject
+    Subject,
+    /// Common name of the subject
+    CommonName,
+    /// Subject alternative names
+    SubjectAlternativeNames,
+    /// Hex encoded SHA-256 fingerprint of the certificate
+    Fingerprint,
+}
+
+impl ClientCertificateField {
+    pub(crate) fn value(&self, certificate: &ClientCertificate) -> Option<Value> {
+        match self {
+            ClientCertificateField::Subject => Some(certificate.subject.clone().into()),
+            ClientCertificateField::CommonName => certificate.common_name.clone().map(Value::from),
+            ClientCertificateField::SubjectAlternativeNames => {
+                Some(certificate.subject_alternative_names.clone().into())
+            }
+            ClientCertificateField::Fingerprint => Some(certificate.fingerprint.clone().into()),
+        }
+    }
+}
+
+/// Stores the client certificate in the context, and uses it to generate claims if the request was not
+/// authenticated with a token
+pub(super) fn authenticate(
+    config: &ClientCertificateConf,
+    request: router::Request,
+) -> ControlFlow<router::Response, router::Request> {
+    let certificate = request
+        .router_request
+        .extensions()
+        .get::<ConnectionInfo>()
+        .and_then(|connection_info| connection_info.client_certificate.clone());
+
+    let certificate = match certificate {
+        Some(certificate) => certificate,
+        None if config.required => {
+            return authentication_failure(
+                request.context,
+                AUTHENTICATION_KIND,
+                AuthenticationError::MissingClientCertificate,
+                StatusCode::UNAUTHORIZED,
+            )
+        }
+        None => return ControlFlow::Continue(request),
+    };
+
+    if let Err(e) = request.context.insert(
+        APOLLO_AUTHENTICATION_CLIENT_CERTIFICATE,
+        (*certificate).clone(),
+    ) {
+        return authentication_failure(
+            request.context,
+            AUTHENTICATION_KIND,
+            AuthenticationError::CannotInsertClaimsIntoContext(e),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        );
+    }
+
+    if !config.claims.is_empty()
+        && !request
+            .context
+            .contains_key(APOLLO_AUTHENTICATION_JWT_CLAIMS)
+    {
+        let claims: serde_json::Map<String, Value> = config
+            .claims
+            .iter()
+            .filter_map(|(claim, field)| {
+                let value = field.value(&certificate)?;
+                // scopes are a space separated list
+                let value = match value {
+                    Value::Array(values) if claim == "scope" => values
+                        .iter()
+                        .filter_map(|value| value.as_str())
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                        .into(),
+                    value => value,
+                };
+                Some((claim.clone(), value))
+            })
+            .collect();
+
+        if let Err(e) = request
+            .context
+            .insert(APOLLO_AUTHENTICATION_JWT_CLAIMS, Value::Object(claims))
+        {
+            return authentication_failure(
+                request.context,
+                AUTHENTICATION_KIND,
+                AuthenticationError::CannotInsertClaimsIntoContext(e),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            );
+        }
+    }
+
+    // This is a metric and will not appear in the logs
+    tracing::info!(
+        monotonic_counter.apollo_authentication_success_count = 1u64,
+        kind = %AUTHENTICATION_KIND
+    );
+    ControlFlow::Continue(request)
+}
@@ -0,0 +1,361 @@
+//! OAuth2 client credentials grant (RFC 6749, section 4.4) for subgraph requests
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use displaydoc::Display;
+use http::header::ACCEPT;
+use http::header::AUTHORIZATION;
+use http::HeaderValue;
+use http::Request;
+use http::StatusCode;
+use mime::APPLICATION_JSON;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use thiserror::Error;
+use tokio::sync::Mutex;
+use tower::BoxError;
+use url::Url;
+
+use super::CLIENT;
+use super::DEFAULT_AUTHENTICATION_NETWORK_TIMEOUT;
+
+const DEFAULT_REFRESH_BEFORE_EXPIRY: Duration = Duration::from_secs(30);
+
+/// Get access tokens for subgraph requests with the OAuth2 client credentials grant
+#[derive(Clone, Debug, JsonSchema, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub(crate) struct ClientCredentialsConfig {
+    /// URL of the OAuth2 token endpoint
+    pub(crate) token_url: String,
+    /// Client identifier
+    pub(crate) client_id: String,
+    /// Client secret
+    pub(crate) client_secret: String,
+    /// How the client credentials are sent to the token endpoint; defaults to `basic`
+    #[serde(default)]
+    pub(crate) client_authentication: ClientAuthenticationMethod,
+    /// Scopes requested for the access token
+    #[serde(default)]
+    pub(crate) scopes: Vec<String>,
+    /// Additional parameters sent to the token endpoint, like `audience` or `resource`
+    #[serde(default)]
+    pub(crate) parameters: HashMap<String, String>,
+    /// Tokens are refreshed this long before they expire, in human-readable format; defaults to 30s
+    #[serde(
+        deserialize_with = "humantime_serde::deserialize",
+        default = "default_refresh_before_expiry"
+    )]
+    #[schemars(with = "String", default = "default_refresh_before_expiry")]
+    pub(crate) refresh_before_expiry: Duration,
+    /// Timeout of token requests in human-readable format; defaults to 15s
+    #[serde(
+        deserialize_with = "humantime_serde::deserialize",
+        default = "default_timeout"
+    )]
+    #[schemars(with = "String", default = "default_timeout")]
+    pub(crate) timeout: Duration,
+}
+
+/// Client authentication method used with the token endpoint
+#[derive(Clone, Debug, Default, JsonSchema, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub(crate) enum ClientAuthenticationMethod {
+    /// HTTP basic authentication (`client_secret_basic`)
+    #[default]
+    Basic,
+    /// Client identifier and secret in the request body (`client_secret_post`)
+    Post,
+}
+
+fn default_refresh_before_expiry() -> Duration {
+    DEFAULT_REFRESH_BEFORE_EXPIRY
+}
+
+fn default_timeout() -> Duration {
+    DEFAULT_AUTHENTICATION_NETWORK_TIMEOUT
+}
+
+#[derive(Debug, Display, Error)]
+pub(crate) enum OAuth2Error {
+    /// could not get an OAuth2 access token for subgraph '{subgraph}': {reason}
+    TokenRequest { subgraph: String, reason: String },
+    /// could not exchange the client token for an OAuth2 access token for subgraph '{subgraph}': {reason}
+    TokenExchange { subgraph: String, reason: String },
+}
+
+/// OAuth2 grant used to get the access tokens of a subgraph, named in the errors and the metrics
+#[derive(Clone, Copy, Debug)]
+pub(super) enum Grant {
+    ClientCredentials,
+    TokenExchange,
+}
+
+#[derive(Deserialize)]
+pub(super) struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    token_type: Option<String>,
+    #[serde(default)]
+    pub(super) expires_in: Option<u64>,
+}
+
+/// An access token, reused until it is about to expire
+pub(super) struct CachedToken {
+    pub(super) authorization: HeaderValue,
+    /// Tokens without expiration time are kept until a subgraph rejects them
+    refresh_at: Option<Instant>,
+}
+
+impl CachedToken {
+    pub(super) fn new(
+        authorization: HeaderValue,
+        expires_in: Option<u64>,
+        requested_at: Instant,
+        refresh_before_expiry: Duration,
+    ) -> Self {
+        let refresh_at = expires_in.map(|expires_in| {
+            let lifetime = Duration::from_secs(expires_in);
+            // tokens living less than twice the refresh margin are refreshed halfway through
+            // their lifetime, otherwise they would never be reused
+            requested_at + lifetime - refresh_before_expiry.min(lifetime / 2)
+        });
+        Self {
+            authorization,
+            refresh_at,
+        }
+    }
+
+    pub(super) fn is_fresh(&self) -> bool {
+        self.refresh_at
+            .map_or(true, |refresh_at| Instant::now() < refresh_at)
+    }
+}
+
+/// Fetches access tokens for a subgraph and keeps them until they are about to expire
+#[derive(Clone)]
+pub(crate) struct ClientCredentialsProvider {
+    config: Arc<ClientCredentialsConfig>,
+    token_url: Url,
+    subgraph_name: String,
+    token: Arc<Mutex<Option<CachedToken>>>,
+}
+
+impl ClientCredentialsProvider {
+    pub(crate) fn new(
+        config: &ClientCredentialsConfig,
+        subgraph_name: &str,
+    ) -> Result<Self, BoxError> {
+        Ok(Self {
+            config: Arc::new(config.clone()),
+            token_url: Url::parse(&config.token_url)?,
+            subgraph_name: subgraph_name.to_string(),
+            token: Default::default(),
+        })
+    }
+
+    /// Adds the `Authorization` header with a valid access token to the request
+    pub(crate) async fn authorize<B>(
+        &self,
+        req: Request<B>,
+        subgraph_name: &str,
+    ) -> Result<Request<B>, BoxError> {
+        set_authorization(
+            req,
+            self.authorization().await,
+            Grant::ClientCredentials,
+            subgraph_name,
+        )
+    }
+
+    /// Drops the cached access token if a subgraph rejected it, so the next request gets a new one
+    pub(crate) async fn reject(&self, authorization: &HeaderValue) {
+        let mut token = self.token.lock().await;
+        if token
+            .as_ref()
+            .is_some_and(|cached| cached.authorization == *authorization)
+        {
+            *token = None;
+        }
+    }
+
+    async fn authorization(&self) -> Result<HeaderValue, String> {
+        // holding the lock while requesting a new token ensures concurrent requests wait for a single token request
+        let mut token = self.token.lock().await;
+        if let Some(cached) = token.as_ref().filter(|cached| cached.is_fresh()) {
+            return Ok(cached.authorization.clone());
+        }
+
+        let requested_at = Instant::now();
+        let scope = self.config.scopes.join(" ");
+        let mut form = vec![("grant_type", "client_credentials")];
+        if !scope.is_empty() {
+            form.push(("scope", scope.as_str()));
+        }
+        for (name, value) in &self.config.parameters {
+            form.push((name.as_str(), value.as_str()));
+        }
+        let response = request_token(
+            &self.token_url,
+            &self.config.client_id,
+            &self.config.client_secret,
+            &self.config.client_authentication,
+            form,
+            self.config.timeout,
+        )
+        .await?;
+        let authorization = authorization_header(&response)?;
+
+        *token = Some(CachedToken::new(
+            authorization.clone(),
+            response.expires_in,
+            requested_at,
+            self.config.refresh_before_expiry,
+        ));
+
+        Ok(authorization)
+    }
+}
+
+/// Sends a request to an OAuth2 token endpoint, authenticated with the client credentials
+pub(super) async fn request_token(
+    token_url: &Url,
+    client_id: &str,
+    client_secret: &str,
+    client_authentication: &ClientAuthenticationMethod,
+    mut form: Vec<(&str, &str)>,
+    timeout: Duration,
+) -> Result<TokenResponse, String> {
+    let client = CLIENT
+        .as_ref()
+        .map_err(|e| format!("could not activate authentication feature: {e}"))?;
+
+    let mut builder = client
+        .post(token_url.clone())
+        .header(ACCEPT, APPLICATION_JSON.essence_str());
+    match client_authentication {
+        ClientAuthenticationMethod::Basic => {
+            builder = builder.basic_auth(client_id, Some(client_secret));
+        }
+        ClientAuthenticationMethod::Post => {
+            form.push(("client_id", client_id));
+            form.push(("client_secret", client_secret));
+        }
+    }
+
+    let response = builder
+        .form(&form)
+        .timeout(timeout)
+        .send()
+        .await
+        .map_err(|e| format!("token request failed: {e}"))?;
+
+    let status = response.status();
+    if status != StatusCode::OK {
+        // OAuth2 error responses contain an `error` code and an optional description
+        let description = response
+            .json::<serde_json::Value>()
+            .await
+            .ok()
+            .and_then(|body| {
+                let error = body.get("error")?.as_str()?.to_string();
+                Some(
+                    match body.get("error_description").and_then(|d| d.as_str()) {
+                        Some(description) => format!("{error}: {description}"),
+                        None => error,
+                    },
+                )
+            });
+        return Err(match description {
+            Some(description) => {
+                format!("token endpoint returned status {status} ({description})")
+            }
+            None => format!("token endpoint returned status {status}"),
+        });
+    }
+
+    response
+        .json::<TokenResponse>()
+        .await
+        .map_err(|e| format!("invalid token response: {e}"))
+}
+
+/// Adds the `Authorization` header to a subgraph request, and records the outcome in the metrics of the grant
+pub(super) fn set_authorization<B>(
+    mut req: Request<B>,
+    authorization: Result<HeaderValue, String>,
+    grant: Grant,
+    subgraph_name: &str,
+) -> Result<Request<B>, BoxError> {
+    increment_counter(grant, subgraph_name, authorization.is_err());
+    match authorization {
+        Ok(authorization) => {
+            req.headers_mut().insert(AUTHORIZATION, authorization);
+            Ok(req)
+        }
+        Err(reason) => {
+            let subgraph = subgraph_name.to_string();
+            let error = match grant {
+                Grant::ClientCredentials => OAuth2Error::TokenRequest { subgraph, reason },
+                Grant::TokenExchange => OAuth2Error::TokenExchange { subgraph, reason },
+            };
+            tracing::error!("{}", error);
+            Err(error.into())
+        }
+    }
+}
+
+/// Builds the `Authorization` header value from a token response
+pub(super) fn authorization_header(response: &TokenResponse) -> Result<HeaderValue, String> {
+    let token_type = response.token_type.as_deref().unwrap_or("Bearer");
+    // the token type is case insensitive, but most servers only accept "Bearer"
+    let token_type = if token_type.eq_ignore_ascii_case("bearer") {
+        "Bearer"
+    } else {
+        token_type
+    };
+    let mut authorization =
+        HeaderValue::from_str(&format!("{token_type} {}", response.access_token))
+            .map_err(|_| "the access token is not a valid header value".to_string())?;
+    authorization.set_sensitive(true);
+    Ok(authorization)
+}
+
+impl std::fmt::Debug for ClientCredentialsProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClientCredentialsProvider")
+            .field("token_url", &self.token_url)
+            .field("client_id", &self.config.client_id)
+            .field("subgraph_name", &self.subgraph_name)
+            .finish()
+    }
+}
+
+fn increment_counter(grant: Grant, subgraph_name: &str, failed: bool) {
+    match grant {
+        Grant::ClientCredentials => tracing::info!(
+            monotonic_counter
+                .apollo
+                .router
+                .operations
+                .authentication
+                .oauth2
+                .client_credentials = 1u64,
+            authentication.oauth2.client_credentials.failed = failed,
+            subgraph.service.name = %subgraph_name,
+        ),
+        Grant::TokenExchange => tracing::info!(
+            monotonic_counter
+                .apollo
+                .router
+                .operations
+                .authentication
+                .oauth2
+                .token_exchange = 1u64,
+            authentication.oauth2.token_exchange.failed = failed,
+            subgraph.service.name = %subgraph_name,
+        ),
+    }
+}
@@ -0,0 +1,306 @@
+//! HMAC signature of subgraph requests
+
+use std::sync::Arc;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine as _;
+use hmac::digest::KeyInit;
+use hmac::Hmac;
+use hmac::Mac;
+use http::HeaderName;
+use http::HeaderValue;
+use http::Request;
+use http_body::Body as _;
+use hyper::Body;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use sha2::Digest;
+use sha2::Sha256;
+use sha2::Sha384;
+use sha2::Sha512;
+use tower::BoxError;
+
+/// Sign subgraph requests with a HMAC, so that subgraphs can verify they come from the router
+#[derive(Clone, Debug, JsonSchema, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub(crate) struct HmacSigningConfig {
+    /// Secret key shared with the subgraphs. Use `${file.path}` or `${env.NAME}` expansion to read it from a file or an environment variable
+    pub(crate) secret: String,
+    /// Hash function used by the HMAC and the body digest; defaults to `sha256`
+    #[serde(default)]
+    pub(crate) algorithm: HmacAlgorithm,
+    /// Components of the request that are signed, in order; defaults to the method, path, timestamp and body digest
+    #[serde(default = "default_components")]
+    pub(crate) components: Vec<SignedComponent>,
+    /// Encoding of the signature; defaults to `hex`
+    #[serde(default)]
+    pub(crate) encoding: SignatureEncoding,
+    /// Name of the header containing the signature; defaults to `x-router-signature`
+    #[serde(default = "default_signature_header")]
+    pub(crate) signature_header: String,
+    /// Name of the header containing the timestamp of the signature, in seconds since the Unix epoch; defaults to `x-router-timestamp`
+    #[serde(default = "default_timestamp_header")]
+    pub(crate) timestamp_header: String,
+    /// Maximum size in bytes of the request bodies buffered to compute the body digest. Larger requests, like file uploads, are refused; defaults to 2MB
+    #[serde(default = "default_max_body_size")]
+    pub(crate) max_body_size: usize,
+}
+
+/// Hash function of the HMAC
+#[derive(Clone, Copy, Debug, Default, JsonSchema, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub(crate) enum HmacAlgorithm {
+    /// HMAC-SHA256
+    #[default]
+    Sha256,
+    /// HMAC-SHA384
+    Sha384,
+    /// HMAC-SHA512
+    Sha512,
+}
+
+/// Component of the request included in the signature
+#[derive(Clone, Debug, JsonSchema, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub(crate) enum SignedComponent {
+    /// HTTP method, in uppercase
+    Method,
+    /// Path and query of the request URL
+    Path,
+    /// Value of a request header, or an empty string if the header is absent
+    Header(String),
+    /// Hex encoded digest of the request body
+    BodyDigest,
+    /// Timestamp of the signature, in seconds since the Unix epoch
+    Timestamp,
+}
+
+/// Encoding of the signature
+#[derive(Clone, Copy, Debug, Default, JsonSchema, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub(crate) enum SignatureEncoding {
+    /// Lowercase hexadecimal
+    #[default]
+    Hex,
+    /// Standard base64 with padding
+    Base64,
+}
+
+fn default_components() -> Vec<SignedComponent> {
+    vec![
+        SignedComponent::Method,
+        SignedComponent::Path,
+        SignedComponent::Timestamp,
+        SignedComponent::BodyDigest,
+    ]
+}
+
+fn default_signature_header() -> String {
+    "x-router-signature".to_string()
+}
+
+fn default_timestamp_header() -> String {
+    "x-router-timestamp".to_string()
+}
+
+fn default_max_body_size() -> usize {
+    2_000_000
+}
+
+/// Adds the signature and timestamp headers to subgraph requests
+#[derive(Clone)]
+pub(crate) struct HmacSigner {
+    config: Arc<HmacSigningConfig>,
+    signature_header: HeaderName,
+    timestamp_header: HeaderName,
+    signed_headers: Vec<Option<HeaderName>>,
+}
+
+impl HmacSigner {
+    pub(crate) fn new(config: &HmacSigningConfig) -> Result<Self, BoxError> {
+        if config.secret.is_empty() {
+            return Err("the HMAC secret must not be empty".into());
+        }
+        let signed_headers = config
+            .components
+            .iter()
+            .map(|component| match component {
+                SignedComponent::Header(name) => HeaderName::try_from(name.as_str()).map(Some),
+                _ => Ok(None),
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            config: Arc::new(config.clone()),
+            signature_header: HeaderName::try_from(config.signature_header.as_str())?,
+            timestamp_header: HeaderName::try_from(config.timestamp_header.as_str())?,
+            signed_headers,
+        })
+    }
+
+    pub(crate) async fn sign(
+        &self,
+        mut req: Request<Body>,
+        subgraph_name: &str,
+    ) -> Result<Request<Body>, BoxError> {
+        // the digest must be sent in a header, before the body: signing it buffers the body
+        if !self
+            .config
+            .components
+            .contains(&SignedComponent::BodyDigest)
+        {
+            self.add_signature(&mut req, &[], subgraph_name)?;
+            return Ok(req);
+        }
+
+        let max_body_size = self.config.max_body_size;
+        let (parts, body) = req.into_parts();
+        let body: Result<_, BoxError> = if body.size_hint().lower() > max_body_size as u64 {
+            Err(http_body::LengthLimitError.into())
+        } else {
+            hyper::body::to_bytes(http_body::Limited::new(body, max_body_size)).await
+        };
+        let body = body.map_err(|err| {
+            increment_failure_counter(subgraph_name);
+            let error = if err.is::<http_body::LengthLimitError>() {
+                format!(
+                    "the request body is larger than the {max_body_size} bytes that can be buffered for HMAC signing, see the `max_body_size` option"
+                )
+            } else {
+                format!("failed to read the request body for HMAC signing: {}", err)
+            };
+            tracing::error!("{}", error);
+            error
+        })?;
+        let mut req = Request::from_parts(parts, Body::from(body.clone()));
+        self.add_signature(&mut req, &body, subgraph_name)?;
+        Ok(req)
+    }
+
+    pub(crate) async fn sign_empty(
+        &self,
+        mut req: Request<()>,
+        subgraph_name: &str,
+    ) -> Result<Request<()>, BoxError> {
+        self.add_signature(&mut req, &[], subgraph_name)?;
+        Ok(req)
+    }
+
+    fn add_signature<B>(
+        &self,
+        req: &mut Request<B>,
+        body: &[u8],
+        subgraph_name: &str,
+    ) -> Result<(), BoxError> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+            .to_string();
+        let message = self.signed_message(req, body, &timestamp);
+        let signature = self
+            .config
+            .algorithm
+            .mac(self.config.secret.as_bytes(), &message);
+        let signature = match self.config.encoding {
+            SignatureEncoding::Hex => hex::encode(signature),
+            SignatureEncoding::Base64 => BASE64_STANDARD.encode(signature),
+        };
+
+        let headers = req.headers_mut();
+        headers.insert(
+            self.timestamp_header.clone(),
+            HeaderValue::from_str(&timestamp)?,
+        );
+        headers.insert(
+            self.signature_header.clone(),
+            HeaderValue::from_str(&signature)?,
+        );
+        increment_success_counter(subgraph_name);
+        Ok(())
+    }
+
+    /// The signed components, separated by new lines
+    fn signed_message<B>(&self, req: &Request<B>, body: &[u8], timestamp: &str) -> Vec<u8> {
+        let components: Vec<String> = self
+            .config
+            .components
+            .iter()
+            .zip(&self.signed_headers)
+            .map(|(component, header_name)| match component {
+                SignedComponent::Method => req.method().as_str().to_string(),
+                SignedComponent::Path => req
+                    .uri()
+                    .path_and_query()
+                    .map(|path| path.as_str().to_string())
+                    .unwrap_or_else(|| "/".to_string()),
+                SignedComponent::Header(_) => header_name
+                    .as_ref()
+                    .map(|name| {
+                        req.headers()
+                            .get_all(name)
+                            .iter()
+                            .filter_map(|value| value.to_str().ok())
+                            .collect::<Vec<_>>()
+                            .join(",")
+                    })
+                    .unwrap_or_default(),
+                SignedComponent::BodyDigest => hex::encode(self.config.algorithm.digest(body)),
+                SignedComponent::Timestamp => timestamp.to_string(),
+            })
+            .collect();
+        components.join("\n").into_bytes()
+    }
+}
+
+impl HmacAlgorithm {
+    fn mac(&self, secret: &[u8], message: &[u8]) -> Vec<u8> {
+        fn compute<M: Mac + KeyInit>(secret: &[u8], message: &[u8]) -> Vec<u8> {
+            let mut mac =
+                <M as KeyInit>::new_from_slice(secret).expect("HMAC can take key of any size");
+            mac.update(message);
+            mac.finalize().into_bytes().to_vec()
+        }
+
+        match self {
+            HmacAlgorithm::Sha256 => compute::<Hmac<Sha256>>(secret, message),
+            HmacAlgorithm::Sha384 => compute::<Hmac<Sha384>>(secret, message),
+            HmacAlgorithm::Sha512 => compute::<Hmac<Sha512>>(secret, message),
+        }
+    }
+
+    fn digest(&self, data: &[u8]) -> Vec<u8> {
+        match self {
+            HmacAlgorithm::Sha256 => Sha256::digest(data).to_vec(),
+            HmacAlgorithm::Sha384 => Sha384::digest(data).to_vec(),
+            HmacAlgorithm::Sha512 => Sha512::digest(data).to_vec(),
+        }
+    }
+}
+
+impl std::fmt::Debug for HmacSigner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HmacSigner")
+            .field("algorithm", &self.config.algorithm)
+            .field("components", &self.config.components)
+            .finish()
+    }
+}
+
+fn increment_success_counter(subgraph_name: &str) {
+    tracing::info!(
+        monotonic_counter.apollo.router.operations.authentication.hmac = 1u64,
+        authentication.hmac.failed = false,
+        subgraph.service.name = %subgraph_name,
+    );
+}
+
+fn increment_failure_counter(subgraph_name: &str) {
+    tracing::info!(
+        monotonic_counter.apollo.router.operations.authentication.hmac = 1u64,
+        authentication.hmac.failed = true,
+        subgraph.service.name = %subgraph_name,
+    );
+}
@@ -0,0 +1,295 @@
+//! OAuth2 token introspection (RFC 7662)
+
+use std::num::NonZeroUsize;
+use std::ops::ControlFlow;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use displaydoc::Display;
+use http::header::ACCEPT;
+use http::StatusCode;
+use jsonwebtoken::decode_header;
+use lru::LruCache;
+use mime::APPLICATION_JSON;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::Digest;
+use sha2::Sha256;
+use thiserror::Error;
+use tokio::sync::Mutex;
+use tower::BoxError;
+use url::Url;
+
+use super::authenticate as authenticate_jwt;
+use super::authentication_failure;
+use super::extract_token;
+use super::jwks::JwksManager;
+use super::AuthenticationError;
+use super::Header;
+use super::JWTConf;
+use super::APOLLO_AUTHENTICATION_JWT_CLAIMS;
+use super::CLIENT;
+use super::DEFAULT_AUTHENTICATION_NETWORK_TIMEOUT;
+use crate::services::router;
+use crate::Context;
+
+const AUTHENTICATION_KIND: &str = "introspection";
+const DEFAULT_INTROSPECTION_CACHE_LIMIT: NonZeroUsize = match NonZeroUsize::new(512) {
+    Some(limit) => limit,
+    None => unreachable!(),
+};
+
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(super) struct IntrospectionConf {
+    /// URL of the OAuth2 token introspection endpoint
+    url: String,
+    /// Client identifier used to authenticate to the introspection endpoint
+    client_id: String,
+    /// Client secret used to authenticate to the introspection endpoint
+    client_secret: String,
+    /// Value of the `token_type_hint` parameter sent to the introspection endpoint
+    #[serde(default)]
+    token_type_hint: Option<String>,
+    /// Timeout of introspection requests in human-readable format; defaults to 15s
+    #[serde(
+        deserialize_with = "humantime_serde::deserialize",
+        default = "default_timeout"
+    )]
+    #[schemars(with = "String", default = "default_timeout")]
+    timeout: Duration,
+    /// Maximum number of introspection results kept in cache; defaults to 512
+    #[serde(default = "default_cache_limit")]
+    cache_limit: NonZeroUsize,
+    /// List of headers to add to the introspection request
+    #[serde(default)]
+    headers: Vec<Header>,
+}
+
+fn default_timeout() -> Duration {
+    DEFAULT_AUTHENTICATION_NETWORK_TIMEOUT
+}
+
+fn default_cache_limit() -> NonZeroUsize {
+    DEFAULT_INTROSPECTION_CACHE_LIMIT
+}
+
+#[derive(Debug, Display, Error)]
+pub(crate) enum IntrospectionError {
+    /// introspection request failed: {0}
+    Request(reqwest::Error),
+
+    /// introspection endpoint returned status {0}
+    Status(StatusCode),
+
+    /// invalid introspection response: {0}
+    InvalidResponse(String),
+
+    /// could not create the introspection client: {0}
+    Client(String),
+}
+
+/// Verifies opaque tokens against an OAuth2 token introspection endpoint
+#[derive(Clone)]
+pub(super) struct Introspector {
+    url: Url,
+    client_id: String,
+    client_secret: String,
+    token_type_hint: Option<String>,
+    timeout: Duration,
+    headers: Vec<Header>,
+    /// Claims of active tokens, indexed by the token's SHA-256 hash, with their expiration time
+    cache: Arc<Mutex<LruCache<String, (Value, u64)>>>,
+}
+
+impl std::fmt::Debug for Introspector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Introspector")
+            .field("url", &self.url)
+            .field("client_id", &self.client_id)
+            .finish()
+    }
+}
+
+impl Introspector {
+    pub(super) fn new(conf: &IntrospectionConf) -> Result<Self, BoxError> {
+        Ok(Self {
+            url: Url::parse(&conf.url)?,
+            client_id: conf.client_id.clone(),
+            client_secret: conf.client_secret.clone(),
+            token_type_hint: conf.token_type_hint.clone(),
+            timeout: conf.timeout,
+            headers: conf.headers.clone(),
+            cache: Arc::new(Mutex::new(LruCache::new(conf.cache_limit))),
+        })
+    }
+
+    /// Returns the claims of the token if it is active
+    ///
+    /// Active tokens are cached until their `exp` claim. Tokens without `exp` are introspected on every request.
+    pub(super) async fn introspect(
+        &self,
+        token: &str,
+    ) -> Result<Option<Value>, IntrospectionError> {
+        let key = hex::encode(Sha256::digest(token.as_bytes()));
+        let now = now();
+
+        {
+            let mut cache = self.cache.lock().await;
+            if let Some((claims, exp)) = cache.get(&key) {
+                if *exp > now {
+                    return Ok(Some(claims.clone()));
+                }
+                cache.pop(&key);
+            }
+        }
+
+        let claims = self.request(token).await?;
+        let claims = match claims {
+            Some(claims) => claims,
+            None => return Ok(None),
+        };
+
+        match claims.get("exp").and_then(|exp| exp.as_u64()) {
+            Some(exp) if exp <= now => return Ok(None),
+            Some(exp) => {
+                self.cache.lock().await.put(key, (claims.clone(), exp));
+            }
+            None => {}
+        }
+
+        Ok(Some(claims))
+    }
+
+    async fn request(&self, token: &str) -> Result<Option<Value>, IntrospectionError> {
+        let client = CLIENT
+            .as_ref()
+            .map_err(|e| IntrospectionError::Client(e.to_string()))?;
+
+        let mut form = vec![("token", token)];
+        if let Some(hint) = &self.token_type_hint {
+            form.push(("token_type_hint", hint.as_str()));
+        }
+
+        let mut builder = client
+            .post(self.url.clone())
+            .header(ACCEPT, APPLICATION_JSON.essence_str())
+            .basic_auth(&self.client_id, Some(&self.client_secret))
+            .form(&form);
+
+        for header in &self.headers {
+            builder = builder.header(header.name.clone(), header.value.clone());
+        }
+
+        let response = builder
+            .timeout(self.timeout)
+            .send()
+            .await
+            .map_err(IntrospectionError::Request)?;
+
+        if !response.status().is_success() {
+            return Err(IntrospectionError::Status(response.status()));
+        }
+
+        let mut claims: Value = response.json().await.map_err(IntrospectionError::Request)?;
+        let claims_object = claims.as_object_mut().ok_or_else(|| {
+            IntrospectionError::InvalidResponse("the response should be a JSON object".to_string())
+        })?;
+
+        match claims_object.remove("active") {
+            Some(Value::Bool(true)) => Ok(Some(claims)),
+            Some(Value::Bool(false)) => Ok(None),
+            _ => Err(IntrospectionError::InvalidResponse(
+                "the response should contain a boolean `active` member".to_string(),
+            )),
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("we should not run before EPOCH")
+        .as_secs()
+}
+
+/// Authenticates the request with token introspection, unless the token is a JWT that can be verified
+/// with the configured JWKS
+pub(super) async fn authenticate(
+    config: &JWTConf,
+    jwks_manager: &JwksManager,
+    introspector: &Introspector,
+    request: router::Request,
+) -> ControlFlow<router::Response, router::Request> {
+    let token = match extract_token(config, request.router_request.headers()) {
+        Some(Ok(token)) if config.jwks.is_empty() || decode_header(token).is_err() => {
+            Some(token.to_string())
+        }
+        _ => None,
+    };
+    // the JWT authentication handles requests without tokens and reports token extraction errors
+    let token = match token {
+        Some(token) => token,
+        None => return authenticate_jwt(config, jwks_manager, request),
+    };
+
+    match introspector.introspect(&token).await {
+        Ok(Some(claims)) => {
+            if let Err(e) = request
+                .context
+                .insert(APOLLO_AUTHENTICATION_JWT_CLAIMS, claims)
+            {
+                return failure_message(
+                    request.context,
+                    AuthenticationError::CannotInsertClaimsIntoContext(e),
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                );
+            }
+            // This is a metric and will not appear in the logs
+            tracing::info!(
+                monotonic_counter.apollo_authentication_success_count = 1u64,
+                kind = %AUTHENTICATION_KIND
+            );
+            tracing::info!(
+                monotonic_counter
+                    .apollo
+                    .router
+                    .operations
+                    .authentication
+                    .introspection = 1u64
+            );
+            ControlFlow::Continue(request)
+        }
+        Ok(None) => failure_message(
+            request.context,
+            AuthenticationError::InactiveToken,
+            StatusCode::UNAUTHORIZED,
+        ),
+        Err(e) => failure_message(
+            request.context,
+            AuthenticationError::CannotIntrospectToken(e),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        ),
+    }
+}
+
+fn failure_message(
+    context: Context,
+    error: AuthenticationError,
+    status: StatusCode,
+) -> ControlFlow<router::Response, router::Request> {
+    // This is a metric and will not appear in the logs
+    tracing::info!(
+        monotonic_counter
+            .apollo
+            .router
+            .operations
+            .authentication
+            .introspection = 1u64,
+        authentication.introspection.failed = true
+    );
+    authentication_failure(context, AUTHENTICATION_KIND, error, status)
+}
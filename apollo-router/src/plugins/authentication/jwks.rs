@@ -23,6 +23,7 @@ use tower::BoxError;
 use tracing_futures::Instrument;
 use url::Url;
 
+use super::claims::ClaimRequirements;
 use super::Header;
 use super::CLIENT;
 use super::DEFAULT_AUTHENTICATION_NETWORK_TIMEOUT;
@@ -38,6 +39,7 @@ pub(super) struct JwksManager {
 pub(super) struct JwksConfig {
     pub(super) url: Url,
     pub(super) issuer: Option<String>,
+    pub(super) claims: Arc<ClaimRequirements>,
     pub(super) algorithms: Option<HashSet<Algorithm>>,
     pub(super) poll_interval: Duration,
     pub(super) headers: Vec<Header>,
@@ -47,6 +49,7 @@ pub(super) struct JwksConfig {
 pub(super) struct JwkSetInfo {
     pub(super) jwks: JwkSet,
     pub(super) issuer: Option<String>,
+    pub(super) claims: Arc<ClaimRequirements>,
     pub(super) algorithms: Option<HashSet<Algorithm>>,
 }
 
@@ -253,6 +256,7 @@ impl<'a> Iterator for Iter<'a> {
                             return Some(JwkSetInfo {
                                 jwks: jwks.clone(),
                                 issuer: config.issuer.clone(),
+                                claims: config.claims.clone(),
                                 algorithms: config.algorithms.clone(),
                             });
                         }
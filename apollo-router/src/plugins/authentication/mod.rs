@@ -9,6 +9,7 @@ use std::time::SystemTime;
 use std::time::UNIX_EPOCH;
 
 use displaydoc::Display;
+use futures::FutureExt;
 use http::HeaderMap;
 use http::HeaderName;
 use http::HeaderValue;
@@ -37,6 +38,13 @@ use tower::ServiceBuilder;
 use tower::ServiceExt;
 use url::Url;
 
+use self::api_key::ApiKeyAuthenticator;
+use self::api_key::ApiKeyConf;
+use self::claims::ClaimRequirements;
+use self::client_certificate::ClientCertificateConf;
+use self::introspection::IntrospectionConf;
+use self::introspection::IntrospectionError;
+use self::introspection::Introspector;
 use self::jwks::JwksManager;
 use self::subgraph::SigningParams;
 use self::subgraph::SigningParamsConfig;
@@ -53,8 +61,15 @@ use crate::register_plugin;
 use crate::services::router;
 use crate::Context;
 
+mod api_key;
+mod claims;
+pub(crate) mod client_certificate;
+pub(crate) mod client_credentials;
+pub(crate) mod hmac_signing;
+pub(crate) mod introspection;
 mod jwks;
 pub(crate) mod subgraph;
+pub(crate) mod token_exchange;
 
 #[cfg(test)]
 mod tests;
@@ -100,6 +115,54 @@ pub(crate) enum AuthenticationError<'a> {
 
     /// Unsupported key algorithm: {0}
     UnsupportedKeyAlgorithm(KeyAlgorithm),
+
+    /// Inactive token
+    InactiveToken,
+
+    /// Cannot introspect token: {0}
+    CannotIntrospectToken(IntrospectionError),
+
+    /// Missing claim: '{0}'
+    MissingClaim(String),
+
+    /// Invalid claim '{claim}': expected {expected}
+    UnexpectedClaimValue { claim: String, expected: Value },
+
+    /// Invalid claim '{0}': not one of the accepted values
+    ClaimNotAccepted(String),
+
+    /// Invalid claim '{claim}': does not match '{pattern}'
+    ClaimDoesNotMatch { claim: String, pattern: String },
+
+    /// Invalid claim '{claim}': expected a value {comparison}
+    ClaimOutOfRange { claim: String, comparison: String },
+
+    /// Invalid claim '{claim}': expected a {expected}
+    InvalidClaimType {
+        claim: String,
+        expected: &'static str,
+    },
+
+    /// Missing client certificate
+    MissingClientCertificate,
+
+    /// Invalid API key
+    InvalidApiKey,
+}
+
+impl AuthenticationError<'_> {
+    /// Reason reported in metrics when the claims of a token do not meet the configured requirements
+    fn claim_failure_reason(&self) -> Option<&'static str> {
+        match self {
+            AuthenticationError::MissingClaim(_) => Some("missing"),
+            AuthenticationError::UnexpectedClaimValue { .. } => Some("equals"),
+            AuthenticationError::ClaimNotAccepted(_) => Some("one_of"),
+            AuthenticationError::ClaimDoesNotMatch { .. } => Some("matches"),
+            AuthenticationError::ClaimOutOfRange { .. } => Some("range"),
+            AuthenticationError::InvalidClaimType { .. } => Some("type"),
+            _ => None,
+        }
+    }
 }
 
 const DEFAULT_AUTHENTICATION_NETWORK_TIMEOUT: Duration = Duration::from_secs(15);
@@ -116,6 +179,9 @@ pub(crate) enum Error {
 struct Router {
     configuration: JWTConf,
     jwks_manager: JwksManager,
+    introspector: Option<Introspector>,
+    client_certificate: Option<ClientCertificateConf>,
+    api_key: Option<ApiKeyAuthenticator>,
 }
 
 struct AuthenticationPlugin {
@@ -156,6 +222,9 @@ struct JwksConf {
     poll_interval: Duration,
     /// Expected issuer for tokens verified by that JWKS
     issuer: Option<String>,
+    /// Requirements on the claims of tokens verified by that JWKS, indexed by claim name
+    #[serde(default)]
+    claims: ClaimRequirements,
     /// List of accepted algorithms. Possible values are `HS256`, `HS384`, `HS512`, `ES256`, `ES384`, `RS256`, `RS384`, `RS512`, `PS256`, `PS384`, `PS512`, `EdDSA`
     #[schemars(with = "Option<Vec<String>>", default)]
     #[serde(default)]
@@ -214,7 +283,14 @@ struct Conf {
 #[serde(deny_unknown_fields)]
 struct RouterConf {
     /// The JWT configuration
+    #[serde(default)]
     jwt: JWTConf,
+    /// OAuth2 token introspection configuration, used to verify opaque tokens
+    introspection: Option<IntrospectionConf>,
+    /// Authentication with the certificate presented by the client, when the TLS configuration of the supergraph verifies client certificates
+    client_certificate: Option<ClientCertificateConf>,
+    /// Authentication with API keys looked up in a key store file
+    api_key: Option<ApiKeyConf>,
 }
 
 fn default_header_name() -> String {
@@ -235,6 +311,13 @@ struct JWTCriteria {
     kid: Option<String>,
 }
 
+/// Requirements on the tokens verified by the keys of a JWKS
+#[derive(Clone, Debug)]
+struct TokenRequirements {
+    issuer: Option<String>,
+    claims: Arc<ClaimRequirements>,
+}
+
 /// Search the list of JWKS to find a key we can use to decode a JWT.
 ///
 /// The search criteria allow us to match a variety of keys depending on which criteria are provided
@@ -243,13 +326,14 @@ struct JWTCriteria {
 fn search_jwks(
     jwks_manager: &JwksManager,
     criteria: &JWTCriteria,
-) -> Option<Vec<(Option<String>, Jwk)>> {
+) -> Option<Vec<(TokenRequirements, Jwk)>> {
     const HIGHEST_SCORE: usize = 2;
     let mut candidates = vec![];
     let mut found_highest_score = false;
     for JwkSetInfo {
         jwks,
         issuer,
+        claims,
         algorithms,
     } in jwks_manager.iter_jwks()
     {
@@ -358,7 +442,16 @@ fn search_jwks(
                 found_highest_score = true;
             }
 
-            candidates.push((key_score, (issuer.clone(), key)));
+            candidates.push((
+                key_score,
+                (
+                    TokenRequirements {
+                        issuer: issuer.clone(),
+                        claims: claims.clone(),
+                    },
+                    key,
+                ),
+            ));
         }
     }
 
@@ -468,6 +561,7 @@ impl Plugin for AuthenticationPlugin {
                 list.push(JwksConfig {
                     url,
                     issuer: jwks_conf.issuer.clone(),
+                    claims: Arc::new(jwks_conf.claims.clone()),
                     algorithms: jwks_conf
                         .algorithms
                         .as_ref()
@@ -481,9 +575,23 @@ impl Plugin for AuthenticationPlugin {
 
             let jwks_manager = JwksManager::new(list).await?;
 
+            let introspector = router_conf
+                .introspection
+                .as_ref()
+                .map(Introspector::new)
+                .transpose()?;
+
+            let api_key = match &router_conf.api_key {
+                Some(api_key_conf) => Some(ApiKeyAuthenticator::new(api_key_conf).await?),
+                None => None,
+            };
+
             Some(Router {
                 configuration: router_conf.jwt,
                 jwks_manager,
+                introspector,
+                client_certificate: router_conf.client_certificate,
+                api_key,
             })
         } else {
             None
@@ -507,6 +615,53 @@ impl Plugin for AuthenticationPlugin {
                 }
             }
 
+            // The methods run in this order: the token, the client certificate, then API keys. Each
+            // one only sets the claims if no earlier method did, so the claims of the token take
+            // precedence over the ones of the client certificate, which take precedence over the
+            // ones of the API key. An unknown API key is still rejected.
+            let service = match config.api_key.clone() {
+                Some(api_key) => ServiceBuilder::new()
+                    .checkpoint(move |request: router::Request| Ok(api_key.authenticate(request)))
+                    .service(service)
+                    .boxed(),
+                None => service,
+            };
+
+            let service = match config.client_certificate.clone() {
+                Some(client_certificate_conf) => ServiceBuilder::new()
+                    .checkpoint(move |request: router::Request| {
+                        Ok(client_certificate::authenticate(
+                            &client_certificate_conf,
+                            request,
+                        ))
+                    })
+                    .service(service)
+                    .boxed(),
+                None => service,
+            };
+
+            if let Some(introspector) = config.introspector.clone() {
+                return ServiceBuilder::new()
+                    .instrument(authentication_service_span())
+                    .oneshot_checkpoint_async(move |request: router::Request| {
+                        let configuration = configuration.clone();
+                        let jwks_manager = jwks_manager.clone();
+                        let introspector = introspector.clone();
+                        async move {
+                            Ok(introspection::authenticate(
+                                &configuration,
+                                &jwks_manager,
+                                &introspector,
+                                request,
+                            )
+                            .await)
+                        }
+                        .boxed()
+                    })
+                    .service(service)
+                    .boxed();
+            }
+
             ServiceBuilder::new()
                 .instrument(authentication_service_span())
                 .checkpoint(move |request: router::Request| {
@@ -532,6 +687,34 @@ impl Plugin for AuthenticationPlugin {
     }
 }
 
+/// Rejects a request that failed an authentication method, and records the failure
+///
+/// `kind` names the method in the failure metric and in the logs.
+pub(super) fn authentication_failure(
+    context: Context,
+    kind: &str,
+    error: AuthenticationError,
+    status: StatusCode,
+) -> ControlFlow<router::Response, router::Request> {
+    // This is a metric and will not appear in the logs
+    tracing::info!(
+        monotonic_counter.apollo_authentication_failure_count = 1u64,
+        kind = %kind
+    );
+    tracing::info!(message = %error, "{kind} authentication failure");
+    let response = router::Response::infallible_builder()
+        .error(
+            graphql::Error::builder()
+                .message(error.to_string())
+                .extension_code("AUTH_ERROR")
+                .build(),
+        )
+        .status_code(status)
+        .context(context)
+        .build();
+    ControlFlow::Break(response)
+}
+
 fn authenticate(
     config: &JWTConf,
     jwks_manager: &JwksManager,
@@ -547,10 +730,6 @@ fn authenticate(
         status: StatusCode,
     ) -> ControlFlow<router::Response, router::Request> {
         // This is a metric and will not appear in the logs
-        tracing::info!(
-            monotonic_counter.apollo_authentication_failure_count = 1u64,
-            kind = %AUTHENTICATION_KIND
-        );
         tracing::info!(
             monotonic_counter
                 .apollo
@@ -560,41 +739,15 @@ fn authenticate(
                 .jwt = 1,
             authentication.jwt.failed = true
         );
-        tracing::info!(message = %error, "jwt authentication failure");
-        let response = router::Response::infallible_builder()
-            .error(
-                graphql::Error::builder()
-                    .message(error.to_string())
-                    .extension_code("AUTH_ERROR")
-                    .build(),
-            )
-            .status_code(status)
-            .context(context)
-            .build();
-        ControlFlow::Break(response)
+        authentication_failure(context, AUTHENTICATION_KIND, error, status)
     }
 
-    let mut jwt = None;
-    for source in &config.sources {
-        match extract_jwt(
-            source,
-            config.ignore_other_prefixes,
-            request.router_request.headers(),
-        ) {
-            None => continue,
-            Some(Err(error)) => {
-                return failure_message(request.context, error, StatusCode::BAD_REQUEST)
-            }
-            Some(Ok(extracted_jwt)) => {
-                jwt = Some(extracted_jwt);
-                break;
-            }
-        }
-    }
-
-    let jwt = match jwt {
-        Some(jwt) => jwt,
+    let jwt = match extract_token(config, request.router_request.headers()) {
         None => return ControlFlow::Continue(request),
+        Some(Err(error)) => {
+            return failure_message(request.context, error, StatusCode::BAD_REQUEST)
+        }
+        Some(Ok(jwt)) => jwt,
     };
 
     // Try to create a valid header to work with
@@ -621,14 +774,14 @@ fn authenticate(
     // Note: This will search through JWKS in the order in which they are defined
     // in configuration.
     if let Some(keys) = search_jwks(jwks_manager, &criteria) {
-        let (issuer, token_data) = match decode_jwt(jwt, keys, criteria) {
+        let (requirements, token_data) = match decode_jwt(jwt, keys, criteria) {
             Ok(data) => data,
             Err((auth_error, status_code)) => {
                 return failure_message(request.context, auth_error, status_code);
             }
         };
 
-        if let Some(configured_issuer) = issuer {
+        if let Some(configured_issuer) = requirements.issuer {
             if let Some(token_issuer) = token_data
                 .claims
                 .as_object()
@@ -648,6 +801,10 @@ fn authenticate(
             }
         }
 
+        if let Err(error) = claims::validate(&requirements.claims, &token_data.claims) {
+            return failure_message(request.context, error, StatusCode::UNAUTHORIZED);
+        }
+
         if let Err(e) = request
             .context
             .insert(APOLLO_AUTHENTICATION_JWT_CLAIMS, token_data.claims)
@@ -683,6 +840,17 @@ fn authenticate(
     }
 }
 
+/// Extracts the token from the first configured source where it is present
+fn extract_token<'a, 'b: 'a>(
+    config: &'a JWTConf,
+    headers: &'b HeaderMap,
+) -> Option<Result<&'b str, AuthenticationError<'a>>> {
+    config
+        .sources
+        .iter()
+        .find_map(|source| extract_jwt(source, config.ignore_other_prefixes, headers))
+}
+
 fn extract_jwt<'a, 'b: 'a>(
     source: &'a Source,
     ignore_other_prefixes: bool,
@@ -778,11 +946,11 @@ fn extract_jwt<'a, 'b: 'a>(
 
 fn decode_jwt(
     jwt: &str,
-    keys: Vec<(Option<String>, Jwk)>,
+    keys: Vec<(TokenRequirements, Jwk)>,
     criteria: JWTCriteria,
-) -> Result<(Option<String>, TokenData<serde_json::Value>), (AuthenticationError, StatusCode)> {
+) -> Result<(TokenRequirements, TokenData<serde_json::Value>), (AuthenticationError, StatusCode)> {
     let mut error = None;
-    for (issuer, jwk) in keys.into_iter() {
+    for (requirements, jwk) in keys.into_iter() {
         let decoding_key = match DecodingKey::from_jwk(&jwk) {
             Ok(k) => k,
             Err(e) => {
@@ -823,7 +991,7 @@ fn decode_jwt(
         validation.validate_aud = false;
 
         match decode::<serde_json::Value>(jwt, &decoding_key, &validation) {
-            Ok(v) => return Ok((issuer, v)),
+            Ok(v) => return Ok((requirements, v)),
             Err(e) => {
                 error = Some((
                     AuthenticationError::CannotDecodeJWT(e),
@@ -12,6 +12,7 @@ use aws_sigv4::http_request::SigningSettings;
 use aws_smithy_runtime_api::client::identity::Identity;
 use aws_types::region::Region;
 use http::HeaderMap;
+use http::HeaderValue;
 use http::Request;
 use hyper::Body;
 use schemars::JsonSchema;
@@ -20,6 +21,12 @@ use tower::BoxError;
 use tower::ServiceBuilder;
 use tower::ServiceExt;
 
+use super::client_credentials::ClientCredentialsConfig;
+use super::client_credentials::ClientCredentialsProvider;
+use super::hmac_signing::HmacSigner;
+use super::hmac_signing::HmacSigningConfig;
+use super::token_exchange::TokenExchangeConfig;
+use super::token_exchange::TokenExchangeProvider;
 use crate::services::SubgraphRequest;
 
 /// Hardcoded Config using access_key and secret.
@@ -168,6 +175,12 @@ impl AWSSigV4Config {
 pub(crate) enum AuthConfig {
     #[serde(rename = "aws_sig_v4")]
     AWSSigV4(AWSSigV4Config),
+    #[serde(rename = "oauth2_client_credentials")]
+    OAuth2ClientCredentials(ClientCredentialsConfig),
+    #[serde(rename = "oauth2_token_exchange")]
+    OAuth2TokenExchange(TokenExchangeConfig),
+    #[serde(rename = "hmac")]
+    Hmac(HmacSigningConfig),
 }
 
 /// Configure subgraph authentication
@@ -190,15 +203,82 @@ pub(crate) struct SigningParams {
 }
 
 #[derive(Clone)]
-pub(crate) struct SigningParamsConfig {
+pub(crate) enum SigningParamsConfig {
+    AWSSigV4(AWSSigV4SigningParams),
+    OAuth2ClientCredentials(ClientCredentialsProvider),
+    OAuth2TokenExchange(TokenExchangeProvider),
+    Hmac(HmacSigner),
+}
+
+impl SigningParamsConfig {
+    /// Returns the signing parameters for a subgraph request, bound to the client request if needed
+    pub(crate) fn for_request(self: &Arc<Self>, req: &SubgraphRequest) -> Arc<Self> {
+        match self.as_ref() {
+            Self::OAuth2TokenExchange(provider) => Arc::new(Self::OAuth2TokenExchange(
+                provider.for_client_request(req.supergraph_request.headers()),
+            )),
+            _ => self.clone(),
+        }
+    }
+
+    /// Whether requests signed with both parameters are sent with the same identity
+    ///
+    /// Parameters bound to different client requests only match if they sign with the same client token.
+    pub(crate) fn same_identity(self: &Arc<Self>, other: &Arc<Self>) -> bool {
+        match (self.as_ref(), other.as_ref()) {
+            (Self::OAuth2TokenExchange(left), Self::OAuth2TokenExchange(right)) => {
+                left.same_subject_token(right)
+            }
+            _ => Arc::ptr_eq(self, other),
+        }
+    }
+
+    pub(crate) async fn sign(
+        &self,
+        req: Request<Body>,
+        subgraph_name: &str,
+    ) -> Result<Request<Body>, BoxError> {
+        match self {
+            Self::AWSSigV4(params) => params.sign(req, subgraph_name).await,
+            Self::OAuth2ClientCredentials(provider) => provider.authorize(req, subgraph_name).await,
+            Self::OAuth2TokenExchange(provider) => provider.authorize(req, subgraph_name).await,
+            Self::Hmac(signer) => signer.sign(req, subgraph_name).await,
+        }
+    }
+
+    pub(crate) async fn sign_empty(
+        &self,
+        req: Request<()>,
+        subgraph_name: &str,
+    ) -> Result<Request<()>, BoxError> {
+        match self {
+            Self::AWSSigV4(params) => params.sign_empty(req, subgraph_name).await,
+            Self::OAuth2ClientCredentials(provider) => provider.authorize(req, subgraph_name).await,
+            Self::OAuth2TokenExchange(provider) => provider.authorize(req, subgraph_name).await,
+            Self::Hmac(signer) => signer.sign_empty(req, subgraph_name).await,
+        }
+    }
+
+    /// Drops the cached OAuth2 access token sent in `authorization` after the subgraph rejected it
+    pub(crate) async fn reject_token(&self, authorization: &HeaderValue) {
+        match self {
+            Self::OAuth2ClientCredentials(provider) => provider.reject(authorization).await,
+            Self::OAuth2TokenExchange(provider) => provider.reject(authorization).await,
+            Self::AWSSigV4(_) | Self::Hmac(_) => {}
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct AWSSigV4SigningParams {
     credentials_provider: Arc<dyn ProvideCredentials>,
     region: Region,
     service_name: String,
     subgraph_name: String,
 }
 
-impl SigningParamsConfig {
-    pub(crate) async fn sign(
+impl AWSSigV4SigningParams {
+    async fn sign(
         &self,
         mut req: Request<Body>,
         subgraph_name: &str,
@@ -238,7 +318,7 @@ impl SigningParamsConfig {
     }
 
     // This function is the same as above, except it's a new one because () doesn't implement HttpBody`
-    pub(crate) async fn sign_empty(
+    async fn sign_empty(
         &self,
         mut req: Request<()>,
         subgraph_name: &str,
@@ -319,7 +399,7 @@ fn increment_failure_counter(subgraph_name: &str) {
     );
 }
 
-pub(super) async fn make_signing_params(
+pub(crate) async fn make_signing_params(
     config: &AuthConfig,
     subgraph_name: &str,
 ) -> Result<SigningParamsConfig, BoxError> {
@@ -339,19 +419,28 @@ pub(super) async fn make_signing_params(
                 .into());
             }
 
-            Ok(SigningParamsConfig {
+            Ok(SigningParamsConfig::AWSSigV4(AWSSigV4SigningParams {
                 region: config.region(),
                 service_name: config.service_name(),
                 credentials_provider,
                 subgraph_name: subgraph_name.to_string(),
-            })
+            }))
         }
+        AuthConfig::OAuth2ClientCredentials(config) => {
+            Ok(SigningParamsConfig::OAuth2ClientCredentials(
+                ClientCredentialsProvider::new(config, subgraph_name)?,
+            ))
+        }
+        AuthConfig::OAuth2TokenExchange(config) => Ok(SigningParamsConfig::OAuth2TokenExchange(
+            TokenExchangeProvider::new(config, subgraph_name)?,
+        )),
+        AuthConfig::Hmac(config) => Ok(SigningParamsConfig::Hmac(HmacSigner::new(config)?)),
     }
 }
 
 /// There are three possible cases
 /// https://github.com/awslabs/aws-sdk-rust/blob/9c3168dafa4fd8885ce4e1fd41cec55ce982a33c/sdk/aws-sigv4/src/http_request/sign.rs#L264C1-L271C6
-fn get_signing_settings(signing_params: &SigningParamsConfig) -> SigningSettings {
+fn get_signing_settings(signing_params: &AWSSigV4SigningParams) -> SigningSettings {
     let mut settings = SigningSettings::default();
     settings.payload_checksum_kind = match signing_params.service_name.as_str() {
         "appsync" | "s3" | "vpc-lattice-svcs" => PayloadChecksumKind::XAmzSha256,
@@ -372,9 +461,11 @@ impl SubgraphAuth {
     ) -> crate::services::subgraph::BoxService {
         if let Some(signing_params) = self.params_for_service(name) {
             ServiceBuilder::new()
-                .map_request(move |req: SubgraphRequest| {
-                    let signing_params = signing_params.clone();
-                    req.context.extensions().lock().insert(signing_params);
+                .map_request(move |mut req: SubgraphRequest| {
+                    // The parameters go with the subgraph request rather than the context, which
+                    // is shared by the fetches of every subgraph
+                    let signing_params = signing_params.for_request(&req);
+                    req.subgraph_request.extensions_mut().insert(signing_params);
                     req
                 })
                 .service(service)
@@ -397,12 +488,22 @@ impl SubgraphAuth {
 
 #[cfg(test)]
 mod test {
+    use std::io;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
     use std::sync::Arc;
 
+    use hmac::Mac;
+    use http::header::AUTHORIZATION;
     use http::header::CONTENT_LENGTH;
     use http::header::CONTENT_TYPE;
     use http::header::HOST;
+    use hyper::server::conn::AddrIncoming;
+    use hyper::service::make_service_fn;
+    use hyper::service::service_fn;
+    use hyper::Server;
     use regex::Regex;
+    use sha2::Digest;
     use tower::Service;
 
     use super::*;
@@ -414,7 +515,7 @@ mod test {
     use crate::Context;
 
     async fn test_signing_settings(service_name: &str) -> SigningSettings {
-        let params: SigningParamsConfig = make_signing_params(
+        let params = make_signing_params(
             &AuthConfig::AWSSigV4(AWSSigV4Config::Hardcoded(AWSSigV4HardcodedConfig {
                 access_key_id: "id".to_string(),
                 secret_access_key: "secret".to_string(),
@@ -426,7 +527,10 @@ mod test {
         )
         .await
         .unwrap();
-        get_signing_settings(&params)
+        match params {
+            SigningParamsConfig::AWSSigV4(params) => get_signing_settings(&params),
+            _ => panic!("expected AWS SigV4 signing parameters"),
+        }
     }
 
     #[tokio::test]
@@ -604,6 +708,488 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_oauth2_client_credentials_config() {
+        serde_yaml::from_str::<Config>(
+            r#"
+        subgraphs:
+          products:
+            oauth2_client_credentials:
+              token_url: "https://idp.example.com/oauth2/token"
+              client_id: "router"
+              client_secret: "secret"
+              client_authentication: post
+              scopes: ["products:read"]
+              parameters:
+                audience: "https://products.example.com"
+              refresh_before_expiry: 1m
+        "#,
+        )
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_oauth2_client_credentials_authorization_header() -> Result<(), BoxError> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let socket_addr = listener.local_addr()?;
+
+        let token_requests = Arc::new(AtomicUsize::new(0));
+        let requests = token_requests.clone();
+        let service = make_service_fn(move |_| {
+            let requests = requests.clone();
+            async move {
+                Ok::<_, io::Error>(service_fn(move |req: http::Request<hyper::Body>| {
+                    let requests = requests.clone();
+                    async move {
+                        let count = requests.fetch_add(1, Ordering::SeqCst) + 1;
+                        let body = hyper::body::to_bytes(req.into_body()).await.unwrap();
+                        let body = String::from_utf8_lossy(&body);
+                        let response = if body.contains("grant_type=client_credentials")
+                            && body.contains("scope=products%3Aread")
+                        {
+                            http::Response::builder()
+                                .header(CONTENT_TYPE, "application/json")
+                                .body(hyper::Body::from(
+                                    serde_json::json!({
+                                        "access_token": format!("token-{count}"),
+                                        "token_type": "bearer",
+                                        "expires_in": 3600,
+                                    })
+                                    .to_string(),
+                                ))
+                                .unwrap()
+                        } else {
+                            http::Response::builder()
+                                .status(http::StatusCode::BAD_REQUEST)
+                                .header(CONTENT_TYPE, "application/json")
+                                .body(hyper::Body::from(
+                                    serde_json::json!({
+                                        "error": "invalid_scope",
+                                        "error_description": "unknown scope",
+                                    })
+                                    .to_string(),
+                                ))
+                                .unwrap()
+                        };
+                        Ok::<_, io::Error>(response)
+                    }
+                }))
+            }
+        });
+        let server = Server::builder(AddrIncoming::from_listener(listener)?).serve(service);
+        tokio::task::spawn(server);
+
+        let config = |scope: &str| {
+            AuthConfig::OAuth2ClientCredentials(
+                serde_json::from_value(serde_json::json!({
+                    "token_url": format!("http://{socket_addr}/token"),
+                    "client_id": "router",
+                    "client_secret": "secret",
+                    "scopes": [scope],
+                }))
+                .unwrap(),
+            )
+        };
+
+        // the token is requested once, then reused until it is about to expire
+        let signing_params = make_signing_params(&config("products:read"), "products").await?;
+        for _ in 0..2 {
+            let request = signing_params
+                .sign(http::Request::new(Body::empty()), "products")
+                .await?;
+            assert_eq!(
+                request.headers().get(http::header::AUTHORIZATION).unwrap(),
+                "Bearer token-1"
+            );
+        }
+        assert_eq!(token_requests.load(Ordering::SeqCst), 1);
+
+        let signing_params = make_signing_params(&config("unknown"), "products").await?;
+        let error = signing_params
+            .sign_empty(http::Request::new(()), "products")
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "could not get an OAuth2 access token for subgraph 'products': token endpoint returned status 400 Bad Request (invalid_scope: unknown scope)"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_oauth2_token_exchange_authorization_header() -> Result<(), BoxError> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let socket_addr = listener.local_addr()?;
+
+        let token_requests = Arc::new(AtomicUsize::new(0));
+        let requests = token_requests.clone();
+        let service = make_service_fn(move |_| {
+            let requests = requests.clone();
+            async move {
+                Ok::<_, io::Error>(service_fn(move |req: http::Request<hyper::Body>| {
+                    let requests = requests.clone();
+                    async move {
+                        requests.fetch_add(1, Ordering::SeqCst);
+                        let body = hyper::body::to_bytes(req.into_body()).await.unwrap();
+                        let form: HashMap<String, String> =
+                            url::form_urlencoded::parse(&body).into_owned().collect();
+                        assert_eq!(
+                            form.get("grant_type").unwrap(),
+                            "urn:ietf:params:oauth:grant-type:token-exchange"
+                        );
+                        assert_eq!(
+                            form.get("subject_token_type").unwrap(),
+                            "urn:ietf:params:oauth:token-type:access_token"
+                        );
+                        assert_eq!(form.get("audience").unwrap(), "products");
+                        let subject_token = form.get("subject_token").unwrap();
+                        Ok::<_, io::Error>(
+                            http::Response::builder()
+                                .header(CONTENT_TYPE, "application/json")
+                                .body(hyper::Body::from(
+                                    serde_json::json!({
+                                        "access_token": format!("exchanged-{subject_token}"),
+                                        "issued_token_type": "urn:ietf:params:oauth:token-type:access_token",
+                                        "token_type": "Bearer",
+                                        "expires_in": 3600,
+                                    })
+                                    .to_string(),
+                                ))
+                                .unwrap(),
+                        )
+                    }
+                }))
+            }
+        });
+        let server = Server::builder(AddrIncoming::from_listener(listener)?).serve(service);
+        tokio::task::spawn(server);
+
+        let signing_params = Arc::new(
+            make_signing_params(
+                &AuthConfig::OAuth2TokenExchange(
+                    serde_json::from_value(serde_json::json!({
+                        "token_url": format!("http://{socket_addr}/token"),
+                        "client_id": "router",
+                        "client_secret": "secret",
+                        "audience": "products",
+                    }))
+                    .unwrap(),
+                ),
+                "products",
+            )
+            .await?,
+        );
+        let subgraph_request = |authorization: Option<&str>| {
+            let mut supergraph_request = http::Request::builder();
+            if let Some(authorization) = authorization {
+                supergraph_request = supergraph_request.header(AUTHORIZATION, authorization);
+            }
+            SubgraphRequest::fake_builder()
+                .supergraph_request(Arc::new(
+                    supergraph_request
+                        .body(Request::builder().query("query").build())
+                        .unwrap(),
+                ))
+                .build()
+        };
+
+        // each client token is exchanged once, then reused until it is about to expire
+        for client_token in ["alice", "bob", "alice"] {
+            let request = signing_params
+                .for_request(&subgraph_request(Some(&format!("Bearer {client_token}"))))
+                .sign(http::Request::new(Body::empty()), "products")
+                .await?;
+            assert_eq!(
+                request.headers().get(AUTHORIZATION).unwrap(),
+                format!("Bearer exchanged-{client_token}").as_str()
+            );
+        }
+        assert_eq!(token_requests.load(Ordering::SeqCst), 2);
+
+        let error = signing_params
+            .for_request(&subgraph_request(None))
+            .sign_empty(http::Request::new(()), "products")
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "could not exchange the client token for an OAuth2 access token for subgraph 'products': the client request has no token to exchange in the 'authorization' header"
+        );
+        assert_eq!(token_requests.load(Ordering::SeqCst), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_oauth2_access_tokens_are_cached_until_rejected() -> Result<(), BoxError> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let socket_addr = listener.local_addr()?;
+
+        let token_requests = Arc::new(AtomicUsize::new(0));
+        let requests = token_requests.clone();
+        let service = make_service_fn(move |_| {
+            let requests = requests.clone();
+            async move {
+                Ok::<_, io::Error>(service_fn(move |req: http::Request<hyper::Body>| {
+                    let requests = requests.clone();
+                    async move {
+                        let count = requests.fetch_add(1, Ordering::SeqCst) + 1;
+                        let body = hyper::body::to_bytes(req.into_body()).await.unwrap();
+                        let form: HashMap<String, String> =
+                            url::form_urlencoded::parse(&body).into_owned().collect();
+                        let mut token = serde_json::json!({
+                            "access_token": format!("token-{count}"),
+                            "token_type": "Bearer",
+                        });
+                        // the test parameter sets the lifetime of the token, if any
+                        if let Some(lifetime) = form.get("lifetime") {
+                            token["expires_in"] = lifetime.parse::<u64>().unwrap().into();
+                        }
+                        Ok::<_, io::Error>(
+                            http::Response::builder()
+                                .header(CONTENT_TYPE, "application/json")
+                                .body(hyper::Body::from(token.to_string()))
+                                .unwrap(),
+                        )
+                    }
+                }))
+            }
+        });
+        let server = Server::builder(AddrIncoming::from_listener(listener)?).serve(service);
+        tokio::task::spawn(server);
+
+        let signing_params = |parameters: serde_json::Value| {
+            make_signing_params(
+                &AuthConfig::OAuth2ClientCredentials(
+                    serde_json::from_value(serde_json::json!({
+                        "token_url": format!("http://{socket_addr}/token"),
+                        "client_id": "router",
+                        "client_secret": "secret",
+                        "parameters": parameters,
+                        "refresh_before_expiry": "30s",
+                    }))
+                    .unwrap(),
+                ),
+                "products",
+            )
+        };
+        let authorization = |signing_params: &SigningParamsConfig| {
+            let signing_params = signing_params.clone();
+            async move {
+                let request = signing_params
+                    .sign(http::Request::new(Body::empty()), "products")
+                    .await
+                    .unwrap();
+                request.headers().get(AUTHORIZATION).unwrap().clone()
+            }
+        };
+
+        // a token living less than the refresh margin is still reused
+        let short_lived = signing_params(serde_json::json!({ "lifetime": "10" })).await?;
+        assert_eq!(authorization(&short_lived).await, "Bearer token-1");
+        assert_eq!(authorization(&short_lived).await, "Bearer token-1");
+        assert_eq!(token_requests.load(Ordering::SeqCst), 1);
+
+        // a token without expiration time is reused until a subgraph rejects it
+        let without_expiry = signing_params(serde_json::json!({})).await?;
+        assert_eq!(authorization(&without_expiry).await, "Bearer token-2");
+        assert_eq!(authorization(&without_expiry).await, "Bearer token-2");
+        without_expiry
+            .reject_token(&HeaderValue::from_static("Bearer token-1"))
+            .await;
+        assert_eq!(authorization(&without_expiry).await, "Bearer token-2");
+        without_expiry
+            .reject_token(&HeaderValue::from_static("Bearer token-2"))
+            .await;
+        assert_eq!(authorization(&without_expiry).await, "Bearer token-3");
+        assert_eq!(token_requests.load(Ordering::SeqCst), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_hmac_config() {
+        serde_yaml::from_str::<Config>(
+            r#"
+        subgraphs:
+          products:
+            hmac:
+              secret: "secret"
+              algorithm: sha512
+              components:
+                - method
+                - path
+                - header: x-request-id
+                - timestamp
+                - body_digest
+              encoding: base64
+              signature_header: x-signature
+              timestamp_header: x-signature-timestamp
+        "#,
+        )
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_hmac_signature_headers() -> Result<(), BoxError> {
+        let config = AuthConfig::Hmac(serde_json::from_value(serde_json::json!({
+            "secret": "secret",
+            "components": ["method", "path", { "header": "x-request-id" }, "timestamp", "body_digest"],
+        }))?);
+        let signing_params = make_signing_params(&config, "products").await?;
+
+        let body = r#"{"query":"{ me { name } }"}"#;
+        let request = signing_params
+            .sign(
+                http::Request::post("http://products.example.com/graphql?version=2")
+                    .header("x-request-id", "1234")
+                    .body(Body::from(body))?,
+                "products",
+            )
+            .await?;
+
+        let timestamp = request
+            .headers()
+            .get("x-router-timestamp")
+            .unwrap()
+            .to_str()?
+            .to_string();
+        let message = format!(
+            "POST\n/graphql?version=2\n1234\n{timestamp}\n{}",
+            hex::encode(sha2::Sha256::digest(body.as_bytes()))
+        );
+        let mut mac = hmac::Hmac::<sha2::Sha256>::new_from_slice(b"secret")?;
+        mac.update(message.as_bytes());
+        assert_eq!(
+            request.headers().get("x-router-signature").unwrap(),
+            hex::encode(mac.finalize().into_bytes()).as_str()
+        );
+        // the body is still sent to the subgraph
+        assert_eq!(hyper::body::to_bytes(request.into_body()).await?, body);
+
+        let request = signing_params
+            .sign_empty(http::Request::new(()), "products")
+            .await?;
+        assert!(request.headers().contains_key("x-router-signature"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_hmac_body_size_limit() -> Result<(), BoxError> {
+        let config = AuthConfig::Hmac(serde_json::from_value(serde_json::json!({
+            "secret": "secret",
+            "max_body_size": 16,
+        }))?);
+        let signing_params = make_signing_params(&config, "products").await?;
+
+        let body = r#"{"query":"{ me { name } }"}"#;
+        let error = signing_params
+            .sign(
+                http::Request::post("http://products.example.com/graphql")
+                    .body(Body::from(body))?,
+                "products",
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "the request body is larger than the 16 bytes that can be buffered for HMAC signing, see the `max_body_size` option"
+        );
+
+        // streamed bodies are not buffered when the body digest is not signed
+        let config = AuthConfig::Hmac(serde_json::from_value(serde_json::json!({
+            "secret": "secret",
+            "components": ["method", "path", "timestamp"],
+            "max_body_size": 16,
+        }))?);
+        let signing_params = make_signing_params(&config, "products").await?;
+        let (mut sender, streamed_body) = Body::channel();
+        let request = signing_params
+            .sign(
+                http::Request::post("http://products.example.com/graphql").body(streamed_body)?,
+                "products",
+            )
+            .await?;
+        assert!(request.headers().contains_key("x-router-signature"));
+        sender.send_data(body.into()).await?;
+        drop(sender);
+        assert_eq!(hyper::body::to_bytes(request.into_body()).await?, body);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_signing_params_are_bound_to_each_subgraph_request() -> Result<(), BoxError> {
+        let signing_params = Arc::new(SigningParams {
+            all: None,
+            subgraphs: [
+                (
+                    "products".to_string(),
+                    Arc::new(
+                        make_signing_params(
+                            &AuthConfig::Hmac(serde_json::from_value(
+                                serde_json::json!({ "secret": "secret" }),
+                            )?),
+                            "products",
+                        )
+                        .await?,
+                    ),
+                ),
+                (
+                    "reviews".to_string(),
+                    Arc::new(
+                        make_signing_params(
+                            &AuthConfig::OAuth2ClientCredentials(serde_json::from_value(
+                                serde_json::json!({
+                                    "token_url": "http://127.0.0.1:1/token",
+                                    "client_id": "router",
+                                    "client_secret": "secret",
+                                }),
+                            )?),
+                            "reviews",
+                        )
+                        .await?,
+                    ),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+        });
+
+        // the fetches of an operation share its context
+        let context = Context::new();
+        for (subgraph, expected_hmac) in [("products", true), ("reviews", false)] {
+            let mut mock = MockSubgraphService::new();
+            mock.expect_call()
+                .times(1)
+                .withf(move |request| {
+                    let params = request
+                        .subgraph_request
+                        .extensions()
+                        .get::<Arc<SigningParamsConfig>>()
+                        .unwrap();
+                    assert_eq!(
+                        matches!(params.as_ref(), SigningParamsConfig::Hmac(_)),
+                        expected_hmac
+                    );
+                    assert!(request
+                        .context
+                        .extensions()
+                        .lock()
+                        .get::<Arc<SigningParamsConfig>>()
+                        .is_none());
+                    true
+                })
+                .returning(example_response);
+
+            let mut service = SubgraphAuth {
+                signing_params: signing_params.clone(),
+            }
+            .subgraph_service(subgraph, mock.boxed());
+            let mut request = example_request();
+            request.context = context.clone();
+            service.ready().await?.call(request).await?;
+        }
+        Ok(())
+    }
+
     fn example_response(_: SubgraphRequest) -> Result<SubgraphResponse, BoxError> {
         Ok(SubgraphResponse::new_from_response(
             http::Response::default(),
@@ -644,11 +1230,12 @@ mod test {
         request: &SubgraphRequest,
         service_name: String,
     ) -> hyper::Request<hyper::Body> {
-        let signing_params = {
-            let ctx = request.context.extensions().lock();
-            let sp = ctx.get::<Arc<SigningParamsConfig>>();
-            sp.cloned().unwrap()
-        };
+        let signing_params = request
+            .subgraph_request
+            .extensions()
+            .get::<Arc<SigningParamsConfig>>()
+            .cloned()
+            .unwrap();
 
         let http_request = request
             .clone()
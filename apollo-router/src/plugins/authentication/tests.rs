@@ -3,6 +3,7 @@ use std::collections::HashSet;
 use std::io;
 use std::path::Path;
 use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
@@ -32,6 +33,8 @@ use tracing::subscriber;
 use super::Header;
 use super::*;
 use crate::assert_snapshot_subscriber;
+use crate::axum_factory::utils::ClientCertificate;
+use crate::axum_factory::utils::ConnectionInfo;
 use crate::plugin::test;
 use crate::plugins::authentication::jwks::parse_jwks;
 use crate::services::supergraph;
@@ -841,6 +844,7 @@ async fn build_jwks_search_components() -> JwksManager {
         urls.push(JwksConfig {
             url,
             issuer: None,
+            claims: Default::default(),
             algorithms: None,
             poll_interval: Duration::from_secs(60),
             headers: Vec::new(),
@@ -952,6 +956,7 @@ fn make_manager(jwk: &Jwk, issuer: Option<String>) -> JwksManager {
     let list = vec![JwksConfig {
         url: url.clone(),
         issuer,
+        claims: Default::default(),
         algorithms: None,
         poll_interval: Duration::from_secs(60),
         headers: Vec::new(),
@@ -1141,6 +1146,167 @@ async fn issuer_check() {
     }
 }
 
+#[tokio::test]
+async fn claims_check() {
+    let signing_key = SigningKey::random(&mut OsRng);
+    let verifying_key = signing_key.verifying_key();
+    let point = verifying_key.to_encoded_point(false);
+
+    let encoding_key = EncodingKey::from_ec_der(&signing_key.to_pkcs8_der().unwrap().to_bytes());
+
+    let jwk = Jwk {
+        common: CommonParameters {
+            public_key_use: Some(PublicKeyUse::Signature),
+            key_operations: Some(vec![KeyOperations::Verify]),
+            key_algorithm: Some(KeyAlgorithm::ES256),
+            key_id: Some("hello".to_string()),
+            ..Default::default()
+        },
+        algorithm: AlgorithmParameters::EllipticCurve(EllipticCurveKeyParameters {
+            key_type: EllipticCurveKeyType::EC,
+            curve: EllipticCurve::P256,
+            x: BASE64_URL_SAFE_NO_PAD.encode(point.x().unwrap()),
+            y: BASE64_URL_SAFE_NO_PAD.encode(point.y().unwrap()),
+        }),
+    };
+
+    let url = Url::from_str("file:///jwks.json").unwrap();
+    let claims: ClaimRequirements = serde_json::from_value(serde_json::json!({
+        "aud": { "one_of": ["router", "gateway"] },
+        "iat": { "gte": 1700000000 },
+        "email": { "matches": "@example\\.com$" },
+        "tenant_status": { "equals": "active" },
+        "level": { "gt": 1, "lte": 3 },
+    }))
+    .unwrap();
+    let manager = JwksManager::new_test(
+        vec![JwksConfig {
+            url: url.clone(),
+            issuer: None,
+            claims: Arc::new(claims),
+            algorithms: None,
+            poll_interval: Duration::from_secs(60),
+            headers: Vec::new(),
+        }],
+        HashMap::from([(
+            url,
+            JwkSet {
+                keys: vec![jwk.clone()],
+            },
+        )]),
+    );
+
+    let mut config = JWTConf::default();
+    config.sources.push(Source::Header {
+        name: super::default_header_name(),
+        value_prefix: super::default_header_value_prefix(),
+    });
+
+    let valid_claims = serde_json::json!({
+        "sub": "test",
+        "exp": get_current_timestamp(),
+        "aud": ["other", "router"],
+        "iat": 1700000001,
+        "email": "user@example.com",
+        "tenant_status": "active",
+        "level": 3,
+    });
+
+    let authenticate_with = |claims: Value| {
+        let token = encode(
+            &jsonwebtoken::Header::new(Algorithm::ES256),
+            &claims,
+            &encoding_key,
+        )
+        .unwrap();
+
+        let request = supergraph::Request::canned_builder()
+            .operation_name("me".to_string())
+            .header(http::header::AUTHORIZATION, format!("Bearer {token}"))
+            .build()
+            .unwrap();
+
+        authenticate(&config, &manager, request.try_into().unwrap())
+    };
+
+    match authenticate_with(valid_claims.clone()) {
+        ControlFlow::Break(res) => {
+            panic!("unexpected response: {res:?}");
+        }
+        ControlFlow::Continue(req) => {
+            let claims: Value = req
+                .context
+                .get(APOLLO_AUTHENTICATION_JWT_CLAIMS)
+                .unwrap()
+                .unwrap();
+            assert_eq!(claims, valid_claims);
+        }
+    }
+
+    let invalid_claims = [
+        ("aud", None, "Missing claim: 'aud'"),
+        (
+            "aud",
+            Some(serde_json::json!("other")),
+            "Invalid claim 'aud': not one of the accepted values",
+        ),
+        (
+            "iat",
+            Some(serde_json::json!(1600000000)),
+            "Invalid claim 'iat': expected a value greater than or equal to 1700000000",
+        ),
+        (
+            "email",
+            Some(serde_json::json!("user@example.org")),
+            "Invalid claim 'email': does not match '@example\\.com$'",
+        ),
+        (
+            "tenant_status",
+            Some(serde_json::json!("suspended")),
+            "Invalid claim 'tenant_status': expected \"active\"",
+        ),
+        (
+            "level",
+            Some(serde_json::json!("high")),
+            "Invalid claim 'level': expected a number",
+        ),
+    ];
+
+    for (claim, value, message) in invalid_claims {
+        let mut claims = valid_claims.clone();
+        match value {
+            Some(value) => claims[claim] = value,
+            None => {
+                claims.as_object_mut().unwrap().remove(claim);
+            }
+        }
+
+        match authenticate_with(claims) {
+            ControlFlow::Break(res) => {
+                assert_eq!(res.response.status(), StatusCode::UNAUTHORIZED);
+                let response: graphql::Response = serde_json::from_slice(
+                    &hyper::body::to_bytes(res.response.into_body())
+                        .await
+                        .unwrap(),
+                )
+                .unwrap();
+                assert_eq!(
+                    response,
+                    graphql::Response::builder()
+                        .errors(vec![graphql::Error::builder()
+                            .extension_code("AUTH_ERROR")
+                            .message(message)
+                            .build()])
+                        .build()
+                );
+            }
+            ControlFlow::Continue(_) => {
+                panic!("claims check should have failed for '{claim}'")
+            }
+        }
+    }
+}
+
 #[tokio::test]
 async fn it_rejects_key_with_restricted_algorithm() {
     let mut sets = vec![];
@@ -1155,6 +1321,7 @@ async fn it_rejects_key_with_restricted_algorithm() {
         urls.push(JwksConfig {
             url,
             issuer: None,
+            claims: Default::default(),
             algorithms: Some(HashSet::from([Algorithm::RS256])),
             poll_interval: Duration::from_secs(60),
             headers: Vec::new(),
@@ -1187,6 +1354,7 @@ async fn it_rejects_and_accepts_keys_with_restricted_algorithms_and_unknown_jwks
         urls.push(JwksConfig {
             url,
             issuer: None,
+            claims: Default::default(),
             algorithms: Some(HashSet::from([Algorithm::RS256])),
             poll_interval: Duration::from_secs(60),
             headers: Vec::new(),
@@ -1226,6 +1394,7 @@ async fn it_accepts_key_without_use_or_keyops() {
         urls.push(JwksConfig {
             url,
             issuer: None,
+            claims: Default::default(),
             algorithms: None,
             poll_interval: Duration::from_secs(60),
             headers: Vec::new(),
@@ -1257,6 +1426,7 @@ async fn it_accepts_elliptic_curve_key_without_alg() {
         urls.push(JwksConfig {
             url,
             issuer: None,
+            claims: Default::default(),
             algorithms: None,
             poll_interval: Duration::from_secs(60),
             headers: Vec::new(),
@@ -1288,6 +1458,7 @@ async fn it_accepts_rsa_key_without_alg() {
         urls.push(JwksConfig {
             url,
             issuer: None,
+            claims: Default::default(),
             algorithms: None,
             poll_interval: Duration::from_secs(60),
             headers: Vec::new(),
@@ -1356,6 +1527,7 @@ async fn jwks_send_headers() {
     let _jwks_manager = JwksManager::new(vec![JwksConfig {
         url,
         issuer: None,
+        claims: Default::default(),
         algorithms: Some(HashSet::from([Algorithm::RS256])),
         poll_interval: Duration::from_secs(60),
         headers: vec![Header {
@@ -1368,3 +1540,449 @@ async fn jwks_send_headers() {
 
     assert!(got_header.load(Ordering::Acquire));
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn it_authenticates_opaque_tokens_with_introspection() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let socket_addr = listener.local_addr().unwrap();
+
+    let introspection_calls = Arc::new(AtomicUsize::new(0));
+    let calls = introspection_calls.clone();
+    let service = make_service_fn(move |_| {
+        let calls = calls.clone();
+        async move {
+            Ok::<_, io::Error>(service_fn(move |req: http::Request<hyper::Body>| {
+                let calls = calls.clone();
+                async move {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    let authorized = req
+                        .headers()
+                        .get(http::header::AUTHORIZATION)
+                        .and_then(|v| v.to_str().ok())
+                        == Some("Basic cm91dGVyOnNlY3JldA==");
+                    let body = hyper::body::to_bytes(req.into_body()).await.unwrap();
+                    let body = String::from_utf8_lossy(&body);
+                    let response = if !authorized {
+                        return Ok::<_, io::Error>(
+                            http::Response::builder()
+                                .status(StatusCode::UNAUTHORIZED)
+                                .body(hyper::Body::empty())
+                                .unwrap(),
+                        );
+                    } else if body.contains("token=active-token") {
+                        serde_json::json!({
+                            "active": true,
+                            "sub": "test",
+                            "scope": "read:user",
+                            "exp": get_current_timestamp() + 3600,
+                        })
+                    } else {
+                        serde_json::json!({ "active": false })
+                    };
+                    Ok::<_, io::Error>(
+                        http::Response::builder()
+                            .header(CONTENT_TYPE, APPLICATION_JSON.essence_str())
+                            .status(StatusCode::OK)
+                            .body::<hyper::Body>(response.to_string().into())
+                            .unwrap(),
+                    )
+                }
+            }))
+        }
+    });
+    let server = Server::builder(AddrIncoming::from_listener(listener).unwrap()).serve(service);
+    tokio::task::spawn(server);
+
+    let introspector = Introspector::new(
+        &serde_json::from_value(serde_json::json!({
+            "url": format!("http://{socket_addr}/introspect"),
+            "client_id": "router",
+            "client_secret": "secret",
+        }))
+        .unwrap(),
+    )
+    .unwrap();
+    let manager = JwksManager::new_test(vec![], HashMap::new());
+    let mut config = JWTConf::default();
+    config.sources.push(Source::Header {
+        name: super::default_header_name(),
+        value_prefix: super::default_header_value_prefix(),
+    });
+
+    for _ in 0..2 {
+        let request = supergraph::Request::canned_builder()
+            .operation_name("me".to_string())
+            .header(http::header::AUTHORIZATION, "Bearer active-token")
+            .build()
+            .unwrap();
+
+        match introspection::authenticate(
+            &config,
+            &manager,
+            &introspector,
+            request.try_into().unwrap(),
+        )
+        .await
+        {
+            ControlFlow::Break(res) => {
+                panic!("unexpected response: {res:?}");
+            }
+            ControlFlow::Continue(req) => {
+                let claims: Value = req
+                    .context
+                    .get(APOLLO_AUTHENTICATION_JWT_CLAIMS)
+                    .unwrap()
+                    .unwrap();
+                assert_eq!(claims["sub"], "test");
+                assert_eq!(claims["scope"], "read:user");
+                assert!(claims.get("active").is_none());
+            }
+        }
+    }
+    // the second request used the cached result
+    assert_eq!(introspection_calls.load(Ordering::SeqCst), 1);
+
+    let request = supergraph::Request::canned_builder()
+        .operation_name("me".to_string())
+        .header(http::header::AUTHORIZATION, "Bearer revoked-token")
+        .build()
+        .unwrap();
+
+    match introspection::authenticate(
+        &config,
+        &manager,
+        &introspector,
+        request.try_into().unwrap(),
+    )
+    .await
+    {
+        ControlFlow::Break(res) => {
+            assert_eq!(res.response.status(), StatusCode::UNAUTHORIZED);
+            let response: graphql::Response = serde_json::from_slice(
+                &hyper::body::to_bytes(res.response.into_body())
+                    .await
+                    .unwrap(),
+            )
+            .unwrap();
+            assert_eq!(
+                response,
+                graphql::Response::builder()
+                    .errors(vec![graphql::Error::builder()
+                        .extension_code("AUTH_ERROR")
+                        .message("Inactive token")
+                        .build()])
+                    .build()
+            );
+        }
+        ControlFlow::Continue(_) => {
+            panic!("inactive tokens should be rejected")
+        }
+    }
+}
+
+#[tokio::test]
+async fn it_authenticates_with_client_certificates() {
+    let config: client_certificate::ClientCertificateConf =
+        serde_json::from_value(serde_json::json!({
+            "required": true,
+            "claims": {
+                "sub": "common_name",
+                "scope": "subject_alternative_names",
+            }
+        }))
+        .unwrap();
+
+    let mut request: router::Request = supergraph::Request::canned_builder()
+        .operation_name("me".to_string())
+        .build()
+        .unwrap()
+        .try_into()
+        .unwrap();
+    request
+        .router_request
+        .extensions_mut()
+        .insert(ConnectionInfo {
+            peer_address: None,
+            server_address: None,
+            client_certificate: Some(Arc::new(ClientCertificate {
+                subject: "CN=billing,O=Example".to_string(),
+                common_name: Some("billing".to_string()),
+                subject_alternative_names: vec![
+                    "read:invoices".to_string(),
+                    "write:invoices".to_string(),
+                ],
+                fingerprint: "0123456789abcdef".to_string(),
+            })),
+        });
+
+    match client_certificate::authenticate(&config, request) {
+        ControlFlow::Break(res) => {
+            panic!("unexpected response: {res:?}");
+        }
+        ControlFlow::Continue(req) => {
+            let certificate: ClientCertificate = req
+                .context
+                .get(client_certificate::APOLLO_AUTHENTICATION_CLIENT_CERTIFICATE)
+                .unwrap()
+                .unwrap();
+            assert_eq!(certificate.fingerprint, "0123456789abcdef");
+
+            let claims: Value = req
+                .context
+                .get(APOLLO_AUTHENTICATION_JWT_CLAIMS)
+                .unwrap()
+                .unwrap();
+            assert_eq!(
+                claims,
+                serde_json::json!({ "sub": "billing", "scope": "read:invoices write:invoices" })
+            );
+        }
+    }
+
+    // the certificate is required
+    let request = supergraph::Request::canned_builder()
+        .operation_name("me".to_string())
+        .build()
+        .unwrap();
+
+    match client_certificate::authenticate(&config, request.try_into().unwrap()) {
+        ControlFlow::Break(res) => {
+            assert_eq!(res.response.status(), StatusCode::UNAUTHORIZED);
+            let response: graphql::Response = serde_json::from_slice(
+                &hyper::body::to_bytes(res.response.into_body())
+                    .await
+                    .unwrap(),
+            )
+            .unwrap();
+            assert_eq!(
+                response,
+                graphql::Response::builder()
+                    .errors(vec![graphql::Error::builder()
+                        .extension_code("AUTH_ERROR")
+                        .message("Missing client certificate")
+                        .build()])
+                    .build()
+            );
+        }
+        ControlFlow::Continue(_) => {
+            panic!("requests without client certificates should be rejected")
+        }
+    }
+}
+
+#[tokio::test]
+async fn it_authenticates_with_api_keys() {
+    use sha2::Digest;
+
+    let hash = |key: &str| hex::encode(sha2::Sha256::digest(key.as_bytes()));
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("keys.yaml");
+    std::fs::write(
+        &path,
+        format!(
+            "keys:\n  - sha256: {}\n    client_name: billing\n    scopes: [\"read:invoices\", \"write:invoices\"]\n    attributes:\n      rate_limit_tier: gold\n",
+            hash("first-key")
+        ),
+    )
+    .unwrap();
+
+    let config: api_key::ApiKeyConf = serde_json::from_value(serde_json::json!({
+        "path": path,
+        "sources": [
+            { "type": "header", "name": "x-api-key" },
+            { "type": "query_parameter", "name": "api_key" }
+        ]
+    }))
+    .unwrap();
+    let authenticator = api_key::ApiKeyAuthenticator::new(&config).await.unwrap();
+
+    let request = |key: Option<&str>, query: Option<&str>| -> router::Request {
+        let mut request: router::Request = supergraph::Request::canned_builder()
+            .operation_name("me".to_string())
+            .build()
+            .unwrap()
+            .try_into()
+            .unwrap();
+        if let Some(key) = key {
+            request
+                .router_request
+                .headers_mut()
+                .insert("x-api-key", HeaderValue::from_str(key).unwrap());
+        }
+        if let Some(query) = query {
+            *request.router_request.uri_mut() =
+                format!("http://localhost/graphql?{query}").parse().unwrap();
+        }
+        request
+    };
+
+    for request in [
+        request(Some("first-key"), None),
+        request(None, Some("api_key=first-key")),
+    ] {
+        match authenticator.authenticate(request) {
+            ControlFlow::Break(res) => {
+                panic!("unexpected response: {res:?}");
+            }
+            ControlFlow::Continue(req) => {
+                let claims: Value = req
+                    .context
+                    .get(APOLLO_AUTHENTICATION_JWT_CLAIMS)
+                    .unwrap()
+                    .unwrap();
+                assert_eq!(
+                    claims,
+                    serde_json::json!({
+                        "client_name": "billing",
+                        "scope": "read:invoices write:invoices",
+                        "rate_limit_tier": "gold"
+                    })
+                );
+            }
+        }
+    }
+
+    // requests without API keys are left to the other authentication methods
+    match authenticator.authenticate(request(None, None)) {
+        ControlFlow::Break(res) => {
+            panic!("unexpected response: {res:?}");
+        }
+        ControlFlow::Continue(req) => {
+            assert!(!req.context.contains_key(APOLLO_AUTHENTICATION_JWT_CLAIMS));
+        }
+    }
+
+    match authenticator.authenticate(request(Some("second-key"), None)) {
+        ControlFlow::Break(res) => {
+            assert_eq!(res.response.status(), StatusCode::UNAUTHORIZED);
+        }
+        ControlFlow::Continue(_) => {
+            panic!("unknown API keys should be rejected")
+        }
+    }
+
+    // the key store is reloaded when the file changes
+    std::fs::write(
+        &path,
+        format!(
+            "keys:\n  - sha256: {}\n    client_name: shipping\n",
+            hash("second-key")
+        ),
+    )
+    .unwrap();
+
+    let mut reloaded = false;
+    for _ in 0..50 {
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        if let ControlFlow::Continue(req) =
+            authenticator.authenticate(request(Some("second-key"), None))
+        {
+            let claims: Value = req
+                .context
+                .get(APOLLO_AUTHENTICATION_JWT_CLAIMS)
+                .unwrap()
+                .unwrap();
+            assert_eq!(claims, serde_json::json!({ "client_name": "shipping" }));
+            reloaded = true;
+            break;
+        }
+    }
+    assert!(reloaded, "the key store was not reloaded");
+    assert!(authenticator
+        .authenticate(request(Some("first-key"), None))
+        .is_break());
+}
+
+#[tokio::test]
+async fn it_prefers_client_certificate_claims_to_api_key_claims() {
+    use sha2::Digest;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("keys.yaml");
+    std::fs::write(
+        &path,
+        format!(
+            "keys:\n  - sha256: {}\n    client_name: billing\n",
+            hex::encode(sha2::Sha256::digest(b"first-key"))
+        ),
+    )
+    .unwrap();
+
+    let claims = Arc::new(std::sync::Mutex::new(None));
+    let captured = claims.clone();
+    let router = crate::TestHarness::builder()
+        .configuration_json(serde_json::json!({
+            "authentication": {
+                "router": {
+                    "jwt": {
+                        "jwks": [{ "url": create_an_url("jwks.json") }]
+                    },
+                    "client_certificate": {
+                        "claims": { "sub": "common_name" }
+                    },
+                    "api_key": { "path": path }
+                }
+            }
+        }))
+        .unwrap()
+        .supergraph_hook(move |_| {
+            let captured = captured.clone();
+            tower::service_fn(move |req: supergraph::Request| {
+                *captured.lock().unwrap() = req
+                    .context
+                    .get::<_, Value>(APOLLO_AUTHENTICATION_JWT_CLAIMS)
+                    .unwrap();
+                async move {
+                    Ok::<_, BoxError>(
+                        supergraph::Response::fake_builder()
+                            .context(req.context)
+                            .build()
+                            .unwrap(),
+                    )
+                }
+            })
+            .boxed()
+        })
+        .build_router()
+        .await
+        .unwrap();
+
+    let request = |key: &str| -> router::Request {
+        let mut request: router::Request = supergraph::Request::canned_builder()
+            .build()
+            .unwrap()
+            .try_into()
+            .unwrap();
+        request
+            .router_request
+            .headers_mut()
+            .insert("x-api-key", HeaderValue::from_str(key).unwrap());
+        request
+            .router_request
+            .extensions_mut()
+            .insert(ConnectionInfo {
+                peer_address: None,
+                server_address: None,
+                client_certificate: Some(Arc::new(ClientCertificate {
+                    subject: "CN=shipping".to_string(),
+                    common_name: Some("shipping".to_string()),
+                    subject_alternative_names: Vec::new(),
+                    fingerprint: "0123456789abcdef".to_string(),
+                })),
+            });
+        request
+    };
+
+    // the client certificate is checked before the API key, so its claims are kept
+    let response = router.clone().oneshot(request("first-key")).await.unwrap();
+    assert_eq!(response.response.status(), StatusCode::OK);
+    assert_eq!(
+        claims.lock().unwrap().take(),
+        Some(serde_json::json!({ "sub": "shipping" }))
+    );
+
+    // an unknown API key is rejected even with a client certificate
+    let response = router.oneshot(request("second-key")).await.unwrap();
+    assert_eq!(response.response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(claims.lock().unwrap().take(), None);
+}
@@ -0,0 +1,279 @@
+//! OAuth2 token exchange (RFC 8693) for subgraph requests
+
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use http::header::AUTHORIZATION;
+use http::HeaderMap;
+use http::HeaderName;
+use http::HeaderValue;
+use http::Request;
+use lru::LruCache;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use sha2::Digest;
+use sha2::Sha256;
+use tokio::sync::Mutex;
+use tower::BoxError;
+use url::Url;
+
+use super::client_credentials::authorization_header;
+use super::client_credentials::request_token;
+use super::client_credentials::set_authorization;
+use super::client_credentials::CachedToken;
+use super::client_credentials::ClientAuthenticationMethod;
+use super::client_credentials::Grant;
+use super::DEFAULT_AUTHENTICATION_NETWORK_TIMEOUT;
+
+const GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:token-exchange";
+const DEFAULT_SUBJECT_TOKEN_TYPE: &str = "urn:ietf:params:oauth:token-type:access_token";
+const DEFAULT_REFRESH_BEFORE_EXPIRY: Duration = Duration::from_secs(30);
+const DEFAULT_TOKEN_EXCHANGE_CACHE_LIMIT: NonZeroUsize = match NonZeroUsize::new(512) {
+    Some(limit) => limit,
+    None => unreachable!(),
+};
+
+/// Exchange the token of the client request for an access token to the subgraph with the OAuth2 token exchange grant
+#[derive(Clone, Debug, JsonSchema, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub(crate) struct TokenExchangeConfig {
+    /// URL of the OAuth2 token endpoint
+    pub(crate) token_url: String,
+    /// Client identifier
+    pub(crate) client_id: String,
+    /// Client secret
+    pub(crate) client_secret: String,
+    /// How the client credentials are sent to the token endpoint; defaults to `basic`
+    #[serde(default)]
+    pub(crate) client_authentication: ClientAuthenticationMethod,
+    /// Header of the client request containing the token to exchange; defaults to `authorization`
+    #[serde(default = "default_subject_token_header")]
+    pub(crate) subject_token_header: String,
+    /// Prefix of the token in the header value; defaults to `Bearer`
+    #[serde(default = "default_subject_token_prefix")]
+    pub(crate) subject_token_prefix: String,
+    /// Type of the token to exchange; defaults to `urn:ietf:params:oauth:token-type:access_token`
+    #[serde(default = "default_subject_token_type")]
+    pub(crate) subject_token_type: String,
+    /// Type of the requested token, sent in the `requested_token_type` parameter
+    #[serde(default)]
+    pub(crate) requested_token_type: Option<String>,
+    /// Logical name of the subgraph, sent in the `audience` parameter
+    #[serde(default)]
+    pub(crate) audience: Option<String>,
+    /// Scopes requested for the access token
+    #[serde(default)]
+    pub(crate) scopes: Vec<String>,
+    /// Additional parameters sent to the token endpoint, like `resource`
+    #[serde(default)]
+    pub(crate) parameters: HashMap<String, String>,
+    /// Tokens are exchanged again this long before they expire, in human-readable format; defaults to 30s
+    #[serde(
+        deserialize_with = "humantime_serde::deserialize",
+        default = "default_refresh_before_expiry"
+    )]
+    #[schemars(with = "String", default = "default_refresh_before_expiry")]
+    pub(crate) refresh_before_expiry: Duration,
+    /// Timeout of token requests in human-readable format; defaults to 15s
+    #[serde(
+        deserialize_with = "humantime_serde::deserialize",
+        default = "default_timeout"
+    )]
+    #[schemars(with = "String", default = "default_timeout")]
+    pub(crate) timeout: Duration,
+    /// Maximum number of exchanged tokens kept in cache; defaults to 512
+    #[serde(default = "default_cache_limit")]
+    pub(crate) cache_limit: NonZeroUsize,
+}
+
+fn default_subject_token_header() -> String {
+    AUTHORIZATION.to_string()
+}
+
+fn default_subject_token_prefix() -> String {
+    "Bearer".to_string()
+}
+
+fn default_subject_token_type() -> String {
+    DEFAULT_SUBJECT_TOKEN_TYPE.to_string()
+}
+
+fn default_refresh_before_expiry() -> Duration {
+    DEFAULT_REFRESH_BEFORE_EXPIRY
+}
+
+fn default_timeout() -> Duration {
+    DEFAULT_AUTHENTICATION_NETWORK_TIMEOUT
+}
+
+fn default_cache_limit() -> NonZeroUsize {
+    DEFAULT_TOKEN_EXCHANGE_CACHE_LIMIT
+}
+
+/// Exchanges the tokens of client requests for access tokens to a subgraph
+///
+/// The provider stored in the plugin has no subject token: [`TokenExchangeProvider::for_client_request`]
+/// binds it to the token of each client request.
+#[derive(Clone)]
+pub(crate) struct TokenExchangeProvider {
+    config: Arc<TokenExchangeConfig>,
+    token_url: Url,
+    subject_token_header: HeaderName,
+    subgraph_name: String,
+    /// Exchanged tokens, indexed by the subject token's SHA-256 hash
+    cache: Arc<Mutex<LruCache<String, CachedToken>>>,
+    subject_token: Option<Arc<str>>,
+}
+
+impl TokenExchangeProvider {
+    pub(crate) fn new(config: &TokenExchangeConfig, subgraph_name: &str) -> Result<Self, BoxError> {
+        Ok(Self {
+            config: Arc::new(config.clone()),
+            token_url: Url::parse(&config.token_url)?,
+            subject_token_header: HeaderName::try_from(config.subject_token_header.as_str())?,
+            subgraph_name: subgraph_name.to_string(),
+            cache: Arc::new(Mutex::new(LruCache::new(config.cache_limit))),
+            subject_token: None,
+        })
+    }
+
+    /// Returns a provider exchanging the token found in the headers of the client request
+    pub(crate) fn for_client_request(&self, headers: &HeaderMap) -> Self {
+        let subject_token = headers
+            .get(&self.subject_token_header)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| {
+                if self.config.subject_token_prefix.is_empty() {
+                    Some(value)
+                } else {
+                    value
+                        .strip_prefix(self.config.subject_token_prefix.as_str())
+                        .and_then(|token| token.strip_prefix(' '))
+                }
+            })
+            .map(str::trim)
+            .filter(|token| !token.is_empty())
+            .map(Arc::from);
+        Self {
+            subject_token,
+            ..self.clone()
+        }
+    }
+
+    /// Whether both providers exchange the same client token for the same subgraph
+    pub(crate) fn same_subject_token(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.cache, &other.cache) && self.subject_token == other.subject_token
+    }
+
+    /// Adds the `Authorization` header with the exchanged access token to the request
+    pub(crate) async fn authorize<B>(
+        &self,
+        req: Request<B>,
+        subgraph_name: &str,
+    ) -> Result<Request<B>, BoxError> {
+        set_authorization(
+            req,
+            self.authorization().await,
+            Grant::TokenExchange,
+            subgraph_name,
+        )
+    }
+
+    /// Drops the token exchanged for the client token if a subgraph rejected it
+    pub(crate) async fn reject(&self, authorization: &HeaderValue) {
+        let Some(subject_token) = self.subject_token.as_deref() else {
+            return;
+        };
+        let key = cache_key(subject_token);
+        let mut cache = self.cache.lock().await;
+        if cache
+            .peek(&key)
+            .is_some_and(|cached| cached.authorization == *authorization)
+        {
+            cache.pop(&key);
+        }
+    }
+
+    async fn authorization(&self) -> Result<HeaderValue, String> {
+        let subject_token = self.subject_token.as_deref().ok_or_else(|| {
+            format!(
+                "the client request has no token to exchange in the '{}' header",
+                self.subject_token_header
+            )
+        })?;
+        let key = cache_key(subject_token);
+
+        {
+            let mut cache = self.cache.lock().await;
+            if let Some(cached) = cache.get(&key) {
+                if cached.is_fresh() {
+                    return Ok(cached.authorization.clone());
+                }
+                cache.pop(&key);
+            }
+        }
+
+        let requested_at = Instant::now();
+        let scope = self.config.scopes.join(" ");
+        let mut form = vec![
+            ("grant_type", GRANT_TYPE),
+            ("subject_token", subject_token),
+            (
+                "subject_token_type",
+                self.config.subject_token_type.as_str(),
+            ),
+        ];
+        if let Some(requested_token_type) = &self.config.requested_token_type {
+            form.push(("requested_token_type", requested_token_type.as_str()));
+        }
+        if let Some(audience) = &self.config.audience {
+            form.push(("audience", audience.as_str()));
+        }
+        if !scope.is_empty() {
+            form.push(("scope", scope.as_str()));
+        }
+        for (name, value) in &self.config.parameters {
+            form.push((name.as_str(), value.as_str()));
+        }
+        let response = request_token(
+            &self.token_url,
+            &self.config.client_id,
+            &self.config.client_secret,
+            &self.config.client_authentication,
+            form,
+            self.config.timeout,
+        )
+        .await?;
+        let authorization = authorization_header(&response)?;
+
+        self.cache.lock().await.put(
+            key,
+            CachedToken::new(
+                authorization.clone(),
+                response.expires_in,
+                requested_at,
+                self.config.refresh_before_expiry,
+            ),
+        );
+
+        Ok(authorization)
+    }
+}
+
+/// Exchanged tokens are indexed by the SHA-256 hash of the client token
+fn cache_key(subject_token: &str) -> String {
+    hex::encode(Sha256::digest(subject_token.as_bytes()))
+}
+
+impl std::fmt::Debug for TokenExchangeProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TokenExchangeProvider")
+            .field("token_url", &self.token_url)
+            .field("client_id", &self.config.client_id)
+            .field("subgraph_name", &self.subgraph_name)
+            .finish()
+    }
+}
@@ -0,0 +1,469 @@
+//! Authorization of arguments and input object fields
+//!
+//! The `@authenticated`, `@requiresScopes` and `@policy` directives can be applied to argument
+//! and input field definitions. Unauthorized arguments, and unauthorized input fields written in
+//! the query, are removed from the query. Input fields provided through variables are only known
+//! at execution time: the variables that can contain them are recorded as [`UnauthorizedVariable`],
+//! and their values are checked against the input object types of the schema before execution.
+//!
+//! Required arguments and input fields cannot be removed, so the field using them is removed
+//! instead.
+
+use apollo_compiler::ast;
+use apollo_compiler::schema;
+use apollo_compiler::Node;
+use serde_json_bytes::Value;
+use tower::BoxError;
+
+use super::AuthorizationDirective;
+use super::UnauthorizedVariable;
+use crate::json_ext::Path;
+use crate::json_ext::PathElement;
+use crate::spec::query::transform;
+
+/// Query filtering of an authorization directive, applied to the arguments and input fields of
+/// the fields it keeps
+pub(crate) trait ArgumentsFilteringVisitor: transform::Visitor {
+    const DIRECTIVE: AuthorizationDirective;
+
+    /// Checks the directives of an argument or input field definition
+    fn is_input_value_authorized(&self, directives: &ast::DirectiveList) -> bool;
+
+    /// Records that the field at the current path uses unauthorized arguments or input fields
+    fn unauthorized_arguments(&mut self);
+
+    fn dry_run(&self) -> bool;
+
+    fn unauthorized_variables(&mut self) -> &mut Vec<UnauthorizedVariable>;
+
+    /// Removes the unauthorized arguments and input fields from an authorized field
+    fn field_arguments(
+        &mut self,
+        field_def: &ast::FieldDefinition,
+        node: &ast::Field,
+    ) -> Result<Option<ast::Field>, BoxError> {
+        let arguments = filter_arguments(
+            self.schema(),
+            field_def,
+            &node.arguments,
+            Self::DIRECTIVE,
+            |directives| self.is_input_value_authorized(directives),
+        );
+        if arguments.filtered || arguments.required {
+            self.unauthorized_arguments();
+        }
+
+        if self.dry_run() {
+            self.unauthorized_variables().extend(arguments.variables);
+            transform::field(self, field_def, node)
+        } else if arguments.required {
+            Ok(None)
+        } else {
+            self.unauthorized_variables().extend(arguments.variables);
+            Ok(
+                transform::field(self, field_def, node)?.map(|field| ast::Field {
+                    arguments: arguments.arguments,
+                    ..field
+                }),
+            )
+        }
+    }
+}
+
+/// Arguments of a field after removing the unauthorized ones
+struct FilteredArguments {
+    arguments: Vec<Node<ast::Argument>>,
+    /// an unauthorized argument or input field was removed
+    filtered: bool,
+    /// an unauthorized argument or input field is required, the whole field must be removed
+    required: bool,
+    /// input fields the request is not authorized to set through variables
+    variables: Vec<UnauthorizedVariable>,
+}
+
+/// Removes the arguments and input object fields of a field that `is_authorized` rejects
+fn filter_arguments(
+    schema: &schema::Schema,
+    field_def: &ast::FieldDefinition,
+    arguments: &[Node<ast::Argument>],
+    directive: AuthorizationDirective,
+    is_authorized: impl Fn(&ast::DirectiveList) -> bool,
+) -> FilteredArguments {
+    let mut filter = ArgumentsFilter {
+        schema,
+        directive,
+        is_authorized: &is_authorized,
+        filtered: false,
+        required: false,
+        variables: Vec::new(),
+    };
+
+    let arguments = arguments
+        .iter()
+        .filter_map(|argument| {
+            let Some(definition) = field_def
+                .arguments
+                .iter()
+                .find(|definition| definition.name == argument.name)
+            else {
+                return Some(argument.clone());
+            };
+
+            if !(filter.is_authorized)(&definition.directives) {
+                filter.unauthorized(definition);
+                return None;
+            }
+
+            Some(Node::new(ast::Argument {
+                name: argument.name.clone(),
+                value: filter.value(definition.ty.inner_named_type(), &argument.value),
+            }))
+        })
+        .collect();
+
+    FilteredArguments {
+        arguments,
+        filtered: filter.filtered,
+        required: filter.required,
+        variables: filter.variables,
+    }
+}
+
+/// Directives of the arguments set on a field, and of all the input fields their values can
+/// contain
+pub(crate) fn argument_directives<'a>(
+    schema: &'a schema::Schema,
+    field_def: &'a ast::FieldDefinition,
+    arguments: &[Node<ast::Argument>],
+) -> Vec<&'a ast::DirectiveList> {
+    let mut directives = Vec::new();
+    let mut visited = Vec::new();
+    for definition in field_def.arguments.iter().filter(|definition| {
+        arguments
+            .iter()
+            .any(|argument| argument.name == definition.name)
+    }) {
+        directives.push(&definition.directives);
+        input_field_directives(
+            schema,
+            definition.ty.inner_named_type(),
+            &mut visited,
+            &mut directives,
+        );
+    }
+    directives
+}
+
+fn input_field_directives<'a>(
+    schema: &'a schema::Schema,
+    type_name: &str,
+    visited: &mut Vec<&'a str>,
+    directives: &mut Vec<&'a ast::DirectiveList>,
+) {
+    let Some((name, schema::ExtendedType::InputObject(input_object))) =
+        schema.types.get_key_value(type_name)
+    else {
+        return;
+    };
+    if visited.contains(&name.as_str()) {
+        return;
+    }
+    visited.push(name.as_str());
+
+    for definition in input_object.fields.values() {
+        directives.push(&definition.directives);
+        input_field_directives(
+            schema,
+            definition.ty.inner_named_type(),
+            visited,
+            directives,
+        );
+    }
+}
+
+fn is_required(definition: &ast::InputValueDefinition) -> bool {
+    definition.ty.is_non_null() && definition.default_value.is_none()
+}
+
+struct ArgumentsFilter<'a> {
+    schema: &'a schema::Schema,
+    directive: AuthorizationDirective,
+    is_authorized: &'a dyn Fn(&ast::DirectiveList) -> bool,
+    filtered: bool,
+    required: bool,
+    variables: Vec<UnauthorizedVariable>,
+}
+
+impl<'a> ArgumentsFilter<'a> {
+    fn unauthorized(&mut self, definition: &ast::InputValueDefinition) {
+        self.filtered = true;
+        self.required |= is_required(definition);
+    }
+
+    fn value(&mut self, type_name: &str, value: &Node<ast::Value>) -> Node<ast::Value> {
+        match &**value {
+            ast::Value::List(values) => Node::new(ast::Value::List(
+                values
+                    .iter()
+                    .map(|value| self.value(type_name, value))
+                    .collect(),
+            )),
+            ast::Value::Object(fields) => {
+                let schema = self.schema;
+                let Some(schema::ExtendedType::InputObject(input_object)) =
+                    schema.types.get(type_name)
+                else {
+                    return value.clone();
+                };
+
+                Node::new(ast::Value::Object(
+                    fields
+                        .iter()
+                        .filter_map(|(name, value)| {
+                            let Some(definition) = input_object.fields.get(name) else {
+                                return Some((name.clone(), value.clone()));
+                            };
+                            if !(self.is_authorized)(&definition.directives) {
+                                self.unauthorized(definition);
+                                return None;
+                            }
+                            Some((
+                                name.clone(),
+                                self.value(definition.ty.inner_named_type(), value),
+                            ))
+                        })
+                        .collect(),
+                ))
+            }
+            ast::Value::Variable(variable) => {
+                self.variable(variable.as_str(), type_name);
+                value.clone()
+            }
+            _ => value.clone(),
+        }
+    }
+
+    /// Records the unauthorized input fields that a variable of this type could contain
+    fn variable(&mut self, variable: &str, type_name: &str) {
+        let mut input_fields = Vec::new();
+        self.unauthorized_input_fields(type_name, true, &mut Vec::new(), &mut input_fields);
+        if !input_fields.is_empty() {
+            self.variables.push(UnauthorizedVariable {
+                variable: variable.to_string(),
+                type_name: type_name.to_string(),
+                input_fields,
+                directive: self.directive,
+            });
+        }
+    }
+
+    /// Collects the unauthorized input fields of an input object type and of all the input
+    /// objects it can contain
+    fn unauthorized_input_fields(
+        &mut self,
+        type_name: &str,
+        always_present: bool,
+        // input objects can be recursive
+        visited: &mut Vec<String>,
+        input_fields: &mut Vec<(String, String)>,
+    ) {
+        let schema = self.schema;
+        let Some(schema::ExtendedType::InputObject(input_object)) = schema.types.get(type_name)
+        else {
+            return;
+        };
+        if visited.iter().any(|visited| visited == type_name) {
+            return;
+        }
+        visited.push(type_name.to_string());
+
+        for (name, definition) in &input_object.fields {
+            if (self.is_authorized)(&definition.directives) {
+                self.unauthorized_input_fields(
+                    definition.ty.inner_named_type(),
+                    always_present && is_required(definition) && !definition.ty.is_list(),
+                    visited,
+                    input_fields,
+                );
+            } else {
+                // a required input field is always present in the variable, unless it is nested
+                // in an optional input field or a list
+                self.required |= always_present && is_required(definition);
+                input_fields.push((type_name.to_string(), name.to_string()));
+            }
+        }
+    }
+}
+
+/// Finds the unauthorized input fields in a variable value, following the input object types of
+/// the schema at any depth, and removes them if `remove` is true
+///
+/// Returns the paths of the input fields that were found, starting with the variable.
+pub(crate) fn remove_input_fields(
+    schema: &schema::Schema,
+    variable: &UnauthorizedVariable,
+    value: &mut Value,
+    remove: bool,
+) -> Vec<Path> {
+    let mut found = Vec::new();
+    let mut path = vec![PathElement::Key(format!("${}", variable.variable), None)];
+    remove_from_value(
+        schema,
+        &variable.input_fields,
+        &variable.type_name,
+        value,
+        remove,
+        &mut path,
+        &mut found,
+    );
+    found
+}
+
+fn remove_from_value(
+    schema: &schema::Schema,
+    input_fields: &[(String, String)],
+    type_name: &str,
+    value: &mut Value,
+    remove: bool,
+    path: &mut Vec<PathElement>,
+    found: &mut Vec<Path>,
+) {
+    match value {
+        Value::Array(values) => {
+            for (index, value) in values.iter_mut().enumerate() {
+                path.push(PathElement::Index(index));
+                remove_from_value(schema, input_fields, type_name, value, remove, path, found);
+                path.pop();
+            }
+        }
+        Value::Object(object) => {
+            let Some(schema::ExtendedType::InputObject(input_object)) = schema.types.get(type_name)
+            else {
+                return;
+            };
+
+            let mut unauthorized = Vec::new();
+            for (name, value) in object.iter_mut() {
+                let name = name.as_str();
+                path.push(PathElement::Key(name.to_string(), None));
+                if input_fields
+                    .iter()
+                    .any(|(input_type, input_field)| input_type == type_name && input_field == name)
+                {
+                    found.push(Path(path.clone()));
+                    unauthorized.push(name.to_string());
+                } else if let Some(definition) = input_object.fields.get(name) {
+                    remove_from_value(
+                        schema,
+                        input_fields,
+                        definition.ty.inner_named_type(),
+                        value,
+                        remove,
+                        path,
+                        found,
+                    );
+                }
+                path.pop();
+            }
+
+            if remove {
+                for name in unauthorized {
+                    object.remove(name.as_str());
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use apollo_compiler::Schema;
+    use serde_json_bytes::json;
+
+    use super::*;
+
+    const SCHEMA: &str = r#"
+        type Query {
+            posts(filter: PostFilter): [String]
+        }
+        input PostFilter {
+            title: String
+            author: AuthorFilter
+            secret: String
+            and: [PostFilter!]
+            not: PostFilter
+        }
+        input AuthorFilter {
+            name: String
+            email: String
+        }
+    "#;
+
+    fn unauthorized_variable() -> UnauthorizedVariable {
+        UnauthorizedVariable {
+            variable: "filter".to_string(),
+            type_name: "PostFilter".to_string(),
+            input_fields: vec![
+                ("PostFilter".to_string(), "secret".to_string()),
+                ("AuthorFilter".to_string(), "email".to_string()),
+            ],
+            directive: AuthorizationDirective::RequiresScopes,
+        }
+    }
+
+    fn paths(paths: Vec<Path>) -> Vec<String> {
+        paths.iter().map(|path| path.to_string()).collect()
+    }
+
+    #[test]
+    fn remove_input_fields_from_variables() {
+        let schema = Schema::parse_and_validate(SCHEMA, "schema.graphql").unwrap();
+        let variable = unauthorized_variable();
+
+        let mut value = json!({ "title": "a", "author": { "name": "b", "email": "c" } });
+        assert_eq!(
+            paths(remove_input_fields(&schema, &variable, &mut value, false)),
+            ["/$filter/author/email"]
+        );
+        assert_eq!(
+            value,
+            json!({ "title": "a", "author": { "name": "b", "email": "c" } })
+        );
+        assert_eq!(
+            paths(remove_input_fields(&schema, &variable, &mut value, true)),
+            ["/$filter/author/email"]
+        );
+        assert_eq!(value, json!({ "title": "a", "author": { "name": "b" } }));
+        assert!(remove_input_fields(&schema, &variable, &mut value, true).is_empty());
+    }
+
+    #[test]
+    fn remove_input_fields_from_recursive_variables() {
+        let schema = Schema::parse_and_validate(SCHEMA, "schema.graphql").unwrap();
+        let variable = unauthorized_variable();
+
+        let mut value = json!({
+            "and": [
+                { "secret": "a" },
+                { "not": { "and": [{ "author": { "email": "b" } }], "secret": "c" } }
+            ],
+            "title": "d"
+        });
+        assert_eq!(
+            paths(remove_input_fields(&schema, &variable, &mut value, true)),
+            [
+                "/$filter/and/0/secret",
+                "/$filter/and/1/not/and/0/author/email",
+                "/$filter/and/1/not/secret"
+            ]
+        );
+        assert_eq!(
+            value,
+            json!({
+                "and": [{}, { "not": { "and": [{ "author": {} }] } }],
+                "title": "d"
+            })
+        );
+    }
+}
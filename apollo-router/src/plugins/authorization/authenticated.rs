@@ -10,6 +10,10 @@ use apollo_compiler::schema::Name;
 use apollo_compiler::Node;
 use tower::BoxError;
 
+use super::arguments::argument_directives;
+use super::arguments::ArgumentsFilteringVisitor;
+use super::AuthorizationDirective;
+use super::UnauthorizedVariable;
 use crate::json_ext::Path;
 use crate::json_ext::PathElement;
 use crate::spec::query::transform;
@@ -109,7 +113,11 @@ impl<'a> traverse::Visitor for AuthenticatedCheckVisitor<'a> {
         field_def: &ast::FieldDefinition,
         node: &executable::Field,
     ) -> Result<(), BoxError> {
-        if self.is_field_authenticated(field_def) {
+        if self.is_field_authenticated(field_def)
+            || argument_directives(self.schema, field_def, &node.arguments)
+                .into_iter()
+                .any(|directives| directives.has(&self.authenticated_directive_name))
+        {
             self.found = true;
             return Ok(());
         }
@@ -179,6 +187,7 @@ pub(crate) struct AuthenticatedVisitor<'a> {
     implementers_map: &'a HashMap<Name, Implementers>,
     pub(crate) query_requires_authentication: bool,
     pub(crate) unauthorized_paths: Vec<Path>,
+    pub(crate) unauthorized_variables: Vec<UnauthorizedVariable>,
     // store the error paths from fragments so we can  add them at
     // the point of application
     fragments_unauthorized_paths: HashMap<&'a ast::Name, Vec<Path>>,
@@ -201,6 +210,7 @@ impl<'a> AuthenticatedVisitor<'a> {
             dry_run,
             query_requires_authentication: false,
             unauthorized_paths: Vec::new(),
+            unauthorized_variables: Vec::new(),
             fragments_unauthorized_paths: HashMap::new(),
             current_path: Path::default(),
             authenticated_directive_name: Schema::directive_name(
@@ -319,6 +329,27 @@ impl<'a> AuthenticatedVisitor<'a> {
     }
 }
 
+impl<'a> ArgumentsFilteringVisitor for AuthenticatedVisitor<'a> {
+    const DIRECTIVE: AuthorizationDirective = AuthorizationDirective::Authenticated;
+
+    fn is_input_value_authorized(&self, directives: &ast::DirectiveList) -> bool {
+        !directives.has(&self.authenticated_directive_name)
+    }
+
+    fn unauthorized_arguments(&mut self) {
+        self.unauthorized_paths.push(self.current_path.clone());
+        self.query_requires_authentication = true;
+    }
+
+    fn dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    fn unauthorized_variables(&mut self) -> &mut Vec<UnauthorizedVariable> {
+        &mut self.unauthorized_variables
+    }
+}
+
 impl<'a> transform::Visitor for AuthenticatedVisitor<'a> {
     fn operation(
         &mut self,
@@ -379,7 +410,7 @@ impl<'a> transform::Visitor for AuthenticatedVisitor<'a> {
                 Ok(None)
             }
         } else {
-            transform::field(self, field_def, node)
+            self.field_arguments(field_def, node)
         };
 
         if is_field_list {
@@ -1663,7 +1694,9 @@ mod tests {
         headers.insert("Accept".into(), "multipart/mixed;deferSpec=20220824".into());
         context.extensions().lock().insert(ClientRequestAccepts {
             multipart_defer: true,
+            incremental_delivery: false,
             multipart_subscription: true,
+            event_stream: false,
             json: true,
             wildcard: true,
         });
@@ -0,0 +1,661 @@
+//! Expressions evaluating the policies of the `@policy` directive
+//!
+//! The syntax is a subset of the Common Expression Language (CEL): literals, lists, member access,
+//! indexing, comparison and boolean operators, `in`, the `has()` macro and a few string functions.
+
+use std::fmt;
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+use regex::Regex;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde_json_bytes::ByteString;
+use serde_json_bytes::Map;
+use serde_json_bytes::Value;
+
+/// Variables that can be used in expressions
+const VARIABLES: [&str; 3] = ["claims", "headers", "context"];
+
+/// A parsed policy expression
+#[derive(Clone)]
+pub(crate) struct Expression {
+    source: String,
+    root: Node,
+}
+
+impl fmt::Debug for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Expression").field(&self.source).finish()
+    }
+}
+
+impl<'de> Deserialize<'de> for Expression {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let source = String::deserialize(deserializer)?;
+        Expression::parse(&source).map_err(serde::de::Error::custom)
+    }
+}
+
+impl Expression {
+    pub(crate) fn parse(source: &str) -> Result<Self, String> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser {
+            tokens: tokens.into_iter().peekable(),
+        };
+        let root = parser.expression()?;
+        if let Some(token) = parser.tokens.next() {
+            return Err(format!("unexpected {token} in expression '{source}'"));
+        }
+        Ok(Self {
+            source: source.to_string(),
+            root,
+        })
+    }
+
+    /// Evaluates the expression to a boolean. `variables` contains the `claims`, `headers` and
+    /// `context` objects
+    pub(crate) fn evaluate(&self, variables: &Map<ByteString, Value>) -> Result<bool, String> {
+        match self.root.evaluate(variables)? {
+            Value::Bool(result) => Ok(result),
+            value => Err(format!(
+                "expression '{}' evaluated to {value} instead of a boolean",
+                self.source
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Identifier(String),
+    String(String),
+    Number(f64),
+    Operator(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Identifier(name) => write!(f, "'{name}'"),
+            Token::String(s) => write!(f, "string {s:?}"),
+            Token::Number(n) => write!(f, "number {n}"),
+            Token::Operator(op) => write!(f, "'{op}'"),
+        }
+    }
+}
+
+const OPERATORS: [&str; 18] = [
+    "==", "!=", "<=", ">=", "&&", "||", "<", ">", "!", "-", "(", ")", "[", "]", ".", ",", "?", ":",
+];
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars: Peekable<CharIndices> = source.char_indices().peekable();
+
+    while let Some(&(position, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let mut identifier = String::new();
+            while let Some(&(_, c)) = chars.peek() {
+                if c.is_ascii_alphanumeric() || c == '_' {
+                    identifier.push(c);
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            tokens.push(Token::Identifier(identifier));
+        } else if c.is_ascii_digit() {
+            let mut number = String::new();
+            while let Some(&(_, c)) = chars.peek() {
+                if c.is_ascii_digit() || c == '.' {
+                    number.push(c);
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            tokens.push(Token::Number(number.parse().map_err(|_| {
+                format!("invalid number '{number}' at position {position}")
+            })?));
+        } else if c == '"' || c == '\'' {
+            let quote = c;
+            chars.next();
+            let mut string = String::new();
+            loop {
+                match chars.next() {
+                    Some((_, c)) if c == quote => break,
+                    Some((_, '\\')) => match chars.next() {
+                        Some((_, 'n')) => string.push('\n'),
+                        Some((_, 't')) => string.push('\t'),
+                        Some((_, c)) => string.push(c),
+                        None => break,
+                    },
+                    Some((_, c)) => string.push(c),
+                    None => return Err(format!("unterminated string at position {position}")),
+                }
+            }
+            tokens.push(Token::String(string));
+        } else {
+            let operator = OPERATORS
+                .iter()
+                .find(|operator| source[position..].starts_with(*operator))
+                .ok_or_else(|| format!("unexpected character '{c}' at position {position}"))?;
+            for _ in 0..operator.len() {
+                chars.next();
+            }
+            tokens.push(Token::Operator(*operator));
+        }
+    }
+
+    Ok(tokens)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum BinaryOperator {
+    Equal,
+    NotEqual,
+    Lower,
+    LowerOrEqual,
+    Greater,
+    GreaterOrEqual,
+    In,
+}
+
+#[derive(Clone, Debug)]
+enum Node {
+    Literal(Value),
+    List(Vec<Node>),
+    Variable(String),
+    Member(Box<Node>, String),
+    Index(Box<Node>, Box<Node>),
+    Has(Box<Node>, String),
+    Not(Box<Node>),
+    Negate(Box<Node>),
+    And(Box<Node>, Box<Node>),
+    Or(Box<Node>, Box<Node>),
+    Conditional(Box<Node>, Box<Node>, Box<Node>),
+    Binary(BinaryOperator, Box<Node>, Box<Node>),
+    Size(Box<Node>),
+    StartsWith(Box<Node>, Box<Node>),
+    EndsWith(Box<Node>, Box<Node>),
+    Contains(Box<Node>, Box<Node>),
+    Matches(Box<Node>, Regex),
+}
+
+struct Parser {
+    tokens: Peekable<std::vec::IntoIter<Token>>,
+}
+
+impl Parser {
+    fn next_is(&mut self, operator: &str) -> bool {
+        if matches!(self.tokens.peek(), Some(Token::Operator(op)) if *op == operator) {
+            self.tokens.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, operator: &str) -> Result<(), String> {
+        match self.tokens.next() {
+            Some(Token::Operator(op)) if op == operator => Ok(()),
+            Some(token) => Err(format!("expected '{operator}', found {token}")),
+            None => Err(format!(
+                "expected '{operator}', found the end of the expression"
+            )),
+        }
+    }
+
+    fn expression(&mut self) -> Result<Node, String> {
+        let condition = self.or()?;
+        if self.next_is("?") {
+            let then = self.expression()?;
+            self.expect(":")?;
+            let otherwise = self.expression()?;
+            return Ok(Node::Conditional(
+                Box::new(condition),
+                Box::new(then),
+                Box::new(otherwise),
+            ));
+        }
+        Ok(condition)
+    }
+
+    fn or(&mut self) -> Result<Node, String> {
+        let mut left = self.and()?;
+        while self.next_is("||") {
+            left = Node::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Node, String> {
+        let mut left = self.relation()?;
+        while self.next_is("&&") {
+            left = Node::And(Box::new(left), Box::new(self.relation()?));
+        }
+        Ok(left)
+    }
+
+    fn relation(&mut self) -> Result<Node, String> {
+        let mut left = self.unary()?;
+        loop {
+            let operator = match self.tokens.peek() {
+                Some(Token::Operator("==")) => BinaryOperator::Equal,
+                Some(Token::Operator("!=")) => BinaryOperator::NotEqual,
+                Some(Token::Operator("<")) => BinaryOperator::Lower,
+                Some(Token::Operator("<=")) => BinaryOperator::LowerOrEqual,
+                Some(Token::Operator(">")) => BinaryOperator::Greater,
+                Some(Token::Operator(">=")) => BinaryOperator::GreaterOrEqual,
+                Some(Token::Identifier(name)) if name == "in" => BinaryOperator::In,
+                _ => return Ok(left),
+            };
+            self.tokens.next();
+            left = Node::Binary(operator, Box::new(left), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Node, String> {
+        if self.next_is("!") {
+            Ok(Node::Not(Box::new(self.unary()?)))
+        } else if self.next_is("-") {
+            Ok(Node::Negate(Box::new(self.unary()?)))
+        } else {
+            self.postfix()
+        }
+    }
+
+    fn postfix(&mut self) -> Result<Node, String> {
+        let mut node = self.primary()?;
+        loop {
+            if self.next_is(".") {
+                let name = match self.tokens.next() {
+                    Some(Token::Identifier(name)) => name,
+                    Some(token) => return Err(format!("expected a field name, found {token}")),
+                    None => return Err("expected a field name".to_string()),
+                };
+                node = if self.next_is("(") {
+                    let arguments = self.arguments()?;
+                    method(node, &name, arguments)?
+                } else {
+                    Node::Member(Box::new(node), name)
+                };
+            } else if self.next_is("[") {
+                let index = self.expression()?;
+                self.expect("]")?;
+                node = Node::Index(Box::new(node), Box::new(index));
+            } else {
+                return Ok(node);
+            }
+        }
+    }
+
+    fn arguments(&mut self) -> Result<Vec<Node>, String> {
+        let mut arguments = Vec::new();
+        if self.next_is(")") {
+            return Ok(arguments);
+        }
+        loop {
+            arguments.push(self.expression()?);
+            if self.next_is(")") {
+                return Ok(arguments);
+            }
+            self.expect(",")?;
+        }
+    }
+
+    fn primary(&mut self) -> Result<Node, String> {
+        match self.tokens.next() {
+            Some(Token::String(s)) => Ok(Node::Literal(s.into())),
+            Some(Token::Number(n)) => Ok(Node::Literal(
+                serde_json::Number::from_f64(n)
+                    .map(Value::Number)
+                    .unwrap_or(Value::Null),
+            )),
+            Some(Token::Identifier(name)) => {
+                match name.as_str() {
+                    "true" => Ok(Node::Literal(true.into())),
+                    "false" => Ok(Node::Literal(false.into())),
+                    "null" => Ok(Node::Literal(Value::Null)),
+                    "has" => {
+                        self.expect("(")?;
+                        match self.arguments()?.as_slice() {
+                            [Node::Member(target, field)] => {
+                                Ok(Node::Has(target.clone(), field.clone()))
+                            }
+                            _ => Err("has() expects a field selection, like has(claims.role)"
+                                .to_string()),
+                        }
+                    }
+                    "size" => {
+                        self.expect("(")?;
+                        match <[Node; 1]>::try_from(self.arguments()?) {
+                            Ok([argument]) => Ok(Node::Size(Box::new(argument))),
+                            Err(_) => Err("size() expects one argument".to_string()),
+                        }
+                    }
+                    name if VARIABLES.contains(&name) => Ok(Node::Variable(name.to_string())),
+                    name => Err(format!(
+                        "unknown identifier '{name}', expected one of: {}",
+                        VARIABLES.join(", ")
+                    )),
+                }
+            }
+            Some(Token::Operator("(")) => {
+                let node = self.expression()?;
+                self.expect(")")?;
+                Ok(node)
+            }
+            Some(Token::Operator("[")) => {
+                let mut elements = Vec::new();
+                if self.next_is("]") {
+                    return Ok(Node::List(elements));
+                }
+                loop {
+                    elements.push(self.expression()?);
+                    if self.next_is("]") {
+                        return Ok(Node::List(elements));
+                    }
+                    self.expect(",")?;
+                }
+            }
+            Some(token) => Err(format!("unexpected {token}")),
+            None => Err("unexpected end of the expression".to_string()),
+        }
+    }
+}
+
+fn method(target: Node, name: &str, arguments: Vec<Node>) -> Result<Node, String> {
+    let target = Box::new(target);
+    if name == "size" {
+        return if arguments.is_empty() {
+            Ok(Node::Size(target))
+        } else {
+            Err("size() expects no arguments".to_string())
+        };
+    }
+
+    let [argument] =
+        <[Node; 1]>::try_from(arguments).map_err(|_| format!("{name}() expects one argument"))?;
+    match name {
+        "startsWith" => Ok(Node::StartsWith(target, Box::new(argument))),
+        "endsWith" => Ok(Node::EndsWith(target, Box::new(argument))),
+        "contains" => Ok(Node::Contains(target, Box::new(argument))),
+        "matches" => match argument {
+            Node::Literal(Value::String(pattern)) => Ok(Node::Matches(
+                target,
+                Regex::new(pattern.as_str()).map_err(|e| format!("invalid regex: {e}"))?,
+            )),
+            _ => Err("matches() expects a string literal".to_string()),
+        },
+        name => Err(format!("unknown function '{name}'")),
+    }
+}
+
+impl Node {
+    fn evaluate(&self, variables: &Map<ByteString, Value>) -> Result<Value, String> {
+        match self {
+            Node::Literal(value) => Ok(value.clone()),
+            Node::List(elements) => Ok(Value::Array(
+                elements
+                    .iter()
+                    .map(|element| element.evaluate(variables))
+                    .collect::<Result<_, _>>()?,
+            )),
+            Node::Variable(name) => {
+                Ok(variables.get(name.as_str()).cloned().unwrap_or(Value::Null))
+            }
+            Node::Member(target, field) => match target.evaluate(variables)? {
+                Value::Object(object) => object
+                    .get(field.as_str())
+                    .cloned()
+                    .ok_or_else(|| format!("no such key: '{field}'")),
+                value => Err(format!("cannot select field '{field}' on {value}")),
+            },
+            Node::Index(target, index) => {
+                match (target.evaluate(variables)?, index.evaluate(variables)?) {
+                    (Value::Object(object), Value::String(key)) => object
+                        .get(key.as_str())
+                        .cloned()
+                        .ok_or_else(|| format!("no such key: '{}'", key.as_str())),
+                    (Value::Array(array), Value::Number(index)) => index
+                        .as_u64()
+                        .or_else(|| {
+                            index
+                                .as_f64()
+                                .filter(|i| i.fract() == 0.0)
+                                .map(|i| i as u64)
+                        })
+                        .and_then(|index| array.get(index as usize))
+                        .cloned()
+                        .ok_or_else(|| format!("index out of bounds: {index}")),
+                    (target, index) => Err(format!("cannot index {target} with {index}")),
+                }
+            }
+            Node::Has(target, field) => match target.evaluate(variables)? {
+                Value::Object(object) => Ok(object.contains_key(field.as_str()).into()),
+                _ => Ok(false.into()),
+            },
+            Node::Not(node) => Ok((!boolean(node.evaluate(variables)?)?).into()),
+            Node::Negate(node) => match node.evaluate(variables)?.as_f64() {
+                Some(n) => Ok(number(-n)),
+                None => Err("cannot negate a value that is not a number".to_string()),
+            },
+            // like in CEL, errors are absorbed when the other operand decides the result
+            Node::And(left, right) => match left.evaluate(variables).and_then(boolean) {
+                Ok(false) => Ok(false.into()),
+                Ok(true) => Ok(boolean(right.evaluate(variables)?)?.into()),
+                Err(error) => match right.evaluate(variables).and_then(boolean) {
+                    Ok(false) => Ok(false.into()),
+                    _ => Err(error),
+                },
+            },
+            Node::Or(left, right) => match left.evaluate(variables).and_then(boolean) {
+                Ok(true) => Ok(true.into()),
+                Ok(false) => Ok(boolean(right.evaluate(variables)?)?.into()),
+                Err(error) => match right.evaluate(variables).and_then(boolean) {
+                    Ok(true) => Ok(true.into()),
+                    _ => Err(error),
+                },
+            },
+            Node::Conditional(condition, then, otherwise) => {
+                if boolean(condition.evaluate(variables)?)? {
+                    then.evaluate(variables)
+                } else {
+                    otherwise.evaluate(variables)
+                }
+            }
+            Node::Binary(operator, left, right) => {
+                let left = left.evaluate(variables)?;
+                let right = right.evaluate(variables)?;
+                binary(*operator, &left, &right).map(Value::from)
+            }
+            Node::Size(node) => match node.evaluate(variables)? {
+                Value::String(s) => Ok(number(s.as_str().chars().count() as f64)),
+                Value::Array(array) => Ok(number(array.len() as f64)),
+                Value::Object(object) => Ok(number(object.len() as f64)),
+                value => Err(format!("cannot get the size of {value}")),
+            },
+            Node::StartsWith(target, argument) => {
+                let (target, argument) = strings(target, argument, variables)?;
+                Ok(target.starts_with(&argument).into())
+            }
+            Node::EndsWith(target, argument) => {
+                let (target, argument) = strings(target, argument, variables)?;
+                Ok(target.ends_with(&argument).into())
+            }
+            Node::Contains(target, argument) => {
+                let (target, argument) = strings(target, argument, variables)?;
+                Ok(target.contains(&argument).into())
+            }
+            Node::Matches(target, regex) => match target.evaluate(variables)? {
+                Value::String(s) => Ok(regex.is_match(s.as_str()).into()),
+                value => Err(format!("matches() expects a string, found {value}")),
+            },
+        }
+    }
+}
+
+fn number(n: f64) -> Value {
+    serde_json::Number::from_f64(n)
+        .map(Value::Number)
+        .unwrap_or(Value::Null)
+}
+
+fn boolean(value: Value) -> Result<bool, String> {
+    match value {
+        Value::Bool(b) => Ok(b),
+        value => Err(format!("expected a boolean, found {value}")),
+    }
+}
+
+fn strings(
+    target: &Node,
+    argument: &Node,
+    variables: &Map<ByteString, Value>,
+) -> Result<(String, String), String> {
+    match (target.evaluate(variables)?, argument.evaluate(variables)?) {
+        (Value::String(target), Value::String(argument)) => {
+            Ok((target.as_str().to_string(), argument.as_str().to_string()))
+        }
+        (target, argument) => Err(format!("expected strings, found {target} and {argument}")),
+    }
+}
+
+fn equals(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Number(l), Value::Number(r)) => l.as_f64() == r.as_f64(),
+        (Value::Array(l), Value::Array(r)) => {
+            l.len() == r.len() && l.iter().zip(r).all(|(l, r)| equals(l, r))
+        }
+        (left, right) => left == right,
+    }
+}
+
+fn binary(operator: BinaryOperator, left: &Value, right: &Value) -> Result<bool, String> {
+    match operator {
+        BinaryOperator::Equal => Ok(equals(left, right)),
+        BinaryOperator::NotEqual => Ok(!equals(left, right)),
+        BinaryOperator::In => match right {
+            Value::Array(array) => Ok(array.iter().any(|element| equals(left, element))),
+            Value::Object(object) => match left {
+                Value::String(key) => Ok(object.contains_key(key.as_str())),
+                _ => Ok(false),
+            },
+            value => Err(format!("cannot use 'in' with {value}")),
+        },
+        _ => {
+            let ordering = match (left, right) {
+                (Value::Number(l), Value::Number(r)) => l
+                    .as_f64()
+                    .zip(r.as_f64())
+                    .and_then(|(l, r)| l.partial_cmp(&r)),
+                (Value::String(l), Value::String(r)) => Some(l.as_str().cmp(r.as_str())),
+                _ => None,
+            }
+            .ok_or_else(|| format!("cannot compare {left} and {right}"))?;
+            Ok(match operator {
+                BinaryOperator::Lower => ordering.is_lt(),
+                BinaryOperator::LowerOrEqual => ordering.is_le(),
+                BinaryOperator::Greater => ordering.is_gt(),
+                _ => ordering.is_ge(),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json_bytes::json;
+
+    use super::*;
+
+    fn evaluate(expression: &str) -> Result<bool, String> {
+        let variables = json!({
+            "claims": {
+                "sub": "1234",
+                "role": "admin",
+                "groups": ["billing", "support"],
+                "level": 3,
+                "email": "ada@example.com"
+            },
+            "headers": {
+                "x-tenant": "acme"
+            },
+            "context": {
+                "operation_name": "me"
+            }
+        });
+        Expression::parse(expression)?.evaluate(variables.as_object().unwrap())
+    }
+
+    #[test]
+    fn operators() {
+        assert_eq!(evaluate("claims.role == 'admin'"), Ok(true));
+        assert_eq!(evaluate("claims.role != \"admin\""), Ok(false));
+        assert_eq!(
+            evaluate("claims.level >= 3 && claims.level < 4.5"),
+            Ok(true)
+        );
+        assert_eq!(
+            evaluate("claims.level > 3 || -claims.level == -3"),
+            Ok(true)
+        );
+        assert_eq!(evaluate("'billing' in claims.groups"), Ok(true));
+        assert_eq!(
+            evaluate("'sub' in claims && !('admin' in claims.groups)"),
+            Ok(true)
+        );
+        assert_eq!(evaluate("headers['x-tenant'] == 'acme'"), Ok(true));
+        assert_eq!(evaluate("claims.groups[1] == 'support'"), Ok(true));
+        assert_eq!(
+            evaluate("claims.groups == ['billing', 'support']"),
+            Ok(true)
+        );
+        assert_eq!(
+            evaluate("claims.role == 'admin' ? context.operation_name == 'me' : false"),
+            Ok(true)
+        );
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(
+            evaluate("has(claims.role) && !has(claims.tenant)"),
+            Ok(true)
+        );
+        assert_eq!(evaluate("claims.email.endsWith('@example.com')"), Ok(true));
+        assert_eq!(evaluate("claims.email.startsWith('bob')"), Ok(false));
+        assert_eq!(evaluate("claims.email.contains('@')"), Ok(true));
+        assert_eq!(evaluate("claims.sub.matches('^[0-9]+$')"), Ok(true));
+        assert_eq!(
+            evaluate("size(claims.groups) == 2 && claims.sub.size() == 4"),
+            Ok(true)
+        );
+    }
+
+    #[test]
+    fn errors() {
+        // errors are absorbed when the other operand decides the result
+        assert_eq!(evaluate("claims.tenant == 'acme' || true"), Ok(true));
+        assert_eq!(evaluate("claims.tenant == 'acme' && false"), Ok(false));
+        assert_eq!(
+            evaluate("claims.tenant == 'acme'"),
+            Err("no such key: 'tenant'".to_string())
+        );
+        assert_eq!(
+            evaluate("claims.role"),
+            Err("expression 'claims.role' evaluated to \"admin\" instead of a boolean".to_string())
+        );
+        assert!(Expression::parse("user.role == 'admin'").is_err());
+        assert!(Expression::parse("claims.role.lowercase()").is_err());
+        assert!(Expression::parse("claims.role == ").is_err());
+        assert!(Expression::parse("claims.sub.matches('[')").is_err());
+    }
+}
@@ -3,13 +3,16 @@
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::ops::ControlFlow;
+use std::sync::Arc;
 
 use apollo_compiler::ast;
+use apollo_compiler::validation::Valid;
 use apollo_compiler::ExecutableDocument;
 use http::StatusCode;
 use schemars::JsonSchema;
 use serde::Deserialize;
 use serde::Serialize;
+use serde_json_bytes::Map;
 use serde_json_bytes::Value;
 use tower::BoxError;
 use tower::ServiceBuilder;
@@ -19,6 +22,7 @@ use self::authenticated::AuthenticatedCheckVisitor;
 use self::authenticated::AuthenticatedVisitor;
 use self::authenticated::AUTHENTICATED_SPEC_BASE_URL;
 use self::authenticated::AUTHENTICATED_SPEC_VERSION_RANGE;
+use self::expression::Expression;
 use self::policy::PolicyExtractionVisitor;
 use self::policy::PolicyFilteringVisitor;
 use self::policy::POLICY_SPEC_BASE_URL;
@@ -48,7 +52,9 @@ use crate::spec::SpecError;
 use crate::Configuration;
 use crate::Context;
 
+pub(crate) mod arguments;
 pub(crate) mod authenticated;
+pub(crate) mod expression;
 pub(crate) mod policy;
 pub(crate) mod scopes;
 
@@ -73,6 +79,10 @@ pub(crate) struct Conf {
     /// `@authenticated`, `@requiresScopes` and `@policy` directives
     #[serde(default)]
     directives: Directives,
+    /// Expressions evaluating the policies of the `@policy` directive, indexed by policy name. Rhai scripts and coprocessors of the supergraph stage run after them, and can overwrite their results
+    #[serde(default)]
+    #[schemars(with = "HashMap<String, String>")]
+    policies: HashMap<String, Expression>,
 }
 
 #[derive(Clone, Debug, serde_derive_default::Default, Deserialize, JsonSchema)]
@@ -87,11 +97,38 @@ pub(crate) struct Directives {
     /// refuse a query entirely if any part would be filtered
     #[serde(default)]
     reject_unauthorized: bool,
+    /// directives evaluated without modifying the query: the paths they would remove are logged and counted in metrics instead of being added to the response
+    #[serde(default)]
+    report_only: ReportOnly,
     /// authorization errors behaviour
     #[serde(default)]
     errors: ErrorConfig,
 }
 
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ReportOnly {
+    /// report the paths `@authenticated` would remove
+    #[serde(default)]
+    authenticated: bool,
+    /// report the paths `@requiresScopes` would remove
+    #[serde(default)]
+    requires_scopes: bool,
+    /// report the paths `@policy` would remove
+    #[serde(default)]
+    policy: bool,
+}
+
+impl ReportOnly {
+    fn contains(&self, directive: AuthorizationDirective) -> bool {
+        match directive {
+            AuthorizationDirective::Authenticated => self.authenticated,
+            AuthorizationDirective::RequiresScopes => self.requires_scopes,
+            AuthorizationDirective::Policy => self.policy,
+        }
+    }
+}
+
 #[derive(
     Clone, Debug, serde_derive_default::Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema,
 )]
@@ -125,6 +162,60 @@ pub(crate) enum ErrorLocation {
 pub(crate) struct UnauthorizedPaths {
     pub(crate) paths: Vec<Path>,
     pub(crate) errors: ErrorConfig,
+    /// paths that directives in report only mode would have removed
+    #[serde(default)]
+    pub(crate) reported: Vec<ReportedPath>,
+    /// input fields that the request is not authorized to set through variables
+    #[serde(default)]
+    pub(crate) variables: Vec<UnauthorizedVariable>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum AuthorizationDirective {
+    Authenticated,
+    RequiresScopes,
+    Policy,
+}
+
+impl AuthorizationDirective {
+    fn name(&self) -> &'static str {
+        match self {
+            AuthorizationDirective::Authenticated => "authenticated",
+            AuthorizationDirective::RequiresScopes => "requiresScopes",
+            AuthorizationDirective::Policy => "policy",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct ReportedPath {
+    pub(crate) path: Path,
+    pub(crate) directive: AuthorizationDirective,
+}
+
+/// Variable whose value can contain unauthorized input fields, only known at execution time
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub(crate) struct UnauthorizedVariable {
+    pub(crate) variable: String,
+    /// input object type of the variable value, or of its elements if it is a list
+    pub(crate) type_name: String,
+    /// unauthorized input fields, as `(input object type, input field)` pairs, of all the input
+    /// objects the variable value can contain
+    pub(crate) input_fields: Vec<(String, String)>,
+    pub(crate) directive: AuthorizationDirective,
+}
+
+/// Input fields removed from the variables of a request, added to the response errors
+#[derive(Clone, Debug, Default)]
+struct UnauthorizedVariables(Vec<Path>);
+
+fn unauthorized_input_field_error(path: &Path) -> graphql::Error {
+    graphql::Error::builder()
+        .message("Unauthorized input field")
+        .extension_code("UNAUTHORIZED_FIELD_OR_TYPE")
+        .extension("inputField", path.to_string())
+        .build()
 }
 
 fn default_enable_directives() -> bool {
@@ -133,6 +224,9 @@ fn default_enable_directives() -> bool {
 
 pub(crate) struct AuthorizationPlugin {
     require_authentication: bool,
+    policies: Arc<HashMap<String, Expression>>,
+    directives: Arc<Directives>,
+    schema: Arc<Valid<apollo_compiler::Schema>>,
 }
 
 impl AuthorizationPlugin {
@@ -325,8 +419,15 @@ impl AuthorizationPlugin {
         configuration: &Configuration,
         key: &QueryKey,
         schema: &Schema,
-    ) -> Result<Option<FilteredQuery>, QueryPlannerError> {
-        let (reject_unauthorized, dry_run) = configuration
+    ) -> Result<
+        (
+            Option<FilteredQuery>,
+            Vec<ReportedPath>,
+            Vec<UnauthorizedVariable>,
+        ),
+        QueryPlannerError,
+    > {
+        let (reject_unauthorized, dry_run, report_only) = configuration
             .apollo_plugins
             .plugins
             .iter()
@@ -342,9 +443,13 @@ impl AuthorizationPlugin {
                         .get("dry_run")
                         .and_then(|v| v.as_bool())
                         .unwrap_or(false),
+                    config
+                        .get("report_only")
+                        .and_then(|v| serde_json::from_value::<ReportOnly>(v.clone()).ok())
+                        .unwrap_or_default(),
                 )
             })
-            .unwrap_or((false, false));
+            .unwrap_or_default();
 
         // The filtered query will then be used
         // to generate selections for response formatting, to execute introspection and
@@ -361,11 +466,31 @@ impl AuthorizationPlugin {
 
         let mut is_filtered = false;
         let mut unauthorized_paths: Vec<Path> = vec![];
+        let mut reported_paths: Vec<ReportedPath> = vec![];
+        let mut unauthorized_variables: Vec<UnauthorizedVariable> = vec![];
+        let mut report = |paths: Vec<Path>, directive| {
+            reported_paths.extend(
+                paths
+                    .into_iter()
+                    .map(|path| ReportedPath { path, directive }),
+            )
+        };
 
-        let filter_res = Self::authenticated_filter_query(schema, dry_run, &doc, is_authenticated)?;
+        let filter_res = Self::authenticated_filter_query(
+            schema,
+            dry_run || report_only.authenticated,
+            &doc,
+            is_authenticated,
+            &mut unauthorized_variables,
+        )?;
 
         let doc = match filter_res {
             None => doc,
+            // in report only mode, the query is not modified
+            Some((_, paths)) if report_only.authenticated => {
+                report(paths, AuthorizationDirective::Authenticated);
+                doc
+            }
             Some((filtered_doc, paths)) => {
                 unauthorized_paths.extend(paths);
 
@@ -380,10 +505,20 @@ impl AuthorizationPlugin {
             }
         };
 
-        let filter_res = Self::scopes_filter_query(schema, dry_run, &doc, scopes)?;
+        let filter_res = Self::scopes_filter_query(
+            schema,
+            dry_run || report_only.requires_scopes,
+            &doc,
+            scopes,
+            &mut unauthorized_variables,
+        )?;
 
         let doc = match filter_res {
             None => doc,
+            Some((_, paths)) if report_only.requires_scopes => {
+                report(paths, AuthorizationDirective::RequiresScopes);
+                doc
+            }
             Some((filtered_doc, paths)) => {
                 unauthorized_paths.extend(paths);
 
@@ -398,10 +533,20 @@ impl AuthorizationPlugin {
             }
         };
 
-        let filter_res = Self::policies_filter_query(schema, dry_run, &doc, policies)?;
+        let filter_res = Self::policies_filter_query(
+            schema,
+            dry_run || report_only.policy,
+            &doc,
+            policies,
+            &mut unauthorized_variables,
+        )?;
 
         let doc = match filter_res {
             None => doc,
+            Some((_, paths)) if report_only.policy => {
+                report(paths, AuthorizationDirective::Policy);
+                doc
+            }
             Some((filtered_doc, paths)) => {
                 unauthorized_paths.extend(paths);
 
@@ -420,10 +565,22 @@ impl AuthorizationPlugin {
             return Err(QueryPlannerError::Unauthorized(unauthorized_paths));
         }
 
+        // the same input field can be used in multiple places
+        let mut seen = HashSet::new();
+        unauthorized_variables.retain(|variable| seen.insert(variable.clone()));
+
         if is_filtered {
-            Ok(Some((unauthorized_paths, doc)))
+            // removed fields and arguments can leave unused variables, which would fail validation
+            let mut doc = doc;
+            transform::remove_unused_variables(&mut doc);
+
+            Ok((
+                Some((unauthorized_paths, doc)),
+                reported_paths,
+                unauthorized_variables,
+            ))
         } else {
-            Ok(None)
+            Ok((None, reported_paths, unauthorized_variables))
         }
     }
 
@@ -432,6 +589,7 @@ impl AuthorizationPlugin {
         dry_run: bool,
         doc: &ast::Document,
         is_authenticated: bool,
+        unauthorized_variables: &mut Vec<UnauthorizedVariable>,
     ) -> Result<Option<(ast::Document, Vec<Path>)>, QueryPlannerError> {
         if let Some(mut visitor) = AuthenticatedVisitor::new(
             schema.supergraph_schema(),
@@ -442,6 +600,10 @@ impl AuthorizationPlugin {
             let modified_query = transform::document(&mut visitor, doc)
                 .map_err(|e| SpecError::TransformError(e.to_string()))?;
 
+            if !is_authenticated {
+                unauthorized_variables.append(&mut visitor.unauthorized_variables);
+            }
+
             if visitor.query_requires_authentication {
                 if is_authenticated {
                     tracing::debug!("the query contains @authenticated, the request is authenticated, keeping the query");
@@ -470,6 +632,7 @@ impl AuthorizationPlugin {
         dry_run: bool,
         doc: &ast::Document,
         scopes: &[String],
+        unauthorized_variables: &mut Vec<UnauthorizedVariable>,
     ) -> Result<Option<(ast::Document, Vec<Path>)>, QueryPlannerError> {
         if let Some(mut visitor) = ScopeFilteringVisitor::new(
             schema.supergraph_schema(),
@@ -480,6 +643,7 @@ impl AuthorizationPlugin {
         ) {
             let modified_query = transform::document(&mut visitor, doc)
                 .map_err(|e| SpecError::TransformError(e.to_string()))?;
+            unauthorized_variables.append(&mut visitor.unauthorized_variables);
             if visitor.query_requires_scopes {
                 tracing::debug!("the query required scopes, the requests present scopes: {scopes:?}, modified query:\n{modified_query}\nunauthorized paths: {:?}",
                 visitor
@@ -502,9 +666,9 @@ impl AuthorizationPlugin {
     fn policies_filter_query(
         schema: &Schema,
         dry_run: bool,
-
         doc: &ast::Document,
         policies: &[String],
+        unauthorized_variables: &mut Vec<UnauthorizedVariable>,
     ) -> Result<Option<(ast::Document, Vec<Path>)>, QueryPlannerError> {
         if let Some(mut visitor) = PolicyFilteringVisitor::new(
             schema.supergraph_schema(),
@@ -515,6 +679,7 @@ impl AuthorizationPlugin {
         ) {
             let modified_query = transform::document(&mut visitor, doc)
                 .map_err(|e| SpecError::TransformError(e.to_string()))?;
+            unauthorized_variables.append(&mut visitor.unauthorized_variables);
 
             if visitor.query_requires_policies {
                 tracing::debug!("the query required policies, the requests present policies: {policies:?}, modified query:\n{modified_query}\nunauthorized paths: {:?}",
@@ -534,6 +699,255 @@ impl AuthorizationPlugin {
             Ok(None)
         }
     }
+
+    /// Logs and counts the paths that directives in report only mode would have removed, with the
+    /// scopes or policies required by the query that the request does not have
+    fn report_unauthorized_paths(context: &Context, reported: &[ReportedPath]) {
+        for directive in [
+            AuthorizationDirective::Authenticated,
+            AuthorizationDirective::RequiresScopes,
+            AuthorizationDirective::Policy,
+        ] {
+            let paths: Vec<String> = reported
+                .iter()
+                .filter(|reported| reported.directive == directive)
+                .map(|reported| reported.path.to_string())
+                .collect();
+            if paths.is_empty() {
+                continue;
+            }
+
+            let missing: Vec<String> = match directive {
+                AuthorizationDirective::Authenticated => vec![],
+                AuthorizationDirective::RequiresScopes => {
+                    let request_scopes: HashSet<String> = context
+                        .get_json_value(APOLLO_AUTHENTICATION_JWT_CLAIMS)
+                        .and_then(|claims| {
+                            claims
+                                .as_object()
+                                .and_then(|claims| claims.get("scope"))
+                                .and_then(|scope| scope.as_str())
+                                .map(|scope| scope.split(' ').map(str::to_string).collect())
+                        })
+                        .unwrap_or_default();
+                    let mut missing: Vec<String> = context
+                        .get::<_, Vec<String>>(REQUIRED_SCOPES_KEY)
+                        .ok()
+                        .flatten()
+                        .unwrap_or_default()
+                        .into_iter()
+                        .filter(|scope| !request_scopes.contains(scope))
+                        .collect();
+                    missing.sort();
+                    missing
+                }
+                AuthorizationDirective::Policy => {
+                    let mut missing: Vec<String> = context
+                        .get::<_, HashMap<String, Option<bool>>>(REQUIRED_POLICIES_KEY)
+                        .ok()
+                        .flatten()
+                        .unwrap_or_default()
+                        .into_iter()
+                        .filter(|(_, result)| *result != Some(true))
+                        .map(|(policy, _)| policy)
+                        .collect();
+                    missing.sort();
+                    missing
+                }
+            };
+
+            u64_counter!(
+                "apollo.router.operations.authorization.report_only",
+                "paths that directives in report only mode would have removed",
+                paths.len() as u64,
+                authorization.directive = directive.name()
+            );
+            tracing::info!(
+                authorization.directive = directive.name(),
+                unauthorized_query_paths = ?paths,
+                missing = ?missing,
+                "Authorization report: paths would have been removed from the query"
+            );
+        }
+    }
+
+    /// Evaluates the policies required by the query that have an expression in the configuration
+    fn evaluate_policies(policies: &HashMap<String, Expression>, request: &supergraph::Request) {
+        let required = match request.context.get_json_value(REQUIRED_POLICIES_KEY) {
+            Some(Value::Object(required)) => required,
+            _ => return,
+        };
+        // only policies without a result yet are evaluated
+        let pending: Vec<(&str, &Expression)> = required
+            .iter()
+            .filter(|(_, result)| result.is_null())
+            .filter_map(|(policy, _)| policies.get_key_value(policy.as_str()))
+            .map(|(policy, expression)| (policy.as_str(), expression))
+            .collect();
+        if pending.is_empty() {
+            return;
+        }
+
+        let mut headers = Map::new();
+        for (name, value) in request.supergraph_request.headers() {
+            if let Ok(value) = value.to_str() {
+                if !headers.contains_key(name.as_str()) {
+                    headers.insert(name.as_str(), value.into());
+                }
+            }
+        }
+        let context: Map<_, _> = request
+            .context
+            .iter()
+            .map(|entry| (entry.key().as_str().into(), entry.value().clone()))
+            .collect();
+        let mut variables = Map::new();
+        variables.insert(
+            "claims",
+            request
+                .context
+                .get_json_value(APOLLO_AUTHENTICATION_JWT_CLAIMS)
+                .unwrap_or(Value::Null),
+        );
+        variables.insert("headers", Value::Object(headers));
+        variables.insert("context", Value::Object(context));
+
+        let results: Vec<(String, bool)> = pending
+            .into_iter()
+            .map(|(policy, expression)| {
+                let authorized = expression.evaluate(&variables).unwrap_or_else(|error| {
+                    tracing::debug!("could not evaluate policy '{policy}': {error}");
+                    false
+                });
+                tracing::info!(
+                    monotonic_counter.apollo.router.operations.authorization.policy = 1u64,
+                    authorization.policy.name = %policy,
+                    authorization.policy.authorized = authorized,
+                );
+                (policy.to_string(), authorized)
+            })
+            .collect();
+
+        request
+            .context
+            .upsert_json_value(REQUIRED_POLICIES_KEY, move |mut value| {
+                if let Value::Object(required) = &mut value {
+                    for (policy, authorized) in results {
+                        required.insert(policy, authorized.into());
+                    }
+                }
+                value
+            });
+    }
+
+    /// Removes the input fields that the request is not authorized to set from its variables.
+    /// Input fields are kept in dry run mode, and the request is refused entirely if
+    /// `reject_unauthorized` is set
+    fn authorize_variables(
+        schema: &apollo_compiler::Schema,
+        directives: &Directives,
+        mut request: execution::Request,
+    ) -> Result<ControlFlow<execution::Response, execution::Request>, BoxError> {
+        let query = request.query_plan.query.clone();
+        let variables = &mut request.supergraph_request.body_mut().variables;
+
+        let mut unauthorized = Vec::new();
+        let mut reported = Vec::new();
+        for variable in &query.unauthorized.variables {
+            let report_only = directives.report_only.contains(variable.directive);
+            let remove = !(report_only || directives.dry_run || directives.reject_unauthorized);
+            let Some(value) = variables.get_mut(variable.variable.as_str()) else {
+                continue;
+            };
+            let paths = arguments::remove_input_fields(schema, variable, value, remove);
+
+            if report_only {
+                reported.extend(paths.into_iter().map(|path| ReportedPath {
+                    path,
+                    directive: variable.directive,
+                }));
+            } else {
+                unauthorized.extend(paths);
+            }
+        }
+
+        if !reported.is_empty() {
+            Self::report_unauthorized_paths(&request.context, &reported);
+        }
+        if unauthorized.is_empty() {
+            return Ok(ControlFlow::Continue(request));
+        }
+
+        if directives.errors.log {
+            let input_fields = unauthorized
+                .iter()
+                .map(|path| path.to_string())
+                .collect::<Vec<_>>();
+            tracing::error!(unauthorized_input_fields = ?input_fields, "Authorization error");
+        }
+
+        if directives.reject_unauthorized {
+            let response = execution::Response::error_builder()
+                .errors(
+                    unauthorized
+                        .iter()
+                        .map(unauthorized_input_field_error)
+                        .collect(),
+                )
+                .context(request.context)
+                .build()?;
+            return Ok(ControlFlow::Break(response));
+        }
+
+        request
+            .context
+            .extensions()
+            .lock()
+            .insert(UnauthorizedVariables(unauthorized));
+        Ok(ControlFlow::Continue(request))
+    }
+
+    /// Adds the errors of the input fields removed from the variables to the first response
+    fn unauthorized_variables_errors(
+        errors: &ErrorConfig,
+        response: execution::Response,
+    ) -> execution::Response {
+        let unauthorized = response
+            .context
+            .extensions()
+            .lock()
+            .remove::<UnauthorizedVariables>();
+        let Some(UnauthorizedVariables(unauthorized)) = unauthorized else {
+            return response;
+        };
+        let location = errors.response.clone();
+
+        let mut first = true;
+        response.map_stream(move |mut response| {
+            if std::mem::take(&mut first) {
+                let errors = unauthorized.iter().map(unauthorized_input_field_error);
+                match location {
+                    ErrorLocation::Errors => response.errors.extend(errors),
+                    ErrorLocation::Extensions => {
+                        let errors = errors.map(|error| {
+                            serde_json_bytes::to_value(error)
+                                .expect("error serialization should not fail")
+                        });
+                        match response.extensions.get_mut("authorizationErrors") {
+                            Some(Value::Array(existing)) => existing.extend(errors),
+                            _ => {
+                                response
+                                    .extensions
+                                    .insert("authorizationErrors", Value::Array(errors.collect()));
+                            }
+                        }
+                    }
+                    ErrorLocation::Disabled => {}
+                }
+            }
+            response
+        })
+    }
 }
 
 #[async_trait::async_trait]
@@ -543,10 +957,26 @@ impl Plugin for AuthorizationPlugin {
     async fn new(init: PluginInit<Self::Config>) -> Result<Self, BoxError> {
         Ok(AuthorizationPlugin {
             require_authentication: init.config.require_authentication,
+            policies: Arc::new(init.config.policies),
+            directives: Arc::new(init.config.directives),
+            schema: init.supergraph_schema,
         })
     }
 
     fn supergraph_service(&self, service: supergraph::BoxService) -> supergraph::BoxService {
+        let service = if self.policies.is_empty() {
+            service
+        } else {
+            let policies = self.policies.clone();
+            ServiceBuilder::new()
+                .map_request(move |request: supergraph::Request| {
+                    Self::evaluate_policies(&policies, &request);
+                    request
+                })
+                .service(service)
+                .boxed()
+        };
+
         if self.require_authentication {
             ServiceBuilder::new()
                 .checkpoint(move |request: supergraph::Request| {
@@ -582,12 +1012,30 @@ impl Plugin for AuthorizationPlugin {
     }
 
     fn execution_service(&self, service: execution::BoxService) -> execution::BoxService {
+        let directives = self.directives.clone();
+        let errors = self.directives.errors.clone();
+        let schema = self.schema.clone();
         ServiceBuilder::new()
+            .checkpoint(move |request: execution::Request| {
+                if request.query_plan.query.unauthorized.variables.is_empty() {
+                    Ok(ControlFlow::Continue(request))
+                } else {
+                    Self::authorize_variables(&schema, &directives, request)
+                }
+            })
+            .map_response(move |response: execution::Response| {
+                Self::unauthorized_variables_errors(&errors, response)
+            })
             .map_request(|request: execution::Request| {
                 let filtered = !request.query_plan.query.unauthorized.paths.is_empty();
                 let needs_authenticated = request.context.contains_key(AUTHENTICATED_KEY);
                 let needs_requires_scopes = request.context.contains_key(REQUIRED_SCOPES_KEY);
 
+                let reported = &request.query_plan.query.unauthorized.reported;
+                if !reported.is_empty() {
+                    Self::report_unauthorized_paths(&request.context, reported);
+                }
+
                 if needs_authenticated || needs_requires_scopes {
                     tracing::info!(
                         monotonic_counter.apollo.router.operations.authorization = 1u64,
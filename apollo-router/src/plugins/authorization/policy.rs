@@ -17,6 +17,10 @@ use apollo_compiler::schema::Name;
 use apollo_compiler::Node;
 use tower::BoxError;
 
+use super::arguments::argument_directives;
+use super::arguments::ArgumentsFilteringVisitor;
+use super::AuthorizationDirective;
+use super::UnauthorizedVariable;
 use crate::json_ext::Path;
 use crate::json_ext::PathElement;
 use crate::spec::query::transform;
@@ -143,6 +147,10 @@ impl<'a> traverse::Visitor for PolicyExtractionVisitor<'a> {
         node: &executable::Field,
     ) -> Result<(), BoxError> {
         self.get_policies_from_field(field_def);
+        for directives in argument_directives(self.schema, field_def, &node.arguments) {
+            self.extracted_policies
+                .extend(policy_argument(directives.get(&self.policy_directive_name)));
+        }
 
         traverse::field(self, field_def, node)
     }
@@ -193,6 +201,7 @@ pub(crate) struct PolicyFilteringVisitor<'a> {
     request_policies: HashSet<String>,
     pub(crate) query_requires_policies: bool,
     pub(crate) unauthorized_paths: Vec<Path>,
+    pub(crate) unauthorized_variables: Vec<UnauthorizedVariable>,
     // store the error paths from fragments so we can  add them at
     // the point of application
     fragments_unauthorized_paths: HashMap<&'a ast::Name, Vec<Path>>,
@@ -235,6 +244,7 @@ impl<'a> PolicyFilteringVisitor<'a> {
             request_policies: successful_policies,
             query_requires_policies: false,
             unauthorized_paths: vec![],
+            unauthorized_variables: vec![],
             fragments_unauthorized_paths: HashMap::new(),
             current_path: Path::default(),
             policy_directive_name: Schema::directive_name(
@@ -417,6 +427,43 @@ impl<'a> PolicyFilteringVisitor<'a> {
     }
 }
 
+impl<'a> ArgumentsFilteringVisitor for PolicyFilteringVisitor<'a> {
+    const DIRECTIVE: AuthorizationDirective = AuthorizationDirective::Policy;
+
+    fn is_input_value_authorized(&self, directives: &ast::DirectiveList) -> bool {
+        match directives.get(&self.policy_directive_name) {
+            None => true,
+            Some(directive) => {
+                let mut policies_sets = policies_sets_argument(directive);
+
+                // The outer array acts like a logical OR: if any of the inner arrays of policies matches, the argument
+                // is authorized.
+                // On an empty set, any returns false, so we must check that case separately
+                let mut empty = true;
+                let res = policies_sets.any(|policies_set| {
+                    empty = false;
+                    self.request_policies.is_superset(&policies_set)
+                });
+
+                empty || res
+            }
+        }
+    }
+
+    fn unauthorized_arguments(&mut self) {
+        self.unauthorized_paths.push(self.current_path.clone());
+        self.query_requires_policies = true;
+    }
+
+    fn dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    fn unauthorized_variables(&mut self) -> &mut Vec<UnauthorizedVariable> {
+        &mut self.unauthorized_variables
+    }
+}
+
 impl<'a> transform::Visitor for PolicyFilteringVisitor<'a> {
     fn operation(
         &mut self,
@@ -485,7 +532,7 @@ impl<'a> transform::Visitor for PolicyFilteringVisitor<'a> {
             && !implementors_with_different_requirements
             && !implementors_with_different_field_requirements
         {
-            transform::field(self, field_def, node)
+            self.field_arguments(field_def, node)
         } else {
             self.unauthorized_paths.push(self.current_path.clone());
             self.query_requires_policies = true;
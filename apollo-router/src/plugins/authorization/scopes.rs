@@ -17,6 +17,10 @@ use apollo_compiler::schema::Name;
 use apollo_compiler::Node;
 use tower::BoxError;
 
+use super::arguments::argument_directives;
+use super::arguments::ArgumentsFilteringVisitor;
+use super::AuthorizationDirective;
+use super::UnauthorizedVariable;
 use crate::json_ext::Path;
 use crate::json_ext::PathElement;
 use crate::spec::query::transform;
@@ -143,6 +147,11 @@ impl<'a> traverse::Visitor for ScopeExtractionVisitor<'a> {
         node: &executable::Field,
     ) -> Result<(), BoxError> {
         self.scopes_from_field(field_def);
+        for directives in argument_directives(self.schema, field_def, &node.arguments) {
+            self.extracted_scopes.extend(scopes_argument(
+                directives.get(&self.requires_scopes_directive_name),
+            ));
+        }
 
         traverse::field(self, field_def, node)
     }
@@ -209,6 +218,7 @@ pub(crate) struct ScopeFilteringVisitor<'a> {
     request_scopes: HashSet<String>,
     pub(crate) query_requires_scopes: bool,
     pub(crate) unauthorized_paths: Vec<Path>,
+    pub(crate) unauthorized_variables: Vec<UnauthorizedVariable>,
     // store the error paths from fragments so we can  add them at
     // the point of application
     fragments_unauthorized_paths: HashMap<&'a ast::Name, Vec<Path>>,
@@ -233,6 +243,7 @@ impl<'a> ScopeFilteringVisitor<'a> {
             dry_run,
             query_requires_scopes: false,
             unauthorized_paths: vec![],
+            unauthorized_variables: vec![],
             fragments_unauthorized_paths: HashMap::new(),
             current_path: Path::default(),
             requires_scopes_directive_name: Schema::directive_name(
@@ -418,6 +429,43 @@ impl<'a> ScopeFilteringVisitor<'a> {
     }
 }
 
+impl<'a> ArgumentsFilteringVisitor for ScopeFilteringVisitor<'a> {
+    const DIRECTIVE: AuthorizationDirective = AuthorizationDirective::RequiresScopes;
+
+    fn is_input_value_authorized(&self, directives: &ast::DirectiveList) -> bool {
+        match directives.get(&self.requires_scopes_directive_name) {
+            None => true,
+            Some(directive) => {
+                let mut scopes_sets = scopes_sets_argument(directive);
+
+                // The outer array acts like a logical OR: if any of the inner arrays of scopes matches, the argument
+                // is authorized.
+                // On an empty set, any returns false, so we must check that case separately
+                let mut empty = true;
+                let res = scopes_sets.any(|scopes_set| {
+                    empty = false;
+                    self.request_scopes.is_superset(&scopes_set)
+                });
+
+                empty || res
+            }
+        }
+    }
+
+    fn unauthorized_arguments(&mut self) {
+        self.unauthorized_paths.push(self.current_path.clone());
+        self.query_requires_scopes = true;
+    }
+
+    fn dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    fn unauthorized_variables(&mut self) -> &mut Vec<UnauthorizedVariable> {
+        &mut self.unauthorized_variables
+    }
+}
+
 impl<'a> transform::Visitor for ScopeFilteringVisitor<'a> {
     fn operation(
         &mut self,
@@ -487,7 +535,7 @@ impl<'a> transform::Visitor for ScopeFilteringVisitor<'a> {
             && !implementors_with_different_requirements
             && !implementors_with_different_field_requirements
         {
-            transform::field(self, field_def, node)
+            self.field_arguments(field_def, node)
         } else {
             self.unauthorized_paths.push(self.current_path.clone());
             self.query_requires_scopes = true;
@@ -5,6 +5,8 @@ use serde_json_bytes::json;
 use tower::ServiceExt;
 
 use crate::graphql;
+use crate::json_ext::Path;
+use crate::metrics::FutureMetricsExt;
 use crate::plugin::test::MockSubgraph;
 use crate::plugin::test::MockSubgraphService;
 use crate::plugins::authorization::CacheKeyMetadata;
@@ -937,6 +939,114 @@ async fn scopes_directive_dry_run() {
     insta::assert_json_snapshot!(response);
 }
 
+#[tokio::test]
+async fn scopes_directive_report_only() {
+    let subgraphs = MockedSubgraphs([
+    ("user", MockSubgraph::builder().with_json(
+            serde_json::json!{{
+                "query": "query($representations:[_Any!]!){_entities(representations:$representations){...on User{name}}}",
+                "variables": {"representations": [{ "__typename": "User", "id":0 }],}
+            }},
+            serde_json::json! {{ "data": { "_entities":[{"name":"Ada"}] } }},
+        ).with_json(
+            serde_json::json!{{
+                "query": "query($representations:[_Any!]!){_entities(representations:$representations){...on User{name phone}}}",
+                "variables": {"representations": [{ "__typename": "User", "id":0 }],}
+            }},
+            serde_json::json! {{ "data": { "_entities":[{"name":"Ada", "phone": "1234"}] } }},
+        ).build()),
+    ("orga", MockSubgraph::builder().with_json(
+        serde_json::json!{{"query":"{orga(id:1){id}}"}},
+        serde_json::json!{{"data": {"orga": { "id": 1 }}}}
+    ).with_json(
+        serde_json::json!{{"query":"{orga(id:1){id creatorUser{__typename id}}}"}},
+        serde_json::json!{{"data": {"orga": { "id": 1, "creatorUser": { "__typename": "User", "id": 0 } }}}}
+    ).with_json(
+        serde_json::json!{{"query":"{orga(id:1){id creatorUser{id name}}}"}},
+        serde_json::json!{{"data": {"orga": { "id": 1, "creatorUser": { "id": 0, "name":"Ada" } }}}}
+    )
+    .with_json(
+        serde_json::json!{{"query":"{orga(id:1){id creatorUser{id name phone}}}"}},
+        serde_json::json!{{"data": {"orga": { "id": 1, "creatorUser": { "id": 0, "name":"Ada", "phone": "1234" } }}}}
+    )
+    .build())
+].into_iter().collect());
+
+    async {
+        let service = TestHarness::builder()
+            .configuration_json(serde_json::json!({
+            "include_subgraph_errors": {
+                "all": true
+            },
+            "authorization": {
+                "directives": {
+                    "enabled": true,
+                    "report_only": {
+                        "requires_scopes": true
+                    },
+                }
+            }}))
+            .unwrap()
+            .schema(SCOPES_SCHEMA)
+            .extra_plugin(subgraphs)
+            .build_router()
+            .await
+            .unwrap();
+
+        let req = graphql::Request {
+            query: Some("query { orga(id: 1) { id creatorUser { id name phone } } }".to_string()),
+            ..Default::default()
+        };
+        let request = router::Request {
+            context: Context::new(),
+            router_request: http::Request::builder()
+                .method("POST")
+                .header(CONTENT_TYPE, "application/json")
+                .header(ACCEPT, "application/json")
+                .body(serde_json::to_vec(&req).unwrap().into())
+                .unwrap(),
+        };
+
+        let response = service
+            .clone()
+            .oneshot(request)
+            .await
+            .unwrap()
+            .into_graphql_response_stream()
+            .await
+            .next()
+            .await
+            .unwrap()
+            .unwrap();
+
+        // the query is executed entirely, and the paths that would have been removed are only reported
+        assert_eq!(
+            serde_json::to_value(&response).unwrap(),
+            serde_json::json!({
+                "data": {
+                    "orga": {
+                        "id": 1,
+                        "creatorUser": {
+                            "id": 0,
+                            "name": "Ada",
+                            "phone": "1234"
+                        }
+                    }
+                }
+            })
+        );
+
+        // the removed path is only counted
+        assert_counter!(
+            "apollo.router.operations.authorization.report_only",
+            1,
+            authorization.directive = "requiresScopes"
+        );
+    }
+    .with_metrics()
+    .await;
+}
+
 #[tokio::test]
 async fn errors_in_extensions() {
     let subgraphs = MockedSubgraphs([
@@ -1157,3 +1267,357 @@ async fn cache_key_metadata() {
 
     insta::assert_json_snapshot!(response);
 }
+
+#[tokio::test]
+async fn policy_expressions() {
+    let query = "query { currentUser { name } }";
+
+    let service = TestHarness::builder()
+        .configuration_json(serde_json::json!({
+            "include_subgraph_errors": {
+                "all": true
+            },
+            "authorization": {
+                "directives": {
+                    "enabled": true
+                },
+                "policies": {
+                    "name": "claims.role == 'admin' && headers['x-tenant'] == 'acme'"
+                }
+            }
+        }))
+        .unwrap()
+        .schema(CACHE_KEY_SCHEMA)
+        .subgraph_hook(|_name, _service| {
+            let mut mock_subgraph_service = MockSubgraphService::new();
+            mock_subgraph_service
+                .expect_call()
+                .returning(move |req: subgraph::Request| {
+                    assert_eq!(req.authorization.policies, vec!["name".to_string()]);
+
+                    Ok(subgraph::Response::fake_builder()
+                        .context(req.context)
+                        .data(serde_json::json! {{
+                            "currentUser": {
+                                "name": "A"
+                            }
+                        }})
+                        .build())
+                });
+            mock_subgraph_service.boxed()
+        })
+        .build_router()
+        .await
+        .unwrap();
+
+    for (role, authorized) in [("admin", true), ("user", false)] {
+        let context = Context::new();
+        context
+            .insert(
+                "apollo_authentication::JWT::claims",
+                json! {{ "role": role }},
+            )
+            .unwrap();
+
+        let request = supergraph::Request::fake_builder()
+            .query(query)
+            .header("x-tenant", "acme")
+            .context(context)
+            .build()
+            .unwrap();
+        let mut response = service
+            .clone()
+            .oneshot(router::Request::try_from(request).unwrap())
+            .await
+            .unwrap();
+        let response = response.next_response().await.unwrap().unwrap();
+        let response: serde_json::Value = serde_json::from_slice(&response).unwrap();
+
+        if authorized {
+            assert_eq!(
+                response["data"],
+                serde_json::json!({ "currentUser": { "name": "A" } })
+            );
+        } else {
+            assert_eq!(
+                response["errors"][0]["extensions"]["code"],
+                "UNAUTHORIZED_FIELD_OR_TYPE"
+            );
+        }
+    }
+}
+
+const ARGUMENTS_SCHEMA: &str = r#"schema
+  @link(url: "https://specs.apollo.dev/link/v1.0")
+  @link(url: "https://specs.apollo.dev/join/v0.3", for: EXECUTION)
+  @link(url: "https://specs.apollo.dev/requiresScopes/v0.1", for: SECURITY)
+  {
+    query: Query
+    mutation: Mutation
+}
+directive @link(url: String, as: String, for: link__Purpose, import: [link__Import]) repeatable on SCHEMA
+directive @join__enumValue(graph: join__Graph!) repeatable on ENUM_VALUE
+directive @join__field(graph: join__Graph, requires: join__FieldSet, provides: join__FieldSet, type: String, external: Boolean, override: String, usedOverridden: Boolean) repeatable on FIELD_DEFINITION | INPUT_FIELD_DEFINITION
+directive @join__graph(name: String!, url: String!) on ENUM_VALUE
+directive @join__implements(graph: join__Graph!, interface: String!) repeatable on OBJECT | INTERFACE
+directive @join__type(graph: join__Graph!, key: join__FieldSet, extension: Boolean! = false, resolvable: Boolean! = true, isInterfaceObject: Boolean! = false) repeatable on OBJECT | INTERFACE | UNION | ENUM | INPUT_OBJECT | SCALAR
+directive @join__unionMember(graph: join__Graph!, member: String!) repeatable on UNION
+
+scalar link__Import
+enum link__Purpose {
+    """
+    `SECURITY` features provide metadata necessary to securely resolve fields.
+    """
+    SECURITY
+  
+    """
+    `EXECUTION` features provide metadata necessary for operation execution.
+    """
+    EXECUTION
+  }
+
+scalar federation__Scope
+directive @requiresScopes(scopes: [[federation__Scope!]!]!) on OBJECT | FIELD_DEFINITION | INTERFACE | SCALAR | ENUM | ARGUMENT_DEFINITION | INPUT_FIELD_DEFINITION
+
+scalar join__FieldSet
+enum join__Graph {
+   ORGA @join__graph(name: "orga", url: "http://localhost:4002/graphql")
+}
+
+type Query
+@join__type(graph: ORGA) {
+   orga(id: ID, includeDeleted: Boolean @requiresScopes(scopes: [["admin"]])): Organization
+   orgas(filter: OrgaFilter): [Organization]
+}
+type Mutation
+@join__type(graph: ORGA) {
+   updateOrga(input: OrgaInput!): Organization
+}
+input OrgaInput
+@join__type(graph: ORGA) {
+   id: ID!
+   name: String
+   internalNote: String @requiresScopes(scopes: [["admin"]])
+}
+input OrgaFilter
+@join__type(graph: ORGA) {
+   name: String
+   internalNote: String @requiresScopes(scopes: [["admin"]])
+   and: [OrgaFilter!]
+   not: OrgaFilter
+}
+type Organization
+@join__type(graph: ORGA, key: "id") {
+   id: ID
+   name: String
+}"#;
+
+#[tokio::test]
+async fn arguments_and_input_fields() {
+    let service = TestHarness::builder()
+        .configuration_json(serde_json::json!({
+            "include_subgraph_errors": {
+                "all": true
+            },
+            "authorization": {
+                "directives": {
+                    "enabled": true
+                }
+            }
+        }))
+        .unwrap()
+        .schema(ARGUMENTS_SCHEMA)
+        .subgraph_hook(|_name, _service| {
+            let mut mock_subgraph_service = MockSubgraphService::new();
+            mock_subgraph_service
+                .expect_call()
+                .returning(move |req: subgraph::Request| {
+                    let body = req.subgraph_request.body();
+                    let query = body.query.clone().unwrap_or_default();
+                    let admin = req.context.get_json_value("admin") == Some(json!(true));
+
+                    // the unauthorized argument and input field are not sent to the subgraph
+                    assert_eq!(query.contains("includeDeleted"), admin);
+                    assert_eq!(
+                        body.variables
+                            .get("input")
+                            .and_then(|input| input.as_object())
+                            .and_then(|input| input.get("internalNote"))
+                            .is_some(),
+                        admin && query.contains("updateOrga"),
+                    );
+
+                    let data = if query.contains("updateOrga") {
+                        serde_json::json! {{ "updateOrga": { "id": "1" } }}
+                    } else {
+                        serde_json::json! {{ "orga": { "id": "1" } }}
+                    };
+                    Ok(subgraph::Response::fake_builder()
+                        .context(req.context)
+                        .data(data)
+                        .build())
+                });
+            mock_subgraph_service.boxed()
+        })
+        .build_supergraph()
+        .await
+        .unwrap();
+
+    for admin in [false, true] {
+        let context = Context::new();
+        context.insert("admin", admin).unwrap();
+        let scope = if admin { "admin" } else { "read" };
+        context
+            .insert(
+                "apollo_authentication::JWT::claims",
+                json! {{ "scope": scope }},
+            )
+            .unwrap();
+
+        let request = supergraph::Request::fake_builder()
+            .query("query { orga(id: 1, includeDeleted: true) { id } }")
+            .context(context.clone())
+            .build()
+            .unwrap();
+        let response = service
+            .clone()
+            .oneshot(request)
+            .await
+            .unwrap()
+            .next_response()
+            .await
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(&response).unwrap()["data"],
+            serde_json::json!({ "orga": { "id": "1" } })
+        );
+        if admin {
+            assert!(response.errors.is_empty());
+        } else {
+            assert_eq!(response.errors.len(), 1);
+            assert_eq!(response.errors[0].path, Some(Path::from("orga")));
+        }
+
+        let request = supergraph::Request::fake_builder()
+            .query("mutation($input: OrgaInput!) { updateOrga(input: $input) { id } }")
+            .variables(
+                json! {{ "input": { "id": "1", "name": "A", "internalNote": "B" } }}
+                    .as_object()
+                    .unwrap()
+                    .clone(),
+            )
+            .context(context)
+            .build()
+            .unwrap();
+        let response = service
+            .clone()
+            .oneshot(request)
+            .await
+            .unwrap()
+            .next_response()
+            .await
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(&response).unwrap()["data"],
+            serde_json::json!({ "updateOrga": { "id": "1" } })
+        );
+        if admin {
+            assert!(response.errors.is_empty());
+        } else {
+            assert_eq!(response.errors.len(), 1);
+            assert_eq!(
+                response.errors[0].extensions.get("inputField"),
+                Some(&json!("/$input/internalNote"))
+            );
+        }
+    }
+}
+
+#[tokio::test]
+async fn input_fields_in_recursive_variables() {
+    let service = TestHarness::builder()
+        .configuration_json(serde_json::json!({
+            "include_subgraph_errors": {
+                "all": true
+            },
+            "authorization": {
+                "directives": {
+                    "enabled": true
+                }
+            }
+        }))
+        .unwrap()
+        .schema(ARGUMENTS_SCHEMA)
+        .subgraph_hook(|_name, _service| {
+            let mut mock_subgraph_service = MockSubgraphService::new();
+            mock_subgraph_service
+                .expect_call()
+                .returning(move |req: subgraph::Request| {
+                    // the unauthorized input fields are removed at any depth
+                    assert_eq!(
+                        req.subgraph_request.body().variables.get("filter"),
+                        Some(&json!({
+                            "and": [{ "name": "A" }, { "not": { "and": [{}] } }]
+                        }))
+                    );
+
+                    Ok(subgraph::Response::fake_builder()
+                        .context(req.context)
+                        .data(serde_json::json! {{ "orgas": [{ "id": "1" }] }})
+                        .build())
+                });
+            mock_subgraph_service.boxed()
+        })
+        .build_supergraph()
+        .await
+        .unwrap();
+
+    let context = Context::new();
+    context
+        .insert(
+            "apollo_authentication::JWT::claims",
+            json! {{ "scope": "read" }},
+        )
+        .unwrap();
+    let request = supergraph::Request::fake_builder()
+        .query("query($filter: OrgaFilter) { orgas(filter: $filter) { id } }")
+        .variables(
+            json! {{ "filter": {
+                "and": [
+                    { "name": "A", "internalNote": "B" },
+                    { "not": { "and": [{ "internalNote": "C" }], "internalNote": "D" } }
+                ]
+            } }}
+            .as_object()
+            .unwrap()
+            .clone(),
+        )
+        .context(context)
+        .build()
+        .unwrap();
+    let response = service
+        .oneshot(request)
+        .await
+        .unwrap()
+        .next_response()
+        .await
+        .unwrap();
+
+    assert_eq!(
+        serde_json::to_value(&response).unwrap()["data"],
+        serde_json::json!({ "orgas": [{ "id": "1" }] })
+    );
+    let input_fields: Vec<_> = response
+        .errors
+        .iter()
+        .map(|error| error.extensions.get("inputField").cloned())
+        .collect();
+    assert_eq!(
+        input_fields,
+        [
+            Some(json!("/$filter/and/0/internalNote")),
+            Some(json!("/$filter/and/1/not/and/0/internalNote")),
+            Some(json!("/$filter/and/1/not/internalNote")),
+        ]
+    );
+}
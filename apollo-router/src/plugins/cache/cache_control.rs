@@ -177,7 +177,7 @@ impl CacheControl {
             prev = true;
         }
         if self.no_cache {
-            write!(&mut s, "{}no_cache", if prev { "," } else { "" },)?;
+            write!(&mut s, "{}no-cache", if prev { "," } else { "" },)?;
             prev = true;
         }
         if self.must_revalidate {
@@ -321,3 +321,33 @@ impl CacheControl {
         !expired
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use http::header::CACHE_CONTROL;
+    use http::HeaderMap;
+    use http::HeaderValue;
+
+    use super::CacheControl;
+
+    #[test]
+    fn no_cache_is_serialized_as_a_cache_control_directive() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            CACHE_CONTROL,
+            HeaderValue::from_static("max-age=60, no-cache, private"),
+        );
+        let cache_control = CacheControl::new(&headers, None).unwrap();
+
+        let mut headers = HeaderMap::new();
+        cache_control.to_headers(&mut headers).unwrap();
+        assert_eq!(
+            headers.get(CACHE_CONTROL).unwrap(),
+            "max-age=60,no-cache,private"
+        );
+
+        // the serialized header can be parsed again
+        let cache_control = CacheControl::new(&headers, None).unwrap();
+        assert!(cache_control.should_revalidate());
+    }
+}
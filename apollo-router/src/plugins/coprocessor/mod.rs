@@ -16,14 +16,10 @@ use http::header;
 use http::HeaderMap;
 use http::HeaderName;
 use http::HeaderValue;
-use hyper::client::HttpConnector;
 use hyper::Body;
-use hyper_rustls::ConfigBuilderExt;
-use hyper_rustls::HttpsConnector;
 use schemars::JsonSchema;
 use serde::Deserialize;
 use serde::Serialize;
-use tower::timeout::TimeoutLayer;
 use tower::util::MapFutureLayer;
 use tower::BoxError;
 use tower::Service;
@@ -38,15 +34,15 @@ use crate::plugin::PluginInit;
 use crate::register_plugin;
 use crate::services;
 use crate::services::external::externalize_header_map;
+use crate::services::external::new_http_client;
 use crate::services::external::Control;
 use crate::services::external::Externalizable;
+use crate::services::external::HTTPClientService;
 use crate::services::external::PipelineStep;
 use crate::services::external::DEFAULT_EXTERNALIZATION_TIMEOUT;
 use crate::services::external::EXTERNALIZABLE_VERSION;
 use crate::services::router;
 use crate::services::subgraph;
-use crate::services::trust_dns_connector::new_async_http_connector;
-use crate::services::trust_dns_connector::AsyncHyperResolver;
 
 #[cfg(test)]
 mod test;
@@ -55,43 +51,15 @@ mod execution;
 mod supergraph;
 
 pub(crate) const EXTERNAL_SPAN_NAME: &str = "external_plugin";
-const POOL_IDLE_TIMEOUT_DURATION: Option<Duration> = Some(Duration::from_secs(5));
 const COPROCESSOR_ERROR_EXTENSION: &str = "ERROR";
 const COPROCESSOR_DESERIALIZATION_ERROR_EXTENSION: &str = "EXTERNAL_DESERIALIZATION_ERROR";
 
-type HTTPClientService =
-    tower::timeout::Timeout<hyper::Client<HttpsConnector<HttpConnector<AsyncHyperResolver>>, Body>>;
-
 #[async_trait::async_trait]
 impl Plugin for CoprocessorPlugin<HTTPClientService> {
     type Config = Conf;
 
     async fn new(init: PluginInit<Self::Config>) -> Result<Self, BoxError> {
-        let mut http_connector = new_async_http_connector()?;
-        http_connector.set_nodelay(true);
-        http_connector.set_keepalive(Some(std::time::Duration::from_secs(60)));
-        http_connector.enforce_http(false);
-
-        let tls_config = rustls::ClientConfig::builder()
-            .with_safe_defaults()
-            .with_native_roots()
-            .with_no_client_auth();
-
-        let connector = hyper_rustls::HttpsConnectorBuilder::new()
-            .with_tls_config(tls_config)
-            .https_or_http()
-            .enable_http1()
-            .enable_http2()
-            .wrap_connector(http_connector);
-
-        let http_client = ServiceBuilder::new()
-            .layer(TimeoutLayer::new(init.config.timeout))
-            .service(
-                hyper::Client::builder()
-                    .pool_idle_timeout(POOL_IDLE_TIMEOUT_DURATION)
-                    .build(connector),
-            );
-
+        let http_client = new_http_client(init.config.timeout)?;
         CoprocessorPlugin::new(http_client, init.config, init.supergraph_sdl)
     }
 
@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use bytesize::ByteSize;
+use futures::stream;
+use futures::stream::StreamExt;
+use futures::stream::TryStreamExt;
+use futures::Stream;
+use http::HeaderMap;
+use tokio_util::io::ReaderStream;
+use uuid::Uuid;
+
+use super::error::FileUploadError;
+use super::Result as UploadResult;
+
+/// The disk space taken by the files of all the requests, in the `buffered` mode
+#[derive(Debug)]
+pub(super) struct BufferUsage {
+    used: AtomicU64,
+    limit: Option<ByteSize>,
+}
+
+impl BufferUsage {
+    pub(super) fn new(limit: Option<ByteSize>) -> Self {
+        Self {
+            used: AtomicU64::new(0),
+            limit,
+        }
+    }
+
+    fn reserve(&self, bytes: u64) -> UploadResult<()> {
+        let Some(limit) = self.limit else {
+            self.used.fetch_add(bytes, Ordering::Relaxed);
+            return Ok(());
+        };
+        self.used
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                used.checked_add(bytes)
+                    .filter(|&used| used <= limit.as_u64())
+            })
+            .map(|_| ())
+            .map_err(|_| FileUploadError::MaxBufferSizeExceeded(limit))
+    }
+
+    fn release(&self, bytes: u64) {
+        self.used.fetch_sub(bytes, Ordering::Relaxed);
+    }
+}
+
+/// A temporary directory holding the files of a single request.
+///
+/// The directory and its content are removed when it is dropped.
+#[derive(Debug)]
+pub(super) struct BufferDirectory {
+    path: PathBuf,
+    usage: Arc<BufferUsage>,
+    // The bytes reserved in `usage` by the files of this directory
+    reserved: u64,
+}
+
+impl BufferDirectory {
+    pub(super) async fn create(parent: &Path, usage: Arc<BufferUsage>) -> UploadResult<Self> {
+        let path = parent.join(format!("router-file-uploads-{}", Uuid::new_v4()));
+        tokio::fs::create_dir_all(&path)
+            .await
+            .map_err(FileUploadError::BufferingFailed)?;
+        Ok(Self {
+            path,
+            usage,
+            reserved: 0,
+        })
+    }
+
+    pub(super) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Reserves disk space before writing to a file of the directory
+    pub(super) fn reserve(&mut self, bytes: usize) -> UploadResult<()> {
+        self.usage.reserve(bytes as u64)?;
+        self.reserved += bytes as u64;
+        Ok(())
+    }
+}
+
+impl Drop for BufferDirectory {
+    fn drop(&mut self) {
+        let path = std::mem::take(&mut self.path);
+        let usage = self.usage.clone();
+        let reserved = self.reserved;
+        let remove = move || {
+            if let Err(err) = std::fs::remove_dir_all(&path) {
+                tracing::error!(
+                    "failed to remove the file uploads directory '{}': {}",
+                    path.display(),
+                    err
+                );
+            }
+            usage.release(reserved);
+        };
+        // Removing the files blocks, so it is kept off the runtime's worker threads
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => {
+                handle.spawn_blocking(remove);
+            }
+            Err(_) => remove(),
+        }
+    }
+}
+
+/// A file of the request, written to disk
+#[derive(Debug)]
+pub(super) struct BufferedFile {
+    pub(super) path: PathBuf,
+    pub(super) headers: HeaderMap,
+}
+
+/// The files of a request in the `buffered` mode.
+///
+/// Unlike files streamed from the client request, they can be read any number of times, in any order.
+#[derive(Debug)]
+pub(super) struct BufferedFiles {
+    files: HashMap<String, BufferedFile>,
+    _directory: BufferDirectory,
+}
+
+impl BufferedFiles {
+    pub(super) fn new(directory: BufferDirectory, files: HashMap<String, BufferedFile>) -> Self {
+        Self {
+            files,
+            _directory: directory,
+        }
+    }
+
+    /// Streams the given files, each one preceded by the prefix built from its headers.
+    ///
+    /// The stream keeps the files on disk until it is dropped.
+    pub(super) fn subgraph_stream<FilePrefixFn>(
+        self: Arc<Self>,
+        file_names: Vec<String>,
+        file_prefix_fn: FilePrefixFn,
+    ) -> impl Stream<Item = UploadResult<Bytes>>
+    where
+        FilePrefixFn: Fn(&HeaderMap) -> Bytes,
+    {
+        stream::iter(file_names)
+            .then(move |name| {
+                let file = self
+                    .files
+                    .get(&name)
+                    .map(|file| (file.path.clone(), file_prefix_fn(&file.headers)));
+                async move {
+                    let (path, prefix) =
+                        file.ok_or_else(|| FileUploadError::MissingFiles(format!("'{}'", name)))?;
+                    let content = tokio::fs::File::open(path)
+                        .await
+                        .map_err(FileUploadError::BufferingFailed)?;
+                    Ok(tokio_stream::once(Ok(prefix)).chain(
+                        ReaderStream::new(content).map_err(FileUploadError::BufferingFailed),
+                    ))
+                }
+            })
+            .try_flatten()
+    }
+}
@@ -1,7 +1,13 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
 use bytesize::ByteSize;
+use regex::Regex;
 use schemars::JsonSchema;
 use serde::Deserialize;
 
+use crate::plugin::serde::deserialize_option_regex;
+
 /// Request limits for a multipart request
 #[derive(Debug, Clone, Copy, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
@@ -13,6 +19,12 @@ pub(crate) struct MultipartRequestLimits {
     #[serde(deserialize_with = "bytesize::ByteSize::deserialize")]
     #[schemars(with = "String")]
     pub(crate) max_file_size: ByteSize,
+
+    /// The maximum size of the whole request, including the operation and all the files
+    /// (default: no limit)
+    #[serde(default)]
+    #[schemars(with = "Option<String>")]
+    pub(crate) max_request_size: Option<ByteSize>,
 }
 
 impl Default for MultipartRequestLimits {
@@ -20,6 +32,7 @@ impl Default for MultipartRequestLimits {
         Self {
             max_files: 5,
             max_file_size: ByteSize::mb(1),
+            max_request_size: None,
         }
     }
 }
@@ -37,6 +50,65 @@ pub(crate) enum MultipartRequestMode {
     /// files.
     #[default]
     Stream,
+
+    /// The files of the multipart request will be written to a temporary directory
+    /// before the query is executed, and removed once the request is complete.
+    ///
+    /// This allows sending files to any number of subgraphs, in any order, at the cost
+    /// of disk space and of the latency of receiving the whole request first.
+    Buffered,
+}
+
+/// Configuration of the temporary storage of files in the `buffered` mode
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields, default)]
+pub(crate) struct MultipartRequestBuffer {
+    /// The directory in which the files of each request are written (default: the
+    /// temporary directory of the system)
+    pub(crate) directory: Option<PathBuf>,
+
+    /// The maximum size of the files written to disk at the same time, across all the
+    /// requests (default: no limit). Requests exceeding it are rejected
+    #[schemars(with = "Option<String>")]
+    pub(crate) max_size: Option<ByteSize>,
+}
+
+/// Validation rule for the files mapped to some variables
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct MultipartFileRule {
+    /// The path of the variables the rule applies to, in the format of the `map` field,
+    /// for example `variables.avatar`. A `*` segment matches any field or list index,
+    /// for example `variables.photos.*`
+    pub(crate) path: String,
+
+    /// The MIME types allowed for the files, for example `image/png` or `image/*`. Types
+    /// are detected from the content of the files, not from their declared content type
+    pub(crate) allowed_types: Option<Vec<String>>,
+
+    /// A regular expression that the names of the files must match
+    #[schemars(with = "Option<String>")]
+    #[serde(default, deserialize_with = "deserialize_option_regex")]
+    pub(crate) filename: Option<Regex>,
+}
+
+/// Scanning of the uploaded files by an external service
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct MultipartFileScan {
+    /// The URL to which each file is sent in a `POST` request. The file is accepted if the
+    /// response has a 2xx status code, and rejected if it has a 4xx status code
+    pub(crate) url: String,
+
+    /// The timeout for scanning a file (default: 10s)
+    #[serde(deserialize_with = "humantime_serde::deserialize")]
+    #[schemars(with = "String", default = "default_scan_timeout")]
+    #[serde(default = "default_scan_timeout")]
+    pub(crate) timeout: Duration,
+}
+
+fn default_scan_timeout() -> Duration {
+    Duration::from_secs(10)
 }
 
 /// Configuration for a multipart request for file uploads.
@@ -51,8 +123,18 @@ pub(crate) struct MultipartRequest {
     /// The supported mode for the request (default: [MultipartRequestMode::Stream])
     pub(crate) mode: MultipartRequestMode,
 
+    /// Temporary storage of files, used in the `buffered` mode
+    pub(crate) buffer: MultipartRequestBuffer,
+
     /// Resource limits for multipart requests
     pub(crate) limits: MultipartRequestLimits,
+
+    /// Validation rules for the files, applied to the files mapped to matching variables
+    pub(crate) rules: Vec<MultipartFileRule>,
+
+    /// Scanning of each file by an external service before it is sent to subgraphs.
+    /// Requires the `buffered` mode
+    pub(crate) scan: Option<MultipartFileScan>,
 }
 
 impl Default for MultipartRequest {
@@ -60,7 +142,10 @@ impl Default for MultipartRequest {
         Self {
             enabled: true,
             mode: Default::default(),
+            buffer: Default::default(),
             limits: Default::default(),
+            rules: Vec::new(),
+            scan: None,
         }
     }
 }
@@ -8,7 +8,7 @@ use crate::graphql;
 pub(super) enum FileUploadError {
     /// Represents an invalid request, wrapping the context as a string
     #[error("invalid multipart request: {0}")]
-    InvalidMultipartRequest(#[from] multer::Error),
+    InvalidMultipartRequest(multer::Error),
 
     #[error("Missing multipart field 'operations', it should be a first field in request body.")]
     MissingOperationsField,
@@ -52,10 +52,45 @@ pub(super) enum FileUploadError {
     #[error("Exceeded the limit of {limit} on {filename} file.")]
     MaxFileSizeLimitExceeded { limit: ByteSize, filename: String },
 
+    #[error("Exceeded the limit of {0} on the size of the request.")]
+    MaxRequestSizeLimitExceeded(ByteSize),
+
+    #[error("Exceeded the limit of {0} on the size of the files buffered to disk.")]
+    MaxBufferSizeExceeded(ByteSize),
+
+    #[error("The name of the {0} file is not allowed.")]
+    InvalidFileName(String),
+
+    #[error("The type '{content_type}' of the {filename} file is not allowed.")]
+    InvalidFileType {
+        content_type: &'static str,
+        filename: String,
+    },
+
+    #[error("The {filename} file was rejected by the scan")]
+    FileRejected { filename: String },
+
+    #[error("Failed to scan the {filename} file: {reason}")]
+    ScanFailed { filename: String, reason: String },
+
+    #[error("Failed to buffer the uploaded files: {0}.")]
+    BufferingFailed(std::io::Error),
+
     #[error("{0}")]
     HyperBodyErrorWrapper(#[from] hyper::Error),
 }
 
+impl From<multer::Error> for FileUploadError {
+    fn from(value: multer::Error) -> Self {
+        match value {
+            multer::Error::StreamSizeExceeded { limit } => {
+                FileUploadError::MaxRequestSizeLimitExceeded(ByteSize::b(limit))
+            }
+            _ => FileUploadError::InvalidMultipartRequest(value),
+        }
+    }
+}
+
 impl From<FileUploadError> for graphql::Error {
     fn from(value: FileUploadError) -> Self {
         Self::builder()
@@ -67,6 +102,19 @@ impl From<FileUploadError> for graphql::Error {
                 FileUploadError::MaxFileSizeLimitExceeded { .. } => {
                     "FILE_UPLOADS_LIMITS_MAX_FILE_SIZE_EXCEEDED".to_string()
                 }
+                FileUploadError::MaxRequestSizeLimitExceeded(_) => {
+                    "FILE_UPLOADS_LIMITS_MAX_REQUEST_SIZE_EXCEEDED".to_string()
+                }
+                FileUploadError::MaxBufferSizeExceeded(_) => {
+                    "FILE_UPLOADS_LIMITS_MAX_BUFFER_SIZE_EXCEEDED".to_string()
+                }
+                FileUploadError::BufferingFailed(_) => "FILE_UPLOADS_BUFFERING_FAILED".to_string(),
+                FileUploadError::InvalidFileName(_) => "FILE_UPLOADS_INVALID_FILE_NAME".to_string(),
+                FileUploadError::InvalidFileType { .. } => {
+                    "FILE_UPLOADS_INVALID_FILE_TYPE".to_string()
+                }
+                FileUploadError::FileRejected { .. } => "FILE_UPLOADS_FILE_REJECTED".to_string(),
+                FileUploadError::ScanFailed { .. } => "FILE_UPLOADS_SCAN_FAILED".to_string(),
                 _ => "FILE_UPLOADS_OPERATION_CANNOT_STREAM".to_string(),
             })
             .build()
@@ -1,4 +1,5 @@
 use std::ops::ControlFlow;
+use std::str::FromStr;
 use std::sync::Arc;
 
 use futures::FutureExt;
@@ -15,13 +16,16 @@ use tower::BoxError;
 use tower::ServiceBuilder;
 use tower::ServiceExt;
 
+use self::buffered_files::BufferUsage;
 use self::config::FileUploadsConfig;
-use self::config::MultipartRequestLimits;
+use self::config::MultipartRequestMode;
 use self::error::FileUploadError;
 use self::map_field::MapField;
 use self::multipart_form_data::MultipartFormData;
+use self::multipart_request::MultipartOptions;
 use self::multipart_request::MultipartRequest;
 use self::rearrange_query_plan::rearrange_query_plan;
+use self::scan::FileScanner;
 use crate::json_ext;
 use crate::layers::ServiceBuilderExt;
 use crate::plugin::PluginInit;
@@ -32,12 +36,15 @@ use crate::services::router;
 use crate::services::subgraph;
 use crate::services::supergraph;
 
+mod buffered_files;
 mod config;
 mod error;
 mod map_field;
 mod multipart_form_data;
 mod multipart_request;
 mod rearrange_query_plan;
+mod scan;
+mod validation;
 
 type Result<T> = std::result::Result<T, error::FileUploadError>;
 
@@ -45,7 +52,7 @@ type Result<T> = std::result::Result<T, error::FileUploadError>;
 #[doc(hidden)] // Only public for integration tests
 struct FileUploadsPlugin {
     enabled: bool,
-    limits: MultipartRequestLimits,
+    options: Arc<MultipartOptions>,
 }
 
 register_private_plugin!("apollo", "preview_file_uploads", FileUploadsPlugin);
@@ -57,20 +64,64 @@ impl PluginPrivate for FileUploadsPlugin {
     async fn new(init: PluginInit<Self::Config>) -> std::result::Result<Self, BoxError> {
         let config = init.config;
         let enabled = config.enabled && config.protocols.multipart.enabled;
-        let limits = config.protocols.multipart.limits;
-        Ok(Self { enabled, limits })
+        let multipart = config.protocols.multipart;
+        let buffer_directory = match multipart.mode {
+            MultipartRequestMode::Stream => None,
+            MultipartRequestMode::Buffered => Some(
+                multipart
+                    .buffer
+                    .directory
+                    .unwrap_or_else(std::env::temp_dir),
+            ),
+        };
+
+        for rule in &multipart.rules {
+            if !rule.path.starts_with("variables.") {
+                return Err(format!(
+                    "invalid file upload rule path '{}', it should start with 'variables.'",
+                    rule.path
+                )
+                .into());
+            }
+            for allowed_type in rule.allowed_types.iter().flatten() {
+                mime::Mime::from_str(allowed_type).map_err(|err| {
+                    format!("invalid file upload rule type '{allowed_type}': {err}")
+                })?;
+            }
+        }
+
+        // Files must be complete before being scanned, so they can't be streamed
+        let scanner = match &multipart.scan {
+            Some(_) if buffer_directory.is_none() => {
+                return Err("scanning file uploads requires the 'buffered' mode".into())
+            }
+            Some(scan) => Some(FileScanner::new(scan)?),
+            None => None,
+        };
+
+        Ok(Self {
+            enabled,
+            options: Arc::new(MultipartOptions {
+                limits: multipart.limits,
+                buffer_directory,
+                buffer_usage: Arc::new(BufferUsage::new(multipart.buffer.max_size)),
+                rules: multipart.rules,
+                scanner,
+            }),
+        })
     }
 
     fn router_service(&self, service: router::BoxService) -> router::BoxService {
         if !self.enabled {
             return service;
         }
-        let limits = self.limits;
+        let options = self.options.clone();
         ServiceBuilder::new()
             .oneshot_checkpoint_async(move |req: router::Request| {
+                let options = options.clone();
                 async move {
                     let context = req.context.clone();
-                    Ok(match router_layer(req, limits).await {
+                    Ok(match router_layer(req, options).await {
                         Ok(req) => ControlFlow::Continue(req),
                         Err(err) => ControlFlow::Break(
                             router::Response::error_builder()
@@ -163,7 +214,7 @@ fn get_multipart_mime(req: &router::Request) -> Option<MediaType> {
 
 async fn router_layer(
     req: router::Request,
-    limits: MultipartRequestLimits,
+    options: Arc<MultipartOptions>,
 ) -> Result<router::Request> {
     if let Some(mime) = get_multipart_mime(&req) {
         let boundary = mime
@@ -173,7 +224,7 @@ async fn router_layer(
 
         let (mut request_parts, request_body) = req.router_request.into_parts();
 
-        let mut multipart = MultipartRequest::new(request_body, boundary, limits);
+        let mut multipart = MultipartRequest::new(request_body, boundary, options);
         let operations_stream = multipart.operations_field().await?;
 
         req.context.extensions().lock().insert(multipart);
@@ -208,6 +259,9 @@ async fn supergraph_layer(mut req: supergraph::Request) -> Result<supergraph::Re
 
     if let Some(mut multipart) = multipart {
         let map_field = multipart.map_field().await?;
+        if multipart.is_buffered() {
+            multipart.buffer_files(&map_field.files_order).await?;
+        }
         let variables = &mut req.supergraph_request.body_mut().variables;
 
         // patch variables to pass validation
@@ -309,7 +363,12 @@ fn execution_layer(req: execution::Request) -> Result<execution::Request> {
         .get::<SupergraphLayerResult>()
         .cloned();
     if let Some(supergraph_result) = supergraph_result {
-        let SupergraphLayerResult { map, .. } = supergraph_result;
+        let SupergraphLayerResult { multipart, map } = supergraph_result;
+
+        // Buffered files can be read by any fetch, in any order
+        if multipart.is_buffered() {
+            return Ok(req);
+        }
 
         let query_plan = Arc::new(rearrange_query_plan(&req.query_plan, &map)?);
         return Ok(execution::Request { query_plan, ..req });
@@ -346,6 +405,11 @@ async fn subgraph_layer(mut req: subgraph::Request) -> subgraph::Request {
     req
 }
 
+/// Whether files are sent with this subgraph request
+pub(crate) fn has_files<B>(req: &http::Request<B>) -> bool {
+    req.extensions().get::<MultipartFormData>().is_some()
+}
+
 static APOLLO_REQUIRE_PREFLIGHT: HeaderName = HeaderName::from_static("apollo-require-preflight");
 static TRUE: http::HeaderValue = HeaderValue::from_static("true");
 
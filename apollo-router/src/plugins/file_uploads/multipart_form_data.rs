@@ -2,6 +2,7 @@ use std::sync::Arc;
 
 use bytes::Bytes;
 use bytes::BytesMut;
+use futures::future::Either;
 use futures::stream::StreamExt;
 use futures::stream::TryStreamExt;
 use futures::Stream;
@@ -67,7 +68,7 @@ impl MultipartFormData {
 
         let last = tokio_stream::once(Ok(format!("\r\n--{}--\r\n", self.boundary).into()));
 
-        let file_names = self.map.keys().cloned().collect();
+        let file_names: Vec<String> = self.map.keys().cloned().collect();
         let boundary = self.boundary;
         let file_prefix = move |headers: &HeaderMap| {
             let mut prefix = BytesMut::new();
@@ -85,10 +86,14 @@ impl MultipartFormData {
             Bytes::from(prefix)
         };
 
-        let files_stream = self
-            .multipart
-            .subgraph_stream(file_names, file_prefix)
-            .await;
+        let files_stream = match self.multipart.buffered_files().await {
+            Some(files) => Either::Left(files.subgraph_stream(file_names, file_prefix)),
+            None => Either::Right(
+                self.multipart
+                    .subgraph_stream(file_names.into_iter().collect(), file_prefix)
+                    .await,
+            ),
+        };
         static_part.chain(files_stream).chain(last)
     }
 }
@@ -1,34 +1,60 @@
 use core::task;
+use std::collections::HashMap;
 use std::collections::HashSet;
 use std::mem;
+use std::path::PathBuf;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::Poll;
 
 use bytes::Bytes;
+use bytes::BytesMut;
 use futures::Stream;
 use http::HeaderMap;
+use indexmap::IndexSet;
 use itertools::Itertools;
 use multer::Constraints;
 use multer::Multipart;
 use multer::SizeLimit;
 use pin_project_lite::pin_project;
+use tokio::io::AsyncWriteExt;
 use tokio::sync::Mutex;
 use tokio::sync::OwnedMutexGuard;
 
+use super::buffered_files::BufferDirectory;
+use super::buffered_files::BufferUsage;
+use super::buffered_files::BufferedFile;
+use super::buffered_files::BufferedFiles;
+use super::config::MultipartFileRule;
 use super::config::MultipartRequestLimits;
 use super::error::FileUploadError;
 use super::map_field::MapField;
 use super::map_field::MapFieldRaw;
+use super::scan::FileScanner;
+use super::validation::FileRules;
+use super::validation::SNIFF_LENGTH;
 use super::Result as UploadResult;
 
 // The limit to set for the map field in the multipart request.
 // We don't expect this to ever be reached, but we can always add a config option if needed later.
 const MAP_SIZE_LIMIT: u64 = 10 * 1024;
 
+/// The settings of the plugin that apply to each multipart request
+#[derive(Clone, Debug)]
+pub(super) struct MultipartOptions {
+    pub(super) limits: MultipartRequestLimits,
+    // Set in the `buffered` mode, to the directory in which files are written
+    pub(super) buffer_directory: Option<PathBuf>,
+    // The disk space taken by the buffered files, shared by all the requests
+    pub(super) buffer_usage: Arc<BufferUsage>,
+    pub(super) rules: Vec<MultipartFileRule>,
+    pub(super) scanner: Option<FileScanner>,
+}
+
 #[derive(Clone, Debug)]
 pub(super) struct MultipartRequest {
     state: Arc<Mutex<MultipartRequestState>>,
+    options: Arc<MultipartOptions>,
 }
 
 #[derive(Debug)]
@@ -39,6 +65,9 @@ struct MultipartRequestState {
     file_sizes: Vec<usize>,
     max_files_exceeded: bool,
     max_files_size_exceeded: bool,
+    buffered_files: Option<Arc<BufferedFiles>>,
+    // The validation rules of each file, by name of its field
+    file_rules: HashMap<String, FileRules>,
 }
 
 impl Drop for MultipartRequestState {
@@ -70,25 +99,36 @@ impl MultipartRequest {
     pub(super) fn new(
         request_body: hyper::Body,
         boundary: String,
-        limits: MultipartRequestLimits,
+        options: Arc<MultipartOptions>,
     ) -> Self {
+        let mut size_limit = SizeLimit::new().for_field("map", MAP_SIZE_LIMIT);
+        if let Some(max_request_size) = options.limits.max_request_size {
+            size_limit = size_limit.whole_stream(max_request_size.as_u64());
+        }
         let multer = Multipart::with_constraints(
             request_body,
             boundary,
-            Constraints::new().size_limit(SizeLimit::new().for_field("map", MAP_SIZE_LIMIT)),
+            Constraints::new().size_limit(size_limit),
         );
         Self {
             state: Arc::new(Mutex::new(MultipartRequestState {
                 multer,
-                limits,
+                limits: options.limits,
                 read_files_counter: 0,
                 file_sizes: Vec::new(),
                 max_files_exceeded: false,
                 max_files_size_exceeded: false,
+                buffered_files: None,
+                file_rules: HashMap::new(),
             })),
+            options,
         }
     }
 
+    pub(super) fn is_buffered(&self) -> bool {
+        self.options.buffer_directory.is_some()
+    }
+
     pub(super) async fn operations_field(&mut self) -> UploadResult<multer::Field<'static>> {
         self.state
             .lock()
@@ -119,9 +159,117 @@ impl MultipartRequest {
             state.max_files_exceeded = true;
             return Err(FileUploadError::MaxFilesLimitExceeded(limit));
         }
+        if !self.options.rules.is_empty() {
+            state.file_rules = map_field
+                .iter()
+                .map(|(file, paths)| (file.clone(), FileRules::new(&self.options.rules, paths)))
+                .collect();
+        }
         MapField::new(map_field)
     }
 
+    /// Reads all the files of the request, and writes them to disk.
+    ///
+    /// This must be called after [`Self::map_field`], in the `buffered` mode only.
+    pub(super) async fn buffer_files(&mut self, file_names: &IndexSet<String>) -> UploadResult<()> {
+        let parent = self
+            .options
+            .buffer_directory
+            .as_ref()
+            .expect("files are only buffered in the buffered mode");
+        let mut state = self.state.lock().await;
+        let mut directory =
+            BufferDirectory::create(parent, self.options.buffer_usage.clone()).await?;
+        let mut files = HashMap::with_capacity(file_names.len());
+
+        while let Some(mut field) = state.multer.next_field().await? {
+            let limit = state.limits.max_files;
+            if state.read_files_counter == limit {
+                state.max_files_exceeded = true;
+                return Err(FileUploadError::MaxFilesLimitExceeded(limit));
+            }
+            state.read_files_counter += 1;
+
+            // Extraneous files are ignored, the same way as in the `stream` mode
+            let name = match field.name() {
+                Some(name) if file_names.contains(name) && !files.contains_key(name) => {
+                    name.to_owned()
+                }
+                _ => continue,
+            };
+            let file_name = field.file_name().map(str::to_owned);
+            let filename = format!("'{}'", file_name.as_deref().unwrap_or(&name));
+            let rules = state.file_rules.remove(&name).unwrap_or_default();
+            rules.check_filename(file_name.as_deref(), &name)?;
+            let mut head = rules.checks_content().then(Vec::new);
+
+            // File names come from the client, so they are not used for paths
+            let path = directory.path().join(files.len().to_string());
+            let mut file = tokio::fs::File::create(&path)
+                .await
+                .map_err(FileUploadError::BufferingFailed)?;
+            let limit = state.limits.max_file_size;
+            let mut file_size = 0;
+            while let Some(bytes) = field.chunk().await? {
+                file_size += bytes.len();
+                if file_size > (limit.as_u64() as usize) {
+                    state.max_files_size_exceeded = true;
+                    return Err(FileUploadError::MaxFileSizeLimitExceeded { limit, filename });
+                }
+                if let Some(file_head) = &mut head {
+                    let missing = SNIFF_LENGTH - file_head.len();
+                    file_head.extend_from_slice(&bytes[..missing.min(bytes.len())]);
+                    if file_head.len() == SNIFF_LENGTH {
+                        rules.check_content(file_head, &filename)?;
+                        head = None;
+                    }
+                }
+                directory.reserve(bytes.len())?;
+                file.write_all(&bytes)
+                    .await
+                    .map_err(FileUploadError::BufferingFailed)?;
+            }
+            file.flush()
+                .await
+                .map_err(FileUploadError::BufferingFailed)?;
+            state.file_sizes.push(file_size);
+            // The type of a file shorter than the sniffed length is checked at its end
+            if let Some(file_head) = head {
+                rules.check_content(&file_head, &filename)?;
+            }
+
+            let headers = field.headers().clone();
+            let file = BufferedFile { path, headers };
+            if let Some(scanner) = &self.options.scanner {
+                scanner
+                    .scan(&file, file_name.as_deref(), &filename, file_size)
+                    .await?;
+            }
+            files.insert(name, file);
+        }
+
+        let missing_files: Vec<_> = file_names
+            .iter()
+            .filter(|name| !files.contains_key(name.as_str()))
+            .collect();
+        if !missing_files.is_empty() {
+            return Err(FileUploadError::MissingFiles(
+                missing_files
+                    .into_iter()
+                    .map(|file| format!("'{}'", file))
+                    .join(", "),
+            ));
+        }
+
+        state.buffered_files = Some(Arc::new(BufferedFiles::new(directory, files)));
+        Ok(())
+    }
+
+    /// The files written to disk, in the `buffered` mode
+    pub(super) async fn buffered_files(&self) -> Option<Arc<BufferedFiles>> {
+        self.state.lock().await.buffered_files.clone()
+    }
+
     pub(super) async fn subgraph_stream<FilePrefixFn>(
         &mut self,
         file_names: HashSet<String>,
@@ -143,6 +291,9 @@ pin_project! {
         #[pin]
         current_field: Option<multer::Field<'static>>,
         current_field_bytes: usize,
+        current_field_rules: FileRules,
+        // The first bytes of the current field, held until its type is checked
+        current_field_head: Option<BytesMut>,
     }
 }
 
@@ -161,6 +312,8 @@ where
             file_prefix_fn,
             current_field: None,
             current_field_bytes: 0,
+            current_field_rules: FileRules::default(),
+            current_field_head: None,
         }
     }
 
@@ -175,31 +328,54 @@ where
                 .map(|name| format!("'{}'", name))
                 .unwrap_or_else(|| "unknown".to_owned());
 
-            let field = Pin::new(field);
-            match field.poll_next(cx) {
-                Poll::Pending => Poll::Pending,
-                Poll::Ready(None) => {
-                    self.current_field = None;
-                    let file_size = self.current_field_bytes;
-                    self.state.file_sizes.push(file_size);
-                    Poll::Ready(None)
-                }
-                Poll::Ready(Some(Ok(bytes))) => {
-                    self.current_field_bytes += bytes.len();
-                    let limit = self.state.limits.max_file_size;
-                    if self.current_field_bytes > (limit.as_u64() as usize) {
+            let mut field = Pin::new(field);
+            loop {
+                match field.as_mut().poll_next(cx) {
+                    Poll::Pending => return Poll::Pending,
+                    Poll::Ready(None) => {
                         self.current_field = None;
-                        self.state.max_files_size_exceeded = true;
-                        Poll::Ready(Some(Err(FileUploadError::MaxFileSizeLimitExceeded {
-                            limit,
-                            filename,
-                        })))
-                    } else {
-                        Poll::Ready(Some(Ok(bytes)))
+                        let file_size = self.current_field_bytes;
+                        self.state.file_sizes.push(file_size);
+                        // The type of a file shorter than the sniffed length is checked at its end
+                        if let Some(head) = self.current_field_head.take() {
+                            if let Err(err) =
+                                self.current_field_rules.check_content(&head, &filename)
+                            {
+                                return Poll::Ready(Some(Err(err)));
+                            }
+                            if !head.is_empty() {
+                                return Poll::Ready(Some(Ok(head.freeze())));
+                            }
+                        }
+                        return Poll::Ready(None);
                     }
-                }
-                Poll::Ready(Some(Err(e))) => {
-                    Poll::Ready(Some(Err(FileUploadError::InvalidMultipartRequest(e))))
+                    Poll::Ready(Some(Ok(bytes))) => {
+                        self.current_field_bytes += bytes.len();
+                        let limit = self.state.limits.max_file_size;
+                        if self.current_field_bytes > (limit.as_u64() as usize) {
+                            self.current_field = None;
+                            self.state.max_files_size_exceeded = true;
+                            return Poll::Ready(Some(Err(
+                                FileUploadError::MaxFileSizeLimitExceeded { limit, filename },
+                            )));
+                        }
+
+                        let Some(head) = &mut self.current_field_head else {
+                            return Poll::Ready(Some(Ok(bytes)));
+                        };
+                        head.extend_from_slice(&bytes);
+                        if head.len() < SNIFF_LENGTH {
+                            continue;
+                        }
+                        let head = mem::take(head).freeze();
+                        self.current_field_head = None;
+                        if let Err(err) = self.current_field_rules.check_content(&head, &filename) {
+                            self.current_field = None;
+                            return Poll::Ready(Some(Err(err)));
+                        }
+                        return Poll::Ready(Some(Ok(head)));
+                    }
+                    Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e.into()))),
                 }
             }
         } else {
@@ -242,7 +418,14 @@ where
 
                         if let Some(name) = field.name() {
                             if self.file_names.remove(name) {
+                                let rules = self.state.file_rules.remove(name).unwrap_or_default();
+                                if let Err(err) = rules.check_filename(field.file_name(), name) {
+                                    return Poll::Ready(Some(Err(err)));
+                                }
                                 let prefix = (self.file_prefix_fn)(field.headers());
+                                self.current_field_head =
+                                    rules.checks_content().then(BytesMut::new);
+                                self.current_field_rules = rules;
                                 self.current_field = Some(field);
                                 return Poll::Ready(Some(Ok(prefix)));
                             }
@@ -273,3 +456,353 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::time::Duration;
+
+    use bytesize::ByteSize;
+    use futures::stream;
+    use futures::StreamExt;
+
+    use super::*;
+    use crate::graphql;
+
+    const BOUNDARY: &str = "boundary";
+    // The prefix sent before each file in the `stream` mode
+    const PREFIX: &[u8] = b"[file]";
+
+    fn options(
+        buffer_directory: Option<&Path>,
+        limits: MultipartRequestLimits,
+    ) -> MultipartOptions {
+        MultipartOptions {
+            limits,
+            buffer_directory: buffer_directory.map(Path::to_path_buf),
+            buffer_usage: Arc::new(BufferUsage::new(None)),
+            rules: Vec::new(),
+            scanner: None,
+        }
+    }
+
+    /// A multipart request with a file for each of the `file0`, `file1`… variables
+    fn multipart_body(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let map: serde_json::Map<String, serde_json::Value> = (0..files.len())
+            .map(|index| {
+                (
+                    index.to_string(),
+                    serde_json::json!([format!("variables.file{index}")]),
+                )
+            })
+            .collect();
+        let mut body = format!(
+            "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"operations\"\r\n\r\n\
+             {{\"query\": \"{{ __typename }}\"}}\r\n\
+             --{BOUNDARY}\r\nContent-Disposition: form-data; name=\"map\"\r\n\r\n{}\r\n",
+            serde_json::Value::Object(map)
+        )
+        .into_bytes();
+        for (index, (file_name, content)) in files.iter().enumerate() {
+            body.extend_from_slice(
+                format!(
+                    "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"{index}\"; \
+                     filename=\"{file_name}\"\r\n\r\n"
+                )
+                .as_bytes(),
+            );
+            body.extend_from_slice(content);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(format!("--{BOUNDARY}--\r\n").as_bytes());
+        body
+    }
+
+    async fn buffer_request(
+        body: hyper::Body,
+        options: Arc<MultipartOptions>,
+    ) -> (MultipartRequest, UploadResult<()>) {
+        let mut request = MultipartRequest::new(body, BOUNDARY.to_string(), options);
+        let result = async {
+            request.operations_field().await?.bytes().await?;
+            let map_field = request.map_field().await?;
+            request.buffer_files(&map_field.files_order).await
+        }
+        .await;
+        (request, result)
+    }
+
+    /// Streams the files of a request in the `stream` mode, received in chunks of `chunk_size`
+    /// bytes, until the first error
+    async fn stream_request(
+        body: &[u8],
+        chunk_size: usize,
+        rules: Vec<MultipartFileRule>,
+    ) -> (Vec<u8>, Option<FileUploadError>) {
+        let chunks: Vec<_> = body
+            .chunks(chunk_size)
+            .map(|chunk| Ok::<_, std::io::Error>(Bytes::copy_from_slice(chunk)))
+            .collect();
+        let options = Arc::new(MultipartOptions {
+            rules,
+            ..options(None, Default::default())
+        });
+        let mut request = MultipartRequest::new(
+            hyper::Body::wrap_stream(stream::iter(chunks)),
+            BOUNDARY.to_string(),
+            options,
+        );
+        request
+            .operations_field()
+            .await
+            .unwrap()
+            .bytes()
+            .await
+            .unwrap();
+        let map_field = request.map_field().await.unwrap();
+        let mut stream = request
+            .subgraph_stream(map_field.files_order.into_iter().collect(), |_| {
+                Bytes::from_static(PREFIX)
+            })
+            .await;
+
+        let mut content = Vec::new();
+        while let Some(bytes) = stream.next().await {
+            match bytes {
+                Ok(bytes) => content.extend_from_slice(&bytes),
+                Err(err) => return (content, Some(err)),
+            }
+        }
+        (content, None)
+    }
+
+    fn rule(path: &str, allowed_types: &[&str], filename: Option<&str>) -> MultipartFileRule {
+        MultipartFileRule {
+            path: path.to_string(),
+            allowed_types: Some(allowed_types.iter().map(|ty| ty.to_string()).collect()),
+            filename: filename.map(|pattern| regex::Regex::new(pattern).unwrap()),
+        }
+    }
+
+    fn entries(directory: &Path) -> usize {
+        std::fs::read_dir(directory).unwrap().count()
+    }
+
+    /// The files are removed in the background, so this waits for the directory to be empty
+    async fn assert_removed(directory: &Path) {
+        for _ in 0..100 {
+            if entries(directory) == 0 {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("the buffered files were not removed");
+    }
+
+    #[tokio::test]
+    async fn it_removes_buffered_files_once_they_are_dropped() {
+        let directory = tempfile::tempdir().unwrap();
+        let options = Arc::new(options(Some(directory.path()), Default::default()));
+        let body = multipart_body(&[("a.txt", b"Hello"), ("b.txt", b"world!")]);
+
+        let (request, result) = buffer_request(body.into(), options).await;
+        result.unwrap();
+        let buffered_files = request.buffered_files().await.unwrap();
+        let content = buffered_files
+            .clone()
+            .subgraph_stream(vec!["1".to_string(), "0".to_string()], |_| Bytes::new())
+            .map(|bytes| bytes.unwrap())
+            .collect::<Vec<_>>()
+            .await
+            .concat();
+        assert_eq!(content, b"world!Hello");
+
+        // The files are kept while a subgraph request may still read them
+        drop(request);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(entries(directory.path()), 1);
+
+        drop(buffered_files);
+        assert_removed(directory.path()).await;
+    }
+
+    #[tokio::test]
+    async fn it_removes_buffered_files_on_error() {
+        let directory = tempfile::tempdir().unwrap();
+        let limits = MultipartRequestLimits {
+            max_file_size: ByteSize::b(8),
+            ..Default::default()
+        };
+        let options = Arc::new(options(Some(directory.path()), limits));
+        let body = multipart_body(&[("a.txt", b"Hello"), ("b.txt", b"Hello, world!")]);
+
+        let (request, result) = buffer_request(body.into(), options).await;
+        assert!(matches!(
+            result,
+            Err(FileUploadError::MaxFileSizeLimitExceeded { .. })
+        ));
+        assert!(request.buffered_files().await.is_none());
+        assert_removed(directory.path()).await;
+    }
+
+    #[tokio::test]
+    async fn it_removes_buffered_files_on_client_disconnect() {
+        let directory = tempfile::tempdir().unwrap();
+        let options = Arc::new(options(Some(directory.path()), Default::default()));
+        let body = multipart_body(&[("a.txt", b"Hello, world!")]);
+        let (sent, _) = body.split_at(body.len() - 20);
+
+        // The client stops sending the request, which is then cancelled
+        let stream = stream::iter([Ok::<_, std::io::Error>(Bytes::copy_from_slice(sent))])
+            .chain(stream::pending());
+        let buffering = tokio::spawn(buffer_request(
+            hyper::Body::wrap_stream(stream),
+            options.clone(),
+        ));
+        for _ in 0..100 {
+            if entries(directory.path()) > 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(entries(directory.path()), 1);
+        buffering.abort();
+        assert_removed(directory.path()).await;
+
+        // The connection is closed while the request is read
+        let stream = stream::iter([
+            Ok(Bytes::copy_from_slice(sent)),
+            Err(std::io::Error::from(std::io::ErrorKind::ConnectionReset)),
+        ]);
+        let (_, result) = buffer_request(hyper::Body::wrap_stream(stream), options).await;
+        assert!(result.is_err());
+        assert_removed(directory.path()).await;
+    }
+
+    #[tokio::test]
+    async fn it_limits_the_size_of_buffered_files() {
+        let directory = tempfile::tempdir().unwrap();
+        let options = Arc::new(MultipartOptions {
+            buffer_usage: Arc::new(BufferUsage::new(Some(ByteSize::b(10)))),
+            ..options(Some(directory.path()), Default::default())
+        });
+        let body = multipart_body(&[("a.txt", b"Hello!")]);
+
+        let (first, result) = buffer_request(body.clone().into(), options.clone()).await;
+        result.unwrap();
+
+        // The files of both requests exceed the limit
+        let (_, result) = buffer_request(body.clone().into(), options.clone()).await;
+        let error = result.unwrap_err();
+        assert!(matches!(error, FileUploadError::MaxBufferSizeExceeded(_)));
+        assert_eq!(
+            graphql::Error::from(error).extensions["code"],
+            "FILE_UPLOADS_LIMITS_MAX_BUFFER_SIZE_EXCEEDED"
+        );
+
+        // The space is released once the files of the first request are removed
+        drop(first);
+        for _ in 0..100 {
+            match buffer_request(body.clone().into(), options.clone()).await {
+                (_, Err(FileUploadError::MaxBufferSizeExceeded(_))) => {
+                    tokio::time::sleep(Duration::from_millis(10)).await
+                }
+                (_, result) => return result.unwrap(),
+            }
+        }
+        panic!("the space of the removed files was not released");
+    }
+
+    #[tokio::test]
+    async fn it_limits_the_size_of_the_request() {
+        let directory = tempfile::tempdir().unwrap();
+        let body = multipart_body(&[("a.txt", b"Hello, world!")]);
+        let size = body.len() as u64;
+
+        let limits = MultipartRequestLimits {
+            max_request_size: Some(ByteSize::b(size - 1)),
+            ..Default::default()
+        };
+        let options = Arc::new(options(Some(directory.path()), limits));
+        let (_, result) = buffer_request(body.clone().into(), options).await;
+        let error = result.unwrap_err();
+        assert!(
+            matches!(error, FileUploadError::MaxRequestSizeLimitExceeded(limit) if limit == ByteSize::b(size - 1))
+        );
+        assert_eq!(
+            graphql::Error::from(error).extensions["code"],
+            "FILE_UPLOADS_LIMITS_MAX_REQUEST_SIZE_EXCEEDED"
+        );
+
+        let limits = MultipartRequestLimits {
+            max_request_size: Some(ByteSize::b(size)),
+            ..Default::default()
+        };
+        let options = Arc::new(options(Some(directory.path()), limits));
+        let (_, result) = buffer_request(body.into(), options).await;
+        result.unwrap();
+    }
+
+    #[test]
+    fn it_maps_multer_errors() {
+        assert!(matches!(
+            FileUploadError::from(multer::Error::StreamSizeExceeded { limit: 1024 }),
+            FileUploadError::MaxRequestSizeLimitExceeded(limit) if limit == ByteSize::b(1024)
+        ));
+        assert!(matches!(
+            FileUploadError::from(multer::Error::IncompleteStream),
+            FileUploadError::InvalidMultipartRequest(multer::Error::IncompleteStream)
+        ));
+    }
+
+    #[tokio::test]
+    async fn it_checks_file_rules_in_stream_mode() {
+        const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\0\0\x10\0\0\0\x10\x08\x06\0\0\0";
+        let rules = || {
+            vec![
+                rule("variables.file0", &["image/png"], Some(r"\.png$")),
+                rule("variables.file1", &["text/plain"], None),
+            ]
+        };
+
+        // The held first bytes of each file are sent once its type is checked, whether the file
+        // is longer or shorter than them
+        let body = multipart_body(&[("image.png", PNG), ("notes.txt", b"Hi")]);
+        for chunk_size in [1, 7, SNIFF_LENGTH, body.len()] {
+            let (content, error) = stream_request(&body, chunk_size, rules()).await;
+            assert!(error.is_none(), "unexpected error: {error:?}");
+            assert_eq!(content, [PREFIX, PNG, PREFIX, &b"Hi"[..]].concat());
+        }
+
+        let body = multipart_body(&[("image.gif", PNG), ("notes.txt", b"Hi")]);
+        let (content, error) = stream_request(&body, 7, rules()).await;
+        assert!(
+            matches!(error, Some(FileUploadError::InvalidFileName(name)) if name == "'image.gif'")
+        );
+        assert!(content.is_empty());
+
+        // No byte of a file with a type that isn't allowed is sent
+        let body = multipart_body(&[("image.png", b"Hello, world! This is not an image.")]);
+        let (content, error) = stream_request(&body, 7, rules()).await;
+        assert!(matches!(
+            error,
+            Some(FileUploadError::InvalidFileType {
+                content_type: "text/plain",
+                ..
+            })
+        ));
+        assert_eq!(content, PREFIX);
+
+        // The type of a file shorter than the sniffed length is checked at its end
+        let body = multipart_body(&[("image.png", PNG), ("notes.txt", b"\0\x01")]);
+        let (content, error) = stream_request(&body, 1, rules()).await;
+        assert!(matches!(
+            error,
+            Some(FileUploadError::InvalidFileType {
+                content_type: "application/octet-stream",
+                ..
+            })
+        ));
+        assert_eq!(content, [PREFIX, PNG, PREFIX].concat());
+    }
+}
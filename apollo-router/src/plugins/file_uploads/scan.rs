@@ -0,0 +1,239 @@
+use http::header::CONTENT_DISPOSITION;
+use http::header::CONTENT_LENGTH;
+use http::header::CONTENT_TYPE;
+use http::HeaderValue;
+use hyper::Body;
+use tokio_util::io::ReaderStream;
+use tower::BoxError;
+use tower::ServiceExt;
+
+use super::buffered_files::BufferedFile;
+use super::config::MultipartFileScan;
+use super::error::FileUploadError;
+use super::Result as UploadResult;
+use crate::services::external::new_http_client;
+use crate::services::external::HTTPClientService;
+
+/// Largest response body read to log why the scanning service rejected a file
+const MAX_REJECTION_REASON_SIZE: usize = 4096;
+
+/// Sends files to an external service, which accepts or rejects them
+#[derive(Clone, Debug)]
+pub(super) struct FileScanner {
+    url: String,
+    http_client: HTTPClientService,
+}
+
+impl FileScanner {
+    pub(super) fn new(config: &MultipartFileScan) -> Result<Self, BoxError> {
+        Ok(Self {
+            url: config.url.clone(),
+            http_client: new_http_client(config.timeout)?,
+        })
+    }
+
+    /// Streams a buffered file to the scanning service.
+    ///
+    /// The file is sent as the body of a `POST` request, with its content type and name in the
+    /// `Content-Type` and `Content-Disposition` headers.
+    pub(super) async fn scan(
+        &self,
+        file: &BufferedFile,
+        file_name: Option<&str>,
+        filename: &str,
+        file_size: usize,
+    ) -> UploadResult<()> {
+        let scan_failed = |reason: String| FileUploadError::ScanFailed {
+            filename: filename.to_string(),
+            reason,
+        };
+
+        let content = tokio::fs::File::open(&file.path)
+            .await
+            .map_err(FileUploadError::BufferingFailed)?;
+        let content_type = file
+            .headers
+            .get(CONTENT_TYPE)
+            .cloned()
+            .unwrap_or_else(|| HeaderValue::from_static("application/octet-stream"));
+        let content_disposition = match file_name {
+            Some(file_name) => HeaderValue::try_from(format!(
+                "attachment; filename=\"{}\"",
+                file_name.replace('"', "\\\"")
+            ))
+            .map_err(|err| scan_failed(err.to_string()))?,
+            None => HeaderValue::from_static("attachment"),
+        };
+        let request = http::Request::post(&self.url)
+            .header(CONTENT_TYPE, content_type)
+            .header(CONTENT_DISPOSITION, content_disposition)
+            .header(CONTENT_LENGTH, file_size)
+            .body(Body::wrap_stream(ReaderStream::new(content)))
+            .map_err(|err| scan_failed(err.to_string()))?;
+
+        let response = self
+            .http_client
+            .clone()
+            .oneshot(request)
+            .await
+            .map_err(|err| scan_failed(err.to_string()))?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        if status.is_client_error() {
+            u64_counter!(
+                "apollo.router.operations.file_uploads.rejected",
+                "files rejected by validation rules or scans",
+                1,
+                reason = "scan"
+            );
+            // the reason is only logged, it may describe the file to the client
+            let body = hyper::body::to_bytes(http_body::Limited::new(
+                response.into_body(),
+                MAX_REJECTION_REASON_SIZE,
+            ))
+            .await
+            .unwrap_or_default();
+            let reason = String::from_utf8_lossy(&body);
+            tracing::warn!(
+                filename,
+                status = %status,
+                reason = %reason,
+                "the scanning service rejected a file"
+            );
+            return Err(FileUploadError::FileRejected {
+                filename: filename.to_string(),
+            });
+        }
+        Err(scan_failed(format!(
+            "the scanning service responded with {status}"
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use http::HeaderMap;
+    use wiremock::matchers::body_bytes;
+    use wiremock::matchers::header;
+    use wiremock::matchers::method;
+    use wiremock::matchers::path;
+    use wiremock::Mock;
+    use wiremock::MockServer;
+    use wiremock::ResponseTemplate;
+
+    use super::*;
+    use crate::metrics::FutureMetricsExt;
+
+    const CONTENT: &[u8] = b"Hello, world!";
+
+    /// Scans a text file with a scanning service answering with the given response
+    async fn scan(response: ResponseTemplate, timeout: Duration) -> UploadResult<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/scan"))
+            .and(header("content-type", "text/plain"))
+            .and(header(
+                "content-disposition",
+                "attachment; filename=\"notes.txt\"",
+            ))
+            .and(header("content-length", CONTENT.len().to_string().as_str()))
+            .and(body_bytes(CONTENT))
+            .respond_with(response)
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let directory = tempfile::tempdir().unwrap();
+        let file_path = directory.path().join("0");
+        std::fs::write(&file_path, CONTENT).unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+        let file = BufferedFile {
+            path: file_path,
+            headers,
+        };
+
+        let scanner = FileScanner::new(&MultipartFileScan {
+            url: format!("{}/scan", server.uri()),
+            timeout,
+        })
+        .unwrap();
+        scanner
+            .scan(&file, Some("notes.txt"), "'notes.txt'", CONTENT.len())
+            .await
+    }
+
+    #[tokio::test]
+    async fn it_accepts_files_on_success() {
+        scan(ResponseTemplate::new(200), Duration::from_secs(10))
+            .await
+            .unwrap();
+        scan(ResponseTemplate::new(204), Duration::from_secs(10))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn it_rejects_files_on_client_errors() {
+        async {
+            let result = scan(
+                ResponseTemplate::new(422).set_body_string("malware detected"),
+                Duration::from_secs(10),
+            )
+            .await;
+            let Err(error @ FileUploadError::FileRejected { .. }) = result else {
+                panic!("expected the file to be rejected, got {result:?}");
+            };
+            // the reason given by the scanning service is not returned to the client
+            assert_eq!(
+                error.to_string(),
+                "The 'notes.txt' file was rejected by the scan"
+            );
+
+            let result = scan(ResponseTemplate::new(403), Duration::from_secs(10)).await;
+            assert!(
+                matches!(result, Err(FileUploadError::FileRejected { .. })),
+                "expected the file to be rejected, got {result:?}"
+            );
+
+            assert_counter!(
+                "apollo.router.operations.file_uploads.rejected",
+                2,
+                "reason" = "scan"
+            );
+        }
+        .with_metrics()
+        .await;
+    }
+
+    #[tokio::test]
+    async fn it_fails_on_server_errors_and_timeouts() {
+        let result = scan(
+            ResponseTemplate::new(503).set_body_string("unavailable"),
+            Duration::from_secs(10),
+        )
+        .await;
+        let Err(FileUploadError::ScanFailed { filename, reason }) = result else {
+            panic!("expected the scan to fail, got {result:?}");
+        };
+        assert_eq!(filename, "'notes.txt'");
+        assert_eq!(
+            reason,
+            "the scanning service responded with 503 Service Unavailable"
+        );
+
+        let result = scan(
+            ResponseTemplate::new(200).set_delay(Duration::from_secs(2)),
+            Duration::from_millis(100),
+        )
+        .await;
+        assert!(
+            matches!(result, Err(FileUploadError::ScanFailed { .. })),
+            "expected the scan to time out, got {result:?}"
+        );
+    }
+}
@@ -0,0 +1,236 @@
+use super::config::MultipartFileRule;
+use super::error::FileUploadError;
+use super::Result as UploadResult;
+
+/// The number of bytes at the start of a file used to detect its type
+pub(super) const SNIFF_LENGTH: usize = 16;
+
+const OCTET_STREAM: &str = "application/octet-stream";
+
+// Signatures of the file types detected from the first bytes of a file
+const SIGNATURES: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"II*\0", "image/tiff"),
+    (b"MM\0*", "image/tiff"),
+    (b"%PDF-", "application/pdf"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"\x1f\x8b", "application/gzip"),
+    (b"OggS", "audio/ogg"),
+    (b"ID3", "audio/mpeg"),
+    (b"\x1a\x45\xdf\xa3", "video/webm"),
+];
+
+/// Detects the type of a file from its first [`SNIFF_LENGTH`] bytes
+pub(super) fn detect_content_type(head: &[u8]) -> &'static str {
+    if let Some((_, content_type)) = SIGNATURES
+        .iter()
+        .find(|(signature, _)| head.starts_with(signature))
+    {
+        return content_type;
+    }
+    if head.len() >= 12 {
+        if &head[0..4] == b"RIFF" {
+            match &head[8..12] {
+                b"WEBP" => return "image/webp",
+                b"WAVE" => return "audio/wav",
+                b"AVI " => return "video/x-msvideo",
+                _ => {}
+            }
+        }
+        if &head[4..8] == b"ftyp" {
+            return "video/mp4";
+        }
+    }
+    if is_text(head) {
+        return "text/plain";
+    }
+    OCTET_STREAM
+}
+
+fn is_text(head: &[u8]) -> bool {
+    // The head may end in the middle of a multi-byte character
+    let valid = match std::str::from_utf8(head) {
+        Ok(text) => text,
+        Err(err) if err.error_len().is_none() => {
+            std::str::from_utf8(&head[..err.valid_up_to()]).expect("prefix is valid UTF-8")
+        }
+        Err(_) => return false,
+    };
+    !valid.is_empty()
+        && !valid
+            .chars()
+            .any(|c| c.is_control() && !matches!(c, '\t' | '\n' | '\r'))
+}
+
+/// The validation rules that apply to a file of the request
+#[derive(Debug, Default)]
+pub(super) struct FileRules {
+    rules: Vec<MultipartFileRule>,
+}
+
+impl FileRules {
+    /// Selects the rules matching one of the paths the file is mapped to
+    pub(super) fn new(rules: &[MultipartFileRule], paths: &[String]) -> Self {
+        Self {
+            rules: rules
+                .iter()
+                .filter(|rule| paths.iter().any(|path| path_matches(&rule.path, path)))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Whether the content of the file must be validated
+    pub(super) fn checks_content(&self) -> bool {
+        self.rules.iter().any(|rule| rule.allowed_types.is_some())
+    }
+
+    pub(super) fn check_filename(&self, filename: Option<&str>, name: &str) -> UploadResult<()> {
+        for pattern in self.rules.iter().filter_map(|rule| rule.filename.as_ref()) {
+            if !filename.is_some_and(|filename| pattern.is_match(filename)) {
+                u64_counter!(
+                    "apollo.router.operations.file_uploads.rejected",
+                    "files rejected by validation rules or scans",
+                    1,
+                    reason = "file_name"
+                );
+                return Err(FileUploadError::InvalidFileName(format!(
+                    "'{}'",
+                    filename.unwrap_or(name)
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the type of the file, detected from its first [`SNIFF_LENGTH`] bytes
+    pub(super) fn check_content(&self, head: &[u8], filename: &str) -> UploadResult<()> {
+        let content_type = detect_content_type(head);
+        for allowed_types in self
+            .rules
+            .iter()
+            .filter_map(|rule| rule.allowed_types.as_ref())
+        {
+            if !allowed_types
+                .iter()
+                .any(|allowed| type_matches(allowed, content_type))
+            {
+                u64_counter!(
+                    "apollo.router.operations.file_uploads.rejected",
+                    "files rejected by validation rules or scans",
+                    1,
+                    reason = "file_type"
+                );
+                return Err(FileUploadError::InvalidFileType {
+                    content_type,
+                    filename: filename.to_string(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Matches a path of the `map` field against the path of a rule, where `*` matches any segment
+fn path_matches(pattern: &str, path: &str) -> bool {
+    let mut pattern = pattern.split('.');
+    let mut path = path.split('.');
+    loop {
+        match (pattern.next(), path.next()) {
+            (None, None) => return true,
+            (Some(expected), Some(segment)) if expected == "*" || expected == segment => {}
+            _ => return false,
+        }
+    }
+}
+
+/// Matches a content type against an allowed type, such as `image/png`, `image/*` or `*/*`
+fn type_matches(allowed: &str, content_type: &str) -> bool {
+    match allowed.strip_suffix("/*") {
+        Some("*") => true,
+        Some(top_level) => content_type
+            .split_once('/')
+            .is_some_and(|(ty, _)| ty.eq_ignore_ascii_case(top_level)),
+        None => allowed.eq_ignore_ascii_case(content_type),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(
+        path: &str,
+        allowed_types: Option<&[&str]>,
+        filename: Option<&str>,
+    ) -> MultipartFileRule {
+        MultipartFileRule {
+            path: path.to_string(),
+            allowed_types: allowed_types
+                .map(|types| types.iter().map(|ty| ty.to_string()).collect()),
+            filename: filename.map(|pattern| regex::Regex::new(pattern).unwrap()),
+        }
+    }
+
+    #[test]
+    fn it_detects_content_types() {
+        assert_eq!(
+            detect_content_type(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"),
+            "image/png"
+        );
+        assert_eq!(
+            detect_content_type(b"\xff\xd8\xff\xe0\0\x10JFIF"),
+            "image/jpeg"
+        );
+        assert_eq!(detect_content_type(b"RIFF\0\0\0\0WEBPVP8 "), "image/webp");
+        assert_eq!(detect_content_type(b"%PDF-1.7\n"), "application/pdf");
+        assert_eq!(detect_content_type(b"Hello, world!"), "text/plain");
+        // A multi-byte character cut at the end of the head
+        assert_eq!(
+            detect_content_type(&"héllo wörld, é".as_bytes()[..SNIFF_LENGTH]),
+            "text/plain"
+        );
+        assert_eq!(
+            detect_content_type(b"\0\x01\x02\x03"),
+            "application/octet-stream"
+        );
+        assert_eq!(detect_content_type(b""), "application/octet-stream");
+    }
+
+    #[test]
+    fn it_selects_rules_by_path() {
+        let rules = [
+            rule("variables.avatar", Some(&["image/*"]), None),
+            rule("variables.photos.*", Some(&["image/png"]), None),
+            rule("variables.documents", None, Some(r"\.pdf$")),
+        ];
+
+        let avatar = FileRules::new(&rules, &["variables.avatar".to_string()]);
+        assert!(avatar.checks_content());
+        assert!(avatar.check_content(b"GIF89a", "'avatar'").is_ok());
+        assert!(avatar.check_content(b"%PDF-1.7\n", "'avatar'").is_err());
+
+        let photo = FileRules::new(&rules, &["variables.photos.1".to_string()]);
+        assert!(photo.check_content(b"\x89PNG\r\n\x1a\n", "'photo'").is_ok());
+        assert!(photo.check_content(b"GIF89a", "'photo'").is_err());
+
+        let unchecked = FileRules::new(&rules, &["variables.photos".to_string()]);
+        assert!(!unchecked.checks_content());
+        assert!(unchecked.check_filename(None, "0").is_ok());
+    }
+
+    #[test]
+    fn it_checks_filenames() {
+        let rules = [rule("variables.document", None, Some(r"^[\w-]+\.pdf$"))];
+        let document = FileRules::new(&rules, &["variables.document".to_string()]);
+        assert!(!document.checks_content());
+        assert!(document
+            .check_filename(Some("report-2024.pdf"), "0")
+            .is_ok());
+        assert!(document.check_filename(Some("../report.pdf"), "0").is_err());
+        assert!(document.check_filename(None, "0").is_err());
+    }
+}
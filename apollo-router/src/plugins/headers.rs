@@ -8,6 +8,7 @@ use access_json::JSONQuery;
 use http::header::HeaderName;
 use http::header::ACCEPT;
 use http::header::ACCEPT_ENCODING;
+use http::header::CACHE_CONTROL;
 use http::header::CONNECTION;
 use http::header::CONTENT_ENCODING;
 use http::header::CONTENT_LENGTH;
@@ -19,10 +20,12 @@ use http::header::TE;
 use http::header::TRAILER;
 use http::header::TRANSFER_ENCODING;
 use http::header::UPGRADE;
+use http::HeaderMap;
 use http::HeaderValue;
 use regex::Regex;
 use schemars::JsonSchema;
 use serde::Deserialize;
+use serde::Deserializer;
 use serde_json::Value;
 use tower::BoxError;
 use tower::Layer;
@@ -31,15 +34,19 @@ use tower::ServiceExt;
 use tower_service::Service;
 
 use crate::plugin::serde::deserialize_header_name;
-use crate::plugin::serde::deserialize_header_value;
 use crate::plugin::serde::deserialize_json_query;
 use crate::plugin::serde::deserialize_option_header_name;
 use crate::plugin::serde::deserialize_option_header_value;
 use crate::plugin::serde::deserialize_regex;
 use crate::plugin::Plugin;
 use crate::plugin::PluginInit;
+use crate::plugins::cache::cache_control::CacheControl;
+use crate::plugins::telemetry::config_new::conditions::Condition;
+use crate::plugins::telemetry::config_new::selectors::SubgraphSelector;
+use crate::plugins::telemetry::config_new::Selector;
 use crate::register_plugin;
 use crate::services::subgraph;
+use crate::services::supergraph;
 use crate::services::SubgraphRequest;
 
 register_plugin!("apollo", "headers", Headers);
@@ -48,9 +55,11 @@ register_plugin!("apollo", "headers", Headers);
 #[serde(rename_all = "snake_case", deny_unknown_fields)]
 struct HeadersLocation {
     /// Propagate/Insert/Remove headers from request
+    #[serde(default)]
     request: Vec<Operation>,
-    // Propagate/Insert/Remove headers from response
-    // response: Option<Operation>
+    /// Propagate/Insert headers from subgraph responses to the client response
+    #[serde(default)]
+    response: Vec<ResponseOperation>,
 }
 
 #[derive(Clone, JsonSchema, Deserialize)]
@@ -69,18 +78,30 @@ schemar_fn!(
 );
 
 #[derive(Clone, JsonSchema, Deserialize)]
-#[serde(rename_all = "snake_case")]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+#[serde(untagged)]
 /// Remove header
 enum Remove {
-    #[schemars(schema_with = "remove_named")]
-    #[serde(deserialize_with = "deserialize_header_name")]
     /// Remove a header given a header name
-    Named(HeaderName),
+    Named {
+        #[schemars(schema_with = "remove_named")]
+        #[serde(deserialize_with = "deserialize_header_name")]
+        named: HeaderName,
 
-    #[schemars(schema_with = "remove_matching")]
-    #[serde(deserialize_with = "deserialize_regex")]
+        /// Apply the rule only when the condition is true
+        #[serde(default)]
+        condition: Option<Condition<SubgraphSelector>>,
+    },
     /// Remove a header given a regex matching header name
-    Matching(Regex),
+    Matching {
+        #[schemars(schema_with = "remove_matching")]
+        #[serde(deserialize_with = "deserialize_regex")]
+        matching: Regex,
+
+        /// Apply the rule only when the condition is true
+        #[serde(default)]
+        condition: Option<Condition<SubgraphSelector>>,
+    },
 }
 
 #[derive(Clone, JsonSchema, Deserialize)]
@@ -105,10 +126,13 @@ struct InsertStatic {
     #[serde(deserialize_with = "deserialize_header_name")]
     name: HeaderName,
 
-    /// The value for the header
+    /// The value for the header, a template like `{{ request_header.x-tenant }}-{{ operation_name }}` can combine selectors
     #[schemars(with = "String")]
-    #[serde(deserialize_with = "deserialize_header_value")]
-    value: HeaderValue,
+    value: HeaderTemplate,
+
+    /// Apply the rule only when the condition is true
+    #[serde(default)]
+    condition: Option<Condition<SubgraphSelector>>,
 }
 
 #[derive(Clone, JsonSchema, Deserialize)]
@@ -121,6 +145,10 @@ struct InsertFromContext {
     name: HeaderName,
     /// Specify context key to fetch value
     from_context: String,
+
+    /// Apply the rule only when the condition is true
+    #[serde(default)]
+    condition: Option<Condition<SubgraphSelector>>,
 }
 
 #[derive(Clone, JsonSchema, Deserialize)]
@@ -141,6 +169,164 @@ struct InsertFromBody {
     #[schemars(with = "Option<String>", default)]
     #[serde(deserialize_with = "deserialize_option_header_value")]
     default: Option<HeaderValue>,
+
+    /// Apply the rule only when the condition is true
+    #[serde(default)]
+    condition: Option<Condition<SubgraphSelector>>,
+}
+
+/// A header value combining static text and selectors
+///
+/// Selectors are written between double braces, like
+/// `{{ request_header.x-tenant }}-{{ operation_name }}`, using the name of a subgraph selector
+/// followed by its argument.
+#[derive(Clone, Debug)]
+struct HeaderTemplate {
+    parts: Vec<TemplatePart>,
+    /// The value of a template without selectors
+    static_value: Option<HeaderValue>,
+}
+
+#[derive(Clone, Debug)]
+enum TemplatePart {
+    Text(String),
+    Selector(Box<SubgraphSelector>),
+}
+
+impl HeaderTemplate {
+    fn parse(template: &str) -> Result<Self, String> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find("{{") {
+            if start > 0 {
+                parts.push(TemplatePart::Text(rest[..start].to_string()));
+            }
+            let end = rest[start..]
+                .find("}}")
+                .ok_or_else(|| format!("unclosed selector in header template '{template}'"))?;
+            let expression = rest[start + 2..start + end].trim();
+            parts.push(TemplatePart::Selector(Box::new(template_selector(
+                expression,
+            )?)));
+            rest = &rest[start + end + 2..];
+        }
+        if !rest.is_empty() {
+            parts.push(TemplatePart::Text(rest.to_string()));
+        }
+
+        for part in &parts {
+            if let TemplatePart::Text(text) = part {
+                HeaderValue::from_str(text)
+                    .map_err(|_| format!("invalid header value in header template '{template}'"))?;
+            }
+        }
+        let static_value = match parts.as_slice() {
+            [] => Some(HeaderValue::from_static("")),
+            [TemplatePart::Text(text)] => HeaderValue::from_str(text).ok(),
+            _ => None,
+        };
+
+        Ok(Self {
+            parts,
+            static_value,
+        })
+    }
+
+    /// Renders the template, or returns `None` if a selector has no value
+    fn render(
+        &self,
+        name: &HeaderName,
+        select: impl Fn(&SubgraphSelector) -> Option<opentelemetry::Value>,
+    ) -> Option<HeaderValue> {
+        if let Some(value) = &self.static_value {
+            return Some(value.clone());
+        }
+
+        let mut value = String::new();
+        for part in &self.parts {
+            match part {
+                TemplatePart::Text(text) => value.push_str(text),
+                TemplatePart::Selector(selector) => value.push_str(&select(selector)?.as_str()),
+            }
+        }
+        match HeaderValue::from_str(&value) {
+            Ok(value) => Some(value),
+            Err(err) => {
+                tracing::error!(
+                    "cannot convert the template into a header value for header name '{}': {:?}",
+                    name,
+                    err
+                );
+                None
+            }
+        }
+    }
+}
+
+impl From<HeaderValue> for HeaderTemplate {
+    fn from(value: HeaderValue) -> Self {
+        Self {
+            parts: Vec::new(),
+            static_value: Some(value),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for HeaderTemplate {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let template = String::deserialize(deserializer)?;
+        HeaderTemplate::parse(&template).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Parses a template expression like `request_header.x-tenant` into a selector
+fn template_selector(expression: &str) -> Result<SubgraphSelector, String> {
+    let (name, argument) = match expression.split_once('.') {
+        Some((name, argument)) => (name.trim(), Some(argument.trim())),
+        None => (expression, None),
+    };
+    let selector = match (name, argument) {
+        ("request_header", Some(header)) => {
+            serde_json::json!({ "supergraph_request_header": header })
+        }
+        ("query_variable", Some(variable)) => {
+            serde_json::json!({ "supergraph_query_variable": variable })
+        }
+        ("context", Some(key)) => serde_json::json!({ "request_context": key }),
+        ("operation_name", None) => serde_json::json!({ "supergraph_operation_name": "string" }),
+        ("operation_kind", None) => serde_json::json!({ "supergraph_operation_kind": "string" }),
+        (
+            name @ ("subgraph_operation_name"
+            | "supergraph_operation_name"
+            | "subgraph_operation_kind"
+            | "supergraph_operation_kind"),
+            None,
+        ) => serde_json::json!({ name: "string" }),
+        ("subgraph_response_status", None) => {
+            serde_json::json!({ "subgraph_response_status": "code" })
+        }
+        (name, Some(argument)) => serde_json::json!({ name: argument }),
+        (name, None) => return Err(format!("unknown selector '{name}' in header template")),
+    };
+    serde_json::from_value(selector)
+        .map_err(|_| format!("unknown selector '{expression}' in header template"))
+}
+
+impl Operation {
+    fn condition(&self) -> Option<&Condition<SubgraphSelector>> {
+        match self {
+            Operation::Insert(Insert::Static(InsertStatic { condition, .. }))
+            | Operation::Insert(Insert::FromContext(InsertFromContext { condition, .. }))
+            | Operation::Insert(Insert::FromBody(InsertFromBody { condition, .. }))
+            | Operation::Remove(Remove::Named { condition, .. })
+            | Operation::Remove(Remove::Matching { condition, .. })
+            | Operation::Propagate(Propagate::Named { condition, .. })
+            | Operation::Propagate(Propagate::Matching { condition, .. }) => condition.as_ref(),
+        }
+    }
 }
 
 schemar_fn!(
@@ -170,6 +356,71 @@ enum Propagate {
         #[schemars(with = "Option<String>", default)]
         #[serde(deserialize_with = "deserialize_option_header_value", default)]
         default: Option<HeaderValue>,
+
+        /// Apply the rule only when the condition is true
+        #[serde(default)]
+        condition: Option<Condition<SubgraphSelector>>,
+    },
+    /// Propagate header given a regex to match header name
+    Matching {
+        /// The regex on header name
+        #[schemars(schema_with = "propagate_matching")]
+        #[serde(deserialize_with = "deserialize_regex")]
+        matching: Regex,
+
+        /// Apply the rule only when the condition is true
+        #[serde(default)]
+        condition: Option<Condition<SubgraphSelector>>,
+    },
+}
+
+#[derive(Clone, JsonSchema, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum ResponseOperation {
+    Insert(ResponseInsert),
+    Propagate(ResponsePropagate),
+}
+
+#[derive(Clone, JsonSchema, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+#[serde(untagged)]
+/// Insert header in the client response
+enum ResponseInsert {
+    /// Insert static header
+    Static(InsertStatic),
+    /// Insert header with a value coming from context key (works only for a string in the context)
+    FromContext(InsertFromContext),
+}
+
+#[derive(Clone, JsonSchema, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+#[serde(untagged)]
+/// Propagate header from subgraph responses to the client response
+enum ResponsePropagate {
+    /// Propagate header given a header name
+    Named {
+        /// The source header name
+        #[schemars(with = "String")]
+        #[serde(deserialize_with = "deserialize_header_name")]
+        named: HeaderName,
+
+        /// An optional target header name
+        #[schemars(with = "Option<String>", default)]
+        #[serde(deserialize_with = "deserialize_option_header_name", default)]
+        rename: Option<HeaderName>,
+
+        /// Default value for the header.
+        #[schemars(with = "Option<String>", default)]
+        #[serde(deserialize_with = "deserialize_option_header_value", default)]
+        default: Option<HeaderValue>,
+
+        /// How to merge the header when several subgraphs return it
+        #[serde(default)]
+        merge: MergeStrategy,
+
+        /// Apply the rule only when the condition is true
+        #[serde(default)]
+        condition: Option<Condition<SubgraphSelector>>,
     },
     /// Propagate header given a regex to match header name
     Matching {
@@ -177,9 +428,109 @@ enum Propagate {
         #[schemars(schema_with = "propagate_matching")]
         #[serde(deserialize_with = "deserialize_regex")]
         matching: Regex,
+
+        /// How to merge the header when several subgraphs return it
+        #[serde(default)]
+        merge: MergeStrategy,
+
+        /// Apply the rule only when the condition is true
+        #[serde(default)]
+        condition: Option<Condition<SubgraphSelector>>,
     },
 }
 
+/// How to merge a header returned by several subgraphs
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, JsonSchema, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum MergeStrategy {
+    /// Keep the values from the first subgraph response
+    First,
+    /// Keep the values from the last subgraph response
+    #[default]
+    Last,
+    /// Keep the values from all subgraph responses
+    Append,
+    /// Keep the most restrictive value: Cache-Control directives are combined, the smallest
+    /// number is kept for numeric headers like rate limits
+    MostRestrictive,
+}
+
+impl ResponseOperation {
+    fn condition(&self) -> Option<&Condition<SubgraphSelector>> {
+        match self {
+            ResponseOperation::Insert(ResponseInsert::Static(InsertStatic {
+                condition, ..
+            }))
+            | ResponseOperation::Insert(ResponseInsert::FromContext(InsertFromContext {
+                condition,
+                ..
+            }))
+            | ResponseOperation::Propagate(ResponsePropagate::Named { condition, .. })
+            | ResponseOperation::Propagate(ResponsePropagate::Matching { condition, .. }) => {
+                condition.as_ref()
+            }
+        }
+    }
+}
+
+/// Headers collected from subgraph responses, applied to the client response
+#[derive(Default)]
+struct PropagatedResponseHeaders(HashMap<HeaderName, Vec<HeaderValue>>);
+
+/// Marks a request whose client response headers were already sent: subgraph responses received
+/// afterwards, for `@defer` fragments or subscription events, cannot change them
+struct ResponseHeadersSent;
+
+impl PropagatedResponseHeaders {
+    fn merge(&mut self, name: &HeaderName, values: Vec<HeaderValue>, strategy: MergeStrategy) {
+        match self.0.get_mut(name) {
+            None => {
+                self.0.insert(name.clone(), values);
+            }
+            Some(current) => match strategy {
+                MergeStrategy::First => {}
+                MergeStrategy::Last => *current = values,
+                MergeStrategy::Append => current.extend(values),
+                MergeStrategy::MostRestrictive => {
+                    *current = most_restrictive(name, current, values)
+                }
+            },
+        }
+    }
+}
+
+fn most_restrictive(
+    name: &HeaderName,
+    current: &[HeaderValue],
+    new: Vec<HeaderValue>,
+) -> Vec<HeaderValue> {
+    if name == CACHE_CONTROL {
+        let parse = |values: &[HeaderValue]| {
+            let mut headers = HeaderMap::new();
+            for value in values {
+                headers.append(CACHE_CONTROL, value.clone());
+            }
+            CacheControl::new(&headers, None)
+        };
+        if let (Ok(current), Ok(new)) = (parse(current), parse(&new)) {
+            let mut headers = HeaderMap::new();
+            if current.merge(&new).to_headers(&mut headers).is_ok() {
+                return headers.get_all(CACHE_CONTROL).iter().cloned().collect();
+            }
+        }
+        return current.to_vec();
+    }
+
+    let number = |values: &[HeaderValue]| match values {
+        [value] => value.to_str().ok()?.trim().parse::<f64>().ok(),
+        _ => None,
+    };
+    match (number(current), number(&new)) {
+        (Some(current), Some(number)) if number < current => new,
+        _ => current.to_vec(),
+    }
+}
+
 /// Configuration for header propagation
 #[derive(Clone, JsonSchema, Default, Deserialize)]
 #[serde(rename_all = "snake_case", deny_unknown_fields, default)]
@@ -193,6 +544,8 @@ struct Config {
 struct Headers {
     all_operations: Arc<Vec<Operation>>,
     subgraph_operations: HashMap<String, Arc<Vec<Operation>>>,
+    all_response_operations: Arc<Vec<ResponseOperation>>,
+    subgraph_response_operations: HashMap<String, Arc<Vec<ResponseOperation>>>,
 }
 
 #[async_trait::async_trait]
@@ -216,26 +569,174 @@ impl Plugin for Headers {
                 (subgraph_name.clone(), Arc::new(operations))
             })
             .collect();
+        let response_operations: Vec<ResponseOperation> = init
+            .config
+            .all
+            .as_ref()
+            .map(|a| a.response.clone())
+            .unwrap_or_default();
+        let subgraph_response_operations = init
+            .config
+            .subgraphs
+            .iter()
+            .map(|(subgraph_name, op)| {
+                let mut operations = response_operations.clone();
+                operations.append(&mut op.response.clone());
+                (subgraph_name.clone(), Arc::new(operations))
+            })
+            .collect();
 
         Ok(Headers {
             all_operations: Arc::new(operations),
             subgraph_operations,
+            all_response_operations: Arc::new(response_operations),
+            subgraph_response_operations,
         })
     }
 
+    fn supergraph_service(&self, service: supergraph::BoxService) -> supergraph::BoxService {
+        if self.all_response_operations.is_empty()
+            && self
+                .subgraph_response_operations
+                .values()
+                .all(|operations| operations.is_empty())
+        {
+            return service;
+        }
+
+        ServiceBuilder::new()
+            .map_response(|mut response: supergraph::Response| {
+                let propagated = {
+                    let mut extensions = response.context.extensions().lock();
+                    extensions.insert(ResponseHeadersSent);
+                    extensions.remove::<PropagatedResponseHeaders>()
+                };
+                if let Some(PropagatedResponseHeaders(propagated)) = propagated {
+                    let headers = response.response.headers_mut();
+                    for (name, values) in propagated {
+                        headers.remove(&name);
+                        for value in values {
+                            headers.append(&name, value);
+                        }
+                    }
+                }
+                response
+            })
+            .service(service)
+            .boxed()
+    }
+
     fn subgraph_service(&self, name: &str, service: subgraph::BoxService) -> subgraph::BoxService {
+        let response_operations = self
+            .subgraph_response_operations
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| self.all_response_operations.clone());
+        let layer = HeadersLayer::new(
+            self.subgraph_operations
+                .get(name)
+                .cloned()
+                .unwrap_or_else(|| self.all_operations.clone()),
+        );
+
+        if response_operations.is_empty() {
+            return ServiceBuilder::new().layer(layer).service(service).boxed();
+        }
+
+        let reserved_headers = layer.reserved_headers.clone();
         ServiceBuilder::new()
-            .layer(HeadersLayer::new(
-                self.subgraph_operations
-                    .get(name)
-                    .cloned()
-                    .unwrap_or_else(|| self.all_operations.clone()),
-            ))
+            .layer(layer)
+            .map_response(move |response: subgraph::Response| {
+                propagate_response_headers(&response_operations, &reserved_headers, &response);
+                response
+            })
             .service(service)
             .boxed()
     }
 }
 
+/// Collects the headers of a subgraph response that must be sent back to the client
+fn propagate_response_headers(
+    operations: &[ResponseOperation],
+    reserved_headers: &HashSet<&'static HeaderName>,
+    response: &subgraph::Response,
+) {
+    let headers = response.response.headers();
+    let mut extensions = response.context.extensions().lock();
+    if extensions.contains_key::<ResponseHeadersSent>() {
+        tracing::debug!(
+            "the client response headers were already sent, response header rules are not applied to this subgraph response"
+        );
+        return;
+    }
+    let propagated = extensions.get_or_default_mut::<PropagatedResponseHeaders>();
+
+    for operation in operations {
+        if let Some(condition) = operation.condition() {
+            if !condition.evaluate_response(response) {
+                continue;
+            }
+        }
+
+        match operation {
+            ResponseOperation::Insert(ResponseInsert::Static(static_insert)) => {
+                if let Some(value) = static_insert.value.render(&static_insert.name, |selector| {
+                    selector.on_response(response)
+                }) {
+                    propagated.0.insert(static_insert.name.clone(), vec![value]);
+                }
+            }
+            ResponseOperation::Insert(ResponseInsert::FromContext(insert_from_context)) => {
+                if let Some(val) = response
+                    .context
+                    .get::<_, String>(&insert_from_context.from_context)
+                    .ok()
+                    .flatten()
+                {
+                    match HeaderValue::from_str(&val) {
+                        Ok(header_value) => {
+                            propagated
+                                .0
+                                .insert(insert_from_context.name.clone(), vec![header_value]);
+                        }
+                        Err(err) => {
+                            tracing::error!("cannot convert from the context into a header value for header name '{}': {:?}", insert_from_context.name, err);
+                        }
+                    }
+                }
+            }
+            ResponseOperation::Propagate(ResponsePropagate::Named {
+                named,
+                rename,
+                default,
+                merge,
+                ..
+            }) => {
+                let values: Vec<HeaderValue> = headers.get_all(named).iter().cloned().collect();
+                let values = if values.is_empty() {
+                    match default {
+                        Some(default) => vec![default.clone()],
+                        None => continue,
+                    }
+                } else {
+                    values
+                };
+                propagated.merge(rename.as_ref().unwrap_or(named), values, *merge);
+            }
+            ResponseOperation::Propagate(ResponsePropagate::Matching {
+                matching, merge, ..
+            }) => {
+                for name in headers.keys().filter(|name| {
+                    !reserved_headers.contains(*name) && matching.is_match(name.as_str())
+                }) {
+                    let values = headers.get_all(name).iter().cloned().collect();
+                    propagated.merge(name, values, *merge);
+                }
+            }
+        }
+    }
+}
+
 struct HeadersLayer {
     operations: Arc<Vec<Operation>>,
     reserved_headers: Arc<HashSet<&'static HeaderName>>,
@@ -312,12 +813,23 @@ impl<S> HeadersService<S> {
         let mut already_propagated: HashSet<&str> = HashSet::new();
 
         for operation in &*self.operations {
+            if let Some(condition) = operation.condition() {
+                if condition.clone().evaluate_request(req) != Some(true) {
+                    continue;
+                }
+            }
+
             match operation {
                 Operation::Insert(insert_config) => match insert_config {
                     Insert::Static(static_insert) => {
-                        req.subgraph_request
-                            .headers_mut()
-                            .insert(&static_insert.name, static_insert.value.clone());
+                        if let Some(value) = static_insert
+                            .value
+                            .render(&static_insert.name, |selector| selector.on_request(req))
+                        {
+                            req.subgraph_request
+                                .headers_mut()
+                                .insert(&static_insert.name, value);
+                        }
                     }
                     Insert::FromContext(insert_from_context) => {
                         if let Some(val) = req
@@ -367,10 +879,10 @@ impl<S> HeadersService<S> {
                         }
                     }
                 },
-                Operation::Remove(Remove::Named(name)) => {
-                    req.subgraph_request.headers_mut().remove(name);
+                Operation::Remove(Remove::Named { named, .. }) => {
+                    req.subgraph_request.headers_mut().remove(named);
                 }
-                Operation::Remove(Remove::Matching(matching)) => {
+                Operation::Remove(Remove::Matching { matching, .. }) => {
                     let headers = req.subgraph_request.headers_mut();
                     let new_headers = headers
                         .drain()
@@ -389,6 +901,7 @@ impl<S> HeadersService<S> {
                     named,
                     rename,
                     default,
+                    ..
                 }) => {
                     if !already_propagated.contains(named.as_str()) {
                         let headers = req.subgraph_request.headers_mut();
@@ -405,7 +918,7 @@ impl<S> HeadersService<S> {
                         already_propagated.insert(named.as_str());
                     }
                 }
-                Operation::Propagate(Propagate::Matching { matching }) => {
+                Operation::Propagate(Propagate::Matching { matching, .. }) => {
                     let mut previous_name = None;
                     let headers = req.subgraph_request.headers_mut();
                     req.supergraph_request
@@ -451,8 +964,10 @@ mod test {
     use tower::BoxError;
 
     use super::*;
+    use crate::context::OPERATION_NAME;
     use crate::graphql::Request;
     use crate::plugin::test::MockSubgraphService;
+    use crate::plugin::test::MockSupergraphService;
     use crate::plugins::headers::Config;
     use crate::plugins::headers::HeadersLayer;
     use crate::query_planner::fetch::OperationKind;
@@ -586,7 +1101,8 @@ mod test {
         let mut service = HeadersLayer::new(Arc::new(vec![Operation::Insert(Insert::Static(
             InsertStatic {
                 name: "c".try_into()?,
-                value: "d".try_into()?,
+                value: HeaderValue::from_static("d").into(),
+                condition: None,
             },
         ))]))
         .layer(mock);
@@ -614,6 +1130,7 @@ mod test {
             Insert::FromContext(InsertFromContext {
                 name: "header_from_context".try_into()?,
                 from_context: "my_key".to_string(),
+                condition: None,
             }),
         )]))
         .layer(mock);
@@ -642,6 +1159,7 @@ mod test {
                 name: "header_from_request".try_into()?,
                 path: JSONQuery::parse(".operationName")?,
                 default: None,
+                condition: None,
             },
         ))]))
         .layer(mock);
@@ -658,9 +1176,10 @@ mod test {
             .withf(|request| request.assert_headers(vec![("ac", "vac"), ("ab", "vab")]))
             .returning(example_response);
 
-        let mut service = HeadersLayer::new(Arc::new(vec![Operation::Remove(Remove::Named(
-            "aa".try_into()?,
-        ))]))
+        let mut service = HeadersLayer::new(Arc::new(vec![Operation::Remove(Remove::Named {
+            named: "aa".try_into()?,
+            condition: None,
+        })]))
         .layer(mock);
 
         service.ready().await?.call(example_request()).await?;
@@ -675,9 +1194,10 @@ mod test {
             .withf(|request| request.assert_headers(vec![("ac", "vac")]))
             .returning(example_response);
 
-        let mut service = HeadersLayer::new(Arc::new(vec![Operation::Remove(Remove::Matching(
-            Regex::from_str("a[ab]")?,
-        ))]))
+        let mut service = HeadersLayer::new(Arc::new(vec![Operation::Remove(Remove::Matching {
+            matching: Regex::from_str("a[ab]")?,
+            condition: None,
+        })]))
         .layer(mock);
 
         service.ready().await?.call(example_request()).await?;
@@ -704,6 +1224,7 @@ mod test {
         let mut service =
             HeadersLayer::new(Arc::new(vec![Operation::Propagate(Propagate::Matching {
                 matching: Regex::from_str("d[ab]")?,
+                condition: None,
             })]))
             .layer(mock);
 
@@ -731,6 +1252,7 @@ mod test {
                 named: "da".try_into()?,
                 rename: None,
                 default: None,
+                condition: None,
             })]))
             .layer(mock);
 
@@ -758,6 +1280,7 @@ mod test {
                 named: "da".try_into()?,
                 rename: Some("ea".try_into()?),
                 default: None,
+                condition: None,
             })]))
             .layer(mock);
 
@@ -785,6 +1308,7 @@ mod test {
                 named: "ea".try_into()?,
                 rename: None,
                 default: Some("defaulted".try_into()?),
+                condition: None,
             })]))
             .layer(mock);
 
@@ -798,6 +1322,7 @@ mod test {
             inner: MockSubgraphService::new(),
             operations: Arc::new(vec![Operation::Propagate(Propagate::Matching {
                 matching: Regex::from_str(".*")?,
+                condition: None,
             })]),
             reserved_headers: Arc::new(RESERVED_HEADERS.iter().collect()),
         };
@@ -876,9 +1401,11 @@ mod test {
                     named: HeaderName::from_static("dc"),
                     rename: None,
                     default: None,
+                    condition: None,
                 }),
                 Operation::Propagate(Propagate::Matching {
                     matching: Regex::from_str("dc")?,
+                    condition: None,
                 }),
             ]),
             reserved_headers: Arc::new(RESERVED_HEADERS.iter().collect()),
@@ -928,6 +1455,273 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_header_template() {
+        let template = HeaderTemplate::parse("static").unwrap();
+        assert_eq!(
+            template.static_value,
+            Some(HeaderValue::from_static("static"))
+        );
+
+        let template =
+            HeaderTemplate::parse("{{ request_header.x-tenant }}-{{ operation_name }}").unwrap();
+        assert_eq!(template.parts.len(), 3);
+        assert!(template.static_value.is_none());
+
+        let template = HeaderTemplate::parse("{{subgraph_request_header.x-id}}").unwrap();
+        assert_eq!(template.parts.len(), 1);
+
+        assert!(HeaderTemplate::parse("{{ request_header.x-tenant").is_err());
+        assert!(HeaderTemplate::parse("{{ unknown }}").is_err());
+        assert!(HeaderTemplate::parse("{{ unknown.argument }}").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_conditional_and_templated_rules() -> Result<(), BoxError> {
+        let config = serde_yaml::from_str::<Config>(
+            r#"
+        all:
+            request:
+                - insert:
+                    name: "x-tenant"
+                    value: "{{ request_header.da }}-{{ operation_name }}"
+                - insert:
+                    name: "x-missing"
+                    value: "{{ request_header.missing }}"
+                - insert:
+                    name: "x-skipped"
+                    value: "skipped"
+                    condition:
+                        eq:
+                            - supergraph_request_header: da
+                            - "other"
+                - remove:
+                    named: "ab"
+                    condition:
+                        exists:
+                            supergraph_request_header: da
+        "#,
+        )?;
+        let headers = Headers::new(PluginInit::fake_new(config, Default::default())).await?;
+
+        let mut mock = MockSubgraphService::new();
+        mock.expect_call()
+            .times(1)
+            .withf(|request| {
+                request.assert_headers(vec![
+                    ("aa", "vaa"),
+                    ("ac", "vac"),
+                    ("x-tenant", "vda-my_operation_name"),
+                ])
+            })
+            .returning(example_response);
+
+        let request = example_request();
+        request
+            .context
+            .insert(OPERATION_NAME, "my_operation_name".to_string())?;
+        headers
+            .subgraph_service("test", mock.boxed())
+            .oneshot(request)
+            .await?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_response_config() {
+        serde_yaml::from_str::<Config>(
+            r#"
+        all:
+            response:
+                - propagate:
+                    named: "set-cookie"
+                    merge: append
+                - propagate:
+                    matching: "x-rate-limit-.*"
+                    merge: most_restrictive
+                - insert:
+                    name: "test"
+                    value: "test"
+        subgraphs:
+          products:
+            response:
+                - propagate:
+                    named: "cache-control"
+                    rename: "x-products-cache-control"
+        "#,
+        )
+        .unwrap();
+
+        assert!(serde_yaml::from_str::<Config>(
+            r#"
+        all:
+            response:
+                - propagate:
+                    named: "set-cookie"
+                    merge: "unknown"
+        "#,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_response_merge_strategies() {
+        let values = |values: &[&'static str]| {
+            values
+                .iter()
+                .copied()
+                .map(HeaderValue::from_static)
+                .collect::<Vec<_>>()
+        };
+
+        let name = HeaderName::from_static("x-rate-limit-remaining");
+        for (strategy, expected) in [
+            (MergeStrategy::First, vec!["10"]),
+            (MergeStrategy::Last, vec!["5"]),
+            (MergeStrategy::Append, vec!["10", "5"]),
+            (MergeStrategy::MostRestrictive, vec!["5"]),
+        ] {
+            let mut propagated = PropagatedResponseHeaders::default();
+            propagated.merge(&name, values(&["10"]), strategy);
+            propagated.merge(&name, values(&["5"]), strategy);
+            propagated.merge(
+                &name,
+                values(&["not a number"]),
+                MergeStrategy::MostRestrictive,
+            );
+            assert_eq!(propagated.0[&name], values(&expected));
+        }
+
+        let mut propagated = PropagatedResponseHeaders::default();
+        propagated.merge(
+            &CACHE_CONTROL,
+            values(&["public, max-age=60"]),
+            MergeStrategy::MostRestrictive,
+        );
+        propagated.merge(
+            &CACHE_CONTROL,
+            values(&["max-age=30", "no-cache"]),
+            MergeStrategy::MostRestrictive,
+        );
+        propagated.merge(
+            &CACHE_CONTROL,
+            values(&["private"]),
+            MergeStrategy::MostRestrictive,
+        );
+        assert_eq!(
+            propagated.0[&CACHE_CONTROL],
+            values(&["max-age=30,no-cache,private"])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_propagate_response_headers() -> Result<(), BoxError> {
+        let config = serde_yaml::from_str::<Config>(
+            r#"
+        all:
+            response:
+                - propagate:
+                    named: "set-cookie"
+                    merge: append
+                - propagate:
+                    matching: "x-rate-limit-.*"
+                    merge: most_restrictive
+        subgraphs:
+          products:
+            response:
+                - insert:
+                    name: "x-products"
+                    value: "true"
+        "#,
+        )?;
+        let headers = Headers::new(PluginInit::fake_new(config, Default::default())).await?;
+
+        let context = Context::new();
+        for (subgraph_name, cookie, remaining) in
+            [("accounts", "a=1", "10"), ("products", "b=2", "5")]
+        {
+            let mut mock = MockSubgraphService::new();
+            mock.expect_call().times(1).returning(move |request| {
+                Ok(SubgraphResponse::new_from_response(
+                    http::Response::builder()
+                        .header("set-cookie", cookie)
+                        .header("x-rate-limit-remaining", remaining)
+                        .header(CONTENT_TYPE, "application/json")
+                        .body(crate::graphql::Response::default())
+                        .expect("expecting valid response"),
+                    request.context,
+                ))
+            });
+            let mut request = example_request();
+            request.context = context.clone();
+            headers
+                .subgraph_service(subgraph_name, mock.boxed())
+                .oneshot(request)
+                .await?;
+        }
+
+        let mut mock = MockSupergraphService::new();
+        mock.expect_call().times(1).returning(|request| {
+            supergraph::Response::fake_builder()
+                .header(CONTENT_TYPE, "application/json")
+                .context(request.context)
+                .build()
+        });
+        let response = headers
+            .supergraph_service(mock.boxed())
+            .oneshot(
+                supergraph::Request::fake_builder()
+                    .context(context)
+                    .build()?,
+            )
+            .await?;
+
+        let actual_headers = response
+            .response
+            .headers()
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.to_str().unwrap()))
+            .collect::<HashSet<_>>();
+        assert_eq!(
+            actual_headers,
+            [
+                ("content-type", "application/json"),
+                ("set-cookie", "a=1"),
+                ("set-cookie", "b=2"),
+                ("x-rate-limit-remaining", "5"),
+                ("x-products", "true"),
+            ]
+            .into_iter()
+            .collect::<HashSet<_>>()
+        );
+
+        // a subgraph response for a deferred fragment arrives after the client headers were sent
+        let mut mock = MockSubgraphService::new();
+        mock.expect_call().times(1).returning(|request| {
+            Ok(SubgraphResponse::new_from_response(
+                http::Response::builder()
+                    .header("set-cookie", "c=3")
+                    .body(crate::graphql::Response::default())
+                    .expect("expecting valid response"),
+                request.context,
+            ))
+        });
+        let mut request = example_request();
+        request.context = response.context.clone();
+        headers
+            .subgraph_service("accounts", mock.boxed())
+            .oneshot(request)
+            .await?;
+        assert!(!response
+            .context
+            .extensions()
+            .lock()
+            .contains_key::<PropagatedResponseHeaders>());
+
+        Ok(())
+    }
+
     fn example_response(_: SubgraphRequest) -> Result<SubgraphResponse, BoxError> {
         Ok(SubgraphResponse::new_from_response(
             http::Response::default(),
@@ -13,7 +13,7 @@ use http::HeaderValue;
 use http::Method;
 use http::StatusCode;
 use multimap::MultiMap;
-use once_cell::sync::OnceCell;
+use parking_lot::RwLock;
 use schemars::JsonSchema;
 use serde::Deserialize;
 use serde::Serialize;
@@ -26,6 +26,8 @@ use tower::ServiceExt;
 use tracing_futures::Instrument;
 use uuid::Uuid;
 
+use crate::configuration::RedisCache;
+use crate::configuration::TlsClient;
 use crate::context::Context;
 use crate::graphql;
 use crate::graphql::Response;
@@ -33,6 +35,7 @@ use crate::json_ext::Object;
 use crate::layers::ServiceBuilderExt;
 use crate::notification::Notify;
 use crate::notification::NotifyError;
+use crate::notification::RedisNotify;
 use crate::plugin::Plugin;
 use crate::plugin::PluginInit;
 use crate::protocols::websocket::WebSocketProtocol;
@@ -40,17 +43,25 @@ use crate::query_planner::OperationKind;
 use crate::register_plugin;
 use crate::services::router;
 use crate::services::subgraph;
+use crate::services::supergraph;
 use crate::Endpoint;
 use crate::ListenAddr;
 
+mod resumption;
+mod throttling;
+
+pub(crate) use self::throttling::throttle;
+
 type HmacSha256 = Hmac<sha2::Sha256>;
 pub(crate) const APOLLO_SUBSCRIPTION_PLUGIN: &str = "apollo.subscription";
 pub(crate) const APOLLO_SUBSCRIPTION_PLUGIN_NAME: &str = "subscription";
-pub(crate) static SUBSCRIPTION_CALLBACK_HMAC_KEY: OnceCell<String> = OnceCell::new();
+pub(crate) static SUBSCRIPTION_CALLBACK_HMAC_KEY: RwLock<Option<String>> = RwLock::new(None);
 pub(crate) const SUBSCRIPTION_WS_CUSTOM_CONNECTION_PARAMS: &str =
     "apollo.subscription.custom_connection_params";
 const CALLBACK_SUBSCRIPTION_HEADER_NAME: &str = "subscription-protocol";
 const CALLBACK_SUBSCRIPTION_HEADER_VALUE: &str = "callback/1.0";
+/// Response extension containing the id of a subscription event, used to resume the subscription
+pub(crate) const SUBSCRIPTION_EVENT_ID_EXTENSION: &str = "subscriptionEventId";
 
 #[derive(Debug, Clone)]
 pub(crate) struct Subscription {
@@ -74,6 +85,30 @@ pub(crate) struct SubscriptionConfig {
     pub(crate) max_opened_subscriptions: Option<usize>,
     /// It represent the capacity of the in memory queue to know how many events we can keep in a buffer
     pub(crate) queue_capacity: Option<usize>,
+    /// Share subscriptions between router instances through Redis, so identical subgraph subscriptions are opened once per cluster instead of once per instance
+    pub(crate) redis: Option<SubscriptionRedis>,
+    /// Limit the events sent to the clients of some subscriptions. The first matching entry applies
+    pub(crate) throttling: Vec<SubscriptionThrottling>,
+    /// Let clients resume their subscriptions after a disconnection, without missing events
+    pub(crate) resumption: SubscriptionResumption,
+}
+
+impl SubscriptionConfig {
+    /// Returns the throttling configuration applying to a subscription
+    pub(crate) fn throttling(
+        &self,
+        operation_name: Option<&str>,
+        root_fields: &[&str],
+    ) -> Option<&SubscriptionThrottling> {
+        self.throttling.iter().find(|throttling| {
+            (throttling.operation_names.is_empty() && throttling.fields.is_empty())
+                || operation_name
+                    .is_some_and(|name| throttling.operation_names.iter().any(|n| n == name))
+                || root_fields
+                    .iter()
+                    .any(|field| throttling.fields.iter().any(|f| f == field))
+        })
+    }
 }
 
 impl Default for SubscriptionConfig {
@@ -84,6 +119,118 @@ impl Default for SubscriptionConfig {
             enable_deduplication: true,
             max_opened_subscriptions: None,
             queue_capacity: None,
+            redis: None,
+            throttling: Vec::new(),
+            resumption: Default::default(),
+        }
+    }
+}
+
+/// Throttling of the events sent to the clients of the subscriptions matching an operation name or a root field
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct SubscriptionThrottling {
+    /// Names of the subscription operations it applies to
+    #[serde(default)]
+    pub(crate) operation_names: Vec<String>,
+    /// Root subscription fields it applies to. If there are neither operation names nor fields, it applies to all subscriptions
+    #[serde(default)]
+    pub(crate) fields: Vec<String>,
+    /// Maximum number of events sent to a client in each interval. By default the number of events is not limited
+    #[serde(default)]
+    pub(crate) max_events: Option<usize>,
+    /// Interval used by `max_events` (default: 1s)
+    #[serde(
+        deserialize_with = "humantime_serde::deserialize",
+        default = "default_throttling_interval"
+    )]
+    #[schemars(with = "String", default = "default_throttling_interval")]
+    pub(crate) interval: Duration,
+    /// What to do with events that cannot be sent right away, because `max_events` is reached or the client queue (of size `queue_capacity`) is full (default: coalesce)
+    #[serde(default)]
+    pub(crate) overflow: SubscriptionOverflow,
+}
+
+fn default_throttling_interval() -> Duration {
+    Duration::from_secs(1)
+}
+
+/// What to do with an event that cannot be sent to the client right away
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum SubscriptionOverflow {
+    /// Keep only the latest event, and send it as soon as possible
+    #[default]
+    Coalesce,
+    /// Drop the event
+    Drop,
+}
+
+/// Resumption of subscriptions after a client reconnects
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields, default)]
+pub(crate) struct SubscriptionResumption {
+    /// Enable the resumption of subscriptions (default: false)
+    pub(crate) enabled: bool,
+    /// Number of events kept per subscription, to be replayed to a reconnecting client (default: 100)
+    pub(crate) buffer_size: usize,
+    /// How long a subscription is kept after its client disconnected (default: 30s)
+    #[serde(deserialize_with = "humantime_serde::deserialize")]
+    #[schemars(with = "String")]
+    pub(crate) grace_period: Duration,
+}
+
+impl Default for SubscriptionResumption {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            buffer_size: resumption::default_buffer_size(),
+            grace_period: resumption::default_grace_period(),
+        }
+    }
+}
+
+/// Redis configuration for subscriptions shared between router instances
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct SubscriptionRedis {
+    /// List of URLs to the Redis cluster
+    pub(crate) urls: Vec<url::Url>,
+
+    /// Redis username if not provided in the URLs. This field takes precedence over the username in the URL
+    pub(crate) username: Option<String>,
+    /// Redis password if not provided in the URLs. This field takes precedence over the password in the URL
+    pub(crate) password: Option<String>,
+
+    #[serde(deserialize_with = "humantime_serde::deserialize", default)]
+    #[schemars(with = "Option<String>", default)]
+    /// Redis request timeout (default: 500ms)
+    pub(crate) timeout: Option<Duration>,
+
+    /// namespace used to prefix Redis keys and channels
+    pub(crate) namespace: Option<String>,
+
+    #[serde(default)]
+    /// TLS client configuration
+    pub(crate) tls: Option<TlsClient>,
+
+    #[serde(default)]
+    /// Prevents the router from starting if it cannot connect to Redis. Otherwise subscriptions are only deduplicated inside each router instance
+    pub(crate) required_to_start: bool,
+}
+
+impl From<SubscriptionRedis> for RedisCache {
+    fn from(value: SubscriptionRedis) -> Self {
+        RedisCache {
+            urls: value.urls,
+            username: value.username,
+            password: value.password,
+            timeout: value.timeout.or(Some(Duration::from_millis(500))),
+            ttl: None,
+            namespace: value.namespace,
+            tls: value.tls,
+            required_to_start: value.required_to_start,
+            reset_ttl: false,
         }
     }
 }
@@ -245,13 +392,46 @@ impl Plugin for Subscription {
     type Config = SubscriptionConfig;
 
     async fn new(init: PluginInit<Self::Config>) -> Result<Self, BoxError> {
+        // the backend outlives the plugin, it is only replaced when its configuration changes
+        let redis_config = init.config.redis.clone().map(RedisCache::from);
+        let current_config = init
+            .notify
+            .cluster()
+            .map(|cluster| cluster.config().clone());
+        if redis_config != current_config {
+            match redis_config {
+                Some(redis_config) => {
+                    let required_to_start = redis_config.required_to_start;
+                    match RedisNotify::new(redis_config).await {
+                        Ok(cluster) => init.notify.set_cluster(Some(cluster)),
+                        Err(err) if required_to_start => return Err(err),
+                        Err(err) => {
+                            tracing::error!(
+                                "cannot connect to Redis, subscriptions will only be deduplicated inside this router instance: {err}"
+                            );
+                            init.notify.set_cluster(None);
+                        }
+                    }
+                }
+                None => init.notify.set_cluster(None),
+            }
+        }
+
         let mut callback_hmac_key = None;
         if init.config.mode.callback.is_some() {
-            callback_hmac_key = Some(
-                SUBSCRIPTION_CALLBACK_HMAC_KEY
-                    .get_or_init(|| Uuid::new_v4().to_string())
-                    .clone(),
-            );
+            // callbacks can reach any router instance, they must all verify them with the same key
+            // kept across reloads, so the verifiers already sent stay valid
+            let candidate = SUBSCRIPTION_CALLBACK_HMAC_KEY
+                .read()
+                .clone()
+                .unwrap_or_else(|| Uuid::new_v4().to_string());
+            // the cluster's key always wins over the local one
+            let key = match init.notify.cluster() {
+                Some(cluster) => cluster.callback_hmac_key(candidate).await?,
+                None => candidate,
+            };
+            *SUBSCRIPTION_CALLBACK_HMAC_KEY.write() = Some(key.clone());
+            callback_hmac_key = Some(key);
             #[cfg(not(test))]
             init.notify
                 .set_ttl(
@@ -273,6 +453,33 @@ impl Plugin for Subscription {
         })
     }
 
+    fn supergraph_service(&self, service: supergraph::BoxService) -> supergraph::BoxService {
+        if !self.config.enabled || !self.config.resumption.enabled {
+            return service;
+        }
+        let resume_config = self.config.resumption.clone();
+        let register_config = self.config.resumption.clone();
+        ServiceBuilder::new()
+            .checkpoint(move |req: supergraph::Request| {
+                match resumption::resume(&req, &resume_config)? {
+                    Some(response) => Ok(ControlFlow::Break(response)),
+                    None => Ok(ControlFlow::Continue(req)),
+                }
+            })
+            .map_future_with_request_data(
+                resumption::Binding::new,
+                move |binding: resumption::Binding, f| {
+                    let config = register_config.clone();
+                    async move {
+                        let response: supergraph::Response = f.await?;
+                        Ok::<_, BoxError>(resumption::register(response, config, binding).await)
+                    }
+                },
+            )
+            .service(service)
+            .boxed()
+    }
+
     fn subgraph_service(
         &self,
         _subgraph_name: &str,
@@ -496,25 +703,31 @@ impl Service<router::Request> for CallbackService {
                                 mut payload,
                                 ..
                             }) => {
-                                let mut handle = match notify.subscribe_if_exist(id).await? {
-                                    Some(handle) => handle.into_sink(),
-                                    None => {
-                                        return Ok(router::Response {
-                                            response: http::Response::builder()
-                                                .status(StatusCode::NOT_FOUND)
-                                                .body("suscription doesn't exist".into())
-                                                .map_err(BoxError::from)?,
-                                            context: req.context,
-                                        });
-                                    }
-                                };
                                 // Keep the subscription to the client opened
                                 payload.subscribed = Some(true);
+                                let exists = match notify.cluster() {
+                                    Some(cluster) => cluster.next(&id, payload).await?,
+                                    None => match notify.subscribe_if_exist(id).await? {
+                                        Some(handle) => {
+                                            handle.into_sink().send_sync(payload)?;
+                                            true
+                                        }
+                                        None => false,
+                                    },
+                                };
+                                if !exists {
+                                    return Ok(router::Response {
+                                        response: http::Response::builder()
+                                            .status(StatusCode::NOT_FOUND)
+                                            .body("suscription doesn't exist".into())
+                                            .map_err(BoxError::from)?,
+                                        context: req.context,
+                                    });
+                                }
                                 tracing::info!(
                                         monotonic_counter.apollo.router.operations.subscriptions.events = 1u64,
                                         subscriptions.mode="callback"
                                     );
-                                handle.send_sync(payload)?;
 
                                 Ok(router::Response {
                                     response: http::Response::builder()
@@ -527,7 +740,11 @@ impl Service<router::Request> for CallbackService {
                             CallbackPayload::Subscription(SubscriptionPayload::Check {
                                 ..
                             }) => {
-                                if notify.exist(id).await? {
+                                let exists = match notify.cluster() {
+                                    Some(cluster) => cluster.exist(&id).await?,
+                                    None => notify.exist(id).await?,
+                                };
+                                if exists {
                                     Ok(router::Response {
                                         response: http::Response::builder()
                                             .status(StatusCode::NO_CONTENT)
@@ -562,7 +779,10 @@ impl Service<router::Request> for CallbackService {
                                     });
                                 }
 
-                                let (mut valid_ids, invalid_ids) = notify.invalid_ids(ids).await?;
+                                let (mut valid_ids, invalid_ids) = match notify.cluster() {
+                                    Some(cluster) => cluster.invalid_ids(ids).await?,
+                                    None => notify.invalid_ids(ids).await?,
+                                };
                                 if invalid_ids.is_empty() {
                                     Ok(router::Response {
                                         response: http::Response::builder()
@@ -611,6 +831,20 @@ impl Service<router::Request> for CallbackService {
                                 errors,
                                 ..
                             }) => {
+                                if let Some(cluster) = notify.cluster() {
+                                    let status = if cluster.complete(&id, errors).await? {
+                                        StatusCode::ACCEPTED
+                                    } else {
+                                        StatusCode::NOT_FOUND
+                                    };
+                                    return Ok(router::Response {
+                                        response: http::Response::builder()
+                                            .status(status)
+                                            .body::<hyper::Body>("".into())
+                                            .map_err(BoxError::from)?,
+                                        context: req.context,
+                                    });
+                                }
                                 if let Some(errors) = errors {
                                     let mut handle = match notify.subscribe(id.clone()).await {
                                          Ok(handle) => handle.into_sink(),
@@ -685,7 +919,8 @@ impl Service<router::Request> for CallbackService {
 
 pub(crate) fn create_verifier(sub_id: &str) -> Result<String, BoxError> {
     let callback_hmac_key = SUBSCRIPTION_CALLBACK_HMAC_KEY
-        .get()
+        .read()
+        .clone()
         .ok_or("subscription callback hmac key is not available")?;
     let mut mac = HmacSha256::new_from_slice(callback_hmac_key.as_bytes())?;
     mac.update(sub_id.as_bytes());
@@ -0,0 +1,494 @@
+//! Resumption of subscriptions after a client reconnects
+//!
+//! Every event of a resumable subscription gets an id made of the subscription id and a sequence
+//! number, added to the response extensions. The latest events are kept in a bounded buffer. When
+//! the client disconnects, the subscription is kept for a grace period: a client sending the id of
+//! the last event it received in the `Last-Event-ID` header gets the missed events, then the new
+//! ones.
+//!
+//! Resumption happens before authorization, so a subscription is bound to the operation and the
+//! caller that opened it, and can only be resumed by a request with the same ones.
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use futures::future::ready;
+use futures::stream::once;
+use futures::StreamExt;
+use http::header::AUTHORIZATION;
+use http::StatusCode;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde_json_bytes::Value;
+use sha2::Digest;
+use sha2::Sha256;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use tokio_stream::wrappers::ReceiverStream;
+use tower::BoxError;
+use uuid::Uuid;
+
+use super::SubscriptionResumption;
+use super::SUBSCRIPTION_EVENT_ID_EXTENSION;
+use crate::graphql;
+use crate::plugins::authentication::APOLLO_AUTHENTICATION_JWT_CLAIMS;
+use crate::services::supergraph;
+
+/// Header sent by a reconnecting client, containing the id of the last event it received
+pub(crate) const LAST_EVENT_ID_HEADER: &str = "last-event-id";
+
+/// Subscriptions that can be resumed, by id
+static RESUMABLE_SUBSCRIPTIONS: Lazy<Mutex<HashMap<String, Resumable>>> =
+    Lazy::new(Default::default);
+
+struct Resumable {
+    /// binding of the request that opened the subscription
+    binding: Binding,
+    clients: mpsc::Sender<Client>,
+}
+
+/// Hash of the operation of a request, with its variables, and of its caller
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Binding(Vec<u8>);
+
+impl Binding {
+    /// The caller is identified by the subject of its JWT, or else by its `Authorization` header
+    pub(crate) fn new(request: &supergraph::Request) -> Self {
+        let body = request.supergraph_request.body();
+        let mut hasher = Sha256::new();
+        // every part is followed by a separator, so they cannot be confused with each other
+        hasher.update(body.query.as_deref().unwrap_or_default());
+        hasher.update([0]);
+        hasher.update(body.operation_name.as_deref().unwrap_or_default());
+        hasher.update([0]);
+        hasher.update(serde_json::to_vec(&body.variables).unwrap_or_default());
+        hasher.update([0]);
+
+        let subject = request
+            .context
+            .get_json_value(APOLLO_AUTHENTICATION_JWT_CLAIMS)
+            .and_then(|claims| claims.as_object()?.get("sub").cloned());
+        match subject {
+            Some(subject) => {
+                hasher.update(b"sub");
+                hasher.update(serde_json::to_vec(&subject).unwrap_or_default());
+            }
+            None => {
+                if let Some(authorization) = request.supergraph_request.headers().get(AUTHORIZATION)
+                {
+                    hasher.update(b"authorization");
+                    hasher.update(authorization.as_bytes());
+                }
+            }
+        }
+
+        Self(hasher.finalize().to_vec())
+    }
+}
+
+/// Client (re)connecting to a subscription
+struct Client {
+    /// sequence number of the last event received by the client
+    last_sequence: u64,
+    sender: mpsc::Sender<graphql::Response>,
+}
+
+/// Resumes a subscription from the `Last-Event-ID` header of a request, if it is still available
+///
+/// Returns an error response if the subscription was opened by another operation or caller.
+pub(crate) fn resume(
+    request: &supergraph::Request,
+    config: &SubscriptionResumption,
+) -> Result<Option<supergraph::Response>, BoxError> {
+    let Some((id, last_sequence)) = request
+        .supergraph_request
+        .headers()
+        .get(LAST_EVENT_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_event_id)
+    else {
+        return Ok(None);
+    };
+    let binding = Binding::new(request);
+    let Some(clients) = RESUMABLE_SUBSCRIPTIONS
+        .lock()
+        .get(id)
+        .map(|resumable| (resumable.binding == binding).then(|| resumable.clients.clone()))
+    else {
+        return Ok(None);
+    };
+    let Some(clients) = clients else {
+        return supergraph::Response::error_builder()
+            .error(
+                graphql::Error::builder()
+                    .message("the subscription cannot be resumed by this request")
+                    .extension_code("SUBSCRIPTION_RESUMPTION_FORBIDDEN")
+                    .build(),
+            )
+            .status_code(StatusCode::FORBIDDEN)
+            .context(request.context.clone())
+            .build()
+            .map(Some);
+    };
+
+    let (sender, receiver) = mpsc::channel(config.buffer_size.max(1));
+    if clients
+        .try_send(Client {
+            last_sequence,
+            sender,
+        })
+        .is_err()
+    {
+        return Ok(None);
+    }
+
+    Ok(Some(supergraph::Response {
+        response: http::Response::new(client_stream(
+            graphql::Response::builder().subscribed(true).build(),
+            receiver,
+        )),
+        context: request.context.clone(),
+    }))
+}
+
+/// Makes a subscription resumable by requests with the same binding
+///
+/// Other responses are returned unmodified.
+pub(crate) async fn register(
+    response: supergraph::Response,
+    config: SubscriptionResumption,
+    binding: Binding,
+) -> supergraph::Response {
+    let supergraph::Response { response, context } = response;
+    let (parts, mut stream) = response.into_parts();
+
+    let first = match stream.next().await {
+        // the subscription is opened
+        Some(first) if first.subscribed == Some(true) => first,
+        first => {
+            return supergraph::Response {
+                response: http::Response::from_parts(
+                    parts,
+                    futures::stream::iter(first).chain(stream).boxed(),
+                ),
+                context,
+            };
+        }
+    };
+
+    let id = Uuid::new_v4().to_string();
+    let (clients_tx, clients_rx) = mpsc::channel(1);
+    let (sender, receiver) = mpsc::channel(config.buffer_size.max(1));
+    RESUMABLE_SUBSCRIPTIONS.lock().insert(
+        id.clone(),
+        Resumable {
+            binding,
+            clients: clients_tx,
+        },
+    );
+    tokio::spawn(relay(id, stream, clients_rx, sender, config));
+
+    supergraph::Response {
+        response: http::Response::from_parts(parts, client_stream(first, receiver)),
+        context,
+    }
+}
+
+fn client_stream(
+    first: graphql::Response,
+    receiver: mpsc::Receiver<graphql::Response>,
+) -> graphql::ResponseStream {
+    once(ready(first))
+        .chain(ReceiverStream::new(receiver))
+        .boxed()
+}
+
+/// Sends the events of the subscription to its current client, and keeps the latest ones
+async fn relay(
+    id: String,
+    mut events: graphql::ResponseStream,
+    mut clients: mpsc::Receiver<Client>,
+    sender: mpsc::Sender<graphql::Response>,
+    config: SubscriptionResumption,
+) {
+    let mut buffer: VecDeque<(u64, graphql::Response)> =
+        VecDeque::with_capacity(config.buffer_size);
+    let mut sequence = 0;
+    let mut client = Some(sender);
+    let mut ended = false;
+    let grace_period = tokio::time::sleep(config.grace_period);
+    tokio::pin!(grace_period);
+
+    loop {
+        tokio::select! {
+            _ = &mut grace_period, if client.is_none() => break,
+            // detects the disconnection of the client even if the subscription has no new event
+            _ = closed(&client), if client.is_some() => {
+                tracing::debug!("subscription client disconnected, waiting for it to resume");
+                client = None;
+                grace_period
+                    .as_mut()
+                    .reset(Instant::now() + config.grace_period);
+            }
+            Some(Client { last_sequence, sender }) = clients.recv() => {
+                // the channel can contain the whole buffer
+                for (_, event) in buffer.iter().filter(|(sequence, _)| *sequence > last_sequence) {
+                    let _ = sender.try_send(event.clone());
+                }
+                client = Some(sender);
+                if ended {
+                    break;
+                }
+            }
+            event = events.next(), if !ended => {
+                let Some(mut event) = event else {
+                    ended = true;
+                    if client.is_some() {
+                        break;
+                    }
+                    continue;
+                };
+
+                sequence += 1;
+                // the empty response closing the subscription is left untouched
+                if event.data.is_some() || !event.errors.is_empty() {
+                    event.extensions.insert(
+                        SUBSCRIPTION_EVENT_ID_EXTENSION,
+                        Value::String(format!("{id}:{sequence}").into()),
+                    );
+                }
+                if buffer.len() >= config.buffer_size {
+                    buffer.pop_front();
+                }
+                if config.buffer_size > 0 {
+                    buffer.push_back((sequence, event.clone()));
+                }
+
+                if let Some(sender) = &client {
+                    if sender.send(event).await.is_err() {
+                        tracing::debug!("subscription client disconnected, waiting for it to resume");
+                        client = None;
+                        grace_period
+                            .as_mut()
+                            .reset(Instant::now() + config.grace_period);
+                    }
+                }
+            }
+        }
+    }
+
+    RESUMABLE_SUBSCRIPTIONS.lock().remove(&id);
+}
+
+/// Resolves once the current client, if any, is disconnected
+async fn closed(client: &Option<mpsc::Sender<graphql::Response>>) {
+    match client {
+        Some(sender) => sender.closed().await,
+        None => futures::future::pending().await,
+    }
+}
+
+/// Splits an event id into the subscription id and the sequence number
+fn parse_event_id(event_id: &str) -> Option<(&str, u64)> {
+    let (id, sequence) = event_id.rsplit_once(':')?;
+    Some((id, sequence.parse().ok()?))
+}
+
+pub(crate) fn default_buffer_size() -> usize {
+    100
+}
+
+pub(crate) fn default_grace_period() -> Duration {
+    Duration::from_secs(30)
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+    use serde_json_bytes::json;
+    use tokio::sync::mpsc;
+    use tokio_stream::wrappers::ReceiverStream;
+
+    use super::*;
+    use crate::Context;
+
+    fn event(value: i64) -> graphql::Response {
+        graphql::Response::builder()
+            .data(json!({ "value": value }))
+            .subscribed(true)
+            .build()
+    }
+
+    fn values(responses: &[graphql::Response]) -> Vec<&Value> {
+        responses
+            .iter()
+            .map(|response| response.data.as_ref().unwrap())
+            .collect()
+    }
+
+    fn request(query: &str, authorization: &str) -> supergraph::Request {
+        supergraph::Request::fake_builder()
+            .query(query)
+            .header(AUTHORIZATION, authorization)
+            .build()
+            .unwrap()
+    }
+
+    /// Opens a resumable subscription for a request, returns its event sender and stream
+    async fn subscribe(
+        request: &supergraph::Request,
+        config: &SubscriptionResumption,
+    ) -> (mpsc::Sender<graphql::Response>, graphql::ResponseStream) {
+        // with a single slot, an event can only be sent once the previous one was processed
+        let (events_tx, events_rx) = mpsc::channel(1);
+        events_tx
+            .send(graphql::Response::builder().subscribed(true).build())
+            .await
+            .unwrap();
+
+        let response = register(
+            supergraph::Response {
+                response: http::Response::new(ReceiverStream::new(events_rx).boxed()),
+                context: Context::new(),
+            },
+            config.clone(),
+            Binding::new(request),
+        )
+        .await;
+        (events_tx, response.response.into_body())
+    }
+
+    async fn first_event_id(
+        events_tx: &mpsc::Sender<graphql::Response>,
+        stream: &mut graphql::ResponseStream,
+    ) -> String {
+        assert_eq!(stream.next().await.unwrap().subscribed, Some(true));
+        events_tx.send(event(1)).await.unwrap();
+        stream
+            .next()
+            .await
+            .unwrap()
+            .extensions
+            .get(SUBSCRIPTION_EVENT_ID_EXTENSION)
+            .and_then(|id| id.as_str())
+            .unwrap()
+            .to_string()
+    }
+
+    #[tokio::test]
+    async fn resume_subscription_after_reconnect() {
+        let config = SubscriptionResumption {
+            enabled: true,
+            buffer_size: 2,
+            grace_period: Duration::from_secs(60),
+        };
+        let query = "subscription { value }";
+        let (events_tx, mut stream) = subscribe(&request(query, "Bearer a"), &config).await;
+        let event_id = first_event_id(&events_tx, &mut stream).await;
+        assert!(event_id.ends_with(":1"));
+
+        // the client disconnects, then misses events
+        drop(stream);
+        for value in 2..=4 {
+            events_tx.send(event(value)).await.unwrap();
+        }
+        // the channel is empty once the relay took the last event, and the relay does not yield
+        // before buffering it
+        drop(events_tx.reserve().await.unwrap());
+
+        let mut request = request(query, "Bearer a");
+        request
+            .supergraph_request
+            .headers_mut()
+            .insert(LAST_EVENT_ID_HEADER, event_id.parse().unwrap());
+        let response = resume(&request, &config).unwrap().unwrap();
+        let mut stream = response.response.into_body();
+        assert_eq!(stream.next().await.unwrap().subscribed, Some(true));
+
+        events_tx.send(event(5)).await.unwrap();
+        drop(events_tx);
+        let events: Vec<_> = stream.collect().await;
+        // only the two latest missed events are kept
+        assert_eq!(
+            values(&events),
+            vec![
+                &json!({ "value": 3 }),
+                &json!({ "value": 4 }),
+                &json!({ "value": 5 })
+            ]
+        );
+
+        assert_eq!(parse_event_id("a:b:12"), Some(("a:b", 12)));
+        assert_eq!(parse_event_id("abc"), None);
+    }
+
+    #[tokio::test]
+    async fn end_silent_subscription_after_grace_period() {
+        let config = SubscriptionResumption {
+            enabled: true,
+            buffer_size: 2,
+            grace_period: Duration::from_millis(50),
+        };
+        let query = "subscription { value }";
+        let (events_tx, mut stream) = subscribe(&request(query, "Bearer a"), &config).await;
+        let event_id = first_event_id(&events_tx, &mut stream).await;
+        let id = parse_event_id(&event_id).unwrap().0.to_string();
+
+        // the client disconnects, and the subscription sends no more events
+        drop(stream);
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert!(!RESUMABLE_SUBSCRIPTIONS.lock().contains_key(&id));
+        assert!(events_tx.is_closed());
+    }
+
+    #[tokio::test]
+    async fn reject_resumption_by_another_operation_or_caller() {
+        let config = SubscriptionResumption {
+            enabled: true,
+            buffer_size: 2,
+            grace_period: Duration::from_secs(60),
+        };
+        let query = "subscription { value }";
+        let (events_tx, mut stream) = subscribe(&request(query, "Bearer a"), &config).await;
+        let event_id = first_event_id(&events_tx, &mut stream).await;
+        drop(stream);
+
+        for mut request in [
+            request(query, "Bearer b"),
+            request("subscription { other: value }", "Bearer a"),
+        ] {
+            request
+                .supergraph_request
+                .headers_mut()
+                .insert(LAST_EVENT_ID_HEADER, event_id.parse().unwrap());
+            let response = resume(&request, &config).unwrap().unwrap();
+            assert_eq!(response.response.status(), StatusCode::FORBIDDEN);
+            let errors = response.response.into_body().next().await.unwrap().errors;
+            assert_eq!(
+                errors[0].extensions.get("code"),
+                Some(&Value::from("SUBSCRIPTION_RESUMPTION_FORBIDDEN"))
+            );
+        }
+
+        // the subject of the JWT identifies the caller, whatever the token
+        let claims = serde_json::json!({ "sub": "user", "exp": 1 });
+        let opened = request(query, "Bearer a");
+        opened
+            .context
+            .insert(APOLLO_AUTHENTICATION_JWT_CLAIMS, claims)
+            .unwrap();
+        let resumed = request(query, "Bearer refreshed");
+        resumed
+            .context
+            .insert(
+                APOLLO_AUTHENTICATION_JWT_CLAIMS,
+                serde_json::json!({ "sub": "user", "exp": 2 }),
+            )
+            .unwrap();
+        assert_eq!(Binding::new(&opened), Binding::new(&resumed));
+        assert_ne!(
+            Binding::new(&opened),
+            Binding::new(&request(query, "Bearer a"))
+        );
+    }
+}
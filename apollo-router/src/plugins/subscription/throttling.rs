@@ -0,0 +1,258 @@
+//! Throttling and coalescing of the events sent to subscription clients
+//!
+//! Events go through a queue of `queue_capacity` events per client. Events that cannot be queued
+//! right away, because the client queue is full or `max_events` is reached for the current
+//! interval, are either dropped or coalesced into the latest one. Errors and the last event of a
+//! subscription are always sent.
+use futures::StreamExt;
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::time::MissedTickBehavior;
+use tokio_stream::wrappers::ReceiverStream;
+
+use super::SubscriptionOverflow;
+use super::SubscriptionThrottling;
+use crate::graphql;
+use crate::services::subgraph::BoxGqlStream;
+
+/// Applies the throttling configuration to the events of a subscription
+pub(crate) fn throttle(
+    stream: BoxGqlStream,
+    config: SubscriptionThrottling,
+    queue_capacity: usize,
+) -> BoxGqlStream {
+    let (sender, receiver) = mpsc::channel(queue_capacity.max(1));
+    tokio::spawn(forward(stream, sender, config));
+    Box::pin(ReceiverStream::new(receiver))
+}
+
+async fn forward(
+    mut stream: BoxGqlStream,
+    sender: mpsc::Sender<graphql::Response>,
+    config: SubscriptionThrottling,
+) {
+    let mut interval = tokio::time::interval(config.interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    // events sent in the current interval
+    let mut sent = 0;
+    let mut pending: Option<graphql::Response> = None;
+
+    loop {
+        let can_send = config
+            .max_events
+            .map_or(true, |max_events| sent < max_events);
+        tokio::select! {
+            biased;
+            _ = sender.closed() => return,
+            permit = sender.reserve(), if can_send && pending.is_some() => match permit {
+                Ok(permit) => {
+                    permit.send(pending.take().expect("checked in the condition"));
+                    sent += 1;
+                }
+                Err(_) => return,
+            },
+            _ = interval.tick(), if config.max_events.is_some() => sent = 0,
+            event = stream.next() => {
+                let Some(event) = event else { break };
+
+                if event.subscribed == Some(false) || !event.errors.is_empty() {
+                    if let Some(pending) = pending.take() {
+                        if sender.send(pending).await.is_err() {
+                            return;
+                        }
+                    }
+                    if sender.send(event).await.is_err() {
+                        return;
+                    }
+                    sent += 1;
+                    continue;
+                }
+
+                let event = if can_send && pending.is_none() {
+                    match sender.try_send(event) {
+                        Ok(()) => {
+                            sent += 1;
+                            continue;
+                        }
+                        Err(TrySendError::Full(event)) => event,
+                        Err(TrySendError::Closed(_)) => return,
+                    }
+                } else {
+                    event
+                };
+
+                match config.overflow {
+                    SubscriptionOverflow::Coalesce => {
+                        if pending.replace(event).is_some() {
+                            u64_counter!(
+                                "apollo.router.operations.subscriptions.events.coalesced",
+                                "Number of subscription events replaced by a more recent one before being sent to the client",
+                                1
+                            );
+                        }
+                    }
+                    SubscriptionOverflow::Drop => {
+                        u64_counter!(
+                            "apollo.router.operations.subscriptions.events.dropped",
+                            "Number of subscription events dropped instead of being sent to the client",
+                            1
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    // the latest event is still sent once the subscription ends
+    if let Some(pending) = pending {
+        let _ = sender.send(pending).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::stream;
+    use futures::StreamExt;
+    use serde_json_bytes::json;
+    use serde_json_bytes::Value;
+    use tokio::sync::mpsc;
+    use tokio_stream::wrappers::ReceiverStream;
+
+    use super::forward;
+    use super::throttle;
+    use crate::graphql;
+    use crate::metrics::FutureMetricsExt;
+    use crate::plugins::subscription::SubscriptionOverflow;
+    use crate::plugins::subscription::SubscriptionThrottling;
+
+    async fn events(
+        max_events: Option<usize>,
+        overflow: SubscriptionOverflow,
+        queue_capacity: usize,
+    ) -> Vec<Value> {
+        let events = (1..=5).map(|i| {
+            graphql::Response::builder()
+                .data(json!({ "price": i }))
+                .subscribed(true)
+                .build()
+        });
+        let config = SubscriptionThrottling {
+            operation_names: Vec::new(),
+            fields: Vec::new(),
+            max_events,
+            interval: Duration::from_secs(3600),
+            overflow,
+        };
+        // forwarded in the current task, so the metrics it records can be collected
+        let (sender, receiver) = mpsc::channel(queue_capacity);
+        let (_, events) = tokio::join!(
+            forward(Box::pin(stream::iter(events)), sender, config),
+            ReceiverStream::new(receiver)
+                .map(|response| response.data.unwrap())
+                .collect::<Vec<_>>()
+        );
+        events
+    }
+
+    fn prices(prices: &[i64]) -> Vec<Value> {
+        prices
+            .iter()
+            .map(|price| json!({ "price": price }))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn throttle_subscription_events() {
+        assert_eq!(
+            events(None, SubscriptionOverflow::Drop, 10).await,
+            prices(&[1, 2, 3, 4, 5])
+        );
+        // the client queue is full after the first event
+        assert_eq!(
+            events(None, SubscriptionOverflow::Drop, 1).await,
+            prices(&[1])
+        );
+        assert_eq!(
+            events(None, SubscriptionOverflow::Coalesce, 1).await,
+            prices(&[1, 5])
+        );
+        assert_eq!(
+            events(Some(2), SubscriptionOverflow::Drop, 10).await,
+            prices(&[1, 2])
+        );
+        assert_eq!(
+            events(Some(2), SubscriptionOverflow::Coalesce, 10).await,
+            prices(&[1, 2, 5])
+        );
+    }
+
+    #[tokio::test]
+    async fn count_dropped_events() {
+        async {
+            assert_eq!(
+                events(Some(2), SubscriptionOverflow::Drop, 10).await,
+                prices(&[1, 2])
+            );
+            assert_counter!("apollo.router.operations.subscriptions.events.dropped", 3);
+        }
+        .with_metrics()
+        .await;
+    }
+
+    #[tokio::test]
+    async fn count_coalesced_events() {
+        async {
+            // 3 is replaced by 4, then 4 by 5
+            assert_eq!(
+                events(Some(2), SubscriptionOverflow::Coalesce, 10).await,
+                prices(&[1, 2, 5])
+            );
+            assert_counter!("apollo.router.operations.subscriptions.events.coalesced", 2);
+        }
+        .with_metrics()
+        .await;
+    }
+
+    #[tokio::test]
+    async fn always_send_errors_and_the_last_event() {
+        let config = SubscriptionThrottling {
+            operation_names: Vec::new(),
+            fields: Vec::new(),
+            max_events: Some(1),
+            interval: Duration::from_secs(3600),
+            overflow: SubscriptionOverflow::Drop,
+        };
+        let events = vec![
+            graphql::Response::builder()
+                .data(json!({ "price": 1 }))
+                .subscribed(true)
+                .build(),
+            graphql::Response::builder()
+                .data(json!({ "price": 2 }))
+                .subscribed(true)
+                .build(),
+            graphql::Response::builder()
+                .error(
+                    graphql::Error::builder()
+                        .message("cannot fetch the price")
+                        .extension_code("SUBGRAPH_ERROR")
+                        .build(),
+                )
+                .subscribed(true)
+                .build(),
+            graphql::Response::builder()
+                .data(json!({ "price": 3 }))
+                .subscribed(false)
+                .build(),
+        ];
+        let received: Vec<_> = throttle(Box::pin(stream::iter(events.clone())), config, 10)
+            .collect()
+            .await;
+        assert_eq!(
+            received,
+            vec![events[0].clone(), events[2].clone(), events[3].clone()]
+        );
+    }
+}
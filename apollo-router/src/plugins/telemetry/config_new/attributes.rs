@@ -1503,6 +1503,7 @@ mod test {
         req.router_request.extensions_mut().insert(ConnectionInfo {
             peer_address: Some(SocketAddr::from_str("192.168.0.8:6060").unwrap()),
             server_address: Some(SocketAddr::from_str("192.168.0.1:8080").unwrap()),
+            client_certificate: None,
         });
         let attributes = common.on_request(&req);
         assert_eq!(
@@ -1525,6 +1526,7 @@ mod test {
         req.router_request.extensions_mut().insert(ConnectionInfo {
             peer_address: Some(SocketAddr::from_str("192.168.0.8:6060").unwrap()),
             server_address: Some(SocketAddr::from_str("192.168.0.1:8080").unwrap()),
+            client_certificate: None,
         });
         let attributes = server.on_request(&req);
         assert_eq!(
@@ -1542,6 +1544,7 @@ mod test {
         req.router_request.extensions_mut().insert(ConnectionInfo {
             peer_address: Some(SocketAddr::from_str("192.168.0.8:6060").unwrap()),
             server_address: Some(SocketAddr::from_str("192.168.0.1:8080").unwrap()),
+            client_certificate: None,
         });
         let attributes = server.on_request(&req);
         assert_eq!(
@@ -1564,6 +1567,7 @@ mod test {
         req.router_request.extensions_mut().insert(ConnectionInfo {
             peer_address: Some(SocketAddr::from_str("192.168.0.8:6060").unwrap()),
             server_address: Some(SocketAddr::from_str("192.168.0.1:8080").unwrap()),
+            client_certificate: None,
         });
         let attributes = server.on_request(&req);
         assert_eq!(
@@ -1581,6 +1585,7 @@ mod test {
         req.router_request.extensions_mut().insert(ConnectionInfo {
             peer_address: Some(SocketAddr::from_str("192.168.0.8:6060").unwrap()),
             server_address: Some(SocketAddr::from_str("192.168.0.1:8080").unwrap()),
+            client_certificate: None,
         });
         let attributes = server.on_request(&req);
         assert_eq!(
@@ -1606,6 +1611,7 @@ mod test {
         req.router_request.extensions_mut().insert(ConnectionInfo {
             peer_address: Some(SocketAddr::from_str("192.168.0.8:6060").unwrap()),
             server_address: Some(SocketAddr::from_str("192.168.0.1:8080").unwrap()),
+            client_certificate: None,
         });
         let attributes = server.on_request(&req);
         assert_eq!(
@@ -1631,6 +1637,7 @@ mod test {
         req.router_request.extensions_mut().insert(ConnectionInfo {
             peer_address: Some(SocketAddr::from_str("192.168.0.8:6060").unwrap()),
             server_address: Some(SocketAddr::from_str("192.168.0.1:8080").unwrap()),
+            client_certificate: None,
         });
         let attributes = server.on_request(&req);
         assert_eq!(
@@ -1656,6 +1663,7 @@ mod test {
         req.router_request.extensions_mut().insert(ConnectionInfo {
             peer_address: Some(SocketAddr::from_str("192.168.0.8:6060").unwrap()),
             server_address: Some(SocketAddr::from_str("192.168.0.1:8080").unwrap()),
+            client_certificate: None,
         });
         let attributes = server.on_request(&req);
         assert_eq!(
@@ -1681,6 +1689,7 @@ mod test {
         req.router_request.extensions_mut().insert(ConnectionInfo {
             peer_address: Some(SocketAddr::from_str("192.168.0.8:6060").unwrap()),
             server_address: Some(SocketAddr::from_str("192.168.0.1:8080").unwrap()),
+            client_certificate: None,
         });
         let attributes = server.on_request(&req);
         assert_eq!(
@@ -1706,6 +1715,7 @@ mod test {
         req.router_request.extensions_mut().insert(ConnectionInfo {
             peer_address: Some(SocketAddr::from_str("192.168.0.8:6060").unwrap()),
             server_address: Some(SocketAddr::from_str("192.168.0.1:8080").unwrap()),
+            client_certificate: None,
         });
         let attributes = server.on_request(&req);
         assert_eq!(
@@ -1728,6 +1738,7 @@ mod test {
         req.router_request.extensions_mut().insert(ConnectionInfo {
             peer_address: Some(SocketAddr::from_str("192.168.0.8:6060").unwrap()),
             server_address: Some(SocketAddr::from_str("192.168.0.1:8080").unwrap()),
+            client_certificate: None,
         });
         let attributes = server.on_request(&req);
         assert_eq!(
@@ -1745,6 +1756,7 @@ mod test {
         req.router_request.extensions_mut().insert(ConnectionInfo {
             peer_address: Some(SocketAddr::from_str("192.168.0.8:6060").unwrap()),
             server_address: Some(SocketAddr::from_str("192.168.0.1:8080").unwrap()),
+            client_certificate: None,
         });
         let attributes = server.on_request(&req);
         assert_eq!(
@@ -1767,6 +1779,7 @@ mod test {
         req.router_request.extensions_mut().insert(ConnectionInfo {
             peer_address: Some(SocketAddr::from_str("192.168.0.8:6060").unwrap()),
             server_address: Some(SocketAddr::from_str("192.168.0.1:8080").unwrap()),
+            client_certificate: None,
         });
         let attributes = server.on_request(&req);
         assert_eq!(
@@ -1784,6 +1797,7 @@ mod test {
         req.router_request.extensions_mut().insert(ConnectionInfo {
             peer_address: Some(SocketAddr::from_str("192.168.0.8:6060").unwrap()),
             server_address: Some(SocketAddr::from_str("192.168.0.1:8080").unwrap()),
+            client_certificate: None,
         });
         let attributes = server.on_request(&req);
         assert_eq!(
@@ -8,11 +8,13 @@ use serde::Deserialize;
 use serde_json_bytes::ByteString;
 use sha2::Digest;
 
+use crate::axum_factory::utils::ConnectionInfo;
 use crate::context::CONTAINS_GRAPHQL_ERROR;
 use crate::context::OPERATION_KIND;
 use crate::context::OPERATION_NAME;
 use crate::plugin::serde::deserialize_json_query;
 use crate::plugin::serde::deserialize_jsonpath;
+use crate::plugins::authentication::client_certificate::ClientCertificateField;
 use crate::plugins::demand_control::CostContext;
 use crate::plugins::telemetry::config::AttributeValue;
 use crate::plugins::telemetry::config_new::cost::CostValue;
@@ -163,6 +165,13 @@ pub(crate) enum RouterSelector {
         /// Optional default value.
         default: Option<String>,
     },
+    /// Information from the certificate presented by the client, when client certificates are verified.
+    ClientCertificate {
+        /// The information to extract from the client certificate.
+        client_certificate: ClientCertificateField,
+        /// Optional default value.
+        default: Option<AttributeValue>,
+    },
     /// Deprecated, should not be used anymore, use static field instead
     Static(String),
     StaticField {
@@ -567,6 +576,17 @@ impl Selector for RouterSelector {
                 .ok()
                 .or_else(|| default.clone())
                 .map(opentelemetry::Value::from),
+            RouterSelector::ClientCertificate {
+                client_certificate,
+                default,
+            } => request
+                .router_request
+                .extensions()
+                .get::<ConnectionInfo>()
+                .and_then(|connection_info| connection_info.client_certificate.as_ref())
+                .and_then(|certificate| client_certificate.value(certificate))
+                .and_then(|value| value.maybe_to_otel_value())
+                .or_else(|| default.maybe_to_otel_value()),
             RouterSelector::TraceId {
                 trace_id: trace_id_format,
             } => trace_id().map(|id| {
@@ -1196,9 +1216,12 @@ mod test {
     use tracing::subscriber;
     use tracing_subscriber::layer::SubscriberExt;
 
+    use crate::axum_factory::utils::ClientCertificate;
+    use crate::axum_factory::utils::ConnectionInfo;
     use crate::context::OPERATION_KIND;
     use crate::context::OPERATION_NAME;
     use crate::graphql;
+    use crate::plugins::authentication::client_certificate::ClientCertificateField;
     use crate::plugins::telemetry::config::AttributeValue;
     use crate::plugins::telemetry::config_new::selectors::OperationKind;
     use crate::plugins::telemetry::config_new::selectors::OperationName;
@@ -2025,6 +2048,45 @@ mod test {
         );
     }
 
+    #[test]
+    fn router_client_certificate() {
+        let selector = RouterSelector::ClientCertificate {
+            client_certificate: ClientCertificateField::CommonName,
+            default: Some("anonymous".into()),
+        };
+
+        let mut request = crate::services::RouterRequest::fake_builder()
+            .build()
+            .unwrap();
+        assert_eq!(selector.on_request(&request), Some("anonymous".into()));
+
+        request
+            .router_request
+            .extensions_mut()
+            .insert(ConnectionInfo {
+                peer_address: None,
+                server_address: None,
+                client_certificate: Some(Arc::new(ClientCertificate {
+                    subject: "CN=billing,O=Example".to_string(),
+                    common_name: Some("billing".to_string()),
+                    subject_alternative_names: vec!["billing.example.com".to_string()],
+                    fingerprint: "0123456789abcdef".to_string(),
+                })),
+            });
+        assert_eq!(selector.on_request(&request), Some("billing".into()));
+
+        let selector = RouterSelector::ClientCertificate {
+            client_certificate: ClientCertificateField::SubjectAlternativeNames,
+            default: None,
+        };
+        assert_eq!(
+            selector.on_request(&request),
+            Some(opentelemetry::Value::Array(opentelemetry::Array::String(
+                vec!["billing.example.com".into()]
+            )))
+        );
+    }
+
     #[test]
     fn router_env() {
         let selector = RouterSelector::Env {
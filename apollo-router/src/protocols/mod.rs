@@ -1,2 +1,3 @@
 pub(crate) mod multipart;
+pub(crate) mod sse;
 pub(crate) mod websocket;
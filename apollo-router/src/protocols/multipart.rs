@@ -14,9 +14,9 @@ use tokio_stream::wrappers::IntervalStream;
 use crate::graphql;
 
 #[cfg(test)]
-const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(10);
+pub(crate) const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(10);
 #[cfg(not(test))]
-const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+pub(crate) const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
 
 #[derive(thiserror::Error, Debug)]
 pub(crate) enum Error {
@@ -37,7 +37,7 @@ struct SubscriptionPayload {
     errors: Vec<graphql::Error>,
 }
 
-enum MessageKind {
+pub(crate) enum MessageKind {
     Heartbeat,
     Message(graphql::Response),
     Eof,
@@ -260,6 +260,43 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_incremental_delivery() {
+        let responses = vec![
+            graphql::Response::builder()
+                .data(serde_json_bytes::json!({"a": 1}))
+                .has_next(true)
+                .build(),
+            graphql::Response::builder()
+                .pending(vec![graphql::PendingResponse::builder()
+                    .id("0".to_string())
+                    .path(graphql::JsonPath::empty())
+                    .build()])
+                .incremental(vec![graphql::IncrementalResponse::builder()
+                    .id("0".to_string())
+                    .data(serde_json_bytes::json!({"b": 2}))
+                    .build()])
+                .completed(vec![graphql::CompletedResponse::builder()
+                    .id("0".to_string())
+                    .build()])
+                .has_next(false)
+                .build(),
+        ];
+
+        let protocol = Multipart::new(stream::iter(responses), ProtocolMode::Defer);
+        let chunks: Vec<String> = protocol
+            .map(|chunk| String::from_utf8(chunk.unwrap().to_vec()).unwrap())
+            .collect()
+            .await;
+        assert_eq!(
+            chunks,
+            vec![
+                "\r\n--graphql\r\ncontent-type: application/json\r\n\r\n{\"data\":{\"a\":1},\"hasNext\":true}\r\n--graphql",
+                "\r\ncontent-type: application/json\r\n\r\n{\"hasNext\":false,\"incremental\":[{\"data\":{\"b\":2},\"id\":\"0\"}],\"pending\":[{\"id\":\"0\",\"path\":[]}],\"completed\":[{\"id\":\"0\"}]}\r\n--graphql--\r\n",
+            ]
+        );
+    }
+
     #[tokio::test]
     async fn test_empty_stream() {
         let responses = vec![];
@@ -0,0 +1,295 @@
+//! GraphQL over Server-Sent Events, in distinct connections mode
+//!
+//! Every GraphQL response is sent as a `next` event, and a `complete` event is sent when the
+//! operation is done. See <https://github.com/enisdenjo/graphql-sse/blob/master/PROTOCOL.md>
+
+use std::pin::Pin;
+use std::task::Poll;
+
+use bytes::Bytes;
+use futures::stream::select;
+use futures::stream::StreamExt;
+use futures::Stream;
+use serde_json_bytes::Value;
+use tokio_stream::once;
+use tokio_stream::wrappers::IntervalStream;
+
+use super::multipart::Error;
+use super::multipart::MessageKind;
+use super::multipart::ProtocolMode;
+use super::multipart::HEARTBEAT_INTERVAL;
+use crate::graphql;
+use crate::plugins::subscription::SUBSCRIPTION_EVENT_ID_EXTENSION;
+
+const NEXT_EVENT: &[u8] = b"event: next\ndata: ";
+const COMPLETE_EVENT: &[u8] = b"event: complete\ndata:\n\n";
+// lines starting with a colon are comments, ignored by the client
+const HEARTBEAT: &[u8] = b":\n\n";
+
+pub(crate) struct EventStream {
+    stream: Pin<Box<dyn Stream<Item = MessageKind> + Send>>,
+    is_terminated: bool,
+    mode: ProtocolMode,
+}
+
+impl EventStream {
+    pub(crate) fn new<S>(stream: S, mode: ProtocolMode) -> Self
+    where
+        S: Stream<Item = graphql::Response> + Send + 'static,
+    {
+        let messages = stream
+            .map(MessageKind::Message)
+            .chain(once(MessageKind::Eof));
+        let stream = match mode {
+            ProtocolMode::Subscription => select(
+                messages,
+                IntervalStream::new(tokio::time::interval(HEARTBEAT_INTERVAL))
+                    .map(|_| MessageKind::Heartbeat),
+            )
+            .boxed(),
+            ProtocolMode::Defer => messages.boxed(),
+        };
+
+        Self {
+            stream,
+            is_terminated: false,
+            mode,
+        }
+    }
+}
+
+impl Stream for EventStream {
+    type Item = Result<Bytes, Error>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        if self.is_terminated {
+            return Poll::Ready(None);
+        }
+        match self.stream.as_mut().poll_next(cx) {
+            Poll::Ready(message) => match message {
+                Some(MessageKind::Heartbeat) => {
+                    Poll::Ready(Some(Ok(Bytes::from_static(HEARTBEAT))))
+                }
+                Some(MessageKind::Message(response)) => {
+                    let is_still_open =
+                        response.has_next.unwrap_or(false) || response.subscribed.unwrap_or(false);
+
+                    // Gracefully closed at the server side
+                    if self.mode == ProtocolMode::Subscription
+                        && !is_still_open
+                        && matches!(response.data, None | Some(Value::Null))
+                        && response.extensions.is_empty()
+                        && response.errors.is_empty()
+                    {
+                        self.is_terminated = true;
+                        return Poll::Ready(Some(Ok(Bytes::from_static(COMPLETE_EVENT))));
+                    }
+
+                    let mut buf = Vec::new();
+                    // lets `EventSource` clients send it back in the `Last-Event-ID` header when reconnecting
+                    if let Some(Value::String(id)) =
+                        response.extensions.get(SUBSCRIPTION_EVENT_ID_EXTENSION)
+                    {
+                        buf.extend_from_slice(b"id: ");
+                        buf.extend_from_slice(id.as_str().as_bytes());
+                        buf.push(b'\n');
+                    }
+                    buf.extend_from_slice(NEXT_EVENT);
+                    serde_json::to_writer(&mut buf, &response)?;
+                    buf.extend_from_slice(b"\n\n");
+                    if !is_still_open {
+                        self.is_terminated = true;
+                        buf.extend_from_slice(COMPLETE_EVENT);
+                    }
+
+                    Poll::Ready(Some(Ok(buf.into())))
+                }
+                Some(MessageKind::Eof) => {
+                    // If the stream ends or is empty
+                    self.is_terminated = true;
+                    Poll::Ready(Some(Ok(Bytes::from_static(COMPLETE_EVENT))))
+                }
+                None => {
+                    self.is_terminated = true;
+                    Poll::Ready(None)
+                }
+            },
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Reads back the GraphQL responses of an event stream produced by [`EventStream`]
+pub(crate) fn parse<S, E>(body: S) -> impl Stream<Item = serde_json::Result<graphql::Response>>
+where
+    S: Stream<Item = Result<Bytes, E>> + Unpin,
+{
+    futures::stream::unfold((body, Vec::new()), |(mut body, mut buf)| async move {
+        loop {
+            if let Some(end) = buf.windows(2).position(|window| window == b"\n\n") {
+                let event: Vec<u8> = buf.drain(..end + 2).collect();
+                let mut event = &event[..end];
+                // the event id comes before the event type
+                if event.starts_with(b"id: ") {
+                    let id_end = event.iter().position(|b| *b == b'\n').unwrap_or(end);
+                    event = &event[(id_end + 1).min(end)..];
+                }
+                // complete events and heartbeats carry no response
+                if let Some(data) = event.strip_prefix(NEXT_EVENT) {
+                    return Some((serde_json::from_slice(data), (body, buf)));
+                }
+                continue;
+            }
+            match body.next().await {
+                Some(Ok(bytes)) => buf.extend_from_slice(&bytes),
+                _ => return None,
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::stream;
+    use serde_json_bytes::json;
+
+    use super::*;
+
+    async fn events(protocol: EventStream) -> Vec<String> {
+        protocol
+            .map(|event| String::from_utf8(event.unwrap().to_vec()).unwrap())
+            .filter(|event| futures::future::ready(event != ":\n\n"))
+            .collect()
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_subscription_events() {
+        let responses = vec![
+            graphql::Response::builder()
+                .data(json!("foo"))
+                .subscribed(true)
+                .build(),
+            graphql::Response::builder()
+                .data(Value::Null)
+                .extension("test", json!("test_extension"))
+                .subscribed(true)
+                .build(),
+            graphql::Response::builder().build(),
+            graphql::Response::builder()
+                .data(json!("ignored"))
+                .subscribed(true)
+                .build(),
+        ];
+
+        let protocol = EventStream::new(stream::iter(responses), ProtocolMode::Subscription);
+        assert_eq!(
+            events(protocol).await,
+            vec![
+                "event: next\ndata: {\"data\":\"foo\"}\n\n",
+                "event: next\ndata: {\"data\":null,\"extensions\":{\"test\":\"test_extension\"}}\n\n",
+                "event: complete\ndata:\n\n",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_subscription_event_ids() {
+        let responses = vec![graphql::Response::builder()
+            .data(json!("foo"))
+            .extension(SUBSCRIPTION_EVENT_ID_EXTENSION, json!("abc:1"))
+            .subscribed(true)
+            .build()];
+
+        let bytes: Vec<u8> =
+            EventStream::new(stream::iter(responses.clone()), ProtocolMode::Subscription)
+                .map(|event| event.unwrap().to_vec())
+                .filter(|event| futures::future::ready(event != b":\n\n"))
+                .concat()
+                .await;
+        assert!(bytes.starts_with(b"id: abc:1\nevent: next\ndata: "));
+
+        let parsed: Vec<graphql::Response> =
+            parse(stream::iter(vec![Ok::<_, Error>(Bytes::from(bytes))]))
+                .map(|response| response.unwrap())
+                .collect()
+                .await;
+        assert_eq!(parsed, responses);
+    }
+
+    #[tokio::test]
+    async fn test_subscription_closed_with_errors() {
+        let responses = vec![graphql::Response::builder()
+            .error(
+                graphql::Error::builder()
+                    .message("subscription closed")
+                    .extension_code("SUBSCRIPTION_CLOSED")
+                    .build(),
+            )
+            .build()];
+
+        let protocol = EventStream::new(stream::iter(responses), ProtocolMode::Subscription);
+        assert_eq!(
+            events(protocol).await,
+            vec![
+                "event: next\ndata: {\"errors\":[{\"message\":\"subscription closed\",\"extensions\":{\"code\":\"SUBSCRIPTION_CLOSED\"}}]}\n\nevent: complete\ndata:\n\n",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_defer_events() {
+        let responses = vec![
+            graphql::Response::builder()
+                .data(json!({"a": 1}))
+                .has_next(true)
+                .build(),
+            graphql::Response::builder().has_next(false).build(),
+        ];
+
+        let protocol = EventStream::new(stream::iter(responses), ProtocolMode::Defer);
+        assert_eq!(
+            events(protocol).await,
+            vec![
+                "event: next\ndata: {\"data\":{\"a\":1},\"hasNext\":true}\n\n",
+                "event: next\ndata: {\"hasNext\":false}\n\nevent: complete\ndata:\n\n",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_parse_events() {
+        let responses = vec![
+            graphql::Response::builder()
+                .data(json!({"a": 1}))
+                .has_next(true)
+                .build(),
+            graphql::Response::builder().has_next(false).build(),
+        ];
+
+        // split the events at arbitrary positions, as the network would
+        let bytes: Vec<u8> = EventStream::new(stream::iter(responses.clone()), ProtocolMode::Defer)
+            .map(|event| event.unwrap().to_vec())
+            .concat()
+            .await;
+        let chunks: Vec<Result<Bytes, Error>> = bytes
+            .chunks(7)
+            .map(|chunk| Ok(Bytes::copy_from_slice(chunk)))
+            .collect();
+
+        let parsed: Vec<graphql::Response> = parse(stream::iter(chunks))
+            .map(|response| response.unwrap())
+            .collect()
+            .await;
+        assert_eq!(parsed, responses);
+    }
+
+    #[tokio::test]
+    async fn test_empty_stream() {
+        let protocol = EventStream::new(stream::iter(vec![]), ProtocolMode::Subscription);
+        assert_eq!(events(protocol).await, vec!["event: complete\ndata:\n\n"]);
+    }
+}
@@ -56,6 +56,7 @@ use crate::services::QueryPlannerContent;
 use crate::services::QueryPlannerRequest;
 use crate::services::QueryPlannerResponse;
 use crate::spec::query::change::QueryHashVisitor;
+use crate::spec::query::stream::remove_stream_directives;
 use crate::spec::Query;
 use crate::spec::Schema;
 use crate::spec::SpecError;
@@ -565,6 +566,8 @@ impl BridgeQueryPlanner {
             unauthorized: UnauthorizedPaths {
                 paths: vec![],
                 errors: AuthorizationPlugin::log_errors(&self.configuration),
+                reported: vec![],
+                variables: vec![],
             },
             subselections,
             defer_stats,
@@ -904,33 +907,34 @@ impl BridgeQueryPlanner {
         mut key: QueryKey,
         mut doc: ParsedDocument,
     ) -> Result<QueryPlannerContent, QueryPlannerError> {
-        let filter_res = if self.enable_authorization_directives {
-            match AuthorizationPlugin::filter_query(&self.configuration, &key, &self.schema) {
-                Err(QueryPlannerError::Unauthorized(unauthorized_paths)) => {
-                    let response = graphql::Response::builder()
-                        .data(Object::new())
-                        .errors(
-                            unauthorized_paths
-                                .into_iter()
-                                .map(|path| {
-                                    graphql::Error::builder()
-                                        .message("Unauthorized field or type")
-                                        .path(path)
-                                        .extension_code("UNAUTHORIZED_FIELD_OR_TYPE")
-                                        .build()
-                                })
-                                .collect(),
-                        )
-                        .build();
-                    return Ok(QueryPlannerContent::Response {
-                        response: Box::new(response),
-                    });
+        let (filter_res, reported_paths, unauthorized_variables) =
+            if self.enable_authorization_directives {
+                match AuthorizationPlugin::filter_query(&self.configuration, &key, &self.schema) {
+                    Err(QueryPlannerError::Unauthorized(unauthorized_paths)) => {
+                        let response = graphql::Response::builder()
+                            .data(Object::new())
+                            .errors(
+                                unauthorized_paths
+                                    .into_iter()
+                                    .map(|path| {
+                                        graphql::Error::builder()
+                                            .message("Unauthorized field or type")
+                                            .path(path)
+                                            .extension_code("UNAUTHORIZED_FIELD_OR_TYPE")
+                                            .build()
+                                    })
+                                    .collect(),
+                            )
+                            .build();
+                        return Ok(QueryPlannerContent::Response {
+                            response: Box::new(response),
+                        });
+                    }
+                    other => other?,
                 }
-                other => other?,
-            }
-        } else {
-            None
-        };
+            } else {
+                (None, vec![], vec![])
+            };
 
         let mut selections = self
             .parse_selections(
@@ -939,6 +943,8 @@ impl BridgeQueryPlanner {
                 &doc,
             )
             .await?;
+        selections.unauthorized.reported = reported_paths;
+        selections.unauthorized.variables = unauthorized_variables;
 
         if let Some((unauthorized_paths, new_doc)) = filter_res {
             key.filtered_query = new_doc.to_string();
@@ -1010,6 +1016,26 @@ impl BridgeQueryPlanner {
             selections.filtered_query = Some(Arc::new(filtered));
         }
 
+        // the query planner does not support `@stream`: streamed lists are planned as usual lists,
+        // and the items after `initialCount` are held back and fetched in chunks during execution
+        if selections
+            .operations
+            .iter()
+            .any(|operation| !operation.stream_fields.is_empty())
+        {
+            let new_doc = remove_stream_directives(self.schema.api_schema(), &doc.ast)
+                .map_err(|e| SpecError::TransformError(e.to_string()))?;
+            key.filtered_query = new_doc.to_string();
+            let executable_document = new_doc
+                .to_executable_validate(self.schema.api_schema())
+                .map_err(|e| SpecError::ValidationError(e.into()))?;
+            doc = Arc::new(ParsedDocumentInner {
+                executable: Arc::new(executable_document),
+                ast: new_doc,
+                hash: doc.hash.clone(),
+            });
+        }
+
         self.plan(
             key.original_query,
             key.filtered_query,
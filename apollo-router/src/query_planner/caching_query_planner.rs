@@ -149,11 +149,13 @@ where
         self.cache.in_memory_cache()
     }
 
+    /// Plans the operations of the persisted query list and the most used entries of the
+    /// previous cache, if any, and stores the results in the cache.
     pub(crate) async fn warm_up(
         &mut self,
         query_analysis: &QueryAnalysisLayer,
-        persisted_query_layer: &PersistedQueryLayer,
-        previous_cache: InMemoryCachePlanner,
+        persisted_query_layer: Option<&PersistedQueryLayer>,
+        previous_cache: Option<InMemoryCachePlanner>,
         count: Option<usize>,
         experimental_reuse_query_plans: bool,
     ) {
@@ -172,43 +174,47 @@ where
                 }),
         );
 
-        let mut cache_keys = {
-            let cache = previous_cache.lock().await;
-
-            let count = count.unwrap_or(cache.len() / 3);
-
-            cache
-                .iter()
-                .map(
-                    |(
-                        CachingQueryKey {
-                            query,
-                            operation,
-                            hash,
-                            metadata,
-                            plan_options,
-                            config_mode: _,
-                            sdl: _,
-                            introspection: _,
+        let mut cache_keys = match &previous_cache {
+            None => Vec::new(),
+            Some(previous_cache) => {
+                let cache = previous_cache.lock().await;
+
+                let count = count.unwrap_or(cache.len() / 3);
+
+                cache
+                    .iter()
+                    .map(
+                        |(
+                            CachingQueryKey {
+                                query,
+                                operation,
+                                hash,
+                                metadata,
+                                plan_options,
+                                config_mode: _,
+                                sdl: _,
+                                introspection: _,
+                            },
+                            _,
+                        )| WarmUpCachingQueryKey {
+                            query: query.clone(),
+                            operation: operation.clone(),
+                            hash: Some(hash.clone()),
+                            metadata: metadata.clone(),
+                            plan_options: plan_options.clone(),
+                            config_mode: self.config_mode.clone(),
+                            introspection: self.introspection,
                         },
-                        _,
-                    )| WarmUpCachingQueryKey {
-                        query: query.clone(),
-                        operation: operation.clone(),
-                        hash: Some(hash.clone()),
-                        metadata: metadata.clone(),
-                        plan_options: plan_options.clone(),
-                        config_mode: self.config_mode.clone(),
-                        introspection: self.introspection,
-                    },
-                )
-                .take(count)
-                .collect::<Vec<_>>()
+                    )
+                    .take(count)
+                    .collect::<Vec<_>>()
+            }
         };
 
         cache_keys.shuffle(&mut thread_rng());
 
-        let persisted_queries_operations = persisted_query_layer.all_operations();
+        let persisted_queries_operations =
+            persisted_query_layer.and_then(|layer| layer.all_operations());
 
         let capacity = cache_keys.len()
             + persisted_queries_operations
@@ -270,7 +276,7 @@ where
 
             if experimental_reuse_query_plans {
                 // if the query hash did not change with the schema update, we can reuse the previously cached entry
-                if let Some(hash) = hash {
+                if let (Some(hash), Some(previous_cache)) = (hash, &previous_cache) {
                     if hash == doc.hash {
                         if let Some(entry) =
                             { previous_cache.lock().await.get(&caching_key).cloned() }
@@ -38,6 +38,8 @@ use crate::query_planner::PARALLEL_SPAN_NAME;
 use crate::query_planner::SEQUENCE_SPAN_NAME;
 use crate::query_planner::SUBSCRIBE_SPAN_NAME;
 use crate::services::SubgraphServiceFactory;
+use crate::spec::query::stream::StreamedItems;
+use crate::spec::query::stream::StreamedLists;
 use crate::spec::Query;
 use crate::spec::Schema;
 use crate::Context;
@@ -56,6 +58,7 @@ impl QueryPlan {
         subscription_handle: Option<SubscriptionHandle>,
         subscription_config: &'a Option<SubscriptionConfig>,
         initial_value: Option<Value>,
+        streamed_lists: Option<&'a StreamedLists>,
     ) -> Response {
         let root = Path::empty();
 
@@ -76,6 +79,7 @@ impl QueryPlan {
                     subscription_handle: &subscription_handle,
                     subscription_config,
                     subgraph_schemas,
+                    streamed_lists,
                 },
                 &root,
                 &initial_value.unwrap_or_default(),
@@ -89,6 +93,68 @@ impl QueryPlan {
         Response::builder().data(value).errors(errors).build()
     }
 
+    #[allow(clippy::too_many_arguments)]
+    /// Execute the part of the plan fetching a chunk of items of a streamed list
+    ///
+    /// The items are inserted in the primary response before executing the plan, then returned
+    /// with the data fetched for them.
+    pub(crate) async fn execute_stream_items<'a>(
+        &self,
+        context: &'a Context,
+        service_factory: &'a Arc<SubgraphServiceFactory>,
+        supergraph_request: &'a Arc<http::Request<Request>>,
+        schema: &'a Arc<Schema>,
+        subgraph_schemas: &'a Arc<HashMap<String, Arc<Valid<apollo_compiler::Schema>>>>,
+        subscription_config: &'a Option<SubscriptionConfig>,
+        primary: &Value,
+        streamed_items: StreamedItems,
+    ) -> (Vec<Value>, Vec<Error>) {
+        let StreamedItems {
+            field, path, items, ..
+        } = streamed_items;
+        let Some(node) = self.root.stream_items_node(&field.path, &path) else {
+            // the items were entirely fetched with the list
+            return (items, Vec::new());
+        };
+
+        let mut value = primary.clone();
+        if let Err(e) = value.insert(&path, Value::Array(items)) {
+            return (Vec::new(), vec![e.to_graphql_error(Some(path))]);
+        }
+        let deferred_fetches = HashMap::new();
+        // the deferred fragments of the items are executed with them, nothing is sent here
+        let (sender, _) = mpsc::channel(1);
+        let (v, errors) = node
+            .execute_recursively(
+                &ExecutionParameters {
+                    context,
+                    service_factory,
+                    schema,
+                    supergraph_request,
+                    deferred_fetches: &deferred_fetches,
+                    query: &self.query,
+                    root_node: &self.root,
+                    subscription_handle: &None,
+                    subscription_config,
+                    subgraph_schemas,
+                    streamed_lists: None,
+                },
+                &Path::empty(),
+                &value,
+                sender,
+            )
+            .await;
+        value.deep_merge(v);
+
+        let mut items = Vec::new();
+        value.select_values_and_paths_mut(schema, &path, |_, list| {
+            if let Value::Array(list) = list {
+                items = std::mem::take(list);
+            }
+        });
+        (items, errors)
+    }
+
     pub fn contains_mutations(&self) -> bool {
         self.root.contains_mutations()
     }
@@ -110,6 +176,8 @@ pub(crate) struct ExecutionParameters<'a> {
     pub(crate) root_node: &'a PlanNode,
     pub(crate) subscription_handle: &'a Option<SubscriptionHandle>,
     pub(crate) subscription_config: &'a Option<SubscriptionConfig>,
+    /// Streamed lists holding back their items from the primary response
+    pub(crate) streamed_lists: Option<&'a StreamedLists>,
 }
 
 impl PlanNode {
@@ -298,6 +366,7 @@ impl PlanNode {
                                         subscription_handle: parameters.subscription_handle,
                                         subscription_config: parameters.subscription_config,
                                         subgraph_schemas: parameters.subgraph_schemas,
+                                        streamed_lists: parameters.streamed_lists,
                                     },
                                     current_dir,
                                     &value,
@@ -488,6 +557,8 @@ impl DeferredNode {
                             subscription_handle: &subscription_handle,
                             subscription_config: &subscription_config,
                             subgraph_schemas: &subgraph_schemas,
+                            // lists in deferred fragments are sent with them
+                            streamed_lists: None,
                         },
                         &Path::default(),
                         &value,
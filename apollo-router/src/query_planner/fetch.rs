@@ -518,8 +518,13 @@ impl FetchNode {
             );
         }
 
-        let (value, errors) =
+        let (mut value, errors) =
             self.response_at_path(parameters.schema, current_dir, paths, response);
+        // the items of streamed lists are fetched after the primary response, and so are the
+        // fragments deferred in them
+        if let Some(streamed_lists) = parameters.streamed_lists {
+            streamed_lists.hold_back(parameters.schema, &mut value);
+        }
         if let Some(id) = &self.id {
             if let Some(sender) = parameters.deferred_fetches.get(id.as_str()) {
                 tracing::info!(monotonic_counter.apollo.router.operations.defer.fetch = 1u64);
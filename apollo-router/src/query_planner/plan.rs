@@ -15,6 +15,7 @@ use crate::error::CacheResolverError;
 use crate::error::ValidationErrors;
 use crate::json_ext::Object;
 use crate::json_ext::Path;
+use crate::json_ext::PathElement;
 use crate::json_ext::Value;
 use crate::plugins::authorization::CacheKeyMetadata;
 use crate::query_planner::fetch::QueryHash;
@@ -71,6 +72,19 @@ impl QueryPlan {
         self.root.is_deferred(operation, variables, &self.query)
     }
 
+    /// Returns the deferred fragments executed with these variables, each one after the fragment
+    /// containing it
+    pub(crate) fn deferred_fragments(
+        &self,
+        operation: Option<&str>,
+        variables: &Object,
+    ) -> Vec<DeferredFragment> {
+        let mut fragments = Vec::new();
+        self.root
+            .deferred_fragments(operation, variables, &self.query, None, &mut fragments);
+        fragments
+    }
+
     pub(crate) fn is_subscription(&self, operation: Option<&str>) -> bool {
         match self.query.operation(operation) {
             Some(op) => matches!(op.kind(), OperationKind::Subscription),
@@ -207,6 +221,127 @@ impl PlanNode {
         }
     }
 
+    fn deferred_fragments(
+        &self,
+        operation: Option<&str>,
+        variables: &Object,
+        query: &Query,
+        parent: Option<&NodeStr>,
+        fragments: &mut Vec<DeferredFragment>,
+    ) {
+        match self {
+            Self::Sequence { nodes } | Self::Parallel { nodes } => {
+                for node in nodes {
+                    node.deferred_fragments(operation, variables, query, parent, fragments);
+                }
+            }
+            Self::Flatten(node) => node
+                .node
+                .deferred_fragments(operation, variables, query, parent, fragments),
+            Self::Fetch(..) | Self::Subscription { .. } => {}
+            Self::Defer { primary, deferred } => {
+                if let Some(node) = &primary.node {
+                    node.deferred_fragments(operation, variables, query, parent, fragments);
+                }
+                for deferred_node in deferred {
+                    fragments.push(DeferredFragment {
+                        label: deferred_node.label.clone(),
+                        parent: parent.cloned(),
+                        query_path: deferred_node.query_path.clone(),
+                    });
+                    if let Some(node) = &deferred_node.node {
+                        node.deferred_fragments(
+                            operation,
+                            variables,
+                            query,
+                            deferred_node.label.as_ref(),
+                            fragments,
+                        );
+                    }
+                }
+            }
+            Self::Condition {
+                if_clause,
+                else_clause,
+                condition,
+            } => {
+                let node = if query
+                    .variable_value(operation, condition.as_str(), variables)
+                    .map(|v| *v == Value::Bool(true))
+                    .unwrap_or(true)
+                {
+                    if_clause
+                } else {
+                    else_clause
+                };
+                if let Some(node) = node {
+                    node.deferred_fragments(operation, variables, query, parent, fragments);
+                }
+            }
+        }
+    }
+
+    /// Returns the part of the plan fetching the items of a streamed list
+    ///
+    /// `stream_path` is the path of the streamed field in the query, and `list_path` the path of
+    /// one of its lists in the response: the returned plan only fetches the items of that list.
+    /// Deferred fragments in the items are not deferred, as the items are sent once complete.
+    pub(crate) fn stream_items_node(&self, stream_path: &Path, list_path: &Path) -> Option<Self> {
+        match self {
+            Self::Sequence { nodes } => {
+                let nodes: Vec<_> = nodes
+                    .iter()
+                    .filter_map(|node| node.stream_items_node(stream_path, list_path))
+                    .collect();
+                (!nodes.is_empty()).then_some(Self::Sequence { nodes })
+            }
+            Self::Parallel { nodes } => {
+                let nodes: Vec<_> = nodes
+                    .iter()
+                    .filter_map(|node| node.stream_items_node(stream_path, list_path))
+                    .collect();
+                (!nodes.is_empty()).then_some(Self::Parallel { nodes })
+            }
+            Self::Flatten(FlattenNode { path, node }) => {
+                let items_path = stream_items_path(path, stream_path)?;
+                Some(Self::Flatten(FlattenNode {
+                    path: list_path.iter().chain(items_path).cloned().collect(),
+                    node: node.clone(),
+                }))
+            }
+            Self::Defer { primary, deferred } => {
+                let nodes: Vec<_> = primary
+                    .node
+                    .as_deref()
+                    .into_iter()
+                    .chain(deferred.iter().filter_map(|node| node.node.as_deref()))
+                    .filter_map(|node| node.stream_items_node(stream_path, list_path))
+                    .collect();
+                (!nodes.is_empty()).then_some(Self::Sequence { nodes })
+            }
+            Self::Condition {
+                condition,
+                if_clause,
+                else_clause,
+            } => {
+                let if_clause = if_clause
+                    .as_ref()
+                    .and_then(|node| node.stream_items_node(stream_path, list_path))
+                    .map(Box::new);
+                let else_clause = else_clause
+                    .as_ref()
+                    .and_then(|node| node.stream_items_node(stream_path, list_path))
+                    .map(Box::new);
+                (if_clause.is_some() || else_clause.is_some()).then(|| Self::Condition {
+                    condition: condition.clone(),
+                    if_clause,
+                    else_clause,
+                })
+            }
+            Self::Fetch(..) | Self::Subscription { .. } => None,
+        }
+    }
+
     /// Iteratively populate a Vec of QueryHashes representing Fetches in this plan.
     ///
     /// Do not include any operations which contain "requires" elements.
@@ -553,6 +688,31 @@ impl PlanNode {
     }
 }
 
+/// Returns the part of a flatten path under the items of a streamed list, starting at the items
+fn stream_items_path<'a>(path: &'a Path, stream_path: &Path) -> Option<&'a [PathElement]> {
+    // the type conditions of the path are not in the query path of the streamed list
+    let mut elements = path
+        .iter()
+        .enumerate()
+        .filter(|(_, element)| match element {
+            PathElement::Key(key, _) => !key.is_empty(),
+            PathElement::Fragment(_) => false,
+            PathElement::Index(_) | PathElement::Flatten(_) => true,
+        });
+    for expected in stream_path.iter() {
+        let (_, element) = elements.next()?;
+        match (expected, element) {
+            (PathElement::Key(expected, _), PathElement::Key(key, _)) if expected == key => {}
+            (PathElement::Flatten(_), PathElement::Flatten(_)) => {}
+            _ => return None,
+        }
+    }
+    match elements.next() {
+        Some((index, PathElement::Flatten(_))) => Some(&path.0[index..]),
+        _ => None,
+    }
+}
+
 /// A flatten node.
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -599,6 +759,17 @@ pub(crate) struct DeferredNode {
     pub(crate) node: Option<Arc<PlanNode>>,
 }
 
+/// A deferred fragment of a plan, as executed for a request.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct DeferredFragment {
+    /// The label of the deferred node.
+    pub(crate) label: Option<NodeStr>,
+    /// The label of the deferred fragment containing this one, `None` for the primary response.
+    pub(crate) parent: Option<NodeStr>,
+    /// Path to the @defer this correspond to.
+    pub(crate) query_path: Path,
+}
+
 /// A deferred node.
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
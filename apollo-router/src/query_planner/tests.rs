@@ -121,6 +121,7 @@ async fn mock_subgraph_service_withf_panics_should_be_reported_as_service_closed
             None,
             &None,
             None,
+            None,
         )
         .await;
     assert_eq!(result.errors.len(), 1);
@@ -183,6 +184,7 @@ async fn fetch_includes_operation_name() {
             None,
             &None,
             None,
+            None,
         )
         .await;
 
@@ -242,6 +244,7 @@ async fn fetch_makes_post_requests() {
             None,
             &None,
             None,
+            None,
         )
         .await;
 
@@ -395,6 +398,7 @@ async fn defer() {
             None,
             &None,
             None,
+            None,
         )
         .await;
 
@@ -504,6 +508,7 @@ async fn defer_if_condition() {
             None,
             &None,
             None,
+            None,
         )
         .await;
 
@@ -527,6 +532,7 @@ async fn defer_if_condition() {
             None,
             &None,
             None,
+            None,
         )
         .await;
 
@@ -559,6 +565,7 @@ async fn defer_if_condition() {
             None,
             &None,
             None,
+            None,
         )
         .await;
     insta::assert_json_snapshot!(defer_disabled);
@@ -681,6 +688,7 @@ async fn dependent_mutations() {
             None,
             &None,
             None,
+            None,
         )
         .await;
 }
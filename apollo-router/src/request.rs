@@ -162,87 +162,64 @@ impl Request {
     }
 
     /// Convert encoded URL query string parameters (also known as "search
-    /// params") into a GraphQL [`Request`].
+    /// params") into a batch of GraphQL [`Request`]s.
     ///
     /// An error will be produced in the event that the query string parameters
-    /// cannot be turned into a valid GraphQL `Request`.
+    /// cannot be decoded. Each entry of the batch that cannot be turned into a
+    /// valid GraphQL `Request` is returned as an error.
     pub(crate) fn batch_from_urlencoded_query(
         url_encoded_query: String,
-    ) -> Result<Vec<Request>, serde_json::Error> {
+    ) -> Result<Vec<Result<Request, serde_json::Error>>, serde_json::Error> {
         let value: serde_json::Value = serde_urlencoded::from_bytes(url_encoded_query.as_bytes())
             .map_err(serde_json::Error::custom)?;
 
-        Request::process_query_values(&value)
+        Ok(Request::process_batch_values(
+            &value,
+            Request::process_value,
+        ))
     }
 
-    /// Convert Bytes into a GraphQL [`Request`].
+    /// Convert Bytes into a batch of GraphQL [`Request`]s.
     ///
-    /// An error will be produced in the event that the bytes array cannot be
-    /// turned into a valid GraphQL `Request`.
-    pub(crate) fn batch_from_bytes(bytes: &[u8]) -> Result<Vec<Request>, serde_json::Error> {
+    /// An error will be produced in the event that the bytes array is not valid
+    /// JSON. Each entry of the batch that cannot be turned into a valid GraphQL
+    /// `Request` is returned as an error.
+    pub(crate) fn batch_from_bytes(
+        bytes: &[u8],
+    ) -> Result<Vec<Result<Request, serde_json::Error>>, serde_json::Error> {
         let value: serde_json::Value =
             serde_json::from_slice(bytes).map_err(serde_json::Error::custom)?;
 
-        Request::process_batch_values(&value)
+        Ok(Request::process_batch_values(&value, |entry| {
+            let bytes = serde_json::to_vec(entry)?;
+            Request::deserialize_from_bytes(&bytes.into())
+        }))
     }
 
-    fn allocate_result_array(value: &serde_json::Value) -> Vec<Request> {
+    fn process_batch_values(
+        value: &serde_json::Value,
+        process_entry: impl Fn(&serde_json::Value) -> Result<Request, serde_json::Error>,
+    ) -> Vec<Result<Request, serde_json::Error>> {
         match value.as_array() {
-            Some(array) => Vec::with_capacity(array.len()),
-            None => Vec::with_capacity(1),
-        }
-    }
-
-    fn process_batch_values(value: &serde_json::Value) -> Result<Vec<Request>, serde_json::Error> {
-        let mut result = Request::allocate_result_array(value);
-
-        if value.is_array() {
-            tracing::info!(
-                histogram.apollo.router.operations.batching.size = result.len() as f64,
-                mode = %BatchingMode::BatchHttpLink // Only supported mode right now
-            );
-
-            tracing::info!(
-                monotonic_counter.apollo.router.operations.batching = 1u64,
-                mode = %BatchingMode::BatchHttpLink // Only supported mode right now
-            );
-            for entry in value
-                .as_array()
-                .expect("We already checked that it was an array")
-            {
-                let bytes = serde_json::to_vec(entry)?;
-                result.push(Request::deserialize_from_bytes(&bytes.into())?);
+            Some(entries) => {
+                // Only supported mode right now
+                let mode = BatchingMode::BatchHttpLink.to_string();
+                u64_histogram!(
+                    "apollo.router.operations.batching.size",
+                    "Number of operations in batches",
+                    entries.len() as u64,
+                    "mode" = mode.clone()
+                );
+                u64_counter!(
+                    "apollo.router.operations.batching",
+                    "Total number of batches",
+                    1,
+                    "mode" = mode
+                );
+                entries.iter().map(process_entry).collect()
             }
-        } else {
-            let bytes = serde_json::to_vec(value)?;
-            result.push(Request::deserialize_from_bytes(&bytes.into())?);
-        }
-        Ok(result)
-    }
-
-    fn process_query_values(value: &serde_json::Value) -> Result<Vec<Request>, serde_json::Error> {
-        let mut result = Request::allocate_result_array(value);
-
-        if value.is_array() {
-            tracing::info!(
-                histogram.apollo.router.operations.batching.size = result.len() as f64,
-                mode = "batch_http_link" // Only supported mode right now
-            );
-
-            tracing::info!(
-                monotonic_counter.apollo.router.operations.batching = 1u64,
-                mode = "batch_http_link" // Only supported mode right now
-            );
-            for entry in value
-                .as_array()
-                .expect("We already checked that it was an array")
-            {
-                result.push(Request::process_value(entry)?);
-            }
-        } else {
-            result.push(Request::process_value(value)?)
+            None => vec![process_entry(value)],
         }
-        Ok(result)
     }
 
     fn process_value(value: &serde_json::Value) -> Result<Request, serde_json::Error> {
@@ -51,6 +51,14 @@ pub struct Response {
 
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
     pub incremental: Vec<IncrementalResponse>,
+
+    /// The deferred fragments announced by this payload, in the current incremental delivery format.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub pending: Vec<PendingResponse>,
+
+    /// The deferred fragments completed by this payload, in the current incremental delivery format.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub completed: Vec<CompletedResponse>,
 }
 
 #[buildstructor::buildstructor]
@@ -69,6 +77,8 @@ impl Response {
         subscribed: Option<bool>,
         incremental: Vec<IncrementalResponse>,
         created_at: Option<Instant>,
+        pending: Vec<PendingResponse>,
+        completed: Vec<CompletedResponse>,
     ) -> Self {
         Self {
             label,
@@ -80,6 +90,8 @@ impl Response {
             subscribed,
             incremental,
             created_at,
+            pending,
+            completed,
         }
     }
 
@@ -187,6 +199,8 @@ impl Response {
             subscribed: None,
             incremental,
             created_at: None,
+            pending: Vec::new(),
+            completed: Vec::new(),
         })
     }
 }
@@ -216,6 +230,18 @@ pub struct IncrementalResponse {
     /// The optional graphql extensions.
     #[serde(skip_serializing_if = "Object::is_empty", default)]
     pub extensions: Object,
+
+    /// The list items sent by a stream directive.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub items: Option<Vec<Value>>,
+
+    /// The id of the pending fragment this patch belongs to, in the current incremental delivery format.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub id: Option<String>,
+
+    /// The path of the data relative to the path of the pending fragment, in the current incremental delivery format.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub sub_path: Option<Path>,
 }
 
 #[buildstructor::buildstructor]
@@ -228,6 +254,9 @@ impl IncrementalResponse {
         path: Option<Path>,
         errors: Vec<Error>,
         extensions: Map<ByteString, Value>,
+        items: Option<Vec<Value>>,
+        id: Option<String>,
+        sub_path: Option<Path>,
     ) -> Self {
         Self {
             label,
@@ -235,6 +264,9 @@ impl IncrementalResponse {
             path,
             errors,
             extensions,
+            items,
+            id,
+            sub_path,
         }
     }
 
@@ -244,6 +276,54 @@ impl IncrementalResponse {
     }
 }
 
+/// A deferred fragment or streamed list announced in the current incremental delivery format.
+/// Its data is sent later in incremental patches with the same id.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct PendingResponse {
+    /// The id referenced by the patches and the completion of this fragment.
+    pub id: String,
+
+    /// The path of the object the fragment applies to.
+    pub path: Path,
+
+    /// The label that was passed to the defer or stream directive.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub label: Option<String>,
+}
+
+#[buildstructor::buildstructor]
+impl PendingResponse {
+    /// Constructor
+    #[builder(visibility = "pub")]
+    fn new(id: String, path: Path, label: Option<String>) -> Self {
+        Self { id, path, label }
+    }
+}
+
+/// The completion of a pending fragment or streamed list, in the current incremental delivery format.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct CompletedResponse {
+    /// The id of the pending fragment.
+    pub id: String,
+
+    /// The errors that prevented the fragment from being delivered.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub errors: Vec<Error>,
+}
+
+#[buildstructor::buildstructor]
+impl CompletedResponse {
+    /// Constructor
+    #[builder(visibility = "pub")]
+    fn new(id: String, errors: Vec<Error>) -> Self {
+        Self { id, errors }
+    }
+}
+
 pub(crate) trait ResponseVisitor {
     fn visit_field(
         &mut self,
@@ -33,6 +33,8 @@ use crate::plugins::traffic_shaping::APOLLO_TRAFFIC_SHAPING;
 use crate::query_planner::BridgeQueryPlannerPool;
 use crate::services::apollo_graph_reference;
 use crate::services::apollo_key;
+use crate::services::connector;
+use crate::services::connector::Connector;
 use crate::services::http::HttpClientServiceFactory;
 use crate::services::layers::persisted_queries::PersistedQueryLayer;
 use crate::services::layers::query_analysis::QueryAnalysisLayer;
@@ -123,6 +125,12 @@ pub(crate) trait RouterFactory:
     type Future: Send;
 
     fn web_endpoints(&self) -> MultiMap<ListenAddr, Endpoint>;
+
+    /// Returns false while the router is still getting ready to serve traffic, e.g. while it
+    /// warms up its query plan cache
+    fn is_ready(&self) -> bool {
+        true
+    }
 }
 
 /// Factory for creating a RouterFactory
@@ -228,7 +236,7 @@ impl YamlRouterFactory {
         initial_telemetry_plugin: Option<Box<dyn DynPlugin>>,
         extra_plugins: Option<Vec<(String, Box<dyn DynPlugin>)>>,
     ) -> Result<RouterCreator, BoxError> {
-        let mut supergraph_creator = self
+        let supergraph_creator = self
             .inner_create_supergraph(
                 configuration.clone(),
                 schema,
@@ -243,14 +251,25 @@ impl YamlRouterFactory {
 
         let persisted_query_layer = Arc::new(PersistedQueryLayer::new(&configuration).await?);
 
+        let prewarm = &configuration
+            .persisted_queries
+            .experimental_prewarm_query_plan_cache;
+        let prewarm_in_background = persisted_query_layer.manifest_poller.is_some()
+            && if previous_router.is_some() {
+                prewarm.on_reload
+            } else {
+                prewarm.on_startup
+            };
+
         if let Some(previous_router) = previous_router {
             let previous_cache = previous_router.previous_cache();
 
             supergraph_creator
                 .warm_up_query_planner(
                     &query_analysis_layer,
-                    &persisted_query_layer,
-                    previous_cache,
+                    // the persisted queries will be planned in the background instead
+                    (!prewarm_in_background).then_some(&*persisted_query_layer),
+                    Some(previous_cache),
                     configuration.supergraph.query_planning.warmed_up_queries,
                     configuration
                         .supergraph
@@ -259,13 +278,23 @@ impl YamlRouterFactory {
                 )
                 .await;
         };
-        RouterCreator::new(
+        let mut router_creator = RouterCreator::new(
             query_analysis_layer,
             persisted_query_layer,
             Arc::new(supergraph_creator),
-            configuration,
+            configuration.clone(),
         )
-        .await
+        .await?;
+
+        if prewarm_in_background {
+            // readiness is only held back at startup: on reload, the previous router
+            // was already serving traffic
+            router_creator.warm_up_persisted_queries_in_background(
+                previous_router.is_none() && prewarm.wait_for_readiness,
+            );
+        }
+
+        Ok(router_creator)
     }
 
     pub(crate) async fn inner_create_supergraph<'a>(
@@ -369,7 +398,7 @@ impl YamlRouterFactory {
 
 pub(crate) async fn create_subgraph_services(
     plugins: &Arc<Plugins>,
-    schema: &Schema,
+    schema: &Arc<Schema>,
     configuration: &Configuration,
 ) -> Result<
     IndexMap<
@@ -408,8 +437,31 @@ pub(crate) async fn create_subgraph_services(
         .and_then(|plugin| (*plugin.1).as_any().downcast_ref::<TrafficShaping>())
         .expect("traffic shaping should always be part of the plugin list");
 
+    for name in configuration.connectors.subgraphs.keys() {
+        if !schema.subgraphs().any(|(subgraph, _)| subgraph == name) {
+            tracing::warn!("connector '{name}' does not match any subgraph of the schema");
+        }
+    }
+    // the base URL of the connectors defined in the schema is set in the configuration
+    for name in connector::directives::subgraphs(schema.supergraph_schema()) {
+        if !configuration.connectors.subgraphs.contains_key(&name) {
+            return Err(format!(
+                "the schema defines connector endpoints for the subgraph '{name}', but connectors.subgraphs.{name}.base_url is not set"
+            )
+            .into());
+        }
+    }
+
     let mut subgraph_services = IndexMap::new();
     for (name, _) in schema.subgraphs() {
+        let connector = configuration
+            .connectors
+            .subgraphs
+            .get(name)
+            .map(|source| Connector::new(name, source, schema.clone()))
+            .transpose()?
+            .map(Arc::new);
+
         let http_service = crate::services::http::HttpClientService::from_config(
             name,
             configuration,
@@ -427,7 +479,8 @@ pub(crate) async fn create_subgraph_services(
                 configuration,
                 subscription_plugin_conf.clone(),
                 http_service_factory,
-            )?,
+            )?
+            .with_connector(connector),
         );
         subgraph_services.insert(name.clone(), subgraph_service);
     }
@@ -729,6 +782,7 @@ mod test {
     use std::error::Error;
     use std::fmt;
     use std::sync::Arc;
+    use std::time::Duration;
 
     use schemars::JsonSchema;
     use serde::Deserialize;
@@ -736,12 +790,16 @@ mod test {
     use tower_http::BoxError;
 
     use crate::configuration::Configuration;
+    use crate::configuration::PersistedQueries;
+    use crate::configuration::PersistedQueriesPrewarmQueryPlanCache;
     use crate::plugin::Plugin;
     use crate::plugin::PluginInit;
     use crate::register_plugin;
     use crate::router_factory::inject_schema_id;
+    use crate::router_factory::RouterFactory;
     use crate::router_factory::RouterSuperServiceFactory;
     use crate::router_factory::YamlRouterFactory;
+    use crate::test_harness::mocks::persisted_queries::*;
 
     #[derive(Debug)]
     struct PluginError;
@@ -850,6 +908,41 @@ mod test {
         assert!(service.is_err())
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_prewarm_persisted_queries_holds_readiness() {
+        let (_id, _body, manifest) = fake_manifest();
+        let (_mock_guard, uplink_config) = mock_pq_uplink(&manifest).await;
+        let config = Configuration::fake_builder()
+            .persisted_query(
+                PersistedQueries::builder()
+                    .enabled(true)
+                    .experimental_prewarm_query_plan_cache(
+                        PersistedQueriesPrewarmQueryPlanCache::builder()
+                            .on_startup(true)
+                            .wait_for_readiness(true)
+                            .build(),
+                    )
+                    .build(),
+            )
+            .uplink(uplink_config)
+            .build()
+            .unwrap();
+        let schema = include_str!("testdata/supergraph.graphql");
+
+        let router_creator = YamlRouterFactory
+            .create(false, Arc::new(config), schema.to_string(), None, None)
+            .await
+            .unwrap();
+
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while !router_creator.is_ready() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("the router should be ready once the persisted queries are planned");
+    }
+
     async fn create_service(config: Configuration) -> Result<(), BoxError> {
         let schema = include_str!("testdata/supergraph.graphql");
 
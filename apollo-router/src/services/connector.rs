@@ -0,0 +1,681 @@
+//! REST connectors, resolving the operations sent to a subgraph with calls to a REST API
+//!
+//! Each root field and each entity type is resolved by an endpoint of the REST API. The JSON
+//! response of the endpoint is mapped by a [`JsonSelection`], then shaped to the selections of the
+//! subgraph operation, so that the query planner and the rest of the pipeline see a regular
+//! subgraph response.
+//!
+//! Endpoints are defined in the configuration, or with the `@connector` directive in the schema
+//! (see [`directives`]).
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use apollo_compiler::ast;
+use futures::future::join_all;
+use futures::stream;
+use futures::StreamExt;
+use http::header::ACCEPT;
+use http::header::CONTENT_TYPE;
+use http::HeaderMap;
+use http::HeaderName;
+use http::HeaderValue;
+use hyper::Body;
+use indexmap::IndexMap;
+use serde_json_bytes::ByteString;
+use serde_json_bytes::Map;
+use serde_json_bytes::Value;
+use tower::BoxError;
+use tower::ServiceExt;
+use tracing::Instrument;
+
+use self::selection::JsonSelection;
+use self::template::UrlTemplate;
+use super::http::HttpClientServiceFactory;
+use super::http::HttpRequest;
+use super::subgraph_service::APPLICATION_JSON_HEADER_VALUE;
+use crate::configuration::ConnectorEndpoint;
+use crate::configuration::ConnectorHeader;
+use crate::configuration::ConnectorSource;
+use crate::error::FetchError;
+use crate::graphql;
+use crate::json_ext::Object;
+use crate::json_ext::Path;
+use crate::json_ext::PathElement;
+use crate::services::SubgraphRequest;
+use crate::services::SubgraphResponse;
+use crate::spec::query::parse_hir_value;
+use crate::spec::Schema;
+use crate::Context;
+
+pub(crate) mod directives;
+mod selection;
+mod template;
+#[cfg(test)]
+mod tests;
+
+const ENTITIES: &str = "_entities";
+const REPRESENTATIONS: &str = "representations";
+const TYPENAME: &str = "__typename";
+
+/// REST API replacing a subgraph
+pub(crate) struct Connector {
+    name: String,
+    base_url: String,
+    headers: Vec<Header>,
+    /// Endpoints keyed by `Type.field`
+    fields: HashMap<String, Endpoint>,
+    /// Endpoints keyed by entity type
+    entities: HashMap<String, Endpoint>,
+    /// Maximum number of concurrent calls resolving entities
+    max_concurrent_requests: usize,
+    schema: Arc<Schema>,
+}
+
+struct Endpoint {
+    method: http::Method,
+    path: UrlTemplate,
+    headers: Vec<Header>,
+    body: Option<JsonSelection>,
+    selection: JsonSelection,
+}
+
+struct Header {
+    name: HeaderName,
+    source: HeaderSource,
+}
+
+enum HeaderSource {
+    Value(HeaderValue),
+    /// Copied from this header of the client request
+    Propagate(HeaderName),
+}
+
+impl Connector {
+    pub(crate) fn new(
+        name: &str,
+        source: &ConnectorSource,
+        schema: Arc<Schema>,
+    ) -> Result<Self, BoxError> {
+        let supergraph_schema = schema.supergraph_schema();
+        let schema_endpoints = directives::endpoints(supergraph_schema, name)?;
+        let defined_twice = |key: &String| {
+            format!("connector '{name}': '{key}' is defined both in the configuration and in the schema")
+        };
+        if let Some(key) = source
+            .fields
+            .keys()
+            .find(|key| schema_endpoints.fields.contains_key(*key))
+        {
+            return Err(defined_twice(key).into());
+        }
+        if let Some(key) = source
+            .entities
+            .keys()
+            .find(|key| schema_endpoints.entities.contains_key(*key))
+        {
+            return Err(defined_twice(key).into());
+        }
+
+        let mut fields = HashMap::new();
+        for (coordinate, endpoint) in source.fields.iter().chain(&schema_endpoints.fields) {
+            let (type_name, field_name) = coordinate.split_once('.').ok_or_else(|| {
+                format!("connector '{name}': invalid field '{coordinate}', expected `Type.field`")
+            })?;
+            if supergraph_schema.type_field(type_name, field_name).is_err() {
+                return Err(format!(
+                    "connector '{name}': the field '{coordinate}' is not defined in the schema"
+                )
+                .into());
+            }
+            fields.insert(
+                coordinate.clone(),
+                Endpoint::new(name, coordinate, endpoint)?,
+            );
+        }
+
+        let mut entities = HashMap::new();
+        for (type_name, endpoint) in source.entities.iter().chain(&schema_endpoints.entities) {
+            if !supergraph_schema.types.contains_key(type_name.as_str()) {
+                return Err(format!(
+                    "connector '{name}': the entity type '{type_name}' is not defined in the schema"
+                )
+                .into());
+            }
+            entities.insert(type_name.clone(), Endpoint::new(name, type_name, endpoint)?);
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            base_url: source.base_url.trim_end_matches('/').to_string(),
+            headers: parse_headers(name, &source.headers)?,
+            fields,
+            entities,
+            max_concurrent_requests: source.max_concurrent_requests.get(),
+            schema,
+        })
+    }
+}
+
+impl Endpoint {
+    fn new(connector: &str, key: &str, endpoint: &ConnectorEndpoint) -> Result<Self, BoxError> {
+        let invalid = |what: &str, error: String| {
+            format!("connector '{connector}', endpoint '{key}': invalid {what}: {error}")
+        };
+        Ok(Self {
+            method: endpoint.method.into(),
+            path: UrlTemplate::parse(&endpoint.path).map_err(|error| invalid("path", error))?,
+            headers: parse_headers(connector, &endpoint.headers)?,
+            body: endpoint
+                .body
+                .as_deref()
+                .map(JsonSelection::parse)
+                .transpose()
+                .map_err(|error| invalid("body", error))?,
+            selection: JsonSelection::parse(&endpoint.selection)
+                .map_err(|error| invalid("selection", error))?,
+        })
+    }
+}
+
+fn parse_headers(connector: &str, headers: &[ConnectorHeader]) -> Result<Vec<Header>, BoxError> {
+    headers
+        .iter()
+        .map(|header| -> Result<Header, BoxError> {
+            let name = HeaderName::try_from(header.name.as_str()).map_err(|_| {
+                format!("connector '{connector}': invalid header name '{}'", header.name)
+            })?;
+            let source = match (&header.value, &header.from) {
+                (Some(_), Some(_)) => {
+                    return Err(format!(
+                        "connector '{connector}': the header '{}' cannot have both a value and a source",
+                        header.name
+                    )
+                    .into())
+                }
+                (Some(value), None) => {
+                    HeaderSource::Value(HeaderValue::try_from(value.as_str()).map_err(|_| {
+                        format!(
+                            "connector '{connector}': invalid value for the header '{}'",
+                            header.name
+                        )
+                    })?)
+                }
+                (None, Some(from)) => {
+                    HeaderSource::Propagate(HeaderName::try_from(from.as_str()).map_err(|_| {
+                        format!("connector '{connector}': invalid header name '{from}'")
+                    })?)
+                }
+                (None, None) => HeaderSource::Propagate(name.clone()),
+            };
+            Ok(Header { name, source })
+        })
+        .collect()
+}
+
+/// Resolves a subgraph request with calls to the REST API
+pub(crate) async fn call(
+    connector: Arc<Connector>,
+    request: SubgraphRequest,
+    client_factory: HttpClientServiceFactory,
+) -> Result<SubgraphResponse, BoxError> {
+    let SubgraphRequest {
+        supergraph_request,
+        subgraph_request,
+        context,
+        ..
+    } = request;
+    let body = subgraph_request.body();
+    let malformed = |reason: String| FetchError::MalformedRequest { reason };
+
+    let document = ast::Document::parse(body.query.as_deref().unwrap_or_default(), "query.graphql")
+        .map_err(|_| {
+            malformed(format!(
+                "cannot parse the operation sent to the connector '{}'",
+                connector.name
+            ))
+        })?;
+    let operation = document
+        .definitions
+        .iter()
+        .find_map(|definition| match definition {
+            ast::Definition::OperationDefinition(operation)
+                if body.operation_name.is_none()
+                    || operation.name.as_ref().map(|name| name.as_str())
+                        == body.operation_name.as_deref() =>
+            {
+                Some(operation)
+            }
+            _ => None,
+        })
+        .ok_or_else(|| malformed("the operation was not found".to_string()))?;
+    if operation.operation_type == ast::OperationType::Subscription {
+        return Err(malformed(format!(
+            "subscriptions are not supported by the connector '{}'",
+            connector.name
+        ))
+        .into());
+    }
+    let root_type = connector
+        .schema
+        .supergraph_schema()
+        .root_operation(operation.operation_type)
+        .ok_or_else(|| malformed("the schema has no root type for the operation".to_string()))?
+        .as_str();
+
+    let execution = Execution {
+        connector: &connector,
+        fragments: document
+            .definitions
+            .iter()
+            .filter_map(|definition| match definition {
+                ast::Definition::FragmentDefinition(fragment) => {
+                    Some((fragment.name.as_str(), &**fragment))
+                }
+                _ => None,
+            })
+            .collect(),
+        variables: &body.variables,
+        client_headers: supergraph_request.headers(),
+        context: &context,
+        client_factory: &client_factory,
+    };
+
+    let fields = execution.collect_fields(&[operation.selection_set.as_slice()], root_type);
+    let results = if operation.operation_type == ast::OperationType::Mutation {
+        // mutation fields are executed in order
+        let mut results = Vec::with_capacity(fields.len());
+        for (key, fields) in &fields {
+            results.push(execution.resolve_root_field(root_type, *key, fields).await);
+        }
+        results
+    } else {
+        join_all(
+            fields
+                .iter()
+                .map(|(key, fields)| execution.resolve_root_field(root_type, *key, fields)),
+        )
+        .await
+    };
+
+    let mut data = Object::new();
+    let mut errors = Vec::new();
+    for (key, value, field_errors) in results {
+        data.insert(ByteString::from(key), value);
+        errors.extend(field_errors);
+    }
+
+    Ok(SubgraphResponse::builder()
+        .data(Value::Object(data))
+        .errors(errors)
+        .extensions(Object::default())
+        .context(context)
+        .build())
+}
+
+struct Execution<'a> {
+    connector: &'a Connector,
+    fragments: HashMap<&'a str, &'a ast::FragmentDefinition>,
+    variables: &'a Object,
+    client_headers: &'a HeaderMap,
+    context: &'a Context,
+    client_factory: &'a HttpClientServiceFactory,
+}
+
+impl<'a> Execution<'a> {
+    async fn resolve_root_field(
+        &self,
+        root_type: &str,
+        key: &'a str,
+        fields: &[&'a ast::Field],
+    ) -> (&'a str, Value, Vec<graphql::Error>) {
+        let path = Path(vec![PathElement::Key(key.to_string(), None)]);
+        let field = fields[0];
+        let (value, errors) = match field.name.as_str() {
+            TYPENAME => (Value::String(root_type.into()), Vec::new()),
+            ENTITIES => self.resolve_entities(fields, path).await,
+            name => {
+                let coordinate = format!("{root_type}.{name}");
+                match self.connector.fields.get(&coordinate) {
+                    Some(endpoint) => {
+                        let input = input("$args", Value::Object(self.arguments(field)));
+                        match self.fetch(endpoint, &input).await {
+                            Ok(value) => {
+                                let field_type = self.field_type(root_type, name);
+                                (self.complete(value, field_type, fields), Vec::new())
+                            }
+                            Err(error) => (Value::Null, vec![error.to_graphql_error(Some(path))]),
+                        }
+                    }
+                    None => {
+                        let error = FetchError::MalformedRequest {
+                            reason: format!(
+                                "the connector '{}' has no endpoint for '{coordinate}'",
+                                self.connector.name
+                            ),
+                        };
+                        (Value::Null, vec![error.to_graphql_error(Some(path))])
+                    }
+                }
+            }
+        };
+        (key, value, errors)
+    }
+
+    async fn resolve_entities(
+        &self,
+        fields: &[&'a ast::Field],
+        path: Path,
+    ) -> (Value, Vec<graphql::Error>) {
+        let representations = match self.arguments(fields[0]).remove(REPRESENTATIONS) {
+            Some(Value::Array(representations)) => representations,
+            _ => {
+                let error = FetchError::MalformedRequest {
+                    reason: "the entity representations are missing".to_string(),
+                };
+                return (Value::Null, vec![error.to_graphql_error(Some(path))]);
+            }
+        };
+
+        let path = &path;
+        // the entities are fetched concurrently, within the limit of the connector, then put back in
+        // the order of the representations
+        let mut results: Vec<_> = stream::iter(representations.into_iter().enumerate())
+            .map(|(index, representation)| async move {
+                let mut path = path.clone();
+                path.push(PathElement::Index(index));
+
+                let type_name = representation
+                    .as_object()
+                    .and_then(|representation| representation.get(TYPENAME))
+                    .and_then(|type_name| type_name.as_str())
+                    .unwrap_or_default()
+                    .to_string();
+                let Some(endpoint) = self.connector.entities.get(&type_name) else {
+                    let error = FetchError::MalformedRequest {
+                        reason: format!(
+                            "the connector '{}' has no endpoint for the entity type '{type_name}'",
+                            self.connector.name
+                        ),
+                    };
+                    return (index, Value::Null, Some(error.to_graphql_error(Some(path))));
+                };
+
+                match self.fetch(endpoint, &input("$this", representation)).await {
+                    Ok(value) => (index, self.complete(value, Some(&type_name), fields), None),
+                    Err(error) => (index, Value::Null, Some(error.to_graphql_error(Some(path)))),
+                }
+            })
+            .buffer_unordered(self.connector.max_concurrent_requests)
+            .collect()
+            .await;
+        results.sort_by_key(|(index, _, _)| *index);
+
+        let (entities, errors): (Vec<_>, Vec<_>) = results
+            .into_iter()
+            .map(|(_, entity, error)| (entity, error))
+            .unzip();
+        (
+            Value::Array(entities),
+            errors.into_iter().flatten().collect(),
+        )
+    }
+
+    /// Calls an endpoint, and maps its response with the endpoint's selection
+    async fn fetch(&self, endpoint: &Endpoint, input: &Value) -> Result<Value, FetchError> {
+        let service = &self.connector.name;
+        let url = format!(
+            "{}{}",
+            self.connector.base_url,
+            endpoint.path.interpolate(input)
+        );
+        let http_error =
+            |status_code: Option<u16>, reason: String| FetchError::SubrequestHttpError {
+                status_code,
+                service: service.clone(),
+                reason,
+            };
+
+        let body = match &endpoint.body {
+            Some(selection) => Body::from(serde_json::to_vec(&selection.apply(input)).map_err(
+                |error| FetchError::MalformedRequest {
+                    reason: error.to_string(),
+                },
+            )?),
+            None => Body::empty(),
+        };
+        let mut request = http::Request::builder()
+            .method(endpoint.method.clone())
+            .uri(url.as_str())
+            .body(body)
+            .map_err(|error| FetchError::MalformedRequest {
+                reason: format!("invalid request to '{url}': {error}"),
+            })?;
+
+        let headers = request.headers_mut();
+        headers.insert(ACCEPT, APPLICATION_JSON_HEADER_VALUE.clone());
+        if endpoint.body.is_some() {
+            headers.insert(CONTENT_TYPE, APPLICATION_JSON_HEADER_VALUE.clone());
+        }
+        for header in self.connector.headers.iter().chain(&endpoint.headers) {
+            let value = match &header.source {
+                HeaderSource::Value(value) => Some(value),
+                HeaderSource::Propagate(from) => self.client_headers.get(from),
+            };
+            if let Some(value) = value {
+                headers.insert(header.name.clone(), value.clone());
+            }
+        }
+
+        let connector_request_span = tracing::info_span!("connector_request",
+            "otel.kind" = "CLIENT",
+            "http.method" = %endpoint.method,
+            "http.url" = %url,
+            "apollo.subgraph.name" = %service,
+        );
+        let response = self
+            .client_factory
+            .create(service)
+            .oneshot(HttpRequest {
+                http_request: request,
+                context: self.context.clone(),
+            })
+            .instrument(connector_request_span)
+            .await
+            .map_err(|error| http_error(None, error.to_string()))?;
+
+        let (parts, body) = response.http_response.into_parts();
+        let status_code = Some(parts.status.as_u16());
+        let body = hyper::body::to_bytes(body)
+            .await
+            .map_err(|error| http_error(status_code, error.to_string()))?;
+        if !parts.status.is_success() {
+            return Err(http_error(
+                status_code,
+                format!("{} {url} returned {}", endpoint.method, parts.status),
+            ));
+        }
+
+        let response = if body.is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_slice(&body).map_err(|error| {
+                FetchError::SubrequestMalformedResponse {
+                    service: service.clone(),
+                    reason: error.to_string(),
+                }
+            })?
+        };
+        Ok(endpoint.selection.apply(&response))
+    }
+
+    fn field_type(&self, type_name: &str, field_name: &str) -> Option<&'a str> {
+        self.connector
+            .schema
+            .supergraph_schema()
+            .type_field(type_name, field_name)
+            .ok()
+            .map(|field| field.ty.inner_named_type().as_str())
+    }
+
+    /// Shapes a value mapped from a REST response to the selections of a field
+    fn complete(&self, value: Value, field_type: Option<&str>, fields: &[&'a ast::Field]) -> Value {
+        let selection_sets: Vec<&'a [ast::Selection]> = fields
+            .iter()
+            .map(|field| field.selection_set.as_slice())
+            .filter(|selection_set| !selection_set.is_empty())
+            .collect();
+        if selection_sets.is_empty() {
+            return value;
+        }
+        let Some(field_type) = field_type else {
+            return Value::Null;
+        };
+
+        match value {
+            Value::Array(items) => Value::Array(
+                items
+                    .into_iter()
+                    .map(|item| self.complete(item, Some(field_type), fields))
+                    .collect(),
+            ),
+            Value::Object(object) => {
+                let concrete_type = object
+                    .get(TYPENAME)
+                    .and_then(|type_name| type_name.as_str())
+                    .unwrap_or(field_type)
+                    .to_string();
+                let mut output = Map::new();
+                for (key, fields) in self.collect_fields(&selection_sets, &concrete_type) {
+                    let name = fields[0].name.as_str();
+                    let value = if name == TYPENAME {
+                        Value::String(concrete_type.as_str().into())
+                    } else {
+                        self.complete(
+                            object.get(name).cloned().unwrap_or_default(),
+                            self.field_type(&concrete_type, name),
+                            &fields,
+                        )
+                    };
+                    output.insert(ByteString::from(key), value);
+                }
+                Value::Object(output)
+            }
+            _ => Value::Null,
+        }
+    }
+
+    /// Groups the fields selected on a type by response key
+    fn collect_fields(
+        &self,
+        selection_sets: &[&'a [ast::Selection]],
+        type_name: &str,
+    ) -> IndexMap<&'a str, Vec<&'a ast::Field>> {
+        let mut fields = IndexMap::new();
+        for selection_set in selection_sets {
+            self.collect_selection_set(selection_set, type_name, &mut fields);
+        }
+        fields
+    }
+
+    fn collect_selection_set(
+        &self,
+        selection_set: &'a [ast::Selection],
+        type_name: &str,
+        fields: &mut IndexMap<&'a str, Vec<&'a ast::Field>>,
+    ) {
+        for selection in selection_set {
+            match selection {
+                ast::Selection::Field(field) => {
+                    if self.is_included(&field.directives) {
+                        let key = field.alias.as_ref().unwrap_or(&field.name).as_str();
+                        fields.entry(key).or_default().push(&**field);
+                    }
+                }
+                ast::Selection::InlineFragment(inline_fragment) => {
+                    let applies = inline_fragment
+                        .type_condition
+                        .as_ref()
+                        .map_or(true, |condition| self.applies(condition, type_name));
+                    if applies && self.is_included(&inline_fragment.directives) {
+                        self.collect_selection_set(
+                            &inline_fragment.selection_set,
+                            type_name,
+                            fields,
+                        );
+                    }
+                }
+                ast::Selection::FragmentSpread(fragment_spread) => {
+                    if let Some(fragment) = self
+                        .fragments
+                        .get(fragment_spread.fragment_name.as_str())
+                        .copied()
+                    {
+                        if self.applies(&fragment.type_condition, type_name)
+                            && self.is_included(&fragment_spread.directives)
+                        {
+                            self.collect_selection_set(&fragment.selection_set, type_name, fields);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn applies(&self, type_condition: &str, type_name: &str) -> bool {
+        type_condition == type_name || self.connector.schema.is_subtype(type_condition, type_name)
+    }
+
+    /// Evaluates the `@skip` and `@include` directives
+    fn is_included(&self, directives: &ast::DirectiveList) -> bool {
+        let condition = |name: &str| {
+            directives
+                .get(name)
+                .and_then(|directive| directive.argument_by_name("if"))
+                .map(|value| argument_value(value, self.variables))
+        };
+        condition("skip") != Some(Value::Bool(true))
+            && condition("include") != Some(Value::Bool(false))
+    }
+
+    fn arguments(&self, field: &ast::Field) -> Object {
+        let mut arguments = Object::new();
+        for argument in &field.arguments {
+            arguments.insert(
+                ByteString::from(argument.name.as_str()),
+                argument_value(&argument.value, self.variables),
+            );
+        }
+        arguments
+    }
+}
+
+fn argument_value(value: &ast::Value, variables: &Object) -> Value {
+    match value {
+        ast::Value::Variable(name) => variables.get(name.as_str()).cloned().unwrap_or_default(),
+        ast::Value::List(values) => Value::Array(
+            values
+                .iter()
+                .map(|value| argument_value(value, variables))
+                .collect(),
+        ),
+        ast::Value::Object(fields) => {
+            let mut object = Object::new();
+            for (name, value) in fields {
+                object.insert(
+                    ByteString::from(name.as_str()),
+                    argument_value(value, variables),
+                );
+            }
+            Value::Object(object)
+        }
+        value => parse_hir_value(value).unwrap_or_default(),
+    }
+}
+
+/// Input of the endpoint templates and selections, like `{ "$args": { "id": 1 } }`
+fn input(name: &str, value: Value) -> Value {
+    let mut input = Object::new();
+    input.insert(ByteString::from(name), value);
+    Value::Object(input)
+}
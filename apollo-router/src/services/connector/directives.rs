@@ -0,0 +1,202 @@
+//! Endpoints of REST connectors defined with the `@connector` directive in the supergraph schema
+//!
+//! The directive is defined in the subgraph schemas, and kept in the supergraph with
+//! `@composeDirective`:
+//!
+//! ```graphql
+//! directive @connector(
+//!   subgraph: String!
+//!   method: String = "GET"
+//!   path: String!
+//!   headers: [String!]
+//!   body: String
+//!   selection: String!
+//! ) repeatable on FIELD_DEFINITION | OBJECT
+//! ```
+//!
+//! On a root field, it defines the endpoint resolving that field. On an object type, it defines
+//! the endpoint resolving its entities. Headers are written `name: value` for a fixed value, or
+//! `name` to copy the header from the client request.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use apollo_compiler::ast;
+use apollo_compiler::schema::ExtendedType;
+use tower::BoxError;
+
+use crate::configuration::ConnectorEndpoint;
+use crate::configuration::ConnectorHeader;
+use crate::configuration::ConnectorMethod;
+
+pub(crate) const CONNECTOR_DIRECTIVE_NAME: &str = "connector";
+
+/// Endpoints of a connector defined in the schema
+#[derive(Default)]
+pub(super) struct SchemaEndpoints {
+    /// Endpoints keyed by `Type.field`
+    pub(super) fields: HashMap<String, ConnectorEndpoint>,
+    /// Endpoints keyed by entity type
+    pub(super) entities: HashMap<String, ConnectorEndpoint>,
+}
+
+/// Names of the subgraphs having endpoints defined in the schema
+pub(crate) fn subgraphs(schema: &apollo_compiler::Schema) -> HashSet<String> {
+    connector_directives(schema)
+        .filter_map(|(_, directive)| string_argument(directive, "subgraph"))
+        .map(|subgraph| subgraph.to_string())
+        .collect()
+}
+
+/// Collects the endpoints defined in the schema for a subgraph
+pub(super) fn endpoints(
+    schema: &apollo_compiler::Schema,
+    subgraph: &str,
+) -> Result<SchemaEndpoints, BoxError> {
+    let mut endpoints = SchemaEndpoints::default();
+    for (location, directive) in connector_directives(schema) {
+        if string_argument(directive, "subgraph") != Some(subgraph) {
+            continue;
+        }
+        let (key, endpoints) = match location {
+            Location::Type(type_name) => (type_name.to_string(), &mut endpoints.entities),
+            Location::Field(type_name, field_name) => {
+                if !is_root_type(schema, type_name) {
+                    return Err(format!(
+                        "connector '{subgraph}': the @{CONNECTOR_DIRECTIVE_NAME} directive on \
+                         '{type_name}.{field_name}' must be on a field of a root type"
+                    )
+                    .into());
+                }
+                (format!("{type_name}.{field_name}"), &mut endpoints.fields)
+            }
+        };
+        let endpoint = parse_endpoint(directive).map_err(|error| {
+            format!(
+                "connector '{subgraph}': invalid @{CONNECTOR_DIRECTIVE_NAME} directive on '{key}': {error}"
+            )
+        })?;
+        if endpoints.insert(key.clone(), endpoint).is_some() {
+            return Err(format!(
+                "connector '{subgraph}': '{key}' has several @{CONNECTOR_DIRECTIVE_NAME} directives"
+            )
+            .into());
+        }
+    }
+    Ok(endpoints)
+}
+
+enum Location<'a> {
+    Type(&'a str),
+    Field(&'a str, &'a str),
+}
+
+fn connector_directives(
+    schema: &apollo_compiler::Schema,
+) -> impl Iterator<Item = (Location<'_>, &ast::Directive)> {
+    schema
+        .types
+        .iter()
+        .filter_map(|(type_name, ty)| match ty {
+            ExtendedType::Object(object) => Some((type_name, object)),
+            _ => None,
+        })
+        .flat_map(|(type_name, object)| {
+            let on_type = object
+                .directives
+                .get_all(CONNECTOR_DIRECTIVE_NAME)
+                .map(move |directive| (Location::Type(type_name.as_str()), &**directive));
+            let on_fields = object.fields.iter().flat_map(move |(field_name, field)| {
+                field
+                    .directives
+                    .get_all(CONNECTOR_DIRECTIVE_NAME)
+                    .map(move |directive| {
+                        (
+                            Location::Field(type_name.as_str(), field_name.as_str()),
+                            &**directive,
+                        )
+                    })
+            });
+            on_type.chain(on_fields)
+        })
+}
+
+fn is_root_type(schema: &apollo_compiler::Schema, type_name: &str) -> bool {
+    [
+        ast::OperationType::Query,
+        ast::OperationType::Mutation,
+        ast::OperationType::Subscription,
+    ]
+    .into_iter()
+    .any(|operation_type| {
+        schema
+            .root_operation(operation_type)
+            .is_some_and(|root| root.as_str() == type_name)
+    })
+}
+
+fn string_argument<'a>(directive: &'a ast::Directive, name: &str) -> Option<&'a str> {
+    directive
+        .argument_by_name(name)
+        .and_then(|value| value.as_str())
+}
+
+fn parse_endpoint(directive: &ast::Directive) -> Result<ConnectorEndpoint, String> {
+    let method = match string_argument(directive, "method") {
+        None => ConnectorMethod::Get,
+        Some(method) => match method.to_ascii_uppercase().as_str() {
+            "GET" => ConnectorMethod::Get,
+            "POST" => ConnectorMethod::Post,
+            "PUT" => ConnectorMethod::Put,
+            "PATCH" => ConnectorMethod::Patch,
+            "DELETE" => ConnectorMethod::Delete,
+            _ => return Err(format!("unsupported method '{method}'")),
+        },
+    };
+    let headers = match directive
+        .argument_by_name("headers")
+        .map(|value| value.as_ref())
+    {
+        None | Some(ast::Value::Null) => Vec::new(),
+        Some(ast::Value::List(headers)) => headers
+            .iter()
+            .map(|header| {
+                header
+                    .as_str()
+                    .map(parse_header)
+                    .ok_or_else(|| "headers must be strings".to_string())
+            })
+            .collect::<Result<_, _>>()?,
+        // a single value is coerced to a list
+        Some(ast::Value::String(header)) => vec![parse_header(header)],
+        Some(_) => return Err("headers must be strings".to_string()),
+    };
+
+    Ok(ConnectorEndpoint {
+        method,
+        path: string_argument(directive, "path")
+            .ok_or("the path is missing")?
+            .to_string(),
+        headers,
+        body: string_argument(directive, "body").map(|body| body.to_string()),
+        selection: string_argument(directive, "selection")
+            .ok_or("the selection is missing")?
+            .to_string(),
+    })
+}
+
+/// Parses `name: value` as a fixed value, and `name` as a header copied from the client request
+fn parse_header(header: &str) -> ConnectorHeader {
+    match header.split_once(':') {
+        Some((name, value)) => ConnectorHeader {
+            name: name.trim().to_string(),
+            from: None,
+            value: Some(value.trim().to_string()),
+        },
+        None => ConnectorHeader {
+            name: header.trim().to_string(),
+            from: None,
+            value: None,
+        },
+    }
+}
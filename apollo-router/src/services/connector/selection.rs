@@ -0,0 +1,323 @@
+//! JSON selections, mapping JSON values to the shape of a GraphQL type
+//!
+//! A selection is a list of properties, separated by spaces or commas:
+//! - `id` selects the `id` property
+//! - `name: title` selects the `title` property, renamed to `name`
+//! - `city: address.city` selects a nested property
+//! - `author { id name }` selects properties of the `author` object
+//! - `"first name"` selects a property whose name is not an identifier
+//!
+//! A selection made of a single path starting with `$` returns the value at that path instead of
+//! an object: `$.results { id }` maps the `results` array of a response, and `$args.input` builds
+//! a request body from a field argument. Arrays are mapped item by item.
+
+use serde_json_bytes::ByteString;
+use serde_json_bytes::Map;
+use serde_json_bytes::Value;
+
+/// Path element referring to the current value
+const CURRENT: &str = "$";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum JsonSelection {
+    /// Value at a path, optionally reshaped by a selection
+    Path {
+        path: Vec<String>,
+        selection: Option<Vec<NamedSelection>>,
+    },
+    /// Object built from the selected properties
+    Object(Vec<NamedSelection>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct NamedSelection {
+    alias: Option<String>,
+    path: Vec<String>,
+    selection: Option<Vec<NamedSelection>>,
+}
+
+impl JsonSelection {
+    pub(crate) fn parse(source: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            source,
+            position: 0,
+        };
+        let mut items = parser.items()?;
+        parser.skip_ignored();
+        if let Some(c) = parser.peek() {
+            return Err(format!(
+                "unexpected character '{c}' at position {}",
+                parser.position
+            ));
+        }
+
+        if items.len() == 1 && items[0].is_path() {
+            let item = items.remove(0);
+            Ok(JsonSelection::Path {
+                path: item.path,
+                selection: item.selection,
+            })
+        } else {
+            check_response_keys(&items)?;
+            Ok(JsonSelection::Object(items))
+        }
+    }
+
+    pub(crate) fn apply(&self, value: &Value) -> Value {
+        match self {
+            JsonSelection::Path { path, selection } => select(value, path, selection.as_deref()),
+            JsonSelection::Object(items) => apply_items(value, items),
+        }
+    }
+}
+
+impl NamedSelection {
+    /// Name of the property in the output object: the alias, or the last key of the path
+    fn response_key(&self) -> Option<&str> {
+        self.alias
+            .as_deref()
+            .or_else(|| self.path.last().map(String::as_str))
+            .filter(|key| *key != CURRENT)
+    }
+
+    /// A lone `$...` path without alias is a path selection rather than a property
+    fn is_path(&self) -> bool {
+        self.alias.is_none() && self.path[0].starts_with(CURRENT)
+    }
+}
+
+fn check_response_keys(items: &[NamedSelection]) -> Result<(), String> {
+    if items.iter().any(|item| item.response_key().is_none()) {
+        return Err("a selection of `$` inside an object needs an alias".to_string());
+    }
+    Ok(())
+}
+
+fn select(value: &Value, path: &[String], selection: Option<&[NamedSelection]>) -> Value {
+    let value = follow(value, path);
+    match selection {
+        Some(items) => apply_items(&value, items),
+        None => value,
+    }
+}
+
+fn follow(value: &Value, path: &[String]) -> Value {
+    match path.split_first() {
+        None => value.clone(),
+        Some((key, rest)) if key == CURRENT => follow(value, rest),
+        Some(_) => match value {
+            Value::Array(items) => {
+                Value::Array(items.iter().map(|item| follow(item, path)).collect())
+            }
+            Value::Object(object) => object
+                .get(path[0].as_str())
+                .map(|value| follow(value, &path[1..]))
+                .unwrap_or_default(),
+            _ => Value::Null,
+        },
+    }
+}
+
+fn apply_items(value: &Value, items: &[NamedSelection]) -> Value {
+    match value {
+        Value::Array(values) => Value::Array(
+            values
+                .iter()
+                .map(|value| apply_items(value, items))
+                .collect(),
+        ),
+        Value::Object(_) => {
+            let mut output = Map::new();
+            for item in items {
+                output.insert(
+                    ByteString::from(item.response_key().unwrap_or_default()),
+                    select(value, &item.path, item.selection.as_deref()),
+                );
+            }
+            Value::Object(output)
+        }
+        _ => Value::Null,
+    }
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.source[self.position..].chars().next()
+    }
+
+    fn skip_ignored(&mut self) {
+        while let Some(c) = self.peek() {
+            if c.is_whitespace() || c == ',' {
+                self.position += c.len_utf8();
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_ignored();
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.position += c.len_utf8();
+                Ok(())
+            }
+            Some(c) => Err(format!(
+                "expected '{expected}' at position {}, found '{c}'",
+                self.position
+            )),
+            None => Err(format!(
+                "expected '{expected}', found the end of the selection"
+            )),
+        }
+    }
+
+    fn items(&mut self) -> Result<Vec<NamedSelection>, String> {
+        let mut items = Vec::new();
+        loop {
+            self.skip_ignored();
+            match self.peek() {
+                None | Some('}') => break,
+                Some(_) => items.push(self.item()?),
+            }
+        }
+        if items.is_empty() {
+            return Err("empty selection".to_string());
+        }
+        Ok(items)
+    }
+
+    fn item(&mut self) -> Result<NamedSelection, String> {
+        let start = self.position;
+        let mut path = self.path()?;
+        self.skip_ignored();
+        let alias = if self.peek() == Some(':') {
+            if path.len() > 1 {
+                return Err(format!("invalid alias at position {start}"));
+            }
+            self.position += 1;
+            let alias = path.remove(0);
+            path = self.path()?;
+            Some(alias)
+        } else {
+            None
+        };
+
+        self.skip_ignored();
+        let selection = if self.peek() == Some('{') {
+            self.position += 1;
+            let items = self.items()?;
+            check_response_keys(&items)?;
+            self.expect('}')?;
+            Some(items)
+        } else {
+            None
+        };
+
+        Ok(NamedSelection {
+            alias,
+            path,
+            selection,
+        })
+    }
+
+    fn path(&mut self) -> Result<Vec<String>, String> {
+        let mut path = vec![self.key()?];
+        while self.peek() == Some('.') {
+            self.position += 1;
+            path.push(self.key()?);
+        }
+        Ok(path)
+    }
+
+    fn key(&mut self) -> Result<String, String> {
+        self.skip_ignored();
+        let rest = &self.source[self.position..];
+        if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted
+                .find('"')
+                .ok_or_else(|| format!("unterminated string at position {}", self.position))?;
+            self.position += end + 2;
+            return Ok(quoted[..end].to_string());
+        }
+
+        let end = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '$'))
+            .unwrap_or(rest.len());
+        if end == 0 {
+            return Err(match rest.chars().next() {
+                Some(c) => format!("unexpected character '{c}' at position {}", self.position),
+                None => "unexpected end of the selection".to_string(),
+            });
+        }
+        self.position += end;
+        Ok(rest[..end].to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json_bytes::json;
+
+    use super::*;
+
+    #[test]
+    fn object_selection() {
+        let selection = JsonSelection::parse(
+            r#"id, name: title author { id "first name" } city: address.city"#,
+        )
+        .unwrap();
+        let value = json!({
+            "id": 1,
+            "title": "Dune",
+            "author": { "id": 2, "first name": "Frank", "last name": "Herbert" },
+            "address": { "city": "Paris" },
+            "ignored": true
+        });
+        assert_eq!(
+            selection.apply(&value),
+            json!({
+                "id": 1,
+                "name": "Dune",
+                "author": { "id": 2, "first name": "Frank" },
+                "city": "Paris"
+            })
+        );
+    }
+
+    #[test]
+    fn path_selection() {
+        let selection = JsonSelection::parse("$.results { id }").unwrap();
+        let value = json!({ "results": [{ "id": 1, "other": 2 }, { "id": 3 }] });
+        assert_eq!(selection.apply(&value), json!([{ "id": 1 }, { "id": 3 }]));
+
+        let selection = JsonSelection::parse("$args.input").unwrap();
+        let value = json!({ "$args": { "input": { "name": "a" } } });
+        assert_eq!(selection.apply(&value), json!({ "name": "a" }));
+    }
+
+    #[test]
+    fn arrays_and_missing_values() {
+        let selection = JsonSelection::parse("tags: items.tag missing nested { id }").unwrap();
+        let value = json!({ "items": [{ "tag": "a" }, { "tag": "b" }], "nested": 1 });
+        assert_eq!(
+            selection.apply(&value),
+            json!({ "tags": ["a", "b"], "missing": null, "nested": null })
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(JsonSelection::parse("").is_err());
+        assert!(JsonSelection::parse("a {").is_err());
+        assert!(JsonSelection::parse("a }").is_err());
+        assert!(JsonSelection::parse("a.b: c").is_err());
+        assert!(JsonSelection::parse("a $ { b }").is_err());
+        assert!(JsonSelection::parse(r#""unterminated"#).is_err());
+    }
+}
@@ -0,0 +1,96 @@
+//! URL templates, inserting values of the field arguments or entity keys in endpoint paths
+
+use serde_json_bytes::Value;
+
+/// Endpoint path with `{...}` expressions, like `/products/{$args.id}`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct UrlTemplate {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Expression(Vec<String>),
+}
+
+impl UrlTemplate {
+    pub(crate) fn parse(source: &str) -> Result<Self, String> {
+        let mut parts = Vec::new();
+        let mut rest = source;
+        while let Some(start) = rest.find('{') {
+            parts.push(Part::Literal(rest[..start].to_string()));
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| format!("unclosed expression in '{source}'"))?;
+            let expression = rest[start + 1..start + end].trim();
+            if expression.is_empty() {
+                return Err(format!("empty expression in '{source}'"));
+            }
+            parts.push(Part::Expression(
+                expression.split('.').map(|key| key.to_string()).collect(),
+            ));
+            rest = &rest[start + end + 1..];
+        }
+        parts.push(Part::Literal(rest.to_string()));
+
+        let literal_brace = parts
+            .iter()
+            .any(|part| matches!(part, Part::Literal(literal) if literal.contains('}')));
+        if literal_brace {
+            return Err(format!("unexpected '}}' in '{source}'"));
+        }
+        parts.retain(|part| !matches!(part, Part::Literal(literal) if literal.is_empty()));
+        Ok(Self { parts })
+    }
+
+    /// Builds the path, with the percent-encoded values of the expressions
+    ///
+    /// Missing and null values are replaced with an empty string.
+    pub(crate) fn interpolate(&self, input: &Value) -> String {
+        let mut output = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(literal) => output.push_str(literal),
+                Part::Expression(path) => {
+                    let value = path
+                        .iter()
+                        .try_fold(input, |value, key| value.as_object()?.get(key.as_str()));
+                    match value {
+                        None | Some(Value::Null) => {}
+                        Some(Value::String(value)) => {
+                            output.push_str(&urlencoding::encode(value.as_str()))
+                        }
+                        Some(value) => output.push_str(&urlencoding::encode(&value.to_string())),
+                    }
+                }
+            }
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json_bytes::json;
+
+    use super::*;
+
+    #[test]
+    fn interpolate() {
+        let template =
+            UrlTemplate::parse("/products/{$args.id}/reviews?lang={ $args.lang }").unwrap();
+        assert_eq!(
+            template.interpolate(&json!({ "$args": { "id": 12, "lang": "en us" } })),
+            "/products/12/reviews?lang=en%20us"
+        );
+        assert_eq!(
+            template.interpolate(&json!({ "$args": {} })),
+            "/products//reviews?lang="
+        );
+
+        assert!(UrlTemplate::parse("/products/{$args.id").is_err());
+        assert!(UrlTemplate::parse("/products/{}").is_err());
+        assert!(UrlTemplate::parse("/products/}").is_err());
+    }
+}
@@ -0,0 +1,321 @@
+use std::sync::Arc;
+
+use serde_json_bytes::json;
+use wiremock::matchers::header;
+use wiremock::matchers::method;
+use wiremock::matchers::path;
+use wiremock::matchers::query_param;
+use wiremock::Mock;
+use wiremock::MockServer;
+use wiremock::ResponseTemplate;
+
+use super::*;
+use crate::plugins::traffic_shaping::Http2Config;
+use crate::query_planner::OperationKind;
+use crate::Configuration;
+
+const SCHEMA: &str = include_str!("../../testdata/supergraph.graphql");
+
+fn connector(base_url: &str) -> Arc<Connector> {
+    let configuration = Configuration::default();
+    let schema = Arc::new(Schema::parse(SCHEMA, &configuration).unwrap());
+    let source: ConnectorSource = serde_json::from_value(serde_json::json!({
+        "base_url": base_url,
+        "headers": [{ "name": "x-api-key" }],
+        "fields": {
+            "Query.topProducts": {
+                "path": "/products?limit={$args.first}",
+                "selection": "$.products { upc: id name price }"
+            }
+        },
+        "entities": {
+            "Product": {
+                "path": "/products/{$this.upc}",
+                "selection": "upc: id name"
+            }
+        }
+    }))
+    .unwrap();
+    Arc::new(Connector::new("products", &source, schema).unwrap())
+}
+
+fn request(query: &str, variables: serde_json_bytes::Value) -> SubgraphRequest {
+    let supergraph_request = http::Request::builder()
+        .header("x-api-key", "secret")
+        .body(graphql::Request::default())
+        .unwrap();
+    let subgraph_request = http::Request::builder()
+        .uri("http://products")
+        .body(
+            graphql::Request::builder()
+                .query(query)
+                .variables(variables.as_object().cloned().unwrap_or_default())
+                .build(),
+        )
+        .unwrap();
+    SubgraphRequest::fake_builder()
+        .supergraph_request(Arc::new(supergraph_request))
+        .subgraph_request(subgraph_request)
+        .operation_kind(OperationKind::Query)
+        .subgraph_name("products")
+        .build()
+}
+
+fn client_factory() -> HttpClientServiceFactory {
+    HttpClientServiceFactory::from_config(
+        "products",
+        &Configuration::default(),
+        Http2Config::Disable,
+    )
+}
+
+#[tokio::test]
+async fn root_fields() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/products"))
+        .and(query_param("limit", "2"))
+        .and(header("x-api-key", "secret"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "products": [
+                { "id": "1", "name": "Table", "price": 100 },
+                { "id": "2", "name": "Chair", "price": 50 }
+            ]
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let response = call(
+        connector(&server.uri()),
+        request(
+            "query($first: Int) { __typename topProducts(first: $first) { upc title: name ... on Product { __typename price } } }",
+            json!({ "first": 2 }),
+        ),
+        client_factory(),
+    )
+    .await
+    .unwrap();
+
+    let body = response.response.body();
+    assert!(body.errors.is_empty());
+    assert_eq!(
+        body.data,
+        Some(json!({
+            "__typename": "Query",
+            "topProducts": [
+                { "upc": "1", "title": "Table", "__typename": "Product", "price": 100 },
+                { "upc": "2", "title": "Chair", "__typename": "Product", "price": 50 }
+            ]
+        }))
+    );
+}
+
+#[tokio::test]
+async fn entities() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/products/1"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(serde_json::json!({ "id": "1", "name": "Table" })),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/products/2"))
+        .respond_with(ResponseTemplate::new(404))
+        .mount(&server)
+        .await;
+
+    let response = call(
+        connector(&server.uri()),
+        request(
+            "query($representations: [_Any!]!) { _entities(representations: $representations) { ... on Product { name } } }",
+            json!({ "representations": [
+                { "__typename": "Product", "upc": "1" },
+                { "__typename": "Product", "upc": "2" }
+            ] }),
+        ),
+        client_factory(),
+    )
+    .await
+    .unwrap();
+
+    let body = response.response.body();
+    assert_eq!(
+        body.data,
+        Some(json!({ "_entities": [{ "name": "Table" }, null] }))
+    );
+    assert_eq!(body.errors.len(), 1);
+    assert_eq!(body.errors[0].path, Some(Path::from("_entities/1")));
+}
+
+#[test]
+fn invalid_configuration() {
+    let configuration = Configuration::default();
+    let schema = Arc::new(Schema::parse(SCHEMA, &configuration).unwrap());
+    let source: ConnectorSource = serde_json::from_value(serde_json::json!({
+        "base_url": "http://localhost",
+        "fields": {
+            "Query.unknown": { "path": "/unknown", "selection": "id" }
+        }
+    }))
+    .unwrap();
+    assert!(Connector::new("products", &source, schema.clone()).is_err());
+
+    let source: ConnectorSource = serde_json::from_value(serde_json::json!({
+        "base_url": "http://localhost",
+        "fields": {
+            "Query.topProducts": { "path": "/products/{$args.first", "selection": "id" }
+        }
+    }))
+    .unwrap();
+    assert!(Connector::new("products", &source, schema).is_err());
+}
+
+#[tokio::test]
+async fn entities_concurrency_is_limited() {
+    let server = MockServer::start().await;
+    for id in 1..=6 {
+        Mock::given(method("GET"))
+            .and(path(format!("/products/{id}")))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({ "id": id.to_string(), "name": format!("Product {id}") }))
+                    // later entities are answered first
+                    .set_delay(std::time::Duration::from_millis(160 - 20 * id)),
+            )
+            .mount(&server)
+            .await;
+    }
+    let configuration = Configuration::default();
+    let schema = Arc::new(Schema::parse(SCHEMA, &configuration).unwrap());
+    let source: ConnectorSource = serde_json::from_value(serde_json::json!({
+        "base_url": server.uri(),
+        "max_concurrent_requests": 2,
+        "entities": {
+            "Product": { "path": "/products/{$this.upc}", "selection": "upc: id name" }
+        }
+    }))
+    .unwrap();
+    let connector = Arc::new(Connector::new("products", &source, schema).unwrap());
+
+    let start = std::time::Instant::now();
+    let response = call(
+        connector,
+        request(
+            "query($representations: [_Any!]!) { _entities(representations: $representations) { ... on Product { name } } }",
+            json!({ "representations": (1..=6).map(|id| json!({ "__typename": "Product", "upc": id.to_string() })).collect::<Vec<_>>() }),
+        ),
+        client_factory(),
+    )
+    .await
+    .unwrap();
+
+    // with 2 calls at a time, the calls take at least half of their total delay
+    assert!(
+        start.elapsed() >= std::time::Duration::from_millis((140 + 120 + 100 + 80 + 60 + 40) / 2)
+    );
+    let body = response.response.body();
+    assert!(body.errors.is_empty());
+    assert_eq!(
+        body.data,
+        Some(json!({
+            "_entities": (1..=6).map(|id| json!({ "name": format!("Product {id}") })).collect::<Vec<_>>()
+        }))
+    );
+}
+
+fn schema_with_directives() -> Arc<Schema> {
+    let schema = SCHEMA
+        .replace(
+            "topProducts(first: Int = 5): [Product] @join__field(graph: PRODUCTS)",
+            r#"topProducts(first: Int = 5): [Product] @join__field(graph: PRODUCTS)
+    @connector(subgraph: "products", path: "/products?limit={$args.first}", headers: ["x-api-key", "x-source: router"], selection: "$.products { upc: id name price }")"#,
+        )
+        .replace(
+            "type Product\n",
+            "type Product\n  @connector(subgraph: \"products\", path: \"/products/{$this.upc}\", selection: \"upc: id name\")\n",
+        )
+        + r#"
+directive @connector(subgraph: String!, method: String = "GET", path: String!, headers: [String!], body: String, selection: String!) repeatable on FIELD_DEFINITION | OBJECT
+"#;
+    Arc::new(Schema::parse(&schema, &Configuration::default()).unwrap())
+}
+
+#[tokio::test]
+async fn schema_directives() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/products"))
+        .and(query_param("limit", "1"))
+        .and(header("x-api-key", "secret"))
+        .and(header("x-source", "router"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "products": [{ "id": "1", "name": "Table", "price": 100 }]
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/products/2"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(serde_json::json!({ "id": "2", "name": "Chair" })),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let schema = schema_with_directives();
+    assert_eq!(
+        directives::subgraphs(schema.supergraph_schema()),
+        ["products".to_string()].into_iter().collect()
+    );
+    let source: ConnectorSource =
+        serde_json::from_value(serde_json::json!({ "base_url": server.uri() })).unwrap();
+    let connector = Arc::new(Connector::new("products", &source, schema).unwrap());
+
+    let response = call(
+        connector.clone(),
+        request(
+            "query($first: Int) { topProducts(first: $first) { upc name } }",
+            json!({ "first": 1 }),
+        ),
+        client_factory(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        response.response.body().data,
+        Some(json!({ "topProducts": [{ "upc": "1", "name": "Table" }] }))
+    );
+
+    let response = call(
+        connector,
+        request(
+            "query($representations: [_Any!]!) { _entities(representations: $representations) { ... on Product { name } } }",
+            json!({ "representations": [{ "__typename": "Product", "upc": "2" }] }),
+        ),
+        client_factory(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        response.response.body().data,
+        Some(json!({ "_entities": [{ "name": "Chair" }] }))
+    );
+}
+
+#[test]
+fn endpoint_defined_in_configuration_and_schema() {
+    let source: ConnectorSource = serde_json::from_value(serde_json::json!({
+        "base_url": "http://localhost",
+        "entities": {
+            "Product": { "path": "/products/{$this.upc}", "selection": "upc: id" }
+        }
+    }))
+    .unwrap();
+    assert!(Connector::new("products", &source, schema_with_directives()).is_err());
+}
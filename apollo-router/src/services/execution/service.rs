@@ -14,6 +14,7 @@ use futures::future::BoxFuture;
 use futures::stream::once;
 use futures::Stream;
 use futures::StreamExt;
+use indexmap::IndexMap;
 use serde_json_bytes::Value;
 use tokio::sync::broadcast;
 use tokio::sync::mpsc;
@@ -31,8 +32,11 @@ use tracing::Instrument;
 use tracing::Span;
 use tracing_core::Level;
 
+use crate::graphql;
+use crate::graphql::CompletedResponse;
 use crate::graphql::Error;
 use crate::graphql::IncrementalResponse;
+use crate::graphql::PendingResponse;
 use crate::graphql::Response;
 use crate::json_ext::Object;
 use crate::json_ext::Path;
@@ -43,15 +47,22 @@ use crate::plugins::subscription::Subscription;
 use crate::plugins::subscription::SubscriptionConfig;
 use crate::plugins::subscription::APOLLO_SUBSCRIPTION_PLUGIN;
 use crate::query_planner::subscription::SubscriptionHandle;
+use crate::query_planner::DeferredFragment;
+use crate::query_planner::QueryPlan;
 use crate::services::execution;
 use crate::services::new_service::ServiceFactory;
+use crate::services::router::ClientRequestAccepts;
 use crate::services::ExecutionRequest;
 use crate::services::ExecutionResponse;
 use crate::services::Plugins;
 use crate::services::SubgraphServiceFactory;
+use crate::spec::query::stream::StreamField;
+use crate::spec::query::stream::StreamedItems;
+use crate::spec::query::stream::StreamedLists;
 use crate::spec::query::subselections::BooleanValues;
 use crate::spec::Query;
 use crate::spec::Schema;
+use crate::Context;
 
 /// [`Service`] for query execution.
 #[derive(Clone)]
@@ -124,10 +135,32 @@ impl ExecutionService {
             .query_plan
             .is_deferred(operation_name.as_deref(), &variables);
         let is_subscription = req.query_plan.is_subscription(operation_name.as_deref());
-        let mut claims = None;
-        if is_deferred {
-            claims = context.get(APOLLO_AUTHENTICATION_JWT_CLAIMS).ok().flatten()
-        }
+        let accepts = context
+            .extensions()
+            .lock()
+            .get::<ClientRequestAccepts>()
+            .cloned()
+            .unwrap_or_default();
+        // streamed lists are only split for clients accepting multiple responses
+        let stream_fields: Vec<(StreamField, usize)> = if !is_subscription
+            && (accepts.multipart_defer || accepts.event_stream)
+        {
+            req.query_plan
+                .query
+                .operation(operation_name.as_deref())
+                .map(|operation| {
+                    operation
+                        .stream_fields
+                        .iter()
+                        .filter_map(|field| Some((field.clone(), field.initial_count(&variables)?)))
+                        .collect()
+                })
+                .unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+        let streamed_lists = (!stream_fields.is_empty()).then(|| StreamedLists::new(stream_fields));
+        let stream_sender = streamed_lists.as_ref().map(|_| sender.clone());
         let (tx_close_signal, subscription_handle) = if is_subscription {
             let (tx_close_signal, rx_close_signal) = broadcast::channel(1);
             (
@@ -142,23 +175,64 @@ impl ExecutionService {
         };
 
         let has_initial_data = req.source_stream_value.is_some();
+        let supergraph_request = Arc::new(req.supergraph_request);
         let mut first = req
             .query_plan
             .execute(
                 &context,
                 &self.subgraph_service_factory,
-                &Arc::new(req.supergraph_request),
+                &supergraph_request,
                 &self.schema,
                 &self.subgraph_schemas,
                 sender,
                 subscription_handle.clone(),
                 &self.subscription_config,
                 req.source_stream_value,
+                streamed_lists.as_ref(),
             )
             .await;
+        let streamed_items = streamed_lists
+            .map(StreamedLists::into_held_back)
+            .unwrap_or_default();
+        let is_streamed = !streamed_items.is_empty();
+        let is_incremental = is_deferred || is_streamed;
+        let mut claims = None;
+        if is_incremental {
+            claims = context.get(APOLLO_AUTHENTICATION_JWT_CLAIMS).ok().flatten()
+        }
+        let mut incremental_delivery =
+            (is_incremental && accepts.incremental_delivery).then(|| {
+                IncrementalDelivery::new(
+                    req.query_plan
+                        .deferred_fragments(operation_name.as_deref(), &variables),
+                    streamed_items
+                        .iter()
+                        .map(|items| StreamedList {
+                            path: items.path.clone(),
+                            label: items.field.label.clone(),
+                            remaining: items.items.len(),
+                        })
+                        .collect(),
+                )
+            });
+        if let (true, Some(stream_sender)) = (is_streamed, stream_sender) {
+            let primary = first.data.clone().unwrap_or_default();
+            tokio::task::spawn(
+                self.clone()
+                    .send_streamed_items(
+                        req.query_plan.clone(),
+                        context.clone(),
+                        supergraph_request.clone(),
+                        primary,
+                        streamed_items,
+                        stream_sender,
+                    )
+                    .in_current_span(),
+            );
+        }
         let query = req.query_plan.query.clone();
-        let stream = if (is_deferred || is_subscription) && !has_initial_data {
-            let stream_mode = if is_deferred {
+        let stream = if (is_incremental || is_subscription) && !has_initial_data {
+            let stream_mode = if is_incremental {
                 StreamMode::Defer
             } else {
                 // Keep the connection opened only if there is no error when init the subscription
@@ -188,7 +262,7 @@ impl ExecutionService {
         let stream = stream
             .map(move |mut response: Response| {
                 // Enforce JWT expiry for deferred responses
-                if is_deferred {
+                if is_incremental {
                     let ts_opt = claims.as_ref().and_then(|x: &Value| {
                         if !x.is_object() {
                             tracing::error!("JWT claims should be an object");
@@ -231,18 +305,108 @@ impl ExecutionService {
                         &query,
                         operation_name.as_deref(),
                         &variables,
-                        is_deferred,
+                        is_incremental,
                         &schema,
                         &mut nullified_paths,
                         response,
                     )
                 }))
             })
+            .filter_map(move |response: Response| {
+                ready(match &mut incremental_delivery {
+                    Some(incremental_delivery) => incremental_delivery.format(response),
+                    None => Some(rewrite_defer_labels(response)),
+                })
+            })
             .boxed();
 
         ExecutionResponse::new_from_response(http::Response::new(stream as _), ctx)
     }
 
+    /// Fetches the items held back from the streamed lists of the primary response, and sends them
+    /// in chunks
+    async fn send_streamed_items(
+        self,
+        query_plan: Arc<QueryPlan>,
+        context: Context,
+        supergraph_request: Arc<http::Request<graphql::Request>>,
+        primary: Value,
+        streamed_items: Vec<StreamedItems>,
+        sender: Sender<Response>,
+    ) {
+        let operation_name = supergraph_request.body().operation_name.as_deref();
+        let variables = &supergraph_request.body().variables;
+        let query = &query_plan.query;
+
+        for items in streamed_items {
+            for chunk in items.chunks(STREAM_CHUNK_SIZE) {
+                let field = chunk.field.clone();
+                let path = chunk.path.clone();
+                let offset = chunk.offset;
+                let (items, mut errors) = query_plan
+                    .execute_stream_items(
+                        &context,
+                        &self.subgraph_service_factory,
+                        &supergraph_request,
+                        &self.schema,
+                        &self.subgraph_schemas,
+                        &self.subscription_config,
+                        &primary,
+                        chunk,
+                    )
+                    .await;
+
+                let mut items = Some(items);
+                for query in query.filtered_query.iter().chain(std::iter::once(query)) {
+                    if let Some(raw_items) = items.take() {
+                        let (formatted, format_errors) = query.format_stream_items(
+                            operation_name,
+                            &field,
+                            &path,
+                            raw_items,
+                            variables,
+                            self.schema.api_schema(),
+                        );
+                        items = formatted;
+                        errors.extend(format_errors);
+                    }
+                }
+                // the paths of the errors are relative to the chunk
+                for error in &mut errors {
+                    if let Some(error_path) = &mut error.path {
+                        if error_path.starts_with(&path) {
+                            if let Some(PathElement::Index(index)) =
+                                error_path.0.get_mut(path.len())
+                            {
+                                *index += offset;
+                            }
+                        }
+                    }
+                }
+
+                let is_complete = items.is_some();
+                let mut items_path = path;
+                items_path.push(PathElement::Index(offset));
+                let response = Response::builder()
+                    .incremental(vec![IncrementalResponse::builder()
+                        .and_label(field.label)
+                        // items that cannot be completed end the stream with their errors
+                        .items(items.unwrap_or_default())
+                        .path(items_path)
+                        .errors(errors)
+                        .build()])
+                    .build();
+                if sender.send(response).await.is_err() {
+                    tracing::debug!("the client closed the connection, stop streaming");
+                    return;
+                }
+                if !is_complete {
+                    break;
+                }
+            }
+        }
+    }
+
     fn process_graphql_response(
         query: &Arc<Query>,
         operation_name: Option<&str>,
@@ -270,6 +434,25 @@ impl ExecutionService {
             }
         }
 
+        // streamed items are formatted when they are fetched, and are not sent if their list was
+        // nullified
+        if !response.incremental.is_empty() {
+            response.incremental.retain(|incremental| {
+                !incremental
+                    .path
+                    .as_ref()
+                    .map(|items_path| {
+                        nullified_paths
+                            .iter()
+                            .any(|path| items_path.starts_with(path))
+                    })
+                    .unwrap_or(false)
+            });
+            response.has_next = Some(response.has_next.unwrap_or(true));
+            return (!response.incremental.is_empty() || response.has_next == Some(false))
+                .then_some(response);
+        }
+
         // Empty response (could happen when a subscription stream is closed from the subgraph)
         if response.subscribed == Some(false)
             && response.data.is_none()
@@ -353,7 +536,6 @@ impl ExecutionService {
                     ),
                 });
 
-                response.label = rewrite_defer_label(&response);
                 Some(response)
             }
             // if the deferred response specified a path, we must extract the
@@ -418,7 +600,7 @@ impl ExecutionService {
     ) -> Option<Response> {
         let query = query.clone();
 
-        let rewritten_label = rewrite_defer_label(&response);
+        let label = response.label.clone();
         let incremental = sub_responses
             .into_iter()
             .filter_map(move |(path, data)| {
@@ -477,7 +659,7 @@ impl ExecutionService {
                 if !data.is_null() || !errors.is_empty() || !extensions.is_empty() {
                     Some(
                         IncrementalResponse::builder()
-                            .and_label(rewritten_label.clone())
+                            .and_label(label.clone())
                             .data(data)
                             .path(path)
                             .errors(errors)
@@ -499,8 +681,342 @@ impl ExecutionService {
     }
 }
 
-fn rewrite_defer_label(response: &Response) -> Option<String> {
-    if let Some(label) = &response.label {
+/// Number of streamed items fetched and sent together
+const STREAM_CHUNK_SIZE: usize = 10;
+
+/// Converts deferred responses to the current incremental delivery format
+///
+/// The deferred fragments and streamed lists of the primary response are announced as pending in
+/// it, and the fragments nested in a deferred fragment in the payload completing that fragment.
+/// Incremental payloads then refer to their fragment by id, with a sub path if their data is deeper
+/// than the path of the fragment.
+struct IncrementalDelivery {
+    next_id: usize,
+    primary_sent: bool,
+    /// deferred fragments of the query plan that are not announced yet
+    fragments: Vec<DeferredFragment>,
+    /// streamed lists to announce in the primary response
+    streamed_lists: Vec<StreamedList>,
+    /// announced deferred fragments, by label
+    pending_fragments: IndexMap<String, PendingFragment>,
+    /// announced streamed lists, by path
+    pending_streams: IndexMap<Path, PendingStream>,
+    /// incremental payloads of fragments nested in a fragment that was not delivered yet
+    held: Vec<IncrementalResponse>,
+}
+
+struct PendingFragment {
+    id: String,
+    path: Path,
+    errors: Vec<Error>,
+}
+
+struct PendingStream {
+    id: String,
+    remaining: usize,
+}
+
+/// A streamed list sending items after the primary response
+struct StreamedList {
+    path: Path,
+    label: Option<String>,
+    remaining: usize,
+}
+
+impl IncrementalDelivery {
+    fn new(fragments: Vec<DeferredFragment>, streamed_lists: Vec<StreamedList>) -> Self {
+        Self {
+            next_id: 0,
+            primary_sent: false,
+            fragments,
+            streamed_lists,
+            pending_fragments: IndexMap::new(),
+            pending_streams: IndexMap::new(),
+            held: Vec::new(),
+        }
+    }
+
+    /// Returns `None` if there is nothing left to send in this response
+    fn format(&mut self, mut response: Response) -> Option<Response> {
+        let mut incremental = std::mem::take(&mut response.incremental);
+        if let Some(path) = response.path.take() {
+            // a deferred fragment that could not be executed, only containing errors
+            incremental.push(
+                IncrementalResponse::builder()
+                    .and_label(response.label.take())
+                    .path(path)
+                    .errors(std::mem::take(&mut response.errors))
+                    .build(),
+            );
+            response.data = None;
+        }
+
+        if !self.primary_sent {
+            self.primary_sent = true;
+            self.announce_fragments(&mut response, None);
+            for list in std::mem::take(&mut self.streamed_lists) {
+                if let Some(data) = &response.data {
+                    if is_null_at(data, &list.path) {
+                        // the items of a nullified list are not sent
+                        continue;
+                    }
+                }
+                let id = self.pending(&mut response, list.path.clone(), list.label);
+                self.pending_streams.insert(
+                    list.path,
+                    PendingStream {
+                        id,
+                        remaining: list.remaining,
+                    },
+                );
+            }
+        }
+
+        loop {
+            let mut delivered = Vec::new();
+            for incremental in incremental {
+                self.deliver(&mut response, incremental, &mut delivered);
+            }
+            if delivered.is_empty() {
+                break;
+            }
+            for label in delivered {
+                if let Some(fragment) = self.pending_fragments.shift_remove(&label) {
+                    response.completed.push(
+                        CompletedResponse::builder()
+                            .id(fragment.id)
+                            .errors(fragment.errors)
+                            .build(),
+                    );
+                }
+                self.announce_fragments(&mut response, Some(&label));
+            }
+            // the fragments nested in the delivered ones can now be delivered
+            incremental = std::mem::take(&mut self.held);
+        }
+
+        if response.has_next == Some(false) {
+            // the fragments under a nullified path are never delivered
+            self.held.clear();
+            for (_, fragment) in self.pending_fragments.drain(..) {
+                response.completed.push(
+                    CompletedResponse::builder()
+                        .id(fragment.id)
+                        .errors(fragment.errors)
+                        .build(),
+                );
+            }
+            for (_, stream) in self.pending_streams.drain(..) {
+                response
+                    .completed
+                    .push(CompletedResponse::builder().id(stream.id).build());
+            }
+        }
+
+        let is_empty = response.data.is_none()
+            && response.errors.is_empty()
+            && response.extensions.is_empty()
+            && response.incremental.is_empty()
+            && response.pending.is_empty()
+            && response.completed.is_empty();
+        (!is_empty || response.has_next == Some(false)).then_some(response)
+    }
+
+    fn deliver(
+        &mut self,
+        response: &mut Response,
+        mut incremental: IncrementalResponse,
+        delivered: &mut Vec<String>,
+    ) {
+        let path = incremental.path.take().unwrap_or_default();
+        if let Some(count) = incremental.items.as_ref().map(Vec::len) {
+            // the path of streamed items is the index of the first one
+            let mut list_path = path;
+            list_path.pop();
+            let label = incremental.label.take();
+            if !self.pending_streams.contains_key(&list_path) {
+                let id = self.pending(response, list_path.clone(), label);
+                self.pending_streams.insert(
+                    list_path.clone(),
+                    PendingStream {
+                        id,
+                        remaining: count,
+                    },
+                );
+            }
+            let Some(stream) = self.pending_streams.get_mut(&list_path) else {
+                return;
+            };
+            stream.remaining = stream.remaining.saturating_sub(count);
+            let id = stream.id.clone();
+            if count == 0 && !incremental.errors.is_empty() {
+                // the items could not be completed, the stream ends with their errors
+                self.pending_streams.shift_remove(&list_path);
+                response.completed.push(
+                    CompletedResponse::builder()
+                        .id(id)
+                        .errors(incremental.errors)
+                        .build(),
+                );
+                return;
+            }
+            let is_complete = stream.remaining == 0;
+            incremental.id = Some(id.clone());
+            response.incremental.push(incremental);
+            if is_complete {
+                self.pending_streams.shift_remove(&list_path);
+                response
+                    .completed
+                    .push(CompletedResponse::builder().id(id).build());
+            }
+            return;
+        }
+
+        let label = incremental.label.take().unwrap_or_default();
+        if !self.pending_fragments.contains_key(&label) {
+            if let Some(index) = self
+                .fragments
+                .iter()
+                .position(|fragment| fragment.label.as_deref() == Some(label.as_str()))
+            {
+                if self.fragments[index].parent.is_some() {
+                    // the fragment containing this one was not delivered yet
+                    incremental.path = Some(path);
+                    incremental.label = Some(label);
+                    self.held.push(incremental);
+                    return;
+                }
+                self.fragments.remove(index);
+            }
+            // a fragment that was not announced is announced with its data
+            let id = self.pending(
+                response,
+                path.clone(),
+                rewrite_defer_label(Some(label.as_str())),
+            );
+            self.pending_fragments.insert(
+                label.clone(),
+                PendingFragment {
+                    id,
+                    path: path.clone(),
+                    errors: Vec::new(),
+                },
+            );
+        }
+        let Some(fragment) = self.pending_fragments.get_mut(&label) else {
+            return;
+        };
+
+        if matches!(incremental.data, None | Some(Value::Null)) && !incremental.errors.is_empty() {
+            // the fragment was nullified, its errors are reported in the completion
+            fragment.errors.append(&mut incremental.errors);
+        } else {
+            if path.len() > fragment.path.len() && path.starts_with(&fragment.path) {
+                incremental.sub_path =
+                    Some(path.iter().skip(fragment.path.len()).cloned().collect());
+            }
+            incremental.id = Some(fragment.id.clone());
+            response.incremental.push(incremental);
+        }
+        if !delivered.contains(&label) {
+            delivered.push(label);
+        }
+    }
+
+    /// Announces the deferred fragments contained in a fragment, or in the primary response
+    fn announce_fragments(&mut self, response: &mut Response, parent: Option<&str>) {
+        let (announced, fragments): (Vec<_>, Vec<_>) = std::mem::take(&mut self.fragments)
+            .into_iter()
+            .partition(|fragment| fragment.parent.as_deref() == parent);
+        self.fragments = fragments;
+
+        for fragment in announced {
+            let path = pending_path(&fragment.query_path);
+            if let Some(data) = &response.data {
+                if is_null_at(data, &path) {
+                    // there is nothing to complete under a null value
+                    continue;
+                }
+            }
+            let label = fragment.label.as_deref().unwrap_or_default().to_owned();
+            let id = self.pending(
+                response,
+                path.clone(),
+                rewrite_defer_label(Some(label.as_str())),
+            );
+            self.pending_fragments.insert(
+                label,
+                PendingFragment {
+                    id,
+                    path,
+                    errors: Vec::new(),
+                },
+            );
+        }
+    }
+
+    fn pending(&mut self, response: &mut Response, path: Path, label: Option<String>) -> String {
+        let id = self.next_id.to_string();
+        self.next_id += 1;
+        response.pending.push(
+            PendingResponse::builder()
+                .id(id.clone())
+                .path(path)
+                .and_label(label)
+                .build(),
+        );
+        id
+    }
+}
+
+/// Returns the path of the object a deferred fragment applies to
+///
+/// Fragments applying to the items of a list are pending on the list.
+fn pending_path(query_path: &Path) -> Path {
+    query_path
+        .iter()
+        .take_while(|element| !matches!(element, PathElement::Flatten(_)))
+        .filter_map(|element| match element {
+            PathElement::Key(key, _) => Some(PathElement::Key(key.clone(), None)),
+            PathElement::Index(index) => Some(PathElement::Index(*index)),
+            PathElement::Flatten(_) | PathElement::Fragment(_) => None,
+        })
+        .collect()
+}
+
+/// Returns true if the value at this path, or one of its parents, is null
+fn is_null_at(data: &Value, path: &Path) -> bool {
+    let mut current = data;
+    for element in path.iter() {
+        let next = match element {
+            PathElement::Key(key, _) => current
+                .as_object()
+                .and_then(|object| object.get(key.as_str())),
+            PathElement::Index(index) => current.as_array().and_then(|items| items.get(*index)),
+            PathElement::Flatten(_) | PathElement::Fragment(_) => None,
+        };
+        match next {
+            Some(next) => current = next,
+            None => return current.is_null(),
+        }
+    }
+    current.is_null()
+}
+
+/// Rewrites the labels of the deferred fragments of a response to the ones of the query
+fn rewrite_defer_labels(mut response: Response) -> Response {
+    response.label = rewrite_defer_label(response.label.as_deref());
+    for incremental in &mut response.incremental {
+        // streamed items have the label of the stream directive
+        if incremental.items.is_none() {
+            incremental.label = rewrite_defer_label(incremental.label.as_deref());
+        }
+    }
+    response
+}
+
+fn rewrite_defer_label(label: Option<&str>) -> Option<String> {
+    if let Some(label) = label {
         #[allow(clippy::manual_map)] // use an explicit `if` to comment each case
         if let Some(rest) = label.strip_prefix('_') {
             // Drop the prefix added in labeler.rs
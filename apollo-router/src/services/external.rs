@@ -11,26 +11,37 @@ use http::HeaderMap;
 use http::HeaderValue;
 use http::Method;
 use http::StatusCode;
+use hyper::client::HttpConnector;
 use hyper::Body;
+use hyper_rustls::ConfigBuilderExt;
+use hyper_rustls::HttpsConnector;
 use opentelemetry::global::get_text_map_propagator;
 use schemars::JsonSchema;
 use serde::de::DeserializeOwned;
 use serde::Deserialize;
 use serde::Serialize;
 use strum_macros::Display;
+use tower::timeout::TimeoutLayer;
 use tower::BoxError;
 use tower::Service;
+use tower::ServiceBuilder;
 
 use crate::plugins::telemetry::otel::OpenTelemetrySpanExt;
 use crate::plugins::telemetry::reload::prepare_context;
 use crate::query_planner::QueryPlan;
+use crate::services::trust_dns_connector::new_async_http_connector;
+use crate::services::trust_dns_connector::AsyncHyperResolver;
 use crate::Context;
 
 pub(crate) const DEFAULT_EXTERNALIZATION_TIMEOUT: Duration = Duration::from_secs(1);
+const POOL_IDLE_TIMEOUT_DURATION: Option<Duration> = Some(Duration::from_secs(5));
 
 /// Version of our externalised data. Rev this if it changes
 pub(crate) const EXTERNALIZABLE_VERSION: u8 = 1;
 
+pub(crate) type HTTPClientService =
+    tower::timeout::Timeout<hyper::Client<HttpsConnector<HttpConnector<AsyncHyperResolver>>, Body>>;
+
 #[derive(Clone, Debug, Display, Deserialize, PartialEq, Serialize, JsonSchema)]
 pub(crate) enum PipelineStep {
     RouterRequest,
@@ -310,6 +321,34 @@ pub(crate) fn externalize_header_map(
     Ok(output)
 }
 
+/// HTTP client of the external services called by the router, like coprocessors
+pub(crate) fn new_http_client(timeout: Duration) -> Result<HTTPClientService, BoxError> {
+    let mut http_connector = new_async_http_connector()?;
+    http_connector.set_nodelay(true);
+    http_connector.set_keepalive(Some(std::time::Duration::from_secs(60)));
+    http_connector.enforce_http(false);
+
+    let tls_config = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_native_roots()
+        .with_no_client_auth();
+
+    let connector = hyper_rustls::HttpsConnectorBuilder::new()
+        .with_tls_config(tls_config)
+        .https_or_http()
+        .enable_http1()
+        .enable_http2()
+        .wrap_connector(http_connector);
+
+    Ok(ServiceBuilder::new()
+        .layer(TimeoutLayer::new(timeout))
+        .service(
+            hyper::Client::builder()
+                .pool_idle_timeout(POOL_IDLE_TIMEOUT_DURATION)
+                .build(connector),
+        ))
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -10,9 +10,11 @@ use futures::Stream;
 use futures::TryFutureExt;
 use global::get_text_map_propagator;
 use http::header::ACCEPT_ENCODING;
+use http::header::AUTHORIZATION;
 use http::header::CONTENT_ENCODING;
 use http::HeaderValue;
 use http::Request;
+use http::StatusCode;
 use hyper::client::HttpConnector;
 use hyper::Body;
 use hyper_rustls::HttpsConnector;
@@ -292,18 +294,20 @@ impl tower::Service<HttpRequest> for HttpClientService {
             .headers_mut()
             .insert(ACCEPT_ENCODING, ACCEPTED_ENCODINGS.clone());
 
-        let signing_params = context
+        let signing_params = http_request
             .extensions()
-            .lock()
             .get::<Arc<SigningParamsConfig>>()
             .cloned();
 
         Box::pin(async move {
-            let http_request = if let Some(signing_params) = signing_params {
+            let http_request = if let Some(signing_params) = &signing_params {
                 signing_params.sign(http_request, &service_name).await?
             } else {
                 http_request
             };
+            let authorization = signing_params
+                .as_ref()
+                .and_then(|_| http_request.headers().get(AUTHORIZATION).cloned());
 
             let display_headers = context.contains_key(LOGGING_DISPLAY_HEADERS);
             let display_body = context.contains_key(LOGGING_DISPLAY_BODY);
@@ -320,6 +324,14 @@ impl tower::Service<HttpRequest> for HttpClientService {
                 .instrument(http_req_span)
                 .await?;
 
+            // a rejected access token is not reused, even if it has not expired
+            if http_response.status() == StatusCode::UNAUTHORIZED {
+                if let (Some(signing_params), Some(authorization)) = (signing_params, authorization)
+                {
+                    signing_params.reject_token(&authorization).await;
+                }
+            }
+
             // Print out the debug for the response
             if display_headers {
                 tracing::info!(response.headers = ?http_response.headers(), apollo.subgraph.name = %service_name, "Response headers from subgraph {service_name:?}");
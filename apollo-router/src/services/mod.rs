@@ -23,6 +23,7 @@ pub(crate) use crate::services::supergraph::service::SupergraphCreator;
 pub(crate) use crate::services::supergraph::Request as SupergraphRequest;
 pub(crate) use crate::services::supergraph::Response as SupergraphResponse;
 
+pub(crate) mod connector;
 pub mod execution;
 pub(crate) mod external;
 pub(crate) mod http;
@@ -77,8 +78,17 @@ pub(crate) const MULTIPART_DEFER_ACCEPT: &str = "multipart/mixed;deferSpec=20220
 pub(crate) const MULTIPART_DEFER_CONTENT_TYPE: &str =
     "multipart/mixed;boundary=\"graphql\";deferSpec=20220824";
 
+// the current incremental delivery format, with `pending` and `completed` fragments, see https://github.com/graphql/defer-stream-wg/discussions/69
+pub(crate) const MULTIPART_INCREMENTAL_SPEC_PARAMETER: &str = "incrementalSpec";
+pub(crate) const MULTIPART_INCREMENTAL_SPEC_VALUE: &str = "v0.2";
+pub(crate) const MULTIPART_INCREMENTAL_ACCEPT: &str = "multipart/mixed;incrementalSpec=v0.2";
+pub(crate) const MULTIPART_INCREMENTAL_CONTENT_TYPE: &str =
+    "multipart/mixed;boundary=\"graphql\";incrementalSpec=v0.2";
+
 pub(crate) const MULTIPART_SUBSCRIPTION_ACCEPT: &str = "multipart/mixed;subscriptionSpec=1.0";
 pub(crate) const MULTIPART_SUBSCRIPTION_CONTENT_TYPE: &str =
     "multipart/mixed;boundary=\"graphql\";subscriptionSpec=1.0";
 pub(crate) const MULTIPART_SUBSCRIPTION_SPEC_PARAMETER: &str = "subscriptionSpec";
 pub(crate) const MULTIPART_SUBSCRIPTION_SPEC_VALUE: &str = "1.0";
+
+pub(crate) const EVENT_STREAM_CONTENT_TYPE: &str = "text/event-stream";
@@ -17,12 +17,14 @@ use serde_json_bytes::Value;
 use static_assertions::assert_impl_all;
 use tower::BoxError;
 
+use self::service::EVENT_STREAM_CONTENT_TYPE_HEADER_VALUE;
 use self::service::MULTIPART_DEFER_CONTENT_TYPE_HEADER_VALUE;
 use self::service::MULTIPART_SUBSCRIPTION_CONTENT_TYPE_HEADER_VALUE;
 use super::supergraph;
 use crate::graphql;
 use crate::http_ext::header_map;
 use crate::json_ext::Path;
+use crate::protocols::sse;
 use crate::services::TryIntoHeaderName;
 use crate::services::TryIntoHeaderValue;
 use crate::Context;
@@ -33,6 +35,7 @@ pub type ServiceResult = Result<Response, BoxError>;
 pub type Body = hyper::Body;
 pub type Error = hyper::Error;
 
+mod batch_limits;
 pub(crate) mod service;
 #[cfg(test)]
 mod tests;
@@ -333,17 +336,28 @@ impl Response {
             {
                 let multipart = Multipart::new(self.response.into_body(), "graphql");
 
-                Either::Left(futures::stream::unfold(multipart, |mut m| async {
-                    if let Ok(Some(response)) = m.next_field().await {
-                        if let Ok(bytes) = response.bytes().await {
-                            return Some((
-                                serde_json::from_slice::<crate::graphql::Response>(&bytes),
-                                m,
-                            ));
+                Either::Left(Either::Left(futures::stream::unfold(
+                    multipart,
+                    |mut m| async {
+                        if let Ok(Some(response)) = m.next_field().await {
+                            if let Ok(bytes) = response.bytes().await {
+                                return Some((
+                                    serde_json::from_slice::<crate::graphql::Response>(&bytes),
+                                    m,
+                                ));
+                            }
                         }
-                    }
-                    None
-                }))
+                        None
+                    },
+                )))
+            } else if self
+                .response
+                .headers()
+                .get(CONTENT_TYPE)
+                .iter()
+                .any(|value| *value == EVENT_STREAM_CONTENT_TYPE_HEADER_VALUE)
+            {
+                Either::Left(Either::Right(sse::parse(self.response.into_body())))
             } else {
                 let mut body = self.response.into_body();
                 let res = body.next().await.and_then(|res| res.ok());
@@ -389,7 +403,10 @@ impl Response {
 #[derive(Clone, Default, Debug)]
 pub(crate) struct ClientRequestAccepts {
     pub(crate) multipart_defer: bool,
+    /// `@defer` responses use the current incremental delivery format instead of the legacy one
+    pub(crate) incremental_delivery: bool,
     pub(crate) multipart_subscription: bool,
+    pub(crate) event_stream: bool,
     pub(crate) json: bool,
     pub(crate) wildcard: bool,
 }
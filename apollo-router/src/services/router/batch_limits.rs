@@ -0,0 +1,178 @@
+//! Size, depth and cost limits of the operations of a batch.
+//!
+//! The depth and cost of an operation are only known once its query is resolved, from APQ or
+//! persisted queries, and analyzed. The operations of a batch are checked at that point, in the
+//! order of the batch: each operation waits for the ones before it to be checked, and an operation
+//! that would take the batch over a limit is rejected without counting towards its totals.
+
+use std::sync::Arc;
+
+use displaydoc::Display;
+use thiserror::Error;
+use tokio::sync::watch;
+
+use crate::configuration::BatchItemErrors;
+use crate::configuration::Batching;
+
+/// the batch exceeds the `batching.{limit}` limit
+#[derive(Debug, Display, Error)]
+pub(crate) struct BatchLimitExceeded {
+    pub(crate) limit: &'static str,
+}
+
+/// The totals of a batch, shared by its operations
+pub(crate) struct BatchLimits {
+    batching: Batching,
+    state: watch::Sender<BatchLimitsState>,
+}
+
+struct BatchLimitsState {
+    /// Operations that were checked, or that failed before they could be
+    checked: Vec<bool>,
+    /// Index of the first operation that was not checked yet
+    next: usize,
+    size: usize,
+    total_depth: u32,
+    total_cost: f64,
+    /// Without per-item errors, the limit that rejected the whole batch
+    exceeded: Option<&'static str>,
+}
+
+impl BatchLimitsState {
+    fn mark_checked(&mut self, index: usize) -> bool {
+        if self.checked[index] {
+            return false;
+        }
+        self.checked[index] = true;
+        while self.checked.get(self.next) == Some(&true) {
+            self.next += 1;
+        }
+        true
+    }
+}
+
+impl BatchLimits {
+    /// Returns the limits of a batch of `size` operations, if any limit is configured
+    pub(crate) fn new(batching: &Batching, size: usize) -> Option<Arc<Self>> {
+        (batching.max_size.is_some()
+            || batching.max_total_depth.is_some()
+            || batching.max_total_cost.is_some())
+        .then(|| {
+            Arc::new(Self {
+                batching: batching.clone(),
+                state: watch::Sender::new(BatchLimitsState {
+                    checked: vec![false; size],
+                    next: 0,
+                    size: 0,
+                    total_depth: 0,
+                    total_cost: 0.0,
+                    exceeded: None,
+                }),
+            })
+        })
+    }
+
+    /// Returns the turn of the operation at `index` in the batch
+    pub(crate) fn turn(self: &Arc<Self>, index: usize) -> BatchTurn {
+        BatchTurn {
+            limits: self.clone(),
+            index,
+        }
+    }
+
+    fn exceeded_limit(
+        &self,
+        state: &BatchLimitsState,
+        depth: u32,
+        cost: f64,
+    ) -> Option<&'static str> {
+        if self
+            .batching
+            .max_size
+            .is_some_and(|max_size| state.size + 1 > max_size)
+        {
+            Some("max_size")
+        } else if self
+            .batching
+            .max_total_depth
+            .is_some_and(|max_total_depth| state.total_depth + depth > max_total_depth)
+        {
+            Some("max_total_depth")
+        } else if self
+            .batching
+            .max_total_cost
+            .as_ref()
+            .is_some_and(|max_total_cost| state.total_cost + cost > max_total_cost.max)
+        {
+            Some("max_total_cost")
+        } else {
+            None
+        }
+    }
+}
+
+/// The place of an operation in its batch, kept in the extensions of its context
+///
+/// An operation that fails before it is checked drops its turn, so that the next operations
+/// don't wait for it.
+pub(crate) struct BatchTurn {
+    limits: Arc<BatchLimits>,
+    index: usize,
+}
+
+impl BatchTurn {
+    /// Checks the operation against the limits of the batch, once the operations before it are checked
+    ///
+    /// Without per-item errors, the operation then waits for the whole batch to be checked, so
+    /// that no operation of a rejected batch is executed.
+    pub(crate) async fn check(self, depth: u32, cost: f64) -> Result<(), BatchLimitExceeded> {
+        let limits = &self.limits;
+        let mut receiver = limits.state.subscribe();
+        let _ = receiver.wait_for(|state| state.next == self.index).await;
+
+        let mut exceeded = None;
+        limits.state.send_modify(|state| {
+            if state.exceeded.is_none() {
+                exceeded = limits.exceeded_limit(state, depth, cost);
+                match exceeded {
+                    None => {
+                        state.size += 1;
+                        state.total_depth += depth;
+                        state.total_cost += cost;
+                    }
+                    Some(limit) => {
+                        u64_counter!(
+                            "apollo.router.operations.batching.errors",
+                            "Total number of failed operations in batches",
+                            1,
+                            reason = limit
+                        );
+                        if limits.batching.item_errors == BatchItemErrors::FailBatch {
+                            state.exceeded = Some(limit);
+                        }
+                    }
+                }
+            }
+            state.mark_checked(self.index);
+        });
+
+        if limits.batching.item_errors == BatchItemErrors::FailBatch {
+            let _ = receiver
+                .wait_for(|state| state.next == state.checked.len())
+                .await;
+            exceeded = limits.state.borrow().exceeded;
+        }
+        match exceeded {
+            Some(limit) => Err(BatchLimitExceeded { limit }),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for BatchTurn {
+    fn drop(&mut self) {
+        self.limits
+            .state
+            .send_if_modified(|state| state.mark_checked(self.index));
+    }
+}
@@ -1,6 +1,8 @@
 //! Implements the router phase of the request lifecycle.
 
 use std::collections::HashMap;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::task::Poll;
 
@@ -15,6 +17,7 @@ use futures::future::BoxFuture;
 use futures::stream;
 use futures::stream::once;
 use futures::stream::StreamExt;
+use http::header::CACHE_CONTROL;
 use http::header::CONTENT_TYPE;
 use http::header::VARY;
 use http::request::Parts;
@@ -34,25 +37,32 @@ use tower::ServiceExt;
 use tower_service::Service;
 use tracing::Instrument;
 
+use super::batch_limits::BatchLimitExceeded;
+use super::batch_limits::BatchLimits;
+use super::batch_limits::BatchTurn;
 use super::ClientRequestAccepts;
 use crate::axum_factory::CanceledRequest;
 use crate::batching::Batch;
 use crate::batching::BatchQuery;
 use crate::cache::DeduplicatingCache;
+use crate::configuration::BatchItemErrors;
 use crate::configuration::Batching;
 use crate::configuration::BatchingMode;
 use crate::graphql;
 use crate::http_ext;
 #[cfg(test)]
 use crate::plugin::test::MockSupergraphService;
+use crate::plugins::demand_control::cost_calculator::static_cost::StaticCostCalculator;
 use crate::protocols::multipart::Multipart;
 use crate::protocols::multipart::ProtocolMode;
+use crate::protocols::sse::EventStream;
 use crate::query_planner::InMemoryCachePlanner;
 use crate::router_factory::RouterFactory;
 use crate::services::layers::apq::APQLayer;
 use crate::services::layers::content_negotiation;
 use crate::services::layers::content_negotiation::GRAPHQL_JSON_RESPONSE_HEADER_VALUE;
 use crate::services::layers::persisted_queries::PersistedQueryLayer;
+use crate::services::layers::query_analysis::ParsedDocument;
 use crate::services::layers::query_analysis::QueryAnalysisLayer;
 use crate::services::layers::static_page::StaticPageLayer;
 use crate::services::new_service::ServiceFactory;
@@ -68,10 +78,13 @@ use crate::services::SupergraphCreator;
 use crate::services::SupergraphRequest;
 use crate::services::SupergraphResponse;
 use crate::services::APPLICATION_JSON_HEADER_VALUE;
+use crate::services::EVENT_STREAM_CONTENT_TYPE;
 use crate::services::MULTIPART_DEFER_ACCEPT;
 use crate::services::MULTIPART_DEFER_CONTENT_TYPE;
+use crate::services::MULTIPART_INCREMENTAL_CONTENT_TYPE;
 use crate::services::MULTIPART_SUBSCRIPTION_ACCEPT;
 use crate::services::MULTIPART_SUBSCRIPTION_CONTENT_TYPE;
+use crate::spec::operation_limits;
 use crate::Configuration;
 use crate::Context;
 use crate::Endpoint;
@@ -79,8 +92,12 @@ use crate::ListenAddr;
 
 pub(crate) static MULTIPART_DEFER_CONTENT_TYPE_HEADER_VALUE: HeaderValue =
     HeaderValue::from_static(MULTIPART_DEFER_CONTENT_TYPE);
+pub(crate) static MULTIPART_INCREMENTAL_CONTENT_TYPE_HEADER_VALUE: HeaderValue =
+    HeaderValue::from_static(MULTIPART_INCREMENTAL_CONTENT_TYPE);
 pub(crate) static MULTIPART_SUBSCRIPTION_CONTENT_TYPE_HEADER_VALUE: HeaderValue =
     HeaderValue::from_static(MULTIPART_SUBSCRIPTION_CONTENT_TYPE);
+pub(crate) static EVENT_STREAM_CONTENT_TYPE_HEADER_VALUE: HeaderValue =
+    HeaderValue::from_static(EVENT_STREAM_CONTENT_TYPE);
 static ACCEL_BUFFERING_HEADER_NAME: HeaderName = HeaderName::from_static("x-accel-buffering");
 static ACCEL_BUFFERING_HEADER_VALUE: HeaderValue = HeaderValue::from_static("no");
 static ORIGIN_HEADER_VALUE: HeaderValue = HeaderValue::from_static("origin");
@@ -249,7 +266,10 @@ impl RouterService {
                     .await
                 {
                     Err(response) => response,
-                    Ok(request) => self.supergraph_creator.create().oneshot(request).await?,
+                    Ok(request) => {
+                        self.check_batch_limits(&request).await?;
+                        self.supergraph_creator.create().oneshot(request).await?
+                    }
                 },
             },
         };
@@ -258,7 +278,9 @@ impl RouterService {
             wildcard: accepts_wildcard,
             json: accepts_json,
             multipart_defer: accepts_multipart_defer,
+            incremental_delivery: accepts_incremental_delivery,
             multipart_subscription: accepts_multipart_subscription,
+            event_stream: accepts_event_stream,
         } = context
             .extensions()
             .lock()
@@ -312,7 +334,12 @@ impl RouterService {
                         })
                     })
                 } else if accepts_multipart_defer || accepts_multipart_subscription {
-                    if accepts_multipart_defer {
+                    if accepts_multipart_defer && accepts_incremental_delivery {
+                        parts.headers.insert(
+                            CONTENT_TYPE,
+                            MULTIPART_INCREMENTAL_CONTENT_TYPE_HEADER_VALUE.clone(),
+                        );
+                    } else if accepts_multipart_defer {
                         parts.headers.insert(
                             CONTENT_TYPE,
                             MULTIPART_DEFER_CONTENT_TYPE_HEADER_VALUE.clone(),
@@ -368,6 +395,48 @@ impl RouterService {
                     });
 
                     Ok(RouterResponse { response, context })
+                } else if accepts_event_stream {
+                    parts
+                        .headers
+                        .insert(CONTENT_TYPE, EVENT_STREAM_CONTENT_TYPE_HEADER_VALUE.clone());
+                    parts
+                        .headers
+                        .insert(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+                    parts.headers.insert(
+                        ACCEL_BUFFERING_HEADER_NAME.clone(),
+                        ACCEL_BUFFERING_HEADER_VALUE.clone(),
+                    );
+
+                    if !response.errors.is_empty() {
+                        Self::count_errors(&response.errors);
+                    }
+
+                    let event_stream = match response.subscribed {
+                        Some(true) => EventStream::new(
+                            body.inspect(|response| {
+                                if !response.errors.is_empty() {
+                                    Self::count_errors(&response.errors);
+                                }
+                            }),
+                            ProtocolMode::Subscription,
+                        ),
+                        _ => EventStream::new(
+                            once(ready(response)).chain(body.inspect(|response| {
+                                if !response.errors.is_empty() {
+                                    Self::count_errors(&response.errors);
+                                }
+                            })),
+                            ProtocolMode::Defer,
+                        ),
+                    };
+
+                    Ok(RouterResponse {
+                        response: http::Response::from_parts(
+                            parts,
+                            Body::wrap_stream(event_stream),
+                        ),
+                        context,
+                    })
                 } else {
                     tracing::info!(
                         monotonic_counter.apollo.router.graphql_error = 1u64,
@@ -379,11 +448,12 @@ impl RouterService {
                             .error(
                                 graphql::Error::builder()
                                     .message(format!(
-                                        r#"'accept' header must be one of: \"*/*\", {:?}, {:?}, {:?} or {:?}"#,
+                                        r#"'accept' header must be one of: \"*/*\", {:?}, {:?}, {:?}, {:?} or {:?}"#,
                                         APPLICATION_JSON.essence_str(),
                                         GRAPHQL_JSON_RESPONSE_HEADER_VALUE,
                                         MULTIPART_DEFER_ACCEPT,
                                         MULTIPART_SUBSCRIPTION_ACCEPT,
+                                        EVENT_STREAM_CONTENT_TYPE,
                                     ))
                                     .extension_code("INVALID_ACCEPT_HEADER")
                                     .build(),
@@ -426,59 +496,142 @@ impl RouterService {
             }
         };
 
+        let deadline = self
+            .batching
+            .timeout
+            .filter(|_| is_batch)
+            .map(|timeout| tokio::time::Instant::now() + timeout);
+
         // We need to handle cases where a failure is part of a batch and thus must be cancelled.
         // Requests can be cancelled at any point of the router pipeline, but all failures bubble back
         // up through here, so we can catch them without having to specially handle batch queries in
         // other portions of the codebase.
-        let futures = supergraph_requests
-            .into_iter()
-            .map(|supergraph_request| async {
-                // We clone the context here, because if the request results in an Err, the
-                // response context will no longer exist.
-                let context = supergraph_request.context.clone();
-                let result = self.process_supergraph_request(supergraph_request).await;
-
-                // Regardless of the result, we need to make sure that we cancel any potential batch queries. This is because
-                // custom rust plugins, rhai scripts, and coprocessors can cancel requests at any time and return a GraphQL
-                // error wrapped in an `Ok` or in a `BoxError` wrapped in an `Err`.
-                let batch_query_opt = context.extensions().lock().remove::<BatchQuery>();
-                if let Some(batch_query) = batch_query_opt {
-                    // Only proceed with signalling cancelled if the batch_query is not finished
-                    if !batch_query.finished() {
-                        tracing::debug!("cancelling batch query in supergraph response");
-                        batch_query
-                            .signal_cancelled("request terminated by user".to_string())
-                            .await?;
-                    }
+        let futures = supergraph_requests.into_iter().map(|item| async {
+            let supergraph_request = match item {
+                Ok(supergraph_request) => supergraph_request,
+                Err(error) => return Ok::<_, BoxError>(Err((StatusCode::BAD_REQUEST, error))),
+            };
+            // We clone the context here, because if the request results in an Err, the
+            // response context will no longer exist.
+            let context = supergraph_request.context.clone();
+            let result = match deadline {
+                Some(deadline) => tokio::time::timeout_at(
+                    deadline,
+                    self.process_supergraph_request(supergraph_request),
+                )
+                .await
+                .map_err(|_| {
+                    u64_counter!(
+                        "apollo.router.operations.batching.errors",
+                        "Total number of failed operations in batches",
+                        1,
+                        reason = "timeout"
+                    );
+                    (
+                        StatusCode::GATEWAY_TIMEOUT,
+                        graphql::Error::builder()
+                            .message("Batch timed out")
+                            .extension_code("BATCH_TIMEOUT")
+                            .build(),
+                    )
+                }),
+                None => Ok(self.process_supergraph_request(supergraph_request).await),
+            };
+
+            // An operation that failed before it was checked against the batch limits lets the
+            // next ones go through
+            context.extensions().lock().remove::<BatchTurn>();
+
+            // Regardless of the result, we need to make sure that we cancel any potential batch queries. This is because
+            // custom rust plugins, rhai scripts, and coprocessors can cancel requests at any time and return a GraphQL
+            // error wrapped in an `Ok` or in a `BoxError` wrapped in an `Err`.
+            let batch_query_opt = context.extensions().lock().remove::<BatchQuery>();
+            if let Some(batch_query) = batch_query_opt {
+                // Only proceed with signalling cancelled if the batch_query is not finished
+                if !batch_query.finished() {
+                    tracing::debug!("cancelling batch query in supergraph response");
+                    batch_query
+                        .signal_cancelled("request terminated by user".to_string())
+                        .await?;
                 }
+            }
 
-                result
-            });
+            match result {
+                Ok(Ok(response)) => Ok(Ok(response)),
+                Ok(Err(error)) => match error.downcast::<BatchLimitExceeded>() {
+                    Ok(exceeded) => Ok(Err((
+                        StatusCode::BAD_REQUEST,
+                        self.batch_limit_error(exceeded.limit),
+                    ))),
+                    Err(error) => Err(error),
+                },
+                Err(error) => Ok(Err(error)),
+            }
+        });
 
         // Use join_all to preserve ordering of concurrent operations
         // (Short circuit processing and propagate any errors in the batch)
         // Note: We use `join_all` here since it awaits all futures before returning, thus allowing us to
         // handle cancellation logic without fear of the other futures getting killed.
-        let mut results: Vec<router::Response> = join_all(futures)
+        let mut results = join_all(futures)
             .await
             .into_iter()
-            .collect::<Result<Vec<router::Response>, BoxError>>()?;
+            .collect::<Result<Vec<_>, BoxError>>()?;
+
+        if self.batching.item_errors == BatchItemErrors::FailBatch {
+            // Without per-item errors, an operation can only fail here if the batch timed out or
+            // exceeded a limit
+            if let Some((status, error)) = results.iter().find_map(|result| result.as_ref().err()) {
+                return router::Response::error_builder()
+                    .error(error.clone())
+                    .status_code(*status)
+                    .header(CONTENT_TYPE, APPLICATION_JSON.essence_str())
+                    .context(context)
+                    .build();
+            }
+        }
 
         // If we detected we are processing a batch, return an array of results even if there is only
         // one result
         if is_batch {
-            let mut results_it = results.into_iter();
-            let first = results_it
-                .next()
-                .expect("we should have at least one response");
-            let (parts, body) = first.response.into_parts();
-            let context = first.context;
+            // The parts of the response are taken from the first operation that was executed
+            let mut first = None;
+            let mut bodies = Vec::with_capacity(results.len());
+            let mut status = StatusCode::OK;
+            for result in results {
+                match result {
+                    Ok(response) => {
+                        let (parts, body) = response.response.into_parts();
+                        bodies.push(hyper::body::to_bytes(body).await?);
+                        first.get_or_insert((parts, response.context));
+                    }
+                    Err((error_status, error)) => {
+                        let response = graphql::Response::builder().error(error).build();
+                        bodies.push(Bytes::from(serde_json::to_vec(&response)?));
+                        status = error_status;
+                    }
+                }
+            }
+            let (parts, context) = match first {
+                Some(first) => first,
+                None => {
+                    // Every operation of the batch failed
+                    let (mut parts, ()) = http::Response::new(()).into_parts();
+                    parts.status = status;
+                    parts
+                        .headers
+                        .insert(CONTENT_TYPE, APPLICATION_JSON_HEADER_VALUE.clone());
+                    (parts, context)
+                }
+            };
+
             let mut bytes = BytesMut::new();
             bytes.put_u8(b'[');
-            bytes.extend_from_slice(&hyper::body::to_bytes(body).await?);
-            for result in results_it {
-                bytes.put(&b", "[..]);
-                bytes.extend_from_slice(&hyper::body::to_bytes(result.response.into_body()).await?);
+            for (index, body) in bodies.into_iter().enumerate() {
+                if index > 0 {
+                    bytes.put(&b", "[..]);
+                }
+                bytes.extend_from_slice(&body);
             }
             bytes.put_u8(b']');
 
@@ -487,21 +640,24 @@ impl RouterService {
                 context,
             })
         } else {
-            Ok(results.pop().expect("we should have at least one response"))
+            Ok(results
+                .pop()
+                .and_then(Result::ok)
+                .expect("we should have at least one response"))
         }
     }
 
     async fn translate_query_request(
         &self,
         parts: &Parts,
-    ) -> Result<(Vec<graphql::Request>, bool), TranslateError> {
+    ) -> Result<(Vec<Result<graphql::Request, graphql::Error>>, bool), TranslateError> {
         let mut is_batch = false;
         parts.uri.query().map(|q| {
             let mut result = vec![];
 
             match graphql::Request::from_urlencoded_query(q.to_string()) {
                 Ok(request) => {
-                    result.push(request);
+                    result.push(Ok(request));
                 }
                 Err(err) => {
                     // It may be a batch of requests, so try that (if config allows) before
@@ -509,15 +665,19 @@ impl RouterService {
                     if self.batching.enabled
                         && matches!(self.batching.mode, BatchingMode::BatchHttpLink)
                     {
-                        result = graphql::Request::batch_from_urlencoded_query(q.to_string())
-                            .map_err(|e| TranslateError {
-                                status: StatusCode::BAD_REQUEST,
-                                error: "failed to decode a valid GraphQL request from path",
-                                extension_code: "INVALID_GRAPHQL_REQUEST",
-                                extension_details: format!(
-                                    "failed to decode a valid GraphQL request from path {e}"
-                                ),
-                            })?;
+                        let invalid_request = |e: serde_json::Error| TranslateError {
+                            status: StatusCode::BAD_REQUEST,
+                            error: "failed to decode a valid GraphQL request from path",
+                            extension_code: "INVALID_GRAPHQL_REQUEST",
+                            extension_details: format!(
+                                "failed to decode a valid GraphQL request from path {e}"
+                            ),
+                        };
+                        result = self.batch_items(
+                            graphql::Request::batch_from_urlencoded_query(q.to_string())
+                                .map_err(invalid_request)?,
+                            invalid_request,
+                        )?;
                         if result.is_empty() {
                             return Err(TranslateError {
                                 status: StatusCode::BAD_REQUEST,
@@ -566,27 +726,30 @@ impl RouterService {
     fn translate_bytes_request(
         &self,
         bytes: &Bytes,
-    ) -> Result<(Vec<graphql::Request>, bool), TranslateError> {
+    ) -> Result<(Vec<Result<graphql::Request, graphql::Error>>, bool), TranslateError> {
         let mut result = vec![];
         let mut is_batch = false;
 
         match graphql::Request::deserialize_from_bytes(bytes) {
             Ok(request) => {
-                result.push(request);
+                result.push(Ok(request));
             }
             Err(err) => {
                 if self.batching.enabled
                     && matches!(self.batching.mode, BatchingMode::BatchHttpLink)
                 {
-                    result =
-                        graphql::Request::batch_from_bytes(bytes).map_err(|e| TranslateError {
-                            status: StatusCode::BAD_REQUEST,
-                            error: "failed to deserialize the request body into JSON",
-                            extension_code: "INVALID_GRAPHQL_REQUEST",
-                            extension_details: format!(
-                                "failed to deserialize the request body into JSON: {e}"
-                            ),
-                        })?;
+                    let invalid_request = |e: serde_json::Error| TranslateError {
+                        status: StatusCode::BAD_REQUEST,
+                        error: "failed to deserialize the request body into JSON",
+                        extension_code: "INVALID_GRAPHQL_REQUEST",
+                        extension_details: format!(
+                            "failed to deserialize the request body into JSON: {e}"
+                        ),
+                    };
+                    result = self.batch_items(
+                        graphql::Request::batch_from_bytes(bytes).map_err(invalid_request)?,
+                        invalid_request,
+                    )?;
                     if result.is_empty() {
                         return Err(TranslateError {
                             status: StatusCode::BAD_REQUEST,
@@ -627,10 +790,135 @@ impl RouterService {
         Ok((result, is_batch))
     }
 
+    /// Handles the operations of a batch that could not be parsed, depending on `batching.item_errors`
+    fn batch_items<'a>(
+        &self,
+        items: Vec<Result<graphql::Request, serde_json::Error>>,
+        invalid_request: impl Fn(serde_json::Error) -> TranslateError<'a>,
+    ) -> Result<Vec<Result<graphql::Request, graphql::Error>>, TranslateError<'a>> {
+        items
+            .into_iter()
+            .map(|item| match item {
+                Ok(request) => Ok(Ok(request)),
+                Err(e) => {
+                    u64_counter!(
+                        "apollo.router.operations.batching.errors",
+                        "Total number of failed operations in batches",
+                        1,
+                        reason = "invalid_request"
+                    );
+                    let err = invalid_request(e);
+                    match self.batching.item_errors {
+                        BatchItemErrors::FailBatch => Err(err),
+                        BatchItemErrors::PerItem => Ok(Err(graphql::Error::builder()
+                            .message(String::from("Invalid GraphQL request"))
+                            .extension_code(err.extension_code)
+                            .extension("details", err.extension_details)
+                            .build())),
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// Rejects a batch with more operations than `batching.max_size` before it is processed
+    ///
+    /// Without per-item errors, a batch exceeding the size limit is rejected as a whole, so there
+    /// is no need to resolve its operations first. The other limits are checked once the query of
+    /// each operation is known, see [`Self::check_batch_limits`].
+    fn check_batch_size(
+        &self,
+        items: &[Result<graphql::Request, graphql::Error>],
+    ) -> Result<(), TranslateError> {
+        let size = items.iter().filter(|item| item.is_ok()).count();
+        if self.batching.item_errors == BatchItemErrors::FailBatch
+            && self
+                .batching
+                .max_size
+                .is_some_and(|max_size| size > max_size)
+        {
+            u64_counter!(
+                "apollo.router.operations.batching.errors",
+                "Total number of failed operations in batches",
+                1,
+                reason = "max_size"
+            );
+            return Err(TranslateError {
+                status: StatusCode::BAD_REQUEST,
+                error: "batch limit exceeded",
+                extension_code: "BATCH_LIMIT_EXCEEDED",
+                extension_details: "the batch exceeds the `batching.max_size` limit".to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Applies the size, depth and cost limits of its batch to an operation
+    ///
+    /// The query of the operation was resolved from APQ or persisted queries and analyzed, so
+    /// its depth and cost are computed from the cached parsed document.
+    async fn check_batch_limits(
+        &self,
+        request: &SupergraphRequest,
+    ) -> Result<(), BatchLimitExceeded> {
+        let Some(turn) = request.context.extensions().lock().remove::<BatchTurn>() else {
+            return Ok(());
+        };
+        let document = request
+            .context
+            .extensions()
+            .lock()
+            .get::<ParsedDocument>()
+            .cloned();
+        let operation_name = request.supergraph_request.body().operation_name.as_deref();
+
+        let depth = match (&document, &self.batching.max_total_depth) {
+            (Some(document), Some(_)) => {
+                operation_limits::depth(&document.executable, operation_name).unwrap_or(0)
+            }
+            _ => 0,
+        };
+        let cost = match (&document, &self.batching.max_total_cost) {
+            (Some(document), Some(max_total_cost)) => {
+                StaticCostCalculator::new(Default::default(), max_total_cost.list_size)
+                    .estimated(
+                        &document.executable,
+                        self.query_analysis_layer.schema.supergraph_schema(),
+                        false,
+                    )
+                    .unwrap_or(0.0)
+            }
+            _ => 0.0,
+        };
+        turn.check(depth, cost).await
+    }
+
+    /// The error of an operation rejected by a limit of its batch
+    fn batch_limit_error(&self, limit: &str) -> graphql::Error {
+        match self.batching.item_errors {
+            BatchItemErrors::FailBatch => graphql::Error::builder()
+                .message(String::from("Invalid GraphQL request"))
+                .extension_code("BATCH_LIMIT_EXCEEDED")
+                .extension(
+                    "details",
+                    format!("the batch exceeds the `batching.{limit}` limit"),
+                )
+                .build(),
+            BatchItemErrors::PerItem => graphql::Error::builder()
+                .message(String::from("Batch limit exceeded"))
+                .extension_code("BATCH_LIMIT_EXCEEDED")
+                .extension(
+                    "details",
+                    format!("the operation exceeds the `batching.{limit}` limit of the batch"),
+                )
+                .build(),
+        }
+    }
+
     async fn translate_request(
         &self,
         req: RouterRequest,
-    ) -> Result<(Vec<SupergraphRequest>, bool), TranslateError> {
+    ) -> Result<(Vec<Result<SupergraphRequest, graphql::Error>>, bool), TranslateError> {
         let RouterRequest {
             router_request,
             context,
@@ -638,10 +926,10 @@ impl RouterService {
 
         let (parts, body) = router_request.into_parts();
 
-        let graphql_requests: Result<(Vec<graphql::Request>, bool), TranslateError> = if parts
-            .method
-            == Method::GET
-        {
+        let graphql_requests: Result<
+            (Vec<Result<graphql::Request, graphql::Error>>, bool),
+            TranslateError,
+        > = if parts.method == Method::GET {
             self.translate_query_request(&parts).await
         } else {
             // FIXME: use a try block when available: https://github.com/rust-lang/rust/issues/31436
@@ -689,9 +977,33 @@ impl RouterService {
             }
         };
 
-        let (ok_results, is_batch) = graphql_requests?;
+        let (items, is_batch) = graphql_requests?;
+        if is_batch {
+            self.check_batch_size(&items)?;
+        }
+
+        // The rejected operations of a batch are put back at their index once the other ones
+        // are turned into supergraph requests
+        let mut ok_results = Vec::with_capacity(items.len());
+        let mut rejected = Vec::new();
+        for (index, item) in items.into_iter().enumerate() {
+            match item {
+                Ok(request) => ok_results.push(request),
+                Err(error) => rejected.push((index, error)),
+            }
+        }
+        if ok_results.is_empty() {
+            return Ok((
+                rejected.into_iter().map(|(_, error)| Err(error)).collect(),
+                is_batch,
+            ));
+        }
         let mut results = Vec::with_capacity(ok_results.len());
         let batch_size = ok_results.len();
+        // Each operation gets a turn to be checked against the limits of the batch
+        let batch_limits = is_batch
+            .then(|| BatchLimits::new(&self.batching, batch_size))
+            .flatten();
 
         // Modifying our Context extensions.
         // If we are processing a batch (is_batch == true), insert our batching configuration.
@@ -753,6 +1065,9 @@ impl RouterService {
                     new_context_guard.insert(client_request_accepts);
                 }
                 new_context_guard.insert(self.batching.clone());
+                if let Some(batch_limits) = &batch_limits {
+                    new_context_guard.insert(batch_limits.turn(index + 1));
+                }
                 // We are only going to insert a BatchQuery if Subgraph processing is enabled
                 if let Some(shared_batch_details) = &shared_batch_details {
                     new_context_guard.insert(
@@ -773,6 +1088,9 @@ impl RouterService {
             });
         }
 
+        if let Some(batch_limits) = &batch_limits {
+            context.extensions().lock().insert(batch_limits.turn(0));
+        }
         if let Some(shared_batch_details) = shared_batch_details {
             context.extensions().lock().insert(
                 Batch::query_for_index(shared_batch_details, 0).map_err(|err| TranslateError {
@@ -792,6 +1110,11 @@ impl RouterService {
             },
         );
 
+        let mut results: Vec<_> = results.into_iter().map(Ok).collect();
+        for (index, error) in rejected {
+            results.insert(index, Err(error));
+        }
+
         Ok((results, is_batch))
     }
 
@@ -844,6 +1167,17 @@ pub(crate) struct RouterCreator {
     query_analysis_layer: QueryAnalysisLayer,
     http_max_request_bytes: usize,
     batching: Batching,
+    warmed_up: Arc<AtomicBool>,
+    warm_up_task: Option<Arc<WarmUpTask>>,
+}
+
+/// Background query plan cache warm up, cancelled when the last [`RouterCreator`] referencing it is dropped
+struct WarmUpTask(tokio::task::JoinHandle<()>);
+
+impl Drop for WarmUpTask {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
 }
 
 impl ServiceFactory<router::Request> for RouterCreator {
@@ -868,6 +1202,10 @@ impl RouterFactory for RouterCreator {
             .for_each(|p| mm.extend(p.web_endpoints()));
         mm
     }
+
+    fn is_ready(&self) -> bool {
+        self.warmed_up.load(Ordering::SeqCst)
+    }
 }
 
 impl RouterCreator {
@@ -895,9 +1233,44 @@ impl RouterCreator {
             http_max_request_bytes: configuration.limits.http_max_request_bytes,
             persisted_query_layer,
             batching: configuration.batching.clone(),
+            warmed_up: Arc::new(AtomicBool::new(true)),
+            warm_up_task: None,
         })
     }
 
+    /// Plans every operation of the persisted query list in a background task.
+    ///
+    /// If `wait_for_readiness` is set, the router is reported as not ready until the task completes.
+    pub(crate) fn warm_up_persisted_queries_in_background(&mut self, wait_for_readiness: bool) {
+        if wait_for_readiness {
+            self.warmed_up.store(false, Ordering::SeqCst);
+        }
+
+        let supergraph_creator = self.supergraph_creator.clone();
+        let query_analysis_layer = self.query_analysis_layer.clone();
+        let persisted_query_layer = self.persisted_query_layer.clone();
+        let warmed_up = self.warmed_up.clone();
+        let task = tokio::spawn(
+            async move {
+                supergraph_creator
+                    .warm_up_query_planner(
+                        &query_analysis_layer,
+                        Some(&persisted_query_layer),
+                        None,
+                        None,
+                        false,
+                    )
+                    .await;
+                warmed_up.store(true, Ordering::SeqCst);
+                tracing::info!(
+                    "finished warming up the query plan cache with the persisted query list"
+                );
+            }
+            .in_current_span(),
+        );
+        self.warm_up_task = Some(Arc::new(WarmUpTask(task)));
+    }
+
     pub(crate) fn make(
         &self,
     ) -> impl Service<
@@ -14,6 +14,7 @@ use tower::ServiceExt;
 use tower_service::Service;
 
 use crate::graphql;
+use crate::metrics::FutureMetricsExt;
 use crate::services::router;
 use crate::services::router::service::from_supergraph_mock_callback;
 use crate::services::router::service::process_vary_header;
@@ -21,6 +22,7 @@ use crate::services::subgraph;
 use crate::services::supergraph;
 use crate::services::SupergraphRequest;
 use crate::services::SupergraphResponse;
+use crate::services::EVENT_STREAM_CONTENT_TYPE;
 use crate::services::MULTIPART_DEFER_CONTENT_TYPE;
 use crate::Context;
 
@@ -471,6 +473,53 @@ async fn it_will_process_a_non_batched_defered_query() {
     assert_eq!(expected_response, data);
 }
 
+#[tokio::test]
+async fn it_will_process_a_deferred_query_over_server_sent_events() {
+    let expected_response = "event: next\ndata: {\"data\":{\"topProducts\":[{\"upc\":\"1\",\"name\":\"Table\",\"reviews\":[{\"product\":{\"name\":\"Table\"},\"author\":{\"id\":\"1\",\"name\":\"Ada Lovelace\"}},{\"product\":{\"name\":\"Table\"},\"author\":{\"id\":\"2\",\"name\":\"Alan Turing\"}}]},{\"upc\":\"2\",\"name\":\"Couch\",\"reviews\":[{\"product\":{\"name\":\"Couch\"},\"author\":{\"id\":\"1\",\"name\":\"Ada Lovelace\"}}]}]},\"hasNext\":true}\n\nevent: next\ndata: {\"hasNext\":false,\"incremental\":[{\"data\":{\"id\":\"1\"},\"path\":[\"topProducts\",0,\"reviews\",0]},{\"data\":{\"id\":\"4\"},\"path\":[\"topProducts\",0,\"reviews\",1]},{\"data\":{\"id\":\"2\"},\"path\":[\"topProducts\",1,\"reviews\",0]}]}\n\nevent: complete\ndata:\n\n";
+    let query = "
+        query TopProducts($first: Int) {
+            topProducts(first: $first) {
+                upc
+                name
+                reviews {
+                    ... @defer {
+                    id
+                    }
+                    product { name }
+                    author { id name }
+                }
+            }
+        }
+    ";
+    let http_request = supergraph::Request::canned_builder()
+        .header(http::header::ACCEPT, EVENT_STREAM_CONTENT_TYPE)
+        .query(query)
+        .build()
+        .unwrap()
+        .supergraph_request
+        .map(|req: crate::request::Request| {
+            let bytes = serde_json::to_vec(&req).unwrap();
+            hyper::Body::from(bytes)
+        });
+    let response = crate::TestHarness::builder()
+        .build_router()
+        .await
+        .unwrap()
+        .oneshot(router::Request::from(http_request))
+        .await
+        .unwrap()
+        .response;
+
+    assert_eq!(response.status(), http::StatusCode::OK);
+    assert_eq!(
+        response.headers().get(CONTENT_TYPE).unwrap(),
+        EVENT_STREAM_CONTENT_TYPE
+    );
+    let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let data = String::from_utf8_lossy(&bytes);
+    assert_eq!(expected_response, data);
+}
+
 #[tokio::test]
 async fn it_will_not_process_a_batched_deferred_query() {
     let expected_response = "[\r\n--graphql\r\ncontent-type: application/json\r\n\r\n{\"errors\":[{\"message\":\"Deferred responses and subscriptions aren't supported in batches\",\"extensions\":{\"code\":\"BATCHING_DEFER_UNSUPPORTED\"}}]}\r\n--graphql--\r\n, \r\n--graphql\r\ncontent-type: application/json\r\n\r\n{\"errors\":[{\"message\":\"Deferred responses and subscriptions aren't supported in batches\",\"extensions\":{\"code\":\"BATCHING_DEFER_UNSUPPORTED\"}}]}\r\n--graphql--\r\n]";
@@ -531,6 +580,363 @@ async fn it_will_not_process_a_batched_deferred_query() {
     assert_eq!(expected_response, data);
 }
 
+async fn send_batch(
+    batching: serde_json::Value,
+    batch: serde_json::Value,
+) -> (http::StatusCode, serde_json::Value) {
+    let http_request = http::Request::builder()
+        .method(Method::POST)
+        .uri("http://localhost/")
+        .header(CONTENT_TYPE, APPLICATION_JSON.essence_str())
+        .header(http::header::ACCEPT, APPLICATION_JSON.essence_str())
+        .body(hyper::Body::from(serde_json::to_vec(&batch).unwrap()))
+        .unwrap();
+    let response = crate::TestHarness::builder()
+        .configuration_json(serde_json::json!({ "batching": batching }))
+        .unwrap()
+        .build_router()
+        .await
+        .unwrap()
+        .oneshot(router::Request::from(http_request))
+        .await
+        .unwrap()
+        .response;
+    let status = response.status();
+    let data = serde_json::from_slice(&hyper::body::to_bytes(response.into_body()).await.unwrap())
+        .unwrap();
+    (status, data)
+}
+
+#[tokio::test]
+async fn it_rejects_a_query_batch_exceeding_max_size() {
+    async {
+        let (status, data) = send_batch(
+            serde_json::json!({
+                "enabled": true,
+                "mode": "batch_http_link",
+                "max_size": 2
+            }),
+            serde_json::json!([
+                { "query": "{ __typename }" },
+                { "query": "{ __typename }" },
+                { "query": "{ __typename }" }
+            ]),
+        )
+        .await;
+        assert_eq!(status, http::StatusCode::BAD_REQUEST);
+        assert_eq!(
+            data,
+            serde_json::json!({
+                "errors": [{
+                    "message": "Invalid GraphQL request",
+                    "extensions": {
+                        "details": "the batch exceeds the `batching.max_size` limit",
+                        "code": "BATCH_LIMIT_EXCEEDED"
+                    }
+                }]
+            })
+        );
+        assert_counter!(
+            "apollo.router.operations.batching.errors",
+            1,
+            "reason" = "max_size"
+        );
+        assert_counter!(
+            "apollo.router.operations.batching",
+            1,
+            "mode" = "batch_http_link"
+        );
+        assert_histogram_sum!(
+            "apollo.router.operations.batching.size",
+            3,
+            "mode" = "batch_http_link"
+        );
+    }
+    .with_metrics()
+    .await;
+}
+
+#[tokio::test]
+async fn it_rejects_a_query_batch_exceeding_max_total_cost() {
+    async {
+        let (status, data) = send_batch(
+            serde_json::json!({
+                "enabled": true,
+                "mode": "batch_http_link",
+                "max_total_cost": { "list_size": 10, "max": 5.0 }
+            }),
+            serde_json::json!([
+                { "query": "{ __typename }" },
+                { "query": "{ topProducts { name } }" }
+            ]),
+        )
+        .await;
+        assert_eq!(status, http::StatusCode::BAD_REQUEST);
+        assert_eq!(
+            data,
+            serde_json::json!({
+                "errors": [{
+                    "message": "Invalid GraphQL request",
+                    "extensions": {
+                        "details": "the batch exceeds the `batching.max_total_cost` limit",
+                        "code": "BATCH_LIMIT_EXCEEDED"
+                    }
+                }]
+            })
+        );
+        assert_counter!(
+            "apollo.router.operations.batching.errors",
+            1,
+            "reason" = "max_total_cost"
+        );
+        assert_counter!(
+            "apollo.router.operations.batching",
+            1,
+            "mode" = "batch_http_link"
+        );
+        assert_histogram_sum!(
+            "apollo.router.operations.batching.size",
+            2,
+            "mode" = "batch_http_link"
+        );
+    }
+    .with_metrics()
+    .await;
+}
+
+#[tokio::test]
+async fn it_times_out_a_query_batch() {
+    async {
+        let http_request = http::Request::builder()
+            .method(Method::POST)
+            .uri("http://localhost/")
+            .header(CONTENT_TYPE, APPLICATION_JSON.essence_str())
+            .header(http::header::ACCEPT, APPLICATION_JSON.essence_str())
+            .body(hyper::Body::from(
+                serde_json::to_vec(&serde_json::json!([
+                    { "query": "{ __typename }" },
+                    { "query": "{ __typename }" }
+                ]))
+                .unwrap(),
+            ))
+            .unwrap();
+        let response = crate::TestHarness::builder()
+            .configuration_json(serde_json::json!({
+                "batching": {
+                    "enabled": true,
+                    "mode": "batch_http_link",
+                    "timeout": "10ms"
+                }
+            }))
+            .unwrap()
+            .supergraph_hook(|service| {
+                service
+                    .map_future(|future| async move {
+                        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+                        future.await
+                    })
+                    .boxed()
+            })
+            .build_router()
+            .await
+            .unwrap()
+            .oneshot(router::Request::from(http_request))
+            .await
+            .unwrap()
+            .response;
+
+        assert_eq!(response.status(), http::StatusCode::GATEWAY_TIMEOUT);
+        let data: serde_json::Value =
+            serde_json::from_slice(&hyper::body::to_bytes(response.into_body()).await.unwrap())
+                .unwrap();
+        assert_eq!(
+            data,
+            serde_json::json!({
+                "errors": [{
+                    "message": "Batch timed out",
+                    "extensions": { "code": "BATCH_TIMEOUT" }
+                }]
+            })
+        );
+        assert_counter!(
+            "apollo.router.operations.batching.errors",
+            2,
+            "reason" = "timeout"
+        );
+        assert_counter!(
+            "apollo.router.operations.batching",
+            1,
+            "mode" = "batch_http_link"
+        );
+        assert_histogram_sum!(
+            "apollo.router.operations.batching.size",
+            2,
+            "mode" = "batch_http_link"
+        );
+    }
+    .with_metrics()
+    .await;
+}
+
+#[tokio::test]
+async fn it_returns_per_item_errors_for_operations_exceeding_batch_limits() {
+    let (status, data) = send_batch(
+        serde_json::json!({
+            "enabled": true,
+            "mode": "batch_http_link",
+            "max_size": 2,
+            "max_total_depth": 1,
+            "item_errors": "per_item"
+        }),
+        serde_json::json!([
+            { "query": "{ __typename }" },
+            { "query": "{ __typename }" },
+            { "query": "{ __typename }" }
+        ]),
+    )
+    .await;
+    assert_eq!(status, http::StatusCode::OK);
+    assert_eq!(
+        data,
+        serde_json::json!([
+            { "data": { "__typename": "Query" } },
+            {
+                "errors": [{
+                    "message": "Batch limit exceeded",
+                    "extensions": {
+                        "details": "the operation exceeds the `batching.max_total_depth` limit of the batch",
+                        "code": "BATCH_LIMIT_EXCEEDED"
+                    }
+                }]
+            },
+            {
+                "errors": [{
+                    "message": "Batch limit exceeded",
+                    "extensions": {
+                        "details": "the operation exceeds the `batching.max_total_depth` limit of the batch",
+                        "code": "BATCH_LIMIT_EXCEEDED"
+                    }
+                }]
+            }
+        ])
+    );
+}
+
+#[tokio::test]
+async fn it_applies_batch_limits_to_persisted_queries() {
+    use sha2::Digest;
+
+    let router = crate::TestHarness::builder()
+        .configuration_json(serde_json::json!({
+            "batching": {
+                "enabled": true,
+                "mode": "batch_http_link",
+                "max_total_depth": 2,
+                "item_errors": "per_item"
+            }
+        }))
+        .unwrap()
+        .build_router()
+        .await
+        .unwrap();
+    let send = |body: serde_json::Value| {
+        let http_request = http::Request::builder()
+            .method(Method::POST)
+            .uri("http://localhost/")
+            .header(CONTENT_TYPE, APPLICATION_JSON.essence_str())
+            .header(http::header::ACCEPT, APPLICATION_JSON.essence_str())
+            .body(hyper::Body::from(serde_json::to_vec(&body).unwrap()))
+            .unwrap();
+        let router = router.clone();
+        async move {
+            let response = router
+                .oneshot(router::Request::from(http_request))
+                .await
+                .unwrap()
+                .response;
+            serde_json::from_slice::<serde_json::Value>(
+                &hyper::body::to_bytes(response.into_body()).await.unwrap(),
+            )
+            .unwrap()
+        }
+    };
+
+    let query = "{ __typename }";
+    let persisted_query = serde_json::json!({
+        "persistedQuery": {
+            "version": 1,
+            "sha256Hash": hex::encode(sha2::Sha256::digest(query.as_bytes())),
+        }
+    });
+    let data = send(serde_json::json!({ "query": query, "extensions": persisted_query })).await;
+    assert_eq!(
+        data,
+        serde_json::json!({ "data": { "__typename": "Query" } })
+    );
+
+    // The operations have no query text, their depth is known once their query is resolved
+    let data = send(serde_json::json!([
+        { "extensions": persisted_query },
+        { "extensions": persisted_query },
+        { "extensions": persisted_query }
+    ]))
+    .await;
+    assert_eq!(
+        data,
+        serde_json::json!([
+            { "data": { "__typename": "Query" } },
+            { "data": { "__typename": "Query" } },
+            {
+                "errors": [{
+                    "message": "Batch limit exceeded",
+                    "extensions": {
+                        "details": "the operation exceeds the `batching.max_total_depth` limit of the batch",
+                        "code": "BATCH_LIMIT_EXCEEDED"
+                    }
+                }]
+            }
+        ])
+    );
+}
+
+#[tokio::test]
+async fn it_returns_per_item_errors_for_invalid_operations_of_a_batch() {
+    let (status, data) = send_batch(
+        serde_json::json!({
+            "enabled": true,
+            "mode": "batch_http_link",
+            "item_errors": "per_item"
+        }),
+        serde_json::json!([
+            { "query": 42 },
+            { "query": "{ __typename }" }
+        ]),
+    )
+    .await;
+    assert_eq!(status, http::StatusCode::OK);
+    assert_eq!(data[0]["errors"][0]["message"], "Invalid GraphQL request");
+    assert_eq!(
+        data[0]["errors"][0]["extensions"]["code"],
+        "INVALID_GRAPHQL_REQUEST"
+    );
+    assert_eq!(
+        data[1],
+        serde_json::json!({ "data": { "__typename": "Query" } })
+    );
+
+    let (status, data) = send_batch(
+        serde_json::json!({
+            "enabled": true,
+            "mode": "batch_http_link",
+            "item_errors": "per_item"
+        }),
+        serde_json::json!([{ "query": 42 }]),
+    )
+    .await;
+    assert_eq!(status, http::StatusCode::BAD_REQUEST);
+    assert_eq!(data.as_array().unwrap().len(), 1);
+}
+
 /// <https://github.com/apollographql/router/issues/3541>
 #[tokio::test]
 async fn escaped_quotes_in_string_literal() {
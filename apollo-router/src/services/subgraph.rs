@@ -25,6 +25,7 @@ use crate::http_ext::TryIntoHeaderName;
 use crate::http_ext::TryIntoHeaderValue;
 use crate::json_ext::Object;
 use crate::json_ext::Path;
+use crate::plugins::authentication::subgraph::SigningParamsConfig;
 use crate::plugins::authentication::APOLLO_AUTHENTICATION_JWT_CLAIMS;
 use crate::plugins::authorization::CacheKeyMetadata;
 use crate::query_planner::fetch::OperationKind;
@@ -122,7 +123,7 @@ impl Request {
 impl Clone for Request {
     fn clone(&self) -> Self {
         // http::Request is not clonable so we have to rebuild a new one
-        // we don't use the extensions field for now
+        // the extensions can't be cloned either, only the signing parameters are kept
         let mut builder = http::Request::builder()
             .method(self.subgraph_request.method())
             .version(self.subgraph_request.version())
@@ -137,6 +138,13 @@ impl Clone for Request {
                     .map(|(name, value)| (name.clone(), value.clone())),
             );
         }
+        if let Some(signing_params) = self
+            .subgraph_request
+            .extensions()
+            .get::<Arc<SigningParamsConfig>>()
+        {
+            builder = builder.extension(signing_params.clone());
+        }
         let subgraph_request = builder.body(self.subgraph_request.body().clone()).unwrap();
 
         Self {
@@ -37,11 +37,14 @@ use tracing::instrument;
 use tracing::Instrument;
 use uuid::Uuid;
 
+use super::connector;
+use super::connector::Connector;
 use super::http::HttpClientServiceFactory;
 use super::http::HttpRequest;
 use super::layers::content_negotiation::GRAPHQL_JSON_RESPONSE_HEADER_VALUE;
 use super::Plugins;
 use crate::batching::assemble_batch;
+use crate::batching::window::BatchWindow;
 use crate::batching::BatchQuery;
 use crate::batching::BatchQueryInfo;
 use crate::configuration::Batching;
@@ -127,6 +130,10 @@ pub(crate) struct SubgraphService {
     /// Subscription config if enabled
     subscription_config: Option<SubscriptionConfig>,
     notify: Notify<String, graphql::Response>,
+    /// REST connector resolving the requests instead of the subgraph
+    connector: Option<Arc<Connector>>,
+    /// Batches the fetches of unrelated client requests, if enabled
+    batch_window: Option<Arc<BatchWindow>>,
 }
 
 impl SubgraphService {
@@ -146,13 +153,20 @@ impl SubgraphService {
             .map(|apq| apq.enabled)
             .unwrap_or(configuration.apq.subgraph.all.enabled);
 
-        SubgraphService::new(
+        let batch_window = configuration
+            .batching
+            .batch_window(&name)
+            .map(|window| Arc::new(BatchWindow::spawn(&name, window, client_factory.clone())));
+
+        let mut service = SubgraphService::new(
             name,
             enable_apq,
             subscription_config,
             configuration.notify.clone(),
             client_factory,
-        )
+        )?;
+        service.batch_window = batch_window;
+        Ok(service)
     }
 
     pub(crate) fn new(
@@ -168,8 +182,15 @@ impl SubgraphService {
             apq: Arc::new(<AtomicBool>::new(enable_apq)),
             subscription_config,
             notify,
+            connector: None,
+            batch_window: None,
         })
     }
+
+    pub(crate) fn with_connector(mut self, connector: Option<Arc<Connector>>) -> Self {
+        self.connector = connector;
+        self
+    }
 }
 
 pub(crate) fn generate_tls_client_config(
@@ -205,6 +226,14 @@ impl tower::Service<SubgraphRequest> for SubgraphService {
     }
 
     fn call(&mut self, mut request: SubgraphRequest) -> Self::Future {
+        if let Some(connector) = &self.connector {
+            return Box::pin(connector::call(
+                connector.clone(),
+                request,
+                self.client_factory.clone(),
+            ));
+        }
+
         let subscription_config = (request.operation_kind == OperationKind::Subscription)
             .then(|| self.subscription_config.clone())
             .flatten();
@@ -244,6 +273,8 @@ impl tower::Service<SubgraphRequest> for SubgraphService {
 
         let arc_apq_enabled = self.apq.clone();
 
+        let batch_window = self.batch_window.clone();
+
         let mut notify = self.notify.clone();
 
         let make_calls = async move {
@@ -281,9 +312,18 @@ impl tower::Service<SubgraphRequest> for SubgraphService {
                         let subscription_id = hashed_request;
 
                         // Call create_or_subscribe on notify
-                        let (handle, created) = notify
-                            .create_or_subscribe(subscription_id.clone(), true)
-                            .await?;
+                        let (handle, created) = match notify.cluster() {
+                            Some(cluster) => {
+                                cluster
+                                    .create_or_subscribe(&mut notify, subscription_id.clone(), true)
+                                    .await?
+                            }
+                            None => {
+                                notify
+                                    .create_or_subscribe(subscription_id.clone(), true)
+                                    .await?
+                            }
+                        };
 
                         // If it existed before just send the right stream (handle) and early return
                         let stream_tx = request.subscription_stream.clone().ok_or_else(|| {
@@ -377,6 +417,7 @@ impl tower::Service<SubgraphRequest> for SubgraphService {
                     body,
                     context,
                     client_factory.clone(),
+                    batch_window.as_deref(),
                     &service_name,
                 )
                 .await;
@@ -414,6 +455,7 @@ impl tower::Service<SubgraphRequest> for SubgraphService {
                 apq_body.clone(),
                 context.clone(),
                 client_factory.clone(),
+                batch_window.as_deref(),
                 &service_name,
             )
             .await?;
@@ -431,6 +473,7 @@ impl tower::Service<SubgraphRequest> for SubgraphService {
                         body,
                         context,
                         client_factory.clone(),
+                        batch_window.as_deref(),
                         &service_name,
                     )
                     .await
@@ -442,6 +485,7 @@ impl tower::Service<SubgraphRequest> for SubgraphService {
                         apq_body,
                         context,
                         client_factory.clone(),
+                        batch_window.as_deref(),
                         &service_name,
                     )
                     .await
@@ -481,9 +525,19 @@ async fn call_websocket(
             reason: "cannot get the websocket stream".to_string(),
         })?;
 
-    let (handle, created) = notify
-        .create_or_subscribe(subscription_hash.clone(), false)
-        .await?;
+    let cluster = notify.cluster();
+    let (handle, created) = match &cluster {
+        Some(cluster) => {
+            cluster
+                .create_or_subscribe(&mut notify, subscription_hash.clone(), false)
+                .await?
+        }
+        None => {
+            notify
+                .create_or_subscribe(subscription_hash.clone(), false)
+                .await?
+        }
+    };
     tracing::info!(
         monotonic_counter.apollo.router.operations.subscriptions = 1u64,
         subscriptions.mode = %"passthrough",
@@ -521,17 +575,12 @@ async fn call_websocket(
         _ => None,
     };
 
+    let signing_params = parts.extensions.get::<Arc<SigningParamsConfig>>().cloned();
     let request = get_websocket_request(service_name.clone(), parts, subgraph_cfg)?;
 
     let display_headers = context.contains_key(LOGGING_DISPLAY_HEADERS);
     let display_body = context.contains_key(LOGGING_DISPLAY_BODY);
 
-    let signing_params = context
-        .extensions()
-        .lock()
-        .get::<Arc<SigningParamsConfig>>()
-        .cloned();
-
     let request = if let Some(signing_params) = signing_params {
         signing_params
             .sign_empty(request, service_name.as_str())
@@ -636,7 +685,7 @@ async fn call_websocket(
 
     let gql_socket = GraphqlWebSocket::new(
         convert_websocket_stream(ws_stream, subscription_hash.clone()),
-        subscription_hash,
+        subscription_hash.clone(),
         subgraph_cfg.protocol,
         connection_params,
     )
@@ -654,14 +703,24 @@ async fn call_websocket(
             reason: format!("cannot send the subgraph request to websocket stream: {err:?}"),
         })?;
 
-    let (handle_sink, handle_stream) = handle.split();
-
-    tokio::task::spawn(async move {
-        let _ = gql_stream
-            .map(Ok::<_, graphql::Error>)
-            .forward(handle_sink)
-            .await;
-    });
+    let handle_stream = match cluster {
+        // events reach the clients of every instance, this one included, through Redis
+        Some(cluster) => {
+            tokio::task::spawn(cluster.publish_stream(subscription_hash, gql_stream));
+            handle.into_stream()
+        }
+        None => {
+            let (handle_sink, handle_stream) = handle.split();
+
+            tokio::task::spawn(async move {
+                let _ = gql_stream
+                    .map(Ok::<_, graphql::Error>)
+                    .forward(handle_sink)
+                    .await;
+            });
+            handle_stream
+        }
+    };
 
     subscription_stream_tx.send(Box::pin(handle_stream)).await?;
 
@@ -1095,6 +1154,7 @@ async fn call_http(
     body: graphql::Request,
     context: Context,
     client_factory: HttpClientServiceFactory,
+    batch_window: Option<&BatchWindow>,
     service_name: &str,
 ) -> Result<SubgraphResponse, BoxError> {
     // We use configuration to determine if calls may be batched. If we have Batching
@@ -1129,6 +1189,12 @@ async fn call_http(
                 service: service_name.to_string(),
                 reason: format!("tx receive failed: {err}"),
             })?
+    } else if let Some(window) = batch_window.filter(|_| {
+        // Subscriptions and file uploads can't be sent in batches
+        request.operation_kind != OperationKind::Subscription
+            && !file_uploads::has_files(&request.subgraph_request)
+    }) {
+        window.fetch(request, body).await
     } else {
         tracing::debug!("we called http");
         let client = client_factory.create(service_name);
@@ -2208,6 +2274,9 @@ mod tests {
             enable_deduplication: true,
             max_opened_subscriptions: None,
             queue_capacity: None,
+            redis: None,
+            throttling: Vec::new(),
+            resumption: Default::default(),
         }
     }
 
@@ -2232,7 +2301,7 @@ mod tests {
 
     #[tokio::test(flavor = "multi_thread")]
     async fn test_subgraph_service_callback() {
-        let _ = SUBSCRIPTION_CALLBACK_HMAC_KEY.set(String::from("TESTEST"));
+        *SUBSCRIPTION_CALLBACK_HMAC_KEY.write() = Some(String::from("TESTEST"));
         let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
         let socket_addr = listener.local_addr().unwrap();
         let spawned_task = tokio::task::spawn(emulate_subgraph_with_callback_data(listener));
@@ -32,7 +32,9 @@ use crate::error::CacheResolverError;
 use crate::graphql;
 use crate::graphql::IntoGraphQLErrors;
 use crate::graphql::Response;
+use crate::notification::DEFAULT_MSG_CHANNEL_SIZE;
 use crate::plugin::DynPlugin;
+use crate::plugins::subscription::throttle;
 use crate::plugins::subscription::SubscriptionConfig;
 use crate::plugins::telemetry::config_new::events::log_event;
 use crate::plugins::telemetry::config_new::events::SupergraphEventResponseLevel;
@@ -69,7 +71,9 @@ use crate::services::QueryPlannerContent;
 use crate::services::QueryPlannerResponse;
 use crate::services::SupergraphRequest;
 use crate::services::SupergraphResponse;
+use crate::spec::Query;
 use crate::spec::Schema;
+use crate::spec::Selection;
 use crate::Configuration;
 use crate::Context;
 use crate::Notify;
@@ -269,6 +273,7 @@ async fn service_call(
             let ClientRequestAccepts {
                 multipart_defer: accepts_multipart_defer,
                 multipart_subscription: accepts_multipart_subscription,
+                event_stream: accepts_event_stream,
                 ..
             } = context
                 .extensions()
@@ -277,13 +282,13 @@ async fn service_call(
                 .cloned()
                 .unwrap_or_default();
             let mut subscription_tx = None;
-            if (is_deferred && !accepts_multipart_defer)
-                || (is_subscription && !accepts_multipart_subscription)
+            if (is_deferred && !(accepts_multipart_defer || accepts_event_stream))
+                || (is_subscription && !(accepts_multipart_subscription || accepts_event_stream))
             {
                 let (error_message, error_code) = if is_deferred {
-                    (String::from("the router received a query with the @defer directive but the client does not accept multipart/mixed HTTP responses. To enable @defer support, add the HTTP header 'Accept: multipart/mixed;deferSpec=20220824'"), "DEFER_BAD_HEADER")
+                    (String::from("the router received a query with the @defer directive but the client does not accept multipart/mixed or text/event-stream HTTP responses. To enable @defer support, add the HTTP header 'Accept: multipart/mixed;deferSpec=20220824' or 'Accept: text/event-stream'"), "DEFER_BAD_HEADER")
                 } else {
-                    (String::from("the router received a query with a subscription but the client does not accept multipart/mixed HTTP responses. To enable subscription support, add the HTTP header 'Accept: multipart/mixed;subscriptionSpec=1.0'"), "SUBSCRIPTION_BAD_HEADER")
+                    (String::from("the router received a query with a subscription but the client does not accept multipart/mixed or text/event-stream HTTP responses. To enable subscription support, add the HTTP header 'Accept: multipart/mixed;subscriptionSpec=1.0' or 'Accept: text/event-stream'"), "SUBSCRIPTION_BAD_HEADER")
                 };
                 let mut response = SupergraphResponse::new_from_graphql_response(
                     graphql::Response::builder()
@@ -413,6 +418,7 @@ async fn subscription_task(
     let mut receiver = sub_params.stream_rx;
     let sender = sub_params.client_sender;
 
+    let query = query_plan.query.clone();
     // Get the rest of the query_plan to execute for subscription events
     let query_plan = match &*query_plan.root {
         crate::query_planner::PlanNode::Subscription { rest, .. } => rest.clone().map(|r| {
@@ -464,6 +470,20 @@ async fn subscription_task(
         }
     };
 
+    let operation_name_option = (!operation_name.is_empty()).then_some(operation_name.as_str());
+    if let Some(throttling) = subscription_config.throttling(
+        operation_name_option,
+        &root_fields(&query, operation_name_option),
+    ) {
+        receiver = throttle(
+            receiver,
+            throttling.clone(),
+            subscription_config
+                .queue_capacity
+                .unwrap_or(DEFAULT_MSG_CHANNEL_SIZE),
+        );
+    }
+
     if limit_is_set {
         OPENED_SUBSCRIPTIONS.fetch_add(1, Ordering::Relaxed);
     }
@@ -568,6 +588,32 @@ async fn subscription_task(
     }
 }
 
+/// Names of the root fields selected by an operation, directly or through fragments
+pub(crate) fn root_fields<'a>(query: &'a Query, operation_name: Option<&str>) -> Vec<&'a str> {
+    let mut fields = Vec::new();
+    if let Some(operation) = query.operation(operation_name) {
+        collect_fields(query, &operation.selection_set, &mut fields);
+    }
+    fields
+}
+
+fn collect_fields<'a>(query: &'a Query, selection_set: &'a [Selection], fields: &mut Vec<&'a str>) {
+    for selection in selection_set {
+        match selection {
+            Selection::Field { name, .. } => fields.push(name.as_str()),
+            Selection::InlineFragment { selection_set, .. } => {
+                collect_fields(query, selection_set, fields)
+            }
+            Selection::FragmentSpread { name, .. } => {
+                // fragments cannot form cycles in a valid query
+                if let Some(fragment) = query.fragments.get(name) {
+                    collect_fields(query, &fragment.selection_set, fields)
+                }
+            }
+        }
+    }
+}
+
 async fn dispatch_event(
     supergraph_req: &SupergraphRequest,
     execution_service_factory: &ExecutionServiceFactory,
@@ -906,14 +952,16 @@ impl SupergraphCreator {
     }
 
     pub(crate) async fn warm_up_query_planner(
-        &mut self,
+        &self,
         query_parser: &QueryAnalysisLayer,
-        persisted_query_layer: &PersistedQueryLayer,
-        previous_cache: InMemoryCachePlanner,
+        persisted_query_layer: Option<&PersistedQueryLayer>,
+        previous_cache: Option<InMemoryCachePlanner>,
         count: Option<usize>,
         experimental_reuse_query_plans: bool,
     ) {
+        // the query planner clones share the same cache
         self.query_planner_service
+            .clone()
             .warm_up(
                 query_parser,
                 persisted_query_layer,
@@ -2,15 +2,20 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
 
+use futures::StreamExt;
 use http::HeaderValue;
 use tower::ServiceExt;
 use tower_service::Service;
 
 use crate::graphql;
 use crate::plugin::test::MockSubgraph;
+use crate::services::router;
 use crate::services::router::ClientRequestAccepts;
 use crate::services::subgraph;
 use crate::services::supergraph;
+use crate::services::supergraph::service::root_fields;
+use crate::services::EVENT_STREAM_CONTENT_TYPE;
+use crate::spec::Query;
 use crate::spec::Schema;
 use crate::test_harness::MockedSubgraphs;
 use crate::Configuration;
@@ -578,6 +583,466 @@ async fn errors_on_deferred_responses() {
     insta::assert_json_snapshot!(stream.next_response().await.unwrap());
 }
 
+#[tokio::test]
+async fn incremental_delivery_format() {
+    let subgraphs = MockedSubgraphs([
+        ("user", MockSubgraph::builder().with_json(
+                serde_json::json!{{"query":"{currentUser{__typename id}}"}},
+                serde_json::json!{{"data": {"currentUser": { "__typename": "User", "id": "0" }}}}
+            )
+            .with_json(
+                serde_json::json!{{
+                    "query":"query($representations:[_Any!]!){_entities(representations:$representations){...on User{name}}}",
+                    "variables": {
+                        "representations":[{"__typename": "User", "id":"0"}]
+                    }
+                }},
+                serde_json::json!{{
+                    "data": {
+                        "_entities": [{ "name": "AAA" }]
+                    }
+                }}
+            ).build()),
+        ("orga", MockSubgraph::default())
+    ].into_iter().collect());
+
+    let service = TestHarness::builder()
+        .configuration_json(serde_json::json!({"include_subgraph_errors": { "all": true } }))
+        .unwrap()
+        .schema(SCHEMA)
+        .extra_plugin(subgraphs)
+        .build_supergraph()
+        .await
+        .unwrap();
+
+    let request = supergraph::Request::fake_builder()
+        .context(incremental_delivery_context())
+        .query("query { currentUser { id  ...@defer(label: \"user\") { name } } }")
+        .build()
+        .unwrap();
+
+    let mut stream = service.oneshot(request).await.unwrap();
+
+    insta::assert_json_snapshot!(stream.next_response().await.unwrap());
+
+    insta::assert_json_snapshot!(stream.next_response().await.unwrap());
+}
+
+#[tokio::test]
+async fn nested_defer_incremental_delivery_format() {
+    let subgraphs = MockedSubgraphs([
+        ("user", MockSubgraph::builder()
+        .with_json(
+            serde_json::json!{{"query":"{currentUser{__typename name id}}"}},
+            serde_json::json!{{"data": {"currentUser": { "__typename": "User", "name": "Ada", "id": "1" }}}}
+        )
+        .with_json(
+            serde_json::json!{{
+                "query":"query($representations:[_Any!]!){_entities(representations:$representations){...on User{org:activeOrganization{__typename id}}}}",
+                "variables": {
+                    "representations":[{"__typename": "User", "id":"1"}]
+                }
+            }},
+            serde_json::json!{{
+                "data": {
+                    "_entities": [{ "org": { "__typename": "Organization", "id": "2" } }]
+                }
+            }}
+        )
+        .with_json(
+            serde_json::json!{{
+                "query":"query($representations:[_Any!]!){_entities(representations:$representations){...on User{name}}}",
+                "variables": {
+                    "representations":[{"__typename": "User", "id":"3"}]
+                }
+            }},
+            serde_json::json!{{
+                "data": {
+                    "_entities": [{ "name": "A" }]
+                }
+            }}
+        ).build()),
+        ("orga", MockSubgraph::builder()
+        .with_json(
+            serde_json::json!{{
+                "query":"query($representations:[_Any!]!){_entities(representations:$representations){...on Organization{creatorUser{__typename id}}}}",
+                "variables": {
+                    "representations":[{"__typename": "Organization", "id":"2"}]
+                }
+            }},
+            serde_json::json!{{
+                "data": {
+                    "_entities": [{ "creatorUser": { "__typename": "User", "id": "3" } }]
+                }
+            }}
+        )
+        .with_json(
+            serde_json::json!{{
+                "query":"query($representations:[_Any!]!){_entities(representations:$representations){...on Organization{nonNullId}}}",
+                "variables": {
+                    "representations":[{"__typename": "Organization", "id":"2"}]
+                }
+            }},
+            serde_json::json!{{
+                "data": {
+                    "_entities": [{ "nonNullId": "2" }]
+                }
+            }}
+        ).build())
+    ].into_iter().collect());
+
+    let service = TestHarness::builder()
+        .configuration_json(serde_json::json!({"include_subgraph_errors": { "all": true } }))
+        .unwrap()
+        .schema(SCHEMA)
+        .extra_plugin(subgraphs)
+        .build_supergraph()
+        .await
+        .unwrap();
+
+    let request = supergraph::Request::fake_builder()
+        .query(
+            r#"query {
+                currentUser {
+                    name
+                    ... @defer(label: "org") {
+                        org: activeOrganization {
+                            id
+                            nonNullId
+                            ... @defer(label: "creator") {
+                                creatorUser {
+                                    name
+                                }
+                            }
+                        }
+                    }
+                }
+            }"#,
+        )
+        .context(incremental_delivery_context())
+        .build()
+        .unwrap();
+    let mut response = service.oneshot(request).await.unwrap();
+
+    // only the outer fragment is announced in the primary response
+    let primary = response.next_response().await.unwrap();
+    insta::assert_json_snapshot!(primary);
+
+    // the nested fragment is announced when the outer one is completed
+    let deferred = response.next_response().await.unwrap();
+    insta::assert_json_snapshot!(deferred);
+
+    let nested = response.next_response().await.unwrap();
+    insta::assert_json_snapshot!(nested);
+}
+
+#[tokio::test]
+async fn defer_in_stream_incremental_delivery_format() {
+    let subgraphs = MockedSubgraphs([
+        ("user", MockSubgraph::builder().with_json(
+                serde_json::json!{{"query":"{currentUser{activeOrganization{__typename id}}}"}},
+                serde_json::json!{{"data": {"currentUser": { "activeOrganization": { "__typename": "Organization", "id": "0" } }}}}
+            ).build()),
+        ("orga", MockSubgraph::builder().with_json(
+            serde_json::json!{{
+                "query":"query($representations:[_Any!]!){_entities(representations:$representations){...on Organization{suborga{__typename id}}}}",
+                "variables": {
+                    "representations":[{"__typename": "Organization", "id":"0"}]
+                }
+            }},
+            serde_json::json!{{
+                "data": {
+                    "_entities": [{ "suborga": [
+                    { "__typename": "Organization", "id": "1"},
+                    { "__typename": "Organization", "id": "2"},
+                    { "__typename": "Organization", "id": "3"},
+                    ] }]
+                },
+            }}
+        )
+        // the deferred fragment is only fetched for the first item, the other items are fetched
+        // with their fragment after the primary response
+        .with_json(
+            serde_json::json!{{
+                "query":"query($representations:[_Any!]!){_entities(representations:$representations){...on Organization{name}}}",
+                "variables": {
+                    "representations":[
+                        {"__typename": "Organization", "id":"1"}
+                    ]
+                }
+            }},
+            serde_json::json!{{
+                "data": {
+                    "_entities": [
+                        { "name": "A" },
+                    ]
+                }
+            }}
+        )
+        .with_json(
+            serde_json::json!{{
+                "query":"query($representations:[_Any!]!){_entities(representations:$representations){...on Organization{name}}}",
+                "variables": {
+                    "representations":[
+                        {"__typename": "Organization", "id":"2"},
+                        {"__typename": "Organization", "id":"3"}
+                    ]
+                }
+            }},
+            serde_json::json!{{
+                "data": {
+                    "_entities": [
+                        { "name": "B" },
+                        { "name": "C" },
+                    ]
+                }
+            }}
+        ).build())
+    ].into_iter().collect());
+
+    let service = TestHarness::builder()
+        .configuration_json(serde_json::json!({
+            "include_subgraph_errors": { "all": true },
+            "supergraph": { "stream_support": true }
+        }))
+        .unwrap()
+        .schema(SCHEMA)
+        .extra_plugin(subgraphs)
+        .build_supergraph()
+        .await
+        .unwrap();
+
+    let request = supergraph::Request::fake_builder()
+        .context(incremental_delivery_context())
+        .query("query { currentUser { activeOrganization { id suborga @stream(initialCount: 1, label: \"suborga\") { id ...@defer { name } } } } }")
+        .build()
+        .unwrap();
+
+    let mut stream = service.oneshot(request).await.unwrap();
+
+    // the deferred fragment and the streamed list are announced in the primary response
+    insta::assert_json_snapshot!(stream.next_response().await.unwrap());
+
+    insta::assert_json_snapshot!(stream.next_response().await.unwrap());
+
+    insta::assert_json_snapshot!(stream.next_response().await.unwrap());
+}
+
+#[tokio::test]
+async fn stream_list_items() {
+    let subgraphs = MockedSubgraphs([
+        ("user", MockSubgraph::builder().with_json(
+                serde_json::json!{{"query":"{currentUser{activeOrganization{__typename id}}}"}},
+                serde_json::json!{{"data": {"currentUser": { "activeOrganization": { "__typename": "Organization", "id": "0" } }}}}
+            ).build()),
+        ("orga", MockSubgraph::builder().with_json(
+            serde_json::json!{{
+                "query":"query($representations:[_Any!]!){_entities(representations:$representations){...on Organization{suborga{id name}}}}",
+                "variables": {
+                    "representations":[{"__typename": "Organization", "id":"0"}]
+                }
+            }},
+            serde_json::json!{{
+                "data": {
+                    "_entities": [{ "suborga": [
+                    { "__typename": "Organization", "id": "1", "name": "A"},
+                    { "__typename": "Organization", "id": "2", "name": "B"},
+                    { "__typename": "Organization", "id": "3", "name": "C"},
+                    ] }]
+                },
+            }}
+        ).build())
+    ].into_iter().collect());
+
+    let service = TestHarness::builder()
+        .configuration_json(serde_json::json!({
+            "include_subgraph_errors": { "all": true },
+            "supergraph": { "stream_support": true }
+        }))
+        .unwrap()
+        .schema(SCHEMA)
+        .extra_plugin(subgraphs)
+        .build_supergraph()
+        .await
+        .unwrap();
+
+    let request = supergraph::Request::fake_builder()
+        .context(defer_context())
+        .query("query { currentUser { activeOrganization { id suborga @stream(initialCount: 1, label: \"suborga\") { id name } } } }")
+        .build()
+        .unwrap();
+
+    let mut stream = service.oneshot(request).await.unwrap();
+
+    insta::assert_json_snapshot!(stream.next_response().await.unwrap());
+
+    insta::assert_json_snapshot!(stream.next_response().await.unwrap());
+}
+
+#[tokio::test]
+async fn stream_items_fetched_in_chunks() {
+    let suborga: Vec<_> = (1..=12)
+        .map(|i| {
+            serde_json::json!({
+                "id": i.to_string(),
+                "creatorUser": { "__typename": "User", "id": format!("u{i}") }
+            })
+        })
+        .collect();
+    let names = |ids: std::ops::RangeInclusive<usize>| {
+        (
+            serde_json::json!({
+                "query":"query($representations:[_Any!]!){_entities(representations:$representations){...on User{name}}}",
+                "variables": {
+                    "representations": ids.clone().map(|i| serde_json::json!({"__typename": "User", "id": format!("u{i}")})).collect::<Vec<_>>()
+                }
+            }),
+            serde_json::json!({
+                "data": {
+                    "_entities": ids.map(|i| serde_json::json!({ "name": format!("User {i}") })).collect::<Vec<_>>()
+                }
+            }),
+        )
+    };
+    let (first_query, first_response) = names(1..=1);
+    let (second_query, second_response) = names(2..=11);
+    let (third_query, third_response) = names(12..=12);
+    // the creators of the items after `initialCount` are fetched after the primary response,
+    // 10 items at a time
+    let subgraphs = MockedSubgraphs([
+        ("user", MockSubgraph::builder().with_json(
+                serde_json::json!{{"query":"{currentUser{activeOrganization{__typename id}}}"}},
+                serde_json::json!{{"data": {"currentUser": { "activeOrganization": { "__typename": "Organization", "id": "0" } }}}}
+            )
+            .with_json(first_query, first_response)
+            .with_json(second_query, second_response)
+            .with_json(third_query, third_response)
+            .build()),
+        ("orga", MockSubgraph::builder().with_json(
+            serde_json::json!{{
+                "query":"query($representations:[_Any!]!){_entities(representations:$representations){...on Organization{suborga{id creatorUser{__typename id}}}}}",
+                "variables": {
+                    "representations":[{"__typename": "Organization", "id":"0"}]
+                }
+            }},
+            serde_json::json!{{
+                "data": {
+                    "_entities": [{ "suborga": suborga }]
+                },
+            }}
+        ).build())
+    ].into_iter().collect());
+
+    let service = TestHarness::builder()
+        .configuration_json(serde_json::json!({
+            "include_subgraph_errors": { "all": true },
+            "supergraph": { "stream_support": true }
+        }))
+        .unwrap()
+        .schema(SCHEMA)
+        .extra_plugin(subgraphs)
+        .build_supergraph()
+        .await
+        .unwrap();
+
+    let request = supergraph::Request::fake_builder()
+        .context(defer_context())
+        .query("query { currentUser { activeOrganization { suborga @stream(initialCount: 1, label: \"suborga\") { id creatorUser { name } } } } }")
+        .build()
+        .unwrap();
+
+    let mut stream = service.oneshot(request).await.unwrap();
+
+    let items = |ids: std::ops::RangeInclusive<usize>| {
+        ids.map(|i| serde_json_bytes::json!({ "id": i.to_string(), "creatorUser": { "name": format!("User {i}") } }))
+            .collect::<Vec<_>>()
+    };
+    let primary = stream.next_response().await.unwrap();
+    assert_eq!(
+        primary.data,
+        Some(
+            serde_json_bytes::json!({ "currentUser": { "activeOrganization": { "suborga": items(1..=1) } } })
+        )
+    );
+    assert_eq!(primary.has_next, Some(true));
+
+    let first_chunk = stream.next_response().await.unwrap();
+    assert_eq!(first_chunk.has_next, Some(true));
+    assert_eq!(first_chunk.incremental.len(), 1);
+    assert_eq!(first_chunk.incremental[0].label.as_deref(), Some("suborga"));
+    assert_eq!(
+        first_chunk.incremental[0]
+            .path
+            .as_ref()
+            .unwrap()
+            .to_string(),
+        "/currentUser/activeOrganization/suborga/1"
+    );
+    assert_eq!(first_chunk.incremental[0].items, Some(items(2..=11)));
+
+    let second_chunk = stream.next_response().await.unwrap();
+    assert_eq!(second_chunk.has_next, Some(false));
+    assert_eq!(
+        second_chunk.incremental[0]
+            .path
+            .as_ref()
+            .unwrap()
+            .to_string(),
+        "/currentUser/activeOrganization/suborga/11"
+    );
+    assert_eq!(second_chunk.incremental[0].items, Some(items(12..=12)));
+    assert!(stream.next_response().await.is_none());
+}
+
+#[tokio::test]
+async fn stream_initial_count_must_be_a_non_negative_integer() {
+    let service = TestHarness::builder()
+        .configuration_json(serde_json::json!({
+            "supergraph": { "stream_support": true }
+        }))
+        .unwrap()
+        .schema(SCHEMA)
+        .build_supergraph()
+        .await
+        .unwrap();
+
+    let request = supergraph::Request::fake_builder()
+        .context(defer_context())
+        .query("query { currentUser { activeOrganization { suborga @stream(initialCount: -1) { id } } } }")
+        .build()
+        .unwrap();
+    let response = service
+        .clone()
+        .oneshot(request)
+        .await
+        .unwrap()
+        .next_response()
+        .await
+        .unwrap();
+    assert_eq!(
+        response.errors[0].extensions.get("code").unwrap(),
+        "GRAPHQL_VALIDATION_FAILED"
+    );
+
+    let request = supergraph::Request::fake_builder()
+        .context(defer_context())
+        .query("query($count: Int) { currentUser { activeOrganization { suborga @stream(initialCount: $count) { id } } } }")
+        .variable("count", serde_json_bytes::json!(-1))
+        .build()
+        .unwrap();
+    let response = service
+        .oneshot(request)
+        .await
+        .unwrap()
+        .next_response()
+        .await
+        .unwrap();
+    assert_eq!(
+        response.errors[0].extensions.get("code").unwrap(),
+        "VALIDATION_INVALID_TYPE_VARIABLE"
+    );
+}
+
 #[tokio::test]
 async fn errors_from_primary_on_deferred_responses() {
     let schema = r#"
@@ -994,6 +1459,87 @@ async fn subscription_with_callback() {
     insta::assert_json_snapshot!(stream.next_response().await.unwrap());
 }
 
+#[tokio::test]
+async fn subscription_over_server_sent_events() {
+    let mut notify = Notify::builder().build();
+    let (handle, _) = notify
+        .create_or_subscribe("TEST_TOPIC".to_string(), false)
+        .await
+        .unwrap();
+    let subgraphs = MockedSubgraphs([
+            ("user", MockSubgraph::builder().with_json(
+                    serde_json::json!{{"query":"subscription{userWasCreated{name activeOrganization{__typename id}}}"}},
+                    serde_json::json!{{"data": {"userWasCreated": { "__typename": "User", "id": "1", "activeOrganization": { "__typename": "Organization", "id": "0" } }}}}
+                ).with_subscription_stream(handle.clone()).build()),
+            ("orga", MockSubgraph::builder().with_json(
+                serde_json::json!{{
+                    "query":"query($representations:[_Any!]!){_entities(representations:$representations){...on Organization{suborga{id name}}}}",
+                    "variables": {
+                        "representations":[{"__typename": "Organization", "id":"0"}]
+                    }
+                }},
+                serde_json::json!{{
+                    "data": {
+                        "_entities": [{ "suborga": [
+                        { "__typename": "Organization", "id": "1", "name": "A"},
+                        ] }]
+                    },
+                    }}
+            ).build())
+        ].into_iter().collect());
+
+    let mut configuration: Configuration = serde_json::from_value(serde_json::json!({"include_subgraph_errors": { "all": true }, "subscription": { "enabled": true, "mode": {"callback": {"public_url": "http://localhost:4545/callback"}}}})).unwrap();
+    configuration.notify = notify.clone();
+    let service = TestHarness::builder()
+        .configuration(Arc::new(configuration))
+        .schema(SCHEMA)
+        .extra_plugin(subgraphs)
+        .build_router()
+        .await
+        .unwrap();
+
+    let request = supergraph::Request::fake_builder()
+        .header(http::header::ACCEPT, EVENT_STREAM_CONTENT_TYPE)
+        .query("subscription { userWasCreated { name activeOrganization { id  suborga { id name } } } }")
+        .build()
+        .unwrap()
+        .supergraph_request
+        .map(|req: crate::request::Request| {
+            hyper::Body::from(serde_json::to_vec(&req).unwrap())
+        });
+    let response = service
+        .oneshot(router::Request::from(request))
+        .await
+        .unwrap()
+        .response;
+    assert_eq!(response.status(), http::StatusCode::OK);
+    assert_eq!(
+        response.headers().get(http::header::CONTENT_TYPE).unwrap(),
+        EVENT_STREAM_CONTENT_TYPE
+    );
+
+    // heartbeats are comments, skip them
+    let mut events = response
+        .into_body()
+        .map(|chunk| String::from_utf8(chunk.unwrap().to_vec()).unwrap())
+        .filter(|event| futures::future::ready(event != ":\n\n"));
+
+    let first = events.next().await.unwrap();
+    assert!(first.starts_with("event: next\ndata: "), "{first}");
+    assert!(first.ends_with("\n\n"), "{first}");
+
+    notify.broadcast(graphql::Response::builder().data(serde_json_bytes::json!({"userWasCreated": { "name": "test", "activeOrganization": { "__typename": "Organization", "id": "0" }}})).build()).await.unwrap();
+    assert_eq!(
+        events.next().await.unwrap(),
+        "event: next\ndata: {\"data\":{\"userWasCreated\":{\"name\":\"test\",\"activeOrganization\":{\"id\":\"0\",\"suborga\":[{\"id\":\"1\",\"name\":\"A\"}]}}}}\n\n"
+    );
+
+    // the subgraph closes the subscription
+    notify.force_delete("TEST_TOPIC".to_string()).await.unwrap();
+    assert_eq!(events.next().await.unwrap(), "event: complete\ndata:\n\n");
+    assert!(events.next().await.is_none());
+}
+
 #[tokio::test]
 async fn subscription_callback_schema_reload() {
     let mut notify = Notify::builder().build();
@@ -1163,6 +1709,90 @@ async fn subscription_with_callback_with_limit() {
     assert!(res.errors.is_empty());
 }
 
+#[test]
+fn subscription_root_fields_through_fragments() {
+    let schema = Schema::parse_test(SCHEMA, &Default::default()).unwrap();
+    for query in [
+        "subscription { userWasCreated { name } }",
+        "subscription { ... on Subscription { userWasCreated { name } } }",
+        "subscription { ...Created } fragment Created on Subscription { userWasCreated { name } }",
+        "subscription { ... { ...Created } } fragment Created on Subscription { ... on Subscription { userWasCreated { name } } }",
+    ] {
+        let query = Query::parse(query, None, &schema, &Default::default()).unwrap();
+        assert_eq!(root_fields(&query, None), vec!["userWasCreated"], "{query:?}");
+    }
+}
+
+#[tokio::test]
+async fn subscription_throttling_by_root_field_in_fragment() {
+    let mut notify = Notify::builder().build();
+    let (handle, _) = notify
+        .create_or_subscribe("TEST_TOPIC".to_string(), false)
+        .await
+        .unwrap();
+    let subgraphs = MockedSubgraphs([
+            ("user", MockSubgraph::builder().with_json(
+                    serde_json::json!{{"query":"subscription{userWasCreated{name}}"}},
+                    serde_json::json!{{"data": {"userWasCreated": { "__typename": "User", "id": "1" }}}}
+                ).with_subscription_stream(handle.clone()).build()),
+        ].into_iter().collect());
+
+    let mut configuration: Configuration = serde_json::from_value(serde_json::json!({
+        "subscription": {
+            "enabled": true,
+            "mode": {"callback": {"public_url": "http://localhost:4545/callback"}},
+            "throttling": [{
+                "fields": ["userWasCreated"],
+                "max_events": 1,
+                "interval": "1h",
+                "overflow": "drop"
+            }]
+        }
+    }))
+    .unwrap();
+    configuration.notify = notify.clone();
+    let service = TestHarness::builder()
+        .configuration(Arc::new(configuration))
+        .schema(SCHEMA)
+        .extra_plugin(subgraphs)
+        .build_supergraph()
+        .await
+        .unwrap();
+
+    let request = supergraph::Request::fake_builder()
+        .query("subscription { ...Created } fragment Created on Subscription { userWasCreated { name } }")
+        .context(subscription_context())
+        .build()
+        .unwrap();
+    let mut stream = service.oneshot(request).await.unwrap();
+    let res = stream.next_response().await.unwrap();
+    assert!(res.errors.is_empty(), "{res:?}");
+
+    for name in ["first", "second", "third"] {
+        notify
+            .broadcast(
+                graphql::Response::builder()
+                    .data(serde_json_bytes::json!({"userWasCreated": { "name": name }}))
+                    .build(),
+            )
+            .await
+            .unwrap();
+    }
+    notify.force_delete("TEST_TOPIC".to_string()).await.unwrap();
+
+    let mut names = Vec::new();
+    while let Some(response) = stream.next_response().await {
+        if let Some(data) = response.data.filter(|data| !data.is_null()) {
+            names.push(data);
+        }
+    }
+    // only one event is sent per interval, the others are dropped
+    assert_eq!(
+        names,
+        vec![serde_json_bytes::json!({"userWasCreated": { "name": "first" }})]
+    );
+}
+
 #[tokio::test]
 async fn subscription_without_header() {
     let subgraphs = MockedSubgraphs(HashMap::new());
@@ -1713,6 +2343,17 @@ fn defer_context() -> Context {
     context
 }
 
+fn incremental_delivery_context() -> Context {
+    let context = Context::new();
+    context.extensions().lock().insert(ClientRequestAccepts {
+        multipart_defer: true,
+        incremental_delivery: true,
+        ..Default::default()
+    });
+
+    context
+}
+
 #[tokio::test]
 async fn interface_object_typename_rewrites() {
     let schema = r#"
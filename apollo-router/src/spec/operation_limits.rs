@@ -110,6 +110,12 @@ pub(crate) fn check(
     Ok(())
 }
 
+/// Returns the depth of an operation, or `None` if it is undefined or ambiguous
+pub(crate) fn depth(document: &ExecutableDocument, operation_name: Option<&str>) -> Option<u32> {
+    let operation = document.get_operation(operation_name).ok()?;
+    Some(count(document, &mut HashMap::new(), &operation.selection_set).depth)
+}
+
 enum Computation<T> {
     InProgress,
     Done(T),
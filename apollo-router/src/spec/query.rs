@@ -19,6 +19,7 @@ use tower::BoxError;
 use tracing::level_filters::LevelFilter;
 
 use self::change::QueryHashVisitor;
+use self::stream::StreamField;
 use self::subselections::BooleanValues;
 use self::subselections::SubSelectionKey;
 use self::subselections::SubSelectionValue;
@@ -28,6 +29,7 @@ use crate::graphql::Request;
 use crate::graphql::Response;
 use crate::json_ext::Object;
 use crate::json_ext::Path;
+use crate::json_ext::PathElement;
 use crate::json_ext::ResponsePathElement;
 use crate::json_ext::Value;
 use crate::plugins::authorization::UnauthorizedPaths;
@@ -45,6 +47,7 @@ use crate::spec::SpecError;
 use crate::Configuration;
 
 pub(crate) mod change;
+pub(crate) mod stream;
 pub(crate) mod subselections;
 pub(crate) mod transform;
 pub(crate) mod traverse;
@@ -190,19 +193,7 @@ impl Query {
                 } else if let Some(operation) = original_operation {
                     let mut output = Object::with_capacity(operation.selection_set.len());
 
-                    let all_variables = if operation.variables.is_empty() {
-                        variables
-                    } else {
-                        operation
-                            .variables
-                            .iter()
-                            .filter_map(|(k, Variable { default_value, .. })| {
-                                default_value.as_ref().map(|v| (k, v))
-                            })
-                            .chain(variables.iter())
-                            .map(|(k, v)| (k.clone(), v.clone()))
-                            .collect()
-                    };
+                    let all_variables = operation.variables_with_defaults(variables);
 
                     let operation_type_name = schema
                         .root_operation(operation.kind.into())
@@ -269,6 +260,132 @@ impl Query {
         vec![]
     }
 
+    /// Formats the items of a streamed list that were sent after the primary response
+    ///
+    /// `path` is the path of the list in the response. The items are formatted with the complete
+    /// selection set of the list, including its deferred fragments. Returns `None` if the items
+    /// cannot be completed, with the errors explaining why.
+    pub(crate) fn format_stream_items(
+        &self,
+        operation_name: Option<&str>,
+        field: &StreamField,
+        path: &Path,
+        items: Vec<Value>,
+        variables: &Object,
+        schema: &ApiSchema,
+    ) -> (Option<Vec<Value>>, Vec<Error>) {
+        let Some(operation) = self.operation(operation_name) else {
+            failfast_debug!("can't find operation for {:?}", operation_name);
+            return (None, Vec::new());
+        };
+        let all_variables = operation.variables_with_defaults(variables.clone());
+
+        // look for the selections of the streamed field, merging them if it is selected several times
+        let mut parent_type = executable::Type::Named(
+            apollo_compiler::ast::NamedType::new_unchecked(operation.type_name.as_str().into()),
+        );
+        let mut field_type: Option<FieldType> = None;
+        let mut selection_set = operation.selection_set.clone();
+        for element in field.path.iter() {
+            let PathElement::Key(key, _) = element else {
+                continue;
+            };
+            let mut fields = Vec::new();
+            self.collect_fields(&selection_set, key, &all_variables, &mut fields);
+            let Some((ty, _)) = fields.first() else {
+                failfast_debug!("can't find streamed field at {}", field.path);
+                return (None, Vec::new());
+            };
+            if let Some(previous) = field_type.replace((*ty).clone()) {
+                parent_type = executable::Type::Named(previous.0.inner_named_type().clone());
+            }
+            selection_set = fields
+                .into_iter()
+                .flat_map(|(_, selection_set)| selection_set.iter().cloned())
+                .collect();
+        }
+        let Some(field_type) = field_type else {
+            return (None, Vec::new());
+        };
+
+        let mut parameters = FormatParameters {
+            variables: &all_variables,
+            schema,
+            errors: Vec::new(),
+            nullified: Vec::new(),
+        };
+        let mut response_path = path
+            .iter()
+            .filter_map(|element| match element {
+                PathElement::Key(key, _) => Some(ResponsePathElement::Key(key.as_str())),
+                PathElement::Index(index) => Some(ResponsePathElement::Index(*index)),
+                PathElement::Flatten(_) | PathElement::Fragment(_) => None,
+            })
+            .collect();
+        let mut input = Value::Array(items);
+        let mut output = Value::Null;
+        let res = self.format_value(
+            &mut parameters,
+            &field_type.0,
+            &mut input,
+            &mut output,
+            &mut response_path,
+            &parent_type,
+            &selection_set,
+        );
+
+        match (res, output) {
+            (Ok(()), Value::Array(items)) => (Some(items), parameters.errors),
+            _ => (None, parameters.errors),
+        }
+    }
+
+    /// Collects the fields selected with this response key, and their selection sets
+    fn collect_fields<'a>(
+        &'a self,
+        selection_set: &'a [Selection],
+        key: &str,
+        variables: &Object,
+        fields: &mut Vec<(&'a FieldType, &'a [Selection])>,
+    ) {
+        for selection in selection_set {
+            match selection {
+                Selection::Field {
+                    name,
+                    alias,
+                    selection_set,
+                    field_type,
+                    include_skip,
+                } => {
+                    if alias.as_ref().unwrap_or(name).as_str() == key
+                        && !include_skip.should_skip(variables)
+                    {
+                        fields.push((field_type, selection_set.as_deref().unwrap_or_default()));
+                    }
+                }
+                Selection::InlineFragment {
+                    selection_set,
+                    include_skip,
+                    ..
+                } => {
+                    if !include_skip.should_skip(variables) {
+                        self.collect_fields(selection_set, key, variables, fields);
+                    }
+                }
+                Selection::FragmentSpread {
+                    name, include_skip, ..
+                } => {
+                    if include_skip.should_skip(variables) {
+                        continue;
+                    }
+                    if let Some(fragment) = self.fragments.get(name) {
+                        self.collect_fields(&fragment.selection_set, key, variables, fields);
+                    }
+                }
+            }
+        }
+    }
+
     pub(crate) fn parse_document(
         query: &str,
         operation_name: Option<&str>,
@@ -351,7 +468,9 @@ impl Query {
         let fragments = Fragments::from_hir(document, schema, &mut defer_stats)?;
         let operations = document
             .all_operations()
-            .map(|operation| Operation::from_hir(operation, schema, &mut defer_stats, &fragments))
+            .map(|operation| {
+                Operation::from_hir(operation, document, schema, &mut defer_stats, &fragments)
+            })
             .collect::<Result<Vec<_>, SpecError>>()?;
 
         let mut visitor =
@@ -968,7 +1087,7 @@ impl Query {
             }
         }
 
-        let errors = operation_variable_types
+        let mut errors = operation_variable_types
             .iter()
             .filter_map(
                 |(
@@ -993,6 +1112,23 @@ impl Query {
             )
             .collect::<Vec<_>>();
 
+        // a negative `initialCount` is valid for the type of the variable, but not for `@stream`
+        errors.extend(
+            self.operations
+                .iter()
+                .filter(|operation| {
+                    operation_name.is_none() || operation.name.as_deref() == operation_name
+                })
+                .flat_map(|operation| operation.stream_fields.iter())
+                .filter_map(|field| field.invalid_initial_count_variable(&request.variables))
+                .map(|name| {
+                    FetchError::ValidationInvalidTypeVariable {
+                        name: name.to_string(),
+                    }
+                    .to_graphql_error(None)
+                }),
+        );
+
         if errors.is_empty() {
             Ok(())
         } else {
@@ -1110,6 +1246,9 @@ pub(crate) struct Operation {
     type_name: String,
     pub(crate) selection_set: Vec<Selection>,
     variables: HashMap<ByteString, Variable>,
+    /// Lists using the `@stream` directive
+    #[serde(default)]
+    pub(crate) stream_fields: Vec<StreamField>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -1121,6 +1260,7 @@ pub(crate) struct Variable {
 impl Operation {
     pub(crate) fn from_hir(
         operation: &executable::Operation,
+        document: &ExecutableDocument,
         schema: &Schema,
         defer_stats: &mut DeferStats,
         fragments: &Fragments,
@@ -1160,9 +1300,26 @@ impl Operation {
             type_name,
             variables,
             kind,
+            stream_fields: stream::collect_stream_fields(document, operation)?,
         })
     }
 
+    /// Adds the default values of the variables that were not provided
+    fn variables_with_defaults(&self, variables: Object) -> Object {
+        if self.variables.is_empty() {
+            variables
+        } else {
+            self.variables
+                .iter()
+                .filter_map(|(k, Variable { default_value, .. })| {
+                    default_value.as_ref().map(|v| (k, v))
+                })
+                .chain(variables.iter())
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect()
+        }
+    }
+
     /// Checks to see if this is a query or mutation containing only
     /// `__typename` at the root level (possibly more than one time, possibly
     /// with aliases). If so, returns Some with a Vec of the output keys
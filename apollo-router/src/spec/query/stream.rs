@@ -0,0 +1,383 @@
+//! `@stream` support
+//!
+//! The query planner does not support `@stream`: the directive is removed from the query it plans,
+//! and streamed lists are planned as usual lists. During execution, the items of each streamed list
+//! after its `initialCount` are held back as soon as the list is fetched, so the primary response
+//! does not wait for their entity fetches. The execution service then runs the part of the plan
+//! fetching those items separately, for each chunk of items, and sends them in subsequent responses.
+
+use apollo_compiler::ast;
+use apollo_compiler::executable;
+use apollo_compiler::ExecutableDocument;
+use parking_lot::Mutex;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json_bytes::Value;
+use tower::BoxError;
+
+use crate::json_ext::Object;
+use crate::json_ext::Path;
+use crate::json_ext::PathElement;
+use crate::json_ext::ValueExt;
+use crate::spec::query::transform;
+use crate::spec::query::transform::document;
+use crate::spec::query::transform::Visitor;
+use crate::spec::Condition;
+use crate::spec::Schema;
+use crate::spec::SpecError;
+
+pub(crate) const STREAM_DIRECTIVE_NAME: &str = "stream";
+
+/// A list field using the `@stream` directive
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub(crate) struct StreamField {
+    /// Path of the list in the response, with `@` for the items of the lists containing it
+    pub(crate) path: Path,
+    pub(crate) label: Option<String>,
+    initial_count: InitialCount,
+    condition: Condition,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+enum InitialCount {
+    Value(usize),
+    /// A variable, and the count used if it is not provided
+    Variable {
+        name: String,
+        default: usize,
+    },
+}
+
+impl StreamField {
+    fn parse(
+        operation: &executable::Operation,
+        directive: &executable::Directive,
+        path: Path,
+    ) -> Result<Self, SpecError> {
+        let initial_count = match directive
+            .argument_by_name("initialCount")
+            .map(|value| value.as_ref())
+        {
+            Some(executable::Value::Variable(name)) => {
+                let default = operation
+                    .variables
+                    .iter()
+                    .find(|variable| variable.name == *name)
+                    .and_then(|variable| variable.default_value.as_ref())
+                    .map(|value| parse_initial_count(value))
+                    .transpose()?
+                    .unwrap_or_default();
+                InitialCount::Variable {
+                    name: name.as_str().to_owned(),
+                    default,
+                }
+            }
+            Some(value) => InitialCount::Value(parse_initial_count(value)?),
+            None => InitialCount::Value(0),
+        };
+        Ok(Self {
+            path,
+            label: directive
+                .argument_by_name("label")
+                .and_then(|value| value.as_str())
+                .map(|label| label.to_owned()),
+            initial_count,
+            condition: Condition::parse(directive).unwrap_or(Condition::Yes),
+        })
+    }
+
+    /// Returns the number of items sent in the primary response, or `None` if the list is not streamed
+    ///
+    /// The variable used as `initialCount` must have been checked with
+    /// [`StreamField::invalid_initial_count_variable`] beforehand.
+    pub(crate) fn initial_count(&self, variables: &Object) -> Option<usize> {
+        if !self.condition.eval(variables).unwrap_or(true) {
+            return None;
+        }
+        match &self.initial_count {
+            InitialCount::Value(count) => Some(*count),
+            InitialCount::Variable { name, default } => match variables.get(name.as_str()) {
+                Some(value) => initial_count_from_json(value),
+                None => Some(*default),
+            },
+        }
+    }
+
+    /// Returns the name of the variable used as `initialCount` if its value is not a non-negative
+    /// integer
+    pub(crate) fn invalid_initial_count_variable(&self, variables: &Object) -> Option<&str> {
+        match &self.initial_count {
+            InitialCount::Variable { name, .. } => variables
+                .get(name.as_str())
+                .filter(|value| initial_count_from_json(value).is_none())
+                .map(|_| name.as_str()),
+            InitialCount::Value(_) => None,
+        }
+    }
+}
+
+fn parse_initial_count(value: &executable::Value) -> Result<usize, SpecError> {
+    value
+        .to_i32()
+        .and_then(|count| usize::try_from(count).ok())
+        .ok_or_else(|| SpecError::InvalidStreamInitialCount(value.to_string()))
+}
+
+fn initial_count_from_json(value: &Value) -> Option<usize> {
+    value.as_u64().and_then(|count| usize::try_from(count).ok())
+}
+
+/// Collects the streamed lists of an operation, outer lists first
+pub(crate) fn collect_stream_fields(
+    document: &ExecutableDocument,
+    operation: &executable::Operation,
+) -> Result<Vec<StreamField>, SpecError> {
+    let mut stream_fields = Vec::new();
+    collect_selection_set(
+        document,
+        operation,
+        &operation.selection_set,
+        &mut Path::default(),
+        &mut stream_fields,
+    )?;
+    Ok(stream_fields)
+}
+
+fn collect_selection_set(
+    document: &ExecutableDocument,
+    operation: &executable::Operation,
+    selection_set: &executable::SelectionSet,
+    path: &mut Path,
+    stream_fields: &mut Vec<StreamField>,
+) -> Result<(), SpecError> {
+    for selection in &selection_set.selections {
+        match selection {
+            executable::Selection::Field(field) => {
+                path.push(PathElement::Key(field.response_key().to_string(), None));
+                if let Some(directive) = field.directives.get(STREAM_DIRECTIVE_NAME) {
+                    stream_fields.push(StreamField::parse(operation, directive, path.clone())?);
+                }
+                let depth = list_depth(field.ty());
+                for _ in 0..depth {
+                    path.push(PathElement::Flatten(None));
+                }
+                collect_selection_set(
+                    document,
+                    operation,
+                    &field.selection_set,
+                    path,
+                    stream_fields,
+                )?;
+                for _ in 0..depth {
+                    path.pop();
+                }
+                path.pop();
+            }
+            executable::Selection::InlineFragment(inline_fragment) => {
+                collect_selection_set(
+                    document,
+                    operation,
+                    &inline_fragment.selection_set,
+                    path,
+                    stream_fields,
+                )?;
+            }
+            executable::Selection::FragmentSpread(fragment_spread) => {
+                if let Some(fragment) = document.fragments.get(&fragment_spread.fragment_name) {
+                    collect_selection_set(
+                        document,
+                        operation,
+                        &fragment.selection_set,
+                        path,
+                        stream_fields,
+                    )?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn list_depth(ty: &ast::Type) -> usize {
+    match ty {
+        ast::Type::List(item) | ast::Type::NonNullList(item) => 1 + list_depth(item),
+        ast::Type::Named(_) | ast::Type::NonNullNamed(_) => 0,
+    }
+}
+
+/// The streamed lists of a primary response, and the items held back from it
+pub(crate) struct StreamedLists {
+    fields: Vec<(StreamField, usize)>,
+    held_back: Mutex<Vec<StreamedItems>>,
+}
+
+/// Items of a streamed list, sent after the primary response
+#[derive(Debug, Clone)]
+pub(crate) struct StreamedItems {
+    pub(crate) field: StreamField,
+    /// Path of the list in the response
+    pub(crate) path: Path,
+    /// Index of the first item in the list
+    pub(crate) offset: usize,
+    pub(crate) items: Vec<Value>,
+}
+
+impl StreamedLists {
+    /// Lists nested in the items of another streamed list are not streamed: they are sent with
+    /// those items
+    pub(crate) fn new(fields: Vec<(StreamField, usize)>) -> Self {
+        let mut outer_fields: Vec<(StreamField, usize)> = Vec::new();
+        for (field, initial_count) in fields {
+            if !outer_fields
+                .iter()
+                .any(|(outer, _)| field.path.starts_with(&outer.path))
+            {
+                outer_fields.push((field, initial_count));
+            }
+        }
+        Self {
+            fields: outer_fields,
+            held_back: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Removes the items after `initialCount` from the streamed lists of a fetch result
+    pub(crate) fn hold_back(&self, schema: &Schema, value: &mut Value) {
+        let mut held_back = self.held_back.lock();
+        for (field, initial_count) in &self.fields {
+            value.select_values_and_paths_mut(schema, &field.path, |path, list| {
+                if let Value::Array(items) = list {
+                    if items.len() > *initial_count {
+                        held_back.push(StreamedItems {
+                            field: field.clone(),
+                            path: path.clone(),
+                            offset: *initial_count,
+                            items: items.split_off(*initial_count),
+                        });
+                    }
+                }
+            });
+        }
+    }
+
+    pub(crate) fn into_held_back(self) -> Vec<StreamedItems> {
+        self.held_back.into_inner()
+    }
+}
+
+impl StreamedItems {
+    /// Splits the items in chunks fetched and sent separately
+    pub(crate) fn chunks(self, chunk_size: usize) -> Vec<StreamedItems> {
+        let mut chunks = Vec::new();
+        let mut offset = self.offset;
+        let mut items = self.items;
+        while !items.is_empty() {
+            let rest = items.split_off(chunk_size.clamp(1, items.len()));
+            let count = items.len();
+            chunks.push(StreamedItems {
+                field: self.field.clone(),
+                path: self.path.clone(),
+                offset,
+                items,
+            });
+            offset += count;
+            items = rest;
+        }
+        chunks
+    }
+}
+
+/// Removes the `@stream` directives from a query, before sending it to the query planner
+pub(crate) fn remove_stream_directives(
+    schema: &apollo_compiler::Schema,
+    doc: &ast::Document,
+) -> Result<ast::Document, BoxError> {
+    document(&mut StreamRemover { schema }, doc)
+}
+
+struct StreamRemover<'a> {
+    schema: &'a apollo_compiler::Schema,
+}
+
+impl Visitor for StreamRemover<'_> {
+    fn field(
+        &mut self,
+        _parent_type: &str,
+        field_def: &ast::FieldDefinition,
+        def: &ast::Field,
+    ) -> Result<Option<ast::Field>, BoxError> {
+        let Some(mut new) = transform::field(self, field_def, def)? else {
+            return Ok(None);
+        };
+        new.directives
+            .retain(|directive| directive.name != STREAM_DIRECTIVE_NAME);
+        Ok(Some(new))
+    }
+
+    fn schema(&self) -> &apollo_compiler::Schema {
+        self.schema
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json_bytes::json;
+
+    use super::*;
+
+    const SCHEMA: &str = r#"
+        directive @stream(label: String, initialCount: Int = 0, if: Boolean! = true) on FIELD
+        type Query { feed: [Post!]! }
+        type Post { id: ID! comments: [[Comment]] }
+        type Comment { text: String }
+    "#;
+
+    #[test]
+    fn stream_fields() {
+        let schema = apollo_compiler::Schema::parse_and_validate(SCHEMA, "schema.graphql").unwrap();
+        let query = r#"query($count: Int) {
+            feed @stream(initialCount: 2, label: "feed") { id ...Comments }
+        }
+        fragment Comments on Post { comments @stream(initialCount: $count) { text } }"#;
+        let document =
+            ExecutableDocument::parse_and_validate(&schema, query, "query.graphql").unwrap();
+        let operation = document.anonymous_operation.as_ref().unwrap();
+
+        let stream_fields = collect_stream_fields(&document, operation).unwrap();
+        assert_eq!(stream_fields.len(), 2);
+        assert_eq!(stream_fields[0].path.to_string(), "/feed");
+        assert_eq!(stream_fields[0].label.as_deref(), Some("feed"));
+        assert_eq!(stream_fields[0].initial_count(&Object::new()), Some(2));
+        assert_eq!(stream_fields[1].path.to_string(), "/feed/@/comments");
+        let variables = json!({ "count": 3 }).as_object().unwrap().clone();
+        assert_eq!(stream_fields[1].initial_count(&variables), Some(3));
+        assert_eq!(stream_fields[1].initial_count(&Object::new()), Some(0));
+        let variables = json!({ "count": -1 }).as_object().unwrap().clone();
+        assert_eq!(
+            stream_fields[1].invalid_initial_count_variable(&variables),
+            Some("count")
+        );
+
+        let ast = ast::Document::parse(query, "query.graphql").unwrap();
+        let removed = remove_stream_directives(&schema, &ast).unwrap().to_string();
+        assert!(!removed.contains("@stream"));
+        assert!(removed.contains("comments"));
+    }
+
+    #[test]
+    fn negative_initial_count() {
+        let schema = apollo_compiler::Schema::parse_and_validate(SCHEMA, "schema.graphql").unwrap();
+        for query in [
+            "{ feed @stream(initialCount: -1) { id } }",
+            "query($count: Int = -1) { feed @stream(initialCount: $count) { id } }",
+        ] {
+            let document =
+                ExecutableDocument::parse_and_validate(&schema, query, "query.graphql").unwrap();
+            let operation = document.anonymous_operation.as_ref().unwrap();
+            assert!(matches!(
+                collect_stream_fields(&document, operation),
+                Err(SpecError::InvalidStreamInitialCount(_))
+            ));
+        }
+    }
+}
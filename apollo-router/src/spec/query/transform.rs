@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::collections::HashSet;
 
 use apollo_compiler::ast;
 use apollo_compiler::schema::FieldLookupError;
@@ -254,6 +255,82 @@ pub(crate) fn collect_fragments(
         .collect()
 }
 
+/// Remove the variable definitions that are not used anymore after a transformation.
+///
+/// Variables used in fragments are considered used by all operations.
+pub(crate) fn remove_unused_variables(document: &mut ast::Document) {
+    let mut fragments_variables = HashSet::new();
+    for definition in &document.definitions {
+        if let ast::Definition::FragmentDefinition(def) = definition {
+            directives_variables(&def.directives, &mut fragments_variables);
+            selection_set_variables(&def.selection_set, &mut fragments_variables);
+        }
+    }
+
+    for definition in &mut document.definitions {
+        if let ast::Definition::OperationDefinition(def) = definition {
+            let mut used = fragments_variables.clone();
+            directives_variables(&def.directives, &mut used);
+            selection_set_variables(&def.selection_set, &mut used);
+            if def
+                .variables
+                .iter()
+                .any(|variable| !used.contains(&variable.name))
+            {
+                def.make_mut()
+                    .variables
+                    .retain(|variable| used.contains(&variable.name));
+            }
+        }
+    }
+}
+
+fn selection_set_variables(set: &[ast::Selection], used: &mut HashSet<ast::Name>) {
+    for selection in set {
+        match selection {
+            ast::Selection::Field(def) => {
+                for argument in &def.arguments {
+                    value_variables(&argument.value, used);
+                }
+                directives_variables(&def.directives, used);
+                selection_set_variables(&def.selection_set, used);
+            }
+            ast::Selection::FragmentSpread(def) => directives_variables(&def.directives, used),
+            ast::Selection::InlineFragment(def) => {
+                directives_variables(&def.directives, used);
+                selection_set_variables(&def.selection_set, used);
+            }
+        }
+    }
+}
+
+fn directives_variables(directives: &ast::DirectiveList, used: &mut HashSet<ast::Name>) {
+    for directive in directives {
+        for argument in &directive.arguments {
+            value_variables(&argument.value, used);
+        }
+    }
+}
+
+fn value_variables(value: &ast::Value, used: &mut HashSet<ast::Name>) {
+    match value {
+        ast::Value::Variable(name) => {
+            used.insert(name.clone());
+        }
+        ast::Value::List(values) => {
+            for value in values {
+                value_variables(value, used);
+            }
+        }
+        ast::Value::Object(fields) => {
+            for (_, value) in fields {
+                value_variables(value, used);
+            }
+        }
+        _ => {}
+    }
+}
+
 #[test]
 fn test_add_directive_to_fields() {
     struct AddDirective {
@@ -169,7 +169,7 @@ impl Schema {
             .federation_supergraph()
             .to_api_schema(ApiSchemaOptions {
                 include_defer: configuration.supergraph.defer_support,
-                ..Default::default()
+                include_stream: configuration.supergraph.stream_support,
             })
             .map_err(|e| {
                 SchemaError::Api(format!(
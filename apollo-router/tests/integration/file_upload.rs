@@ -9,6 +9,8 @@ use tower::BoxError;
 
 const FILE_CONFIG: &str = include_str!("../fixtures/file_upload/default.router.yaml");
 const FILE_CONFIG_LARGE_LIMITS: &str = include_str!("../fixtures/file_upload/large.router.yaml");
+const FILE_CONFIG_BUFFERED: &str = include_str!("../fixtures/file_upload/buffered.router.yaml");
+const FILE_CONFIG_RULES: &str = include_str!("../fixtures/file_upload/rules.router.yaml");
 
 /// Create a valid handler for the [helper::FileUploadTestServer].
 macro_rules! make_handler {
@@ -892,6 +894,205 @@ async fn it_fails_incompatible_query_order() -> Result<(), BoxError> {
         .await
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn it_supports_any_query_order_when_buffered() -> Result<(), BoxError> {
+    use reqwest::multipart::Form;
+    use reqwest::multipart::Part;
+
+    // Construct the same request as `it_fails_incompatible_query_order`, which can't be
+    // streamed but works once the files are buffered
+    let request = Form::new()
+        .part(
+            "operations",
+            Part::text(
+                serde_json::json!({
+                    "query": "mutation SomeMutation($file0: UploadClone, $file1: Upload) {
+                        file1: singleUpload(file: $file1) { filename body }
+                        file0: singleUploadClone(file: $file0) { filename body }
+                    }",
+                    "variables": {
+                        "file0": null,
+                        "file1": null,
+                    },
+                })
+                .to_string(),
+            ),
+        )
+        .part(
+            "map",
+            Part::text(
+                serde_json::json!({
+                    "0": ["variables.file0"],
+                    "1": ["variables.file1"],
+                })
+                .to_string(),
+            ),
+        )
+        .part("0", Part::text("file0 contents").file_name("file0"))
+        .part("1", Part::text("file1 contents").file_name("file1"));
+
+    // Run the test
+    helper::FileUploadTestServer::builder()
+        .config(FILE_CONFIG_BUFFERED)
+        .handler(make_handler!(
+            "/s1" => helper::echo_single_file,
+            "/s2" => helper::echo_single_file
+        ))
+        .request(request)
+        .subgraph_mapping("uploads", "/s1")
+        .subgraph_mapping("uploads_clone", "/s2")
+        .build()
+        .run_test(|response| {
+            insta::assert_json_snapshot!(response, @r###"
+            {
+              "data": {
+                "file1": {
+                  "filename": "file1",
+                  "body": "file1 contents"
+                },
+                "file0": {
+                  "filename": "file0",
+                  "body": "file0 contents"
+                }
+              }
+            }
+            "###);
+        })
+        .await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn it_sends_a_buffered_file_to_multiple_subgraphs() -> Result<(), BoxError> {
+    use reqwest::multipart::Form;
+    use reqwest::multipart::Part;
+
+    // Construct a manual multipart request with a file used by two subgraphs
+    // Note: With the `stream` mode of file upload this is impossible since a file can only be
+    // streamed once
+    let request = Form::new()
+        .part(
+            "operations",
+            Part::text(
+                serde_json::json!({
+                    "query": "mutation SomeMutation($file0: Upload, $file1: UploadClone) {
+                        file0: singleUpload(file: $file0) { filename body }
+                        file1: singleUploadClone(file: $file1) { filename body }
+                    }",
+                    "variables": {
+                        "file0": null,
+                        "file1": null,
+                    },
+                })
+                .to_string(),
+            ),
+        )
+        .part(
+            "map",
+            Part::text(
+                serde_json::json!({
+                    "0": ["variables.file0", "variables.file1"],
+                })
+                .to_string(),
+            ),
+        )
+        .part("0", Part::text("file0 contents").file_name("file0"));
+
+    // Run the test
+    helper::FileUploadTestServer::builder()
+        .config(FILE_CONFIG_BUFFERED)
+        .handler(make_handler!(
+            "/s1" => helper::echo_files,
+            "/s2" => helper::echo_files
+        ))
+        .request(request)
+        .subgraph_mapping("uploads", "/s1")
+        .subgraph_mapping("uploads_clone", "/s2")
+        .build()
+        .run_test(|response| {
+            insta::assert_json_snapshot!(response, @r###"
+            {
+              "data": {
+                "file0": {
+                  "filename": "file0",
+                  "body": "file0 contents"
+                },
+                "file1": {
+                  "filename": "file0",
+                  "body": "file0 contents"
+                }
+              }
+            }
+            "###);
+        })
+        .await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn it_fails_with_file_type_rule() -> Result<(), BoxError> {
+    // A text file, with a name allowed by the rule but a type that isn't
+    let request = helper::create_request(
+        vec!["notes.png"],
+        vec![tokio_stream::once(Ok(Bytes::from_static(b"Hello, world!")))],
+    );
+
+    // Run the test
+    helper::FileUploadTestServer::builder()
+        .config(FILE_CONFIG_RULES)
+        .handler(make_handler!(helper::always_fail))
+        .request(request)
+        .subgraph_mapping("uploads", "/")
+        .build()
+        .run_test(|response| {
+            insta::assert_json_snapshot!(response, @r###"
+            {
+              "errors": [
+                {
+                  "message": "The type 'text/plain' of the 'notes.png' file is not allowed.",
+                  "extensions": {
+                    "code": "FILE_UPLOADS_INVALID_FILE_TYPE"
+                  }
+                }
+              ]
+            }
+            "###);
+        })
+        .await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn it_fails_with_file_name_rule() -> Result<(), BoxError> {
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+    // A PNG file with a name that isn't allowed by the rule
+    let request = helper::create_request(
+        vec!["image.exe"],
+        vec![tokio_stream::once(Ok(Bytes::from_static(PNG)))],
+    );
+
+    // Run the test
+    helper::FileUploadTestServer::builder()
+        .config(FILE_CONFIG_RULES)
+        .handler(make_handler!(helper::always_fail))
+        .request(request)
+        .subgraph_mapping("uploads", "/")
+        .build()
+        .run_test(|response| {
+            insta::assert_json_snapshot!(response, @r###"
+            {
+              "errors": [
+                {
+                  "message": "The name of the 'image.exe' file is not allowed.",
+                  "extensions": {
+                    "code": "FILE_UPLOADS_INVALID_FILE_NAME"
+                  }
+                }
+              ]
+            }
+            "###);
+        })
+        .await
+}
+
 mod helper {
     use std::collections::BTreeMap;
     use std::collections::HashMap;
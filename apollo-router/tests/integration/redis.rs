@@ -986,3 +986,112 @@ async fn test_redis_query_plan_config_update(updated_config: &str, new_cache_key
         .assert_redis_cache_contains(new_cache_key, Some(starting_key))
         .await;
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn subscriptions_are_shared_between_router_instances() -> Result<(), BoxError> {
+    if !graph_os_enabled() {
+        return Ok(());
+    }
+    let namespace = uuid::Uuid::new_v4().to_string();
+    let accounts = wiremock::MockServer::start().await;
+    // the subscription is opened once for the whole cluster
+    wiremock::Mock::given(wiremock::matchers::method("POST"))
+        .respond_with(wiremock::ResponseTemplate::new(200).set_body_json(json!({ "data": null })))
+        .expect(1)
+        .mount(&accounts)
+        .await;
+
+    let mut routers = Vec::new();
+    for _ in 0..2 {
+        let callback_address = std::net::TcpListener::bind("127.0.0.1:0")?.local_addr()?;
+        let config = json!({
+            "subscription": {
+                "enabled": true,
+                "mode": {
+                    "callback": {
+                        "public_url": format!("http://{callback_address}/callback"),
+                        "listen": callback_address.to_string(),
+                        "path": "/callback",
+                        "heartbeat_interval": "disabled"
+                    }
+                },
+                "redis": {
+                    "urls": ["redis://127.0.0.1:6379"],
+                    "namespace": namespace,
+                    "required_to_start": true
+                }
+            }
+        });
+        let mut router = IntegrationTest::builder()
+            .config(config.to_string())
+            .subgraph_override("accounts", accounts.uri())
+            .build()
+            .await;
+        router.start().await;
+        router.assert_started().await;
+        routers.push((router, callback_address));
+    }
+
+    let query = "subscription { userWasCreated { name } }";
+    let (_, first_response) = routers[0].0.run_subscription(query).await;
+    assert!(first_response.status().is_success());
+    let requests = accounts.received_requests().await.unwrap();
+    let subgraph_request: Value = serde_json::from_slice(&requests[0].body)?;
+    let extension = &subgraph_request["extensions"]["subscription"];
+    let id = extension["subscriptionId"].as_str().unwrap();
+    let verifier = extension["verifier"].as_str().unwrap();
+
+    let (_, second_response) = routers[1].0.run_subscription(query).await;
+    assert!(second_response.status().is_success());
+
+    // the second instance accepts the callbacks of the subscription opened by the first one, and
+    // each instance relays them to the clients of the other
+    let client = reqwest::Client::new();
+    let next = client
+        .post(format!("http://{}/callback/{id}", routers[1].1))
+        .json(&json!({
+            "kind": "subscription",
+            "action": "next",
+            "id": id,
+            "verifier": verifier,
+            "payload": { "data": { "userWasCreated": { "name": "Ada" } } }
+        }))
+        .send()
+        .await?;
+    assert_eq!(next.status(), 200);
+    let complete = client
+        .post(format!("http://{}/callback/{id}", routers[0].1))
+        .json(&json!({
+            "kind": "subscription",
+            "action": "complete",
+            "id": id,
+            "verifier": verifier
+        }))
+        .send()
+        .await?;
+    assert_eq!(complete.status(), 202);
+
+    for response in [first_response, second_response] {
+        let body = tokio::time::timeout(std::time::Duration::from_secs(5), response.text())
+            .await
+            .expect("the subscription must be completed on every instance")?;
+        assert!(body.contains(r#""userWasCreated":{"name":"Ada"}"#));
+    }
+
+    // the callback key must expire once no instance runs
+    let config = RedisConfig::from_url("redis://127.0.0.1:6379").unwrap();
+    let client = RedisClient::new(config, None, None, None);
+    let connection_task = client.connect();
+    client.wait_for_connect().await.unwrap();
+    let ttl: i64 = client
+        .pttl(format!("{namespace}:subscription_callback_hmac_key"))
+        .await?;
+    assert!(ttl > 0);
+    client.quit().await.unwrap();
+    let _ = connection_task.await;
+
+    for (mut router, _) in routers {
+        router.graceful_shutdown().await;
+    }
+    Ok(())
+}